@@ -0,0 +1,155 @@
+// Where the nodes of a tree get allocated. A tree takes its allocator as
+// the `A` parameter, which defaults to the global allocator. With the
+// `allocator_api` feature (nightly only) `A` can be anything implementing
+// the standard library's `Allocator`, such as a bump or pool allocator.
+// Without it this module stands in for the std types, and `Global` is the
+// only allocator there is.
+
+#[cfg(not(feature = "allocator_api"))]
+use std::alloc;
+use std::alloc::Layout;
+use std::mem::MaybeUninit;
+use std::ptr::{self, NonNull};
+
+#[cfg(feature = "allocator_api")]
+pub use std::alloc::{Allocator, Global};
+
+/// Stands in for `std::alloc::Allocator` when the `allocator_api` feature
+/// is off. Only `Global` implements it.
+#[cfg(not(feature = "allocator_api"))]
+pub trait Allocator {}
+
+/// The global allocator, which every tree uses unless told otherwise.
+#[cfg(not(feature = "allocator_api"))]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Global;
+
+#[cfg(not(feature = "allocator_api"))]
+impl Allocator for Global {}
+
+// moves `value` into memory from `alloc`
+#[cfg(feature = "allocator_api")]
+pub fn allocate<T, A: Allocator>(value: T, alloc: A) -> NonNull<T> {
+    let (ptr, _) = Box::into_raw_with_allocator(Box::new_in(value, alloc));
+    // a box is never null
+    unsafe { NonNull::new_unchecked(ptr) }
+}
+
+#[cfg(not(feature = "allocator_api"))]
+pub fn allocate<T, A: Allocator>(value: T, _alloc: A) -> NonNull<T> {
+    NonNull::from(Box::leak(Box::new(value)))
+}
+
+// like `allocate`, but hands `value` back instead of aborting when the
+// allocator is out of memory. The memory is the same as `allocate`'s, so
+// it goes back through `deallocate` or `free` just the same.
+#[cfg(feature = "allocator_api")]
+pub fn try_allocate<T, A: Allocator>(value: T, alloc: A) -> Result<NonNull<T>, T> {
+    match alloc.allocate(Layout::new::<T>()) {
+        Ok(ptr) => {
+            let ptr = ptr.cast::<T>();
+            unsafe { ptr::write(ptr.as_ptr(), value) };
+            Ok(ptr)
+        },
+        Err(_) => Err(value),
+    }
+}
+
+#[cfg(not(feature = "allocator_api"))]
+pub fn try_allocate<T, A: Allocator>(value: T, _alloc: A) -> Result<NonNull<T>, T> {
+    let layout = Layout::new::<T>();
+    if layout.size() == 0 {
+        return Ok(NonNull::from(Box::leak(Box::new(value))));
+    }
+
+    // memory from the global allocator with `T`'s layout is what a box of
+    // `T` would have had
+    match NonNull::new(unsafe { alloc::alloc(layout) } as *mut T) {
+        Some(ptr) => {
+            unsafe { ptr::write(ptr.as_ptr(), value) };
+            Ok(ptr)
+        },
+        None => Err(value),
+    }
+}
+
+// moves the value from `allocate` back out and frees its memory. `ptr`
+// must have come from `allocate` with an allocator equivalent to `alloc`,
+// and can't be used afterwards.
+#[cfg(feature = "allocator_api")]
+pub unsafe fn deallocate<T, A: Allocator>(ptr: NonNull<T>, alloc: A) -> T {
+    *Box::from_raw_in(ptr.as_ptr(), alloc)
+}
+
+#[cfg(not(feature = "allocator_api"))]
+pub unsafe fn deallocate<T, A: Allocator>(ptr: NonNull<T>, _alloc: A) -> T {
+    *Box::from_raw(ptr.as_ptr())
+}
+
+// frees memory from `allocate` whose value has already been moved out
+// or dropped. The same rules as for `deallocate` apply.
+#[cfg(feature = "allocator_api")]
+pub unsafe fn free<T, A: Allocator>(ptr: NonNull<T>, alloc: A) {
+    drop(Box::from_raw_in(ptr.as_ptr() as *mut MaybeUninit<T>, alloc))
+}
+
+#[cfg(not(feature = "allocator_api"))]
+pub unsafe fn free<T, A: Allocator>(ptr: NonNull<T>, _alloc: A) {
+    drop(Box::from_raw(ptr.as_ptr() as *mut MaybeUninit<T>))
+}
+
+#[cfg(all(test, feature = "allocator_api"))]
+mod tests {
+    pub use expectest::prelude::*;
+    pub use std::alloc::{AllocError, Allocator, Global, Layout};
+    pub use std::cell::Cell;
+    pub use std::ptr::NonNull;
+    pub use std::rc::Rc;
+    pub use RedBlackTree;
+
+    // keeps track of how many allocations are still live
+    #[derive(Clone)]
+    pub struct Counting(Rc<Cell<usize>>);
+
+    unsafe impl Allocator for Counting {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.0.set(self.0.get() + 1);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.set(self.0.get() - 1);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    mod allocator_api {
+        use super::*;
+
+        #[test]
+        fn allocates_and_frees_every_node_through_the_trees_allocator() {
+            let live = Rc::new(Cell::new(0));
+            let mut tree = RedBlackTree::new_in(Counting(live.clone()));
+            for value in 0..100 {
+                tree.insert(value);
+            }
+            expect!(live.get()).to(be_equal_to(100));
+
+            for value in 0..50 {
+                expect!(tree.remove(&(value * 2))).to(be_some().value(value * 2));
+            }
+            // the removed nodes are kept for reuse until the pool is trimmed
+            expect!(live.get()).to(be_equal_to(100));
+            tree.trim_pool(0);
+            expect!(live.get()).to(be_equal_to(50));
+            expect!(tree.iter().cloned().collect::<Vec<_>>())
+                .to(be_equal_to((0..50).map(|value| value * 2 + 1).collect::<Vec<_>>()));
+
+            let copy = tree.clone();
+            expect!(live.get()).to(be_equal_to(100));
+            drop(tree);
+            drop(copy);
+            expect!(live.get()).to(be_equal_to(0));
+        }
+    }
+}
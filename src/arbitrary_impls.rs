@@ -0,0 +1,57 @@
+// Arbitrary trees are built by replaying an arbitrary sequence of inserts
+// and removes, so fuzzers reach the shapes removal leaves behind as well as
+// the ones insertion does.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use RedBlackTree;
+
+impl<'a, T> Arbitrary<'a> for RedBlackTree<T> where T: PartialOrd + Arbitrary<'a> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<RedBlackTree<T>> {
+        let mut tree = RedBlackTree::new();
+        for op in u.arbitrary_iter::<(bool, T)>()? {
+            let (insert, value) = op?;
+            if insert {
+                tree.insert(value);
+            } else {
+                tree.remove(&value);
+            }
+        }
+
+        Ok(tree)
+    }
+
+    fn arbitrary_take_rest(mut u: Unstructured<'a>) -> Result<RedBlackTree<T>> {
+        Self::arbitrary(&mut u)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod arbitrary {
+        use super::*;
+
+        #[test]
+        fn replays_the_inserts_and_removes_it_reads() {
+            // each op is a continue byte, a bool byte and a u8
+            let bytes = [1, 1, 5, 1, 1, 3, 1, 1, 9, 0];
+            let tree = RedBlackTree::<u8>::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![3, 5, 9]));
+
+            let bytes = [1, 1, 5, 1, 0, 5, 1, 1, 3, 0];
+            let tree = RedBlackTree::<u8>::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![3]));
+        }
+
+        #[test]
+        fn builds_a_tree_from_any_input() {
+            let bytes: Vec<u8> = (0..255).map(|i: u8| i.wrapping_mul(37)).collect();
+            let tree = RedBlackTree::<u16>::arbitrary_take_rest(Unstructured::new(&bytes)).unwrap();
+            expect!(tree.iter().count()).to(be_equal_to(tree.count));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+// rkyv support. A tree archives as the flat, sorted array of its elements,
+// which can be searched in place straight out of a memory mapped file
+// without deserializing anything. Deserializing bulk builds the tree again
+// in O(n).
+
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+use rkyv::{Archive, Archived, Deserialize, Fallible, Serialize};
+use rkyv::ser::{ScratchSpace, Serializer};
+use rkyv::vec::{ArchivedVec, VecResolver};
+
+use RedBlackTree;
+
+/// The archived form of a `RedBlackTree`: its elements in sorted order.
+#[repr(transparent)]
+pub struct ArchivedRedBlackTree<A> {
+    values: ArchivedVec<A>,
+}
+
+impl<A> ArchivedRedBlackTree<A> {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The archived elements, in ascending order.
+    pub fn as_slice(&self) -> &[A] {
+        self.values.as_slice()
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<'_, A> {
+        self.values.iter()
+    }
+
+    /// Binary searches the archive for `value` in O(log n).
+    pub fn contains<Q: ?Sized>(&self, value: &Q) -> bool where A: PartialOrd<Q> {
+        self.values
+            .binary_search_by(|probe| probe.partial_cmp(value).unwrap_or(Ordering::Less))
+            .is_ok()
+    }
+
+    /// The archived elements that fall within `range`, in ascending order.
+    pub fn range<Q: ?Sized, R>(&self, range: R) -> &[A] where A: PartialOrd<Q>, R: RangeBounds<Q> {
+        let values = self.values.as_slice();
+        let start = match range.start_bound() {
+            Bound::Included(start) => values.partition_point(|value| *value < *start),
+            Bound::Excluded(start) => values.partition_point(|value| *value <= *start),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => values.partition_point(|value| *value <= *end),
+            Bound::Excluded(end) => values.partition_point(|value| *value < *end),
+            Bound::Unbounded => values.len(),
+        };
+
+        if start < end { &values[start..end] } else { &[] }
+    }
+}
+
+impl<T> Archive for RedBlackTree<T> where T: PartialOrd + Archive {
+    type Archived = ArchivedRedBlackTree<Archived<T>>;
+    type Resolver = VecResolver;
+
+    unsafe fn resolve(&self, pos: usize, resolver: VecResolver, out: *mut Self::Archived) {
+        // the archived tree is a transparent wrapper around its vector
+        ArchivedVec::resolve_from_len(self.count, pos, resolver, out as *mut ArchivedVec<Archived<T>>);
+    }
+}
+
+impl<T, S> Serialize<S> for RedBlackTree<T> where T: PartialOrd + Serialize<S>, S: ScratchSpace + Serializer + ?Sized {
+    fn serialize(&self, serializer: &mut S) -> Result<VecResolver, S::Error> {
+        ArchivedVec::<Archived<T>>::serialize_from_iter::<T, _, _, _>(self.iter(), serializer)
+    }
+}
+
+impl<T, D> Deserialize<RedBlackTree<T>, D> for ArchivedRedBlackTree<Archived<T>>
+where T: PartialOrd + Archive, Archived<T>: Deserialize<T, D>, D: Fallible + ?Sized {
+    fn deserialize(&self, deserializer: &mut D) -> Result<RedBlackTree<T>, D::Error> {
+        let mut values = Vec::with_capacity(self.len());
+        for value in self.iter() {
+            values.push(value.deserialize(deserializer)?);
+        }
+
+        Ok(RedBlackTree::from_sorted_vec(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use rkyv::{archived_root, to_bytes, Deserialize, Infallible};
+    use RedBlackTree;
+
+    mod rkyv {
+        use super::*;
+
+        #[test]
+        fn queries_the_archive_without_deserializing_it() {
+            let tree: RedBlackTree<u32> = vec![40, 10, 30, 20, 50].into_iter().collect();
+            let bytes = to_bytes::<_, 256>(&tree).unwrap();
+            let archived = unsafe { archived_root::<RedBlackTree<u32>>(&bytes[..]) };
+
+            expect!(archived.len()).to(be_equal_to(5));
+            expect!(archived.contains(&30)).to(be_true());
+            expect!(archived.contains(&35)).to(be_false());
+            expect!(archived.range(20..40).to_vec()).to(be_equal_to(vec![20, 30]));
+            expect!(archived.range(..=20).to_vec()).to(be_equal_to(vec![10, 20]));
+            expect!(archived.range(60..).len()).to(be_equal_to(0));
+        }
+
+        #[test]
+        fn deserializes_back_into_a_tree() {
+            let tree: RedBlackTree<String> = vec!["b".to_string(), "a".to_string()].into_iter().collect();
+            let bytes = to_bytes::<_, 256>(&tree).unwrap();
+            let archived = unsafe { archived_root::<RedBlackTree<String>>(&bytes[..]) };
+            expect!(archived.contains("a")).to(be_true());
+
+            let copy: RedBlackTree<String> = archived.deserialize(&mut Infallible).unwrap();
+            expect!(copy == tree).to(be_true());
+        }
+    }
+}
@@ -0,0 +1,946 @@
+// A red-black tree that keeps all of its nodes in one buffer and links them
+// by u32 index instead of by pointer. Filling the tree costs a few
+// reallocations of the buffer rather than an allocation per element,
+// dropping it frees a single buffer, and nodes inserted together sit
+// together in memory. The buffer never has holes in it: a removal moves
+// the last node into the slot it frees, so every index below the length
+// is a node, and a link is either one of those or nothing at all.
+//
+// This is the balancing all of the crate's index-linked trees share. The
+// tree itself is `Arena`, which works over any `Slots`: a Vec for
+// `ArenaRedBlackTree`, and a fixed size array for `FixedRedBlackTree`, in
+// which an insertion can find there's no room left. Every node also holds
+// an `Augment`, something extra it keeps about its subtree, which is
+// nothing for those two, a lazy shift of the keys for `ShiftRedBlackTree`,
+// the sum of the weights for `WeightedRedBlackTree`, the span of the values
+// for `GapRedBlackTree`, and the number of elements in the chunks for
+// `ChunkedRedBlackTree`.
+//
+// Everything is done by splitting and joining, as in "Just Join for
+// Parallel Ordered Sets" (Blelloch, Ferizovic and Sun), with the black
+// heights of the pieces carried along rather than measured again at every
+// join. A join only moves the nodes on the spine it walks down, so those
+// hand their augmentation down to their children as the walk passes them,
+// and sum it back up on the way back. Where an element goes is found on a
+// walk down beforehand, so a comparison that panics leaves the tree as it
+// was. Links are followed with `get`, and one that led nowhere would read
+// as an empty subtree, so nothing in here panics.
+
+use std::alloc::{self, Layout};
+use std::convert::TryFrom;
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::num::NonZeroU32;
+
+use inline::InlineVec;
+use node::{Color, Dir};
+use {get_dir, search_dir};
+
+// the index of a node plus one, so that a missing link takes no room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Id(NonZeroU32);
+
+impl Id {
+    fn new(index: usize) -> Option<Id> {
+        let id = u32::try_from(index.checked_add(1)?).ok()?;
+        NonZeroU32::new(id).map(Id)
+    }
+
+    fn index(self) -> usize {
+        self.0.get() as usize - 1
+    }
+}
+
+pub type Link = Option<Id>;
+
+// What a node keeps about its subtree, besides its size. `pull_up` works
+// it out again from the children's whenever they change, and `push_down`
+// hands anything still to be applied to the whole subtree on to the
+// node's own value and its children, before they're moved around.
+pub trait Augment<T>: Copy {
+    fn pull_up(&mut self, _value: &T, _left: Option<&Self>, _right: Option<&Self>) {}
+
+    fn push_down(&mut self, _value: &mut T, _left: Option<&mut Self>, _right: Option<&mut Self>) {}
+}
+
+impl<T> Augment<T> for () {}
+
+#[derive(Clone)]
+pub struct ArenaNode<T, A> {
+    value: T,
+    augment: A,
+    color: Color,
+    size: u32,
+    parent: Link,
+    left: Link,
+    right: Link,
+}
+
+impl<T, A> ArenaNode<T, A> {
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn augment(&self) -> &A {
+        &self.augment
+    }
+
+    pub fn left(&self) -> Link {
+        self.left
+    }
+
+    pub fn right(&self) -> Link {
+        self.right
+    }
+}
+
+// where an `Arena` keeps its nodes
+pub trait Slots<N> {
+    fn as_slice(&self) -> &[N];
+    fn as_mut_slice(&mut self) -> &mut [N];
+    // adds a node at the end, or hands it back when there's no room
+    fn push(&mut self, node: N) -> Result<(), N>;
+    fn pop(&mut self) -> Option<N>;
+    fn clear(&mut self);
+}
+
+impl<N> Slots<N> for Vec<N> {
+    fn as_slice(&self) -> &[N] {
+        self
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [N] {
+        self
+    }
+
+    fn push(&mut self, node: N) -> Result<(), N> {
+        Vec::push(self, node);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<N> {
+        Vec::pop(self)
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+}
+
+impl<N, const M: usize> Slots<N> for InlineVec<N, M> {
+    fn as_slice(&self) -> &[N] {
+        InlineVec::as_slice(self)
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [N] {
+        InlineVec::as_mut_slice(self)
+    }
+
+    fn push(&mut self, node: N) -> Result<(), N> {
+        InlineVec::push(self, node)
+    }
+
+    fn pop(&mut self) -> Option<N> {
+        InlineVec::pop(self)
+    }
+
+    fn clear(&mut self) {
+        InlineVec::clear(self);
+    }
+}
+
+#[derive(Clone)]
+pub struct Arena<T, A, S> {
+    slots: S,
+    root: Link,
+    // the black nodes on every path down from the root
+    height: usize,
+    marker: PhantomData<(T, A)>,
+}
+
+// the two sides of a split, with their black heights, and the node that
+// was at the split point, if there was one
+#[derive(Clone, Copy)]
+struct Split {
+    left: Link,
+    left_height: usize,
+    middle: Link,
+    right: Link,
+    right_height: usize,
+}
+
+#[derive(Clone)]
+pub struct ArenaRedBlackTree<T> where T: PartialOrd {
+    arena: Arena<T, (), Vec<ArenaNode<T, ()>>>,
+}
+
+impl<T> ArenaRedBlackTree<T> where T: PartialOrd {
+    pub const fn new() -> ArenaRedBlackTree<T> {
+        ArenaRedBlackTree { arena: Arena::new(Vec::new()) }
+    }
+
+    /// Creates an empty tree with room for `capacity` elements before the
+    /// arena has to grow.
+    pub fn with_capacity(capacity: usize) -> ArenaRedBlackTree<T> {
+        ArenaRedBlackTree { arena: Arena::new(Vec::with_capacity(capacity)) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Makes room for at least `additional` more elements, so inserting
+    /// them won't grow the arena.
+    pub fn reserve(&mut self, additional: usize) {
+        self.arena.slots.reserve(additional);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.len() == 0
+    }
+
+    /// Inserts `value`, after any elements equal to it. The tree links its
+    /// nodes by u32 index, so it holds at most `u32::MAX` elements, and
+    /// running out of indices is handled like running out of memory.
+    pub fn insert(&mut self, value: T) {
+        let index = self.arena.insert_position(&value);
+        self.arena.push_at(index, value, ());
+    }
+
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let index = self.arena.position(value)?;
+        self.arena.remove_at(index).map(|(value, ())| value)
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.arena.position(value).is_some()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.arena.iter()
+    }
+
+    /// Removes every element, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.arena.clear();
+    }
+
+    /// Frees the capacity the arena isn't using.
+    pub fn shrink_to_fit(&mut self) {
+        self.arena.slots.shrink_to_fit();
+    }
+}
+
+impl<T, A, S> Arena<T, A, S> where A: Augment<T>, S: Slots<ArenaNode<T, A>> {
+    pub const fn new(slots: S) -> Arena<T, A, S> {
+        Arena {
+            slots,
+            root: None,
+            height: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.as_slice().len()
+    }
+
+    pub fn root(&self) -> Link {
+        self.root
+    }
+
+    pub fn node(&self, id: Id) -> Option<&ArenaNode<T, A>> {
+        self.slots.as_slice().get(id.index())
+    }
+
+    pub fn get(&self, link: Link) -> Option<&ArenaNode<T, A>> {
+        link.and_then(|id| self.node(id))
+    }
+
+    fn node_mut(&mut self, id: Id) -> Option<&mut ArenaNode<T, A>> {
+        self.slots.as_mut_slice().get_mut(id.index())
+    }
+
+    pub fn size(&self, link: Link) -> usize {
+        self.get(link).map_or(0, |node| node.size as usize)
+    }
+
+    // the node with `index` elements before it
+    pub fn select(&self, index: usize) -> Link {
+        let mut index = index;
+        let mut link = self.root;
+        while let Some(node) = self.get(link) {
+            let left_size = self.size(node.left);
+            if index < left_size {
+                link = node.left;
+            } else if index == left_size {
+                return link;
+            } else {
+                index -= left_size + 1;
+                link = node.right;
+            }
+        }
+
+        None
+    }
+
+    pub fn nodes(&self) -> Nodes<'_, T, A> {
+        Nodes::new(self.slots.as_slice(), self.root)
+    }
+
+    // the nodes from the one with `index` elements before it on
+    pub fn nodes_from(&self, index: usize) -> Nodes<'_, T, A> {
+        let remaining = self.len().saturating_sub(index);
+        Nodes { nodes: self.slots.as_slice(), next: self.select(index), remaining }
+    }
+
+    // hands the element with `index` elements before it to `f` to change,
+    // and then works the augmentation out again for it and every node
+    // above it. Anything still to be handed down to the element is handed
+    // down first, on the way to it.
+    pub fn update_at<F, R>(&mut self, index: usize, f: F) -> Option<R> where F: FnOnce(&mut T) -> R {
+        let mut index = index;
+        let mut link = self.root;
+        while let Some(id) = link {
+            self.push_down(id);
+            let node = self.node(id)?;
+            let left_size = self.size(node.left);
+            if index < left_size {
+                link = node.left;
+            } else if index == left_size {
+                break;
+            } else {
+                index -= left_size + 1;
+                link = node.right;
+            }
+        }
+
+        let id = link?;
+        let result = f(&mut self.node_mut(id)?.value);
+        let mut link = Some(id);
+        while let Some(id) = link {
+            self.update(id);
+            link = self.node(id).and_then(|node| node.parent);
+        }
+
+        Some(result)
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.root = None;
+        self.height = 0;
+    }
+
+    // walks down from the root, going whichever way `dir` says from each
+    // node, until it says to stop. Returns how many elements come before
+    // where the walk ended, and the node it stopped at, if it did.
+    pub fn find<F>(&self, mut dir: F) -> (usize, Link) where F: FnMut(&T, &A) -> Option<Dir> {
+        let mut index = 0;
+        let mut link = self.root;
+        while let Some(node) = self.get(link) {
+            let left_size = self.size(node.left);
+            match dir(&node.value, &node.augment) {
+                Some(Dir::Left) => link = node.left,
+                Some(Dir::Right) => {
+                    index += left_size + 1;
+                    link = node.right;
+                },
+                None => return (index + left_size, link),
+            }
+        }
+
+        (index, None)
+    }
+
+    // puts `value` in with `index` elements before it, or hands it back
+    // if there's no slot left to put it in, leaving the tree as it was
+    pub fn insert_at(&mut self, index: usize, value: T, augment: A) -> Result<(), (T, A)> {
+        let id = self.allocate(value, augment)?;
+        let (left, left_height, right, right_height) = self.split_before(index);
+        let (root, height) = self.join(left, left_height, id, right, right_height);
+        self.set_root(root, height);
+        Ok(())
+    }
+
+    // takes out the element with `index` elements before it
+    pub fn remove_at(&mut self, index: usize) -> Option<(T, A)> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let split = self.split(self.root, self.height, index);
+        let (root, height) = self.concat(split.left, split.left_height, split.right, split.right_height);
+        self.set_root(root, height);
+        self.release(split.middle?)
+    }
+
+    // hands `f` the augmentation at the root of a subtree that holds the
+    // elements from `index` on and nothing else, to change them all at once
+    pub fn update_from<F>(&mut self, index: usize, f: F) where F: FnOnce(&mut A) {
+        let (left, left_height, right, right_height) = self.split_before(index);
+        if let Some(node) = right.and_then(|id| self.node_mut(id)) {
+            f(&mut node.augment);
+        }
+
+        let (root, height) = self.concat(left, left_height, right, right_height);
+        self.set_root(root, height);
+    }
+
+    fn allocate(&mut self, value: T, augment: A) -> Result<Id, (T, A)> {
+        let id = match Id::new(self.len()) {
+            Some(id) => id,
+            None => return Err((value, augment)),
+        };
+
+        let node = ArenaNode { value, augment, color: Color::Red, size: 1, parent: None, left: None, right: None };
+        match self.slots.push(node) {
+            Ok(()) => Ok(id),
+            Err(node) => Err((node.value, node.augment)),
+        }
+    }
+
+    // takes the detached node `id` out of the buffer, and moves the last
+    // node into its slot so there's no hole left behind
+    fn release(&mut self, id: Id) -> Option<(T, A)> {
+        let last = Id::new(self.len().checked_sub(1)?)?;
+        if id.index() > last.index() {
+            return None;
+        }
+
+        if id != last {
+            self.slots.as_mut_slice().swap(id.index(), last.index());
+            let (parent, left, right) = match self.node(id) {
+                Some(node) => (node.parent, node.left, node.right),
+                None => return None,
+            };
+
+            match parent {
+                Some(parent) => {
+                    let dir = if self.child(parent, Dir::Left) == Some(last) { Dir::Left } else { Dir::Right };
+                    self.set_child(parent, dir, Some(id));
+                },
+                None => self.root = Some(id),
+            }
+
+            self.set_parent(left, Some(id));
+            self.set_parent(right, Some(id));
+        }
+
+        self.slots.pop().map(|node| (node.value, node.augment))
+    }
+
+    // splits the tree into the first `index` elements and the rest
+    fn split_before(&mut self, index: usize) -> (Link, usize, Link, usize) {
+        let split = self.split(self.root, self.height, index);
+        let (right, right_height) = match split.middle {
+            Some(middle) => self.join(None, 0, middle, split.right, split.right_height),
+            None => (split.right, split.right_height),
+        };
+
+        (split.left, split.left_height, right, right_height)
+    }
+
+    // splits the subtree at `link`, whose black height is `height`,
+    // around the node with `index` elements before it
+    fn split(&mut self, link: Link, height: usize, index: usize) -> Split {
+        let (id, color, left, right) = match self.expose(link) {
+            Some(parts) => parts,
+            None => return Split { left: None, left_height: 0, middle: None, right: None, right_height: 0 },
+        };
+
+        let child_height = height.saturating_sub(color.is_black() as usize);
+        let left_size = self.size(left);
+        if index < left_size {
+            let split = self.split(left, child_height, index);
+            let (right, right_height) = self.join(split.right, split.right_height, id, right, child_height);
+            Split { right, right_height, ..split }
+        } else if index == left_size {
+            Split { left, left_height: child_height, middle: Some(id), right, right_height: child_height }
+        } else {
+            let split = self.split(right, child_height, index - left_size - 1);
+            let (left, left_height) = self.join(left, child_height, id, split.left, split.left_height);
+            Split { left, left_height, ..split }
+        }
+    }
+
+    // takes the node at `link` off its children, once it has handed its
+    // augmentation down to them
+    fn expose(&mut self, link: Link) -> Option<(Id, Color, Link, Link)> {
+        let id = link?;
+        self.push_down(id);
+        let node = self.node_mut(id)?;
+        Some((id, node.color, node.left.take(), node.right.take()))
+    }
+
+    // joins two trees where every element of `left` comes before every
+    // element of `right`
+    fn concat(&mut self, left: Link, left_height: usize, right: Link, right_height: usize) -> (Link, usize) {
+        let split = self.split(right, right_height, 0);
+        match split.middle {
+            Some(first) => self.join(left, left_height, first, split.right, split.right_height),
+            None => (left, left_height),
+        }
+    }
+
+    // joins `left` and `right` around the detached `pivot`, where every
+    // element of `left` comes before the pivot and every element of
+    // `right` after it, and returns the tree with its black height
+    fn join(&mut self, left: Link, left_height: usize, pivot: Id, right: Link, right_height: usize) -> (Link, usize) {
+        // with both roots black, the only thing a join can break is a red
+        // pivot hung under a red node, which the rotation on the way back
+        // up from it fixes
+        let (left, left_height) = self.blacken(left, left_height);
+        let (right, right_height) = self.blacken(right, right_height);
+        let root = match (left, right) {
+            (Some(tall), short) if left_height > right_height => self.join_towards(tall, left_height, pivot, short, right_height, Dir::Right),
+            (short, Some(tall)) if right_height > left_height => self.join_towards(tall, right_height, pivot, short, left_height, Dir::Left),
+            (left, right) => self.attach(Color::Red, left, pivot, right),
+        };
+
+        (Some(root), left_height.max(right_height))
+    }
+
+    // walks down the `dir` spine of the taller `tall` until the black
+    // heights match, hangs `short` there, and fixes any red-red pair on
+    // the way back up
+    fn join_towards(&mut self, tall: Id, tall_height: usize, pivot: Id, short: Link, short_height: usize, dir: Dir) -> Id {
+        let color = self.color(Some(tall));
+        if color.is_black() && tall_height == short_height {
+            return self.hang(Some(tall), pivot, short, dir);
+        }
+
+        self.push_down(tall);
+        let child_height = tall_height.saturating_sub(color.is_black() as usize);
+        let joined = match self.child(tall, dir) {
+            Some(child) => self.join_towards(child, child_height, pivot, short, short_height, dir),
+            None => self.hang(None, pivot, short, dir),
+        };
+
+        self.set_child(tall, dir, Some(joined));
+        let outer = self.child(joined, dir);
+        if color.is_black() && self.color(Some(joined)).is_red() && self.color(outer).is_red() {
+            self.set_color(outer, Color::Black);
+            return self.rotate(tall, dir.opposite());
+        }
+
+        self.update(tall);
+        tall
+    }
+
+    // a red `pivot` with `short` on its `dir` side and `tall` on the other
+    fn hang(&mut self, tall: Link, pivot: Id, short: Link, dir: Dir) -> Id {
+        match dir {
+            Dir::Right => self.attach(Color::Red, tall, pivot, short),
+            Dir::Left => self.attach(Color::Red, short, pivot, tall),
+        }
+    }
+
+    // moves `id` down towards `dir`, and its child on the other side up
+    // into its place. Both hand their augmentations down first, and the
+    // one that goes down is updated first, since the other sums over it.
+    fn rotate(&mut self, id: Id, dir: Dir) -> Id {
+        self.push_down(id);
+        let up = match self.child(id, dir.opposite()) {
+            Some(up) => up,
+            None => {
+                self.update(id);
+                return id;
+            },
+        };
+
+        self.push_down(up);
+        let inner = self.child(up, dir);
+        self.set_child(id, dir.opposite(), inner);
+        self.update(id);
+        self.set_child(up, dir, Some(id));
+        self.update(up);
+        up
+    }
+
+    fn attach(&mut self, color: Color, left: Link, pivot: Id, right: Link) -> Id {
+        if let Some(node) = self.node_mut(pivot) {
+            node.color = color;
+            node.left = left;
+            node.right = right;
+        }
+
+        self.update(pivot);
+        pivot
+    }
+
+    // recomputes the size and augmentation of `id` from its children's,
+    // and points the children back at it
+    fn update(&mut self, id: Id) {
+        let (left, right) = match self.node(id) {
+            Some(node) => (node.left, node.right),
+            None => return,
+        };
+
+        let left_node = self.get(left).map(|node| (node.size, node.augment));
+        let right_node = self.get(right).map(|node| (node.size, node.augment));
+        self.set_parent(left, Some(id));
+        self.set_parent(right, Some(id));
+        if let Some(node) = self.node_mut(id) {
+            node.size = 1 + left_node.map_or(0, |(size, _)| size) + right_node.map_or(0, |(size, _)| size);
+            let left = left_node.as_ref().map(|(_, augment)| augment);
+            let right = right_node.as_ref().map(|(_, augment)| augment);
+            node.augment.pull_up(&node.value, left, right);
+        }
+    }
+
+    fn push_down(&mut self, id: Id) {
+        let (left, right) = match self.node(id) {
+            Some(node) => (node.left, node.right),
+            None => return,
+        };
+
+        let mut left_augment = self.get(left).map(|node| node.augment);
+        let mut right_augment = self.get(right).map(|node| node.augment);
+        if let Some(node) = self.node_mut(id) {
+            node.augment.push_down(&mut node.value, left_augment.as_mut(), right_augment.as_mut());
+        }
+
+        for (child, augment) in [(left, left_augment), (right, right_augment)] {
+            if let (Some(node), Some(augment)) = (child.and_then(|id| self.node_mut(id)), augment) {
+                node.augment = augment;
+            }
+        }
+    }
+
+    // makes a red root black, which adds one to the black height
+    fn blacken(&mut self, link: Link, height: usize) -> (Link, usize) {
+        if self.color(link).is_red() {
+            self.set_color(link, Color::Black);
+            return (link, height + 1);
+        }
+
+        (link, height)
+    }
+
+    fn set_root(&mut self, root: Link, height: usize) {
+        let (root, height) = self.blacken(root, height);
+        self.set_parent(root, None);
+        self.root = root;
+        self.height = height;
+    }
+
+    // missing nodes count as black
+    fn color(&self, link: Link) -> Color {
+        self.get(link).map_or(Color::Black, |node| node.color)
+    }
+
+    fn set_color(&mut self, link: Link, color: Color) {
+        if let Some(node) = link.and_then(|id| self.node_mut(id)) {
+            node.color = color;
+        }
+    }
+
+    fn set_parent(&mut self, link: Link, parent: Link) {
+        if let Some(node) = link.and_then(|id| self.node_mut(id)) {
+            node.parent = parent;
+        }
+    }
+
+    fn child(&self, id: Id, dir: Dir) -> Link {
+        self.node(id).and_then(|node| match dir {
+            Dir::Left => node.left,
+            Dir::Right => node.right,
+        })
+    }
+
+    fn set_child(&mut self, id: Id, dir: Dir, child: Link) {
+        if let Some(node) = self.node_mut(id) {
+            match dir {
+                Dir::Left => node.left = child,
+                Dir::Right => node.right = child,
+            }
+        }
+    }
+
+    // checks the colors, that every path down has as many black nodes,
+    // and that the sizes and parent links add up
+    #[cfg(test)]
+    pub fn assert_balanced(&self) {
+        fn black_height<T, A, S>(arena: &Arena<T, A, S>, link: Link, parent: Link) -> usize where A: Augment<T>, S: Slots<ArenaNode<T, A>> {
+            let node = match arena.get(link) {
+                Some(node) => node,
+                None => return 0,
+            };
+
+            assert!(node.parent == parent);
+            if node.color.is_red() {
+                assert!(arena.color(node.left).is_black() && arena.color(node.right).is_black());
+            }
+
+            assert_eq!(node.size as usize, arena.size(node.left) + 1 + arena.size(node.right));
+            let left = black_height(arena, node.left, link);
+            assert_eq!(left, black_height(arena, node.right, link));
+            left + node.color.is_black() as usize
+        }
+
+        assert!(self.color(self.root).is_black());
+        assert_eq!(black_height(self, self.root, None), self.height);
+        assert_eq!(self.size(self.root), self.len());
+    }
+}
+
+impl<T, S> Arena<T, (), S> where S: Slots<ArenaNode<T, ()>> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { nodes: self.nodes() }
+    }
+}
+
+impl<T, A, S> Arena<T, A, S> where T: PartialOrd, A: Augment<T>, S: Slots<ArenaNode<T, A>> {
+    // how many elements `value` goes after, which is every one that it
+    // doesn't sort before, as in `RedBlackTree`
+    pub fn insert_position(&self, value: &T) -> usize {
+        self.find(|other, _| Some(get_dir(*value < *other))).0
+    }
+
+    // how many elements come before one equal to `value`
+    pub fn position(&self, value: &T) -> Option<usize> {
+        match self.find(|other, _| search_dir(value, other)) {
+            (index, Some(_)) => Some(index),
+            (_, None) => None,
+        }
+    }
+}
+
+impl<T, A> Arena<T, A, Vec<ArenaNode<T, A>>> where A: Augment<T> {
+    // a Vec only runs out of room once the indices do, which is treated
+    // the same as the allocator running out of memory
+    pub fn push_at(&mut self, index: usize, value: T, augment: A) {
+        if self.insert_at(index, value, augment).is_err() {
+            alloc::handle_alloc_error(Layout::new::<ArenaNode<T, A>>());
+        }
+    }
+}
+
+impl<T> Default for ArenaRedBlackTree<T> where T: PartialOrd {
+    fn default() -> ArenaRedBlackTree<T> {
+        ArenaRedBlackTree::new()
+    }
+}
+
+impl<T> Debug for ArenaRedBlackTree<T> where T: PartialOrd + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> FromIterator<T> for ArenaRedBlackTree<T> where T: PartialOrd {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> ArenaRedBlackTree<T> {
+        let mut tree = ArenaRedBlackTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T> Extend<T> for ArenaRedBlackTree<T> where T: PartialOrd {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.arena.slots.reserve(iter.size_hint().0);
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ArenaRedBlackTree<T> where T: PartialOrd {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+// Walks the nodes of an `Arena` in order by following the parent links
+// back up, so it doesn't need any memory of its own.
+pub struct Nodes<'a, T, A> where T: 'a, A: 'a {
+    nodes: &'a [ArenaNode<T, A>],
+    next: Link,
+    remaining: usize,
+}
+
+impl<'a, T, A> Nodes<'a, T, A> {
+    fn new(nodes: &'a [ArenaNode<T, A>], root: Link) -> Nodes<'a, T, A> {
+        Nodes { nodes, next: root.map(|root| leftmost(nodes, root)), remaining: nodes.len() }
+    }
+}
+
+impl<'a, T, A> Iterator for Nodes<'a, T, A> {
+    type Item = &'a ArenaNode<T, A>;
+
+    fn next(&mut self) -> Option<&'a ArenaNode<T, A>> {
+        let current = self.next?;
+        let node = self.nodes.get(current.index())?;
+        self.next = successor(self.nodes, current);
+        self.remaining = self.remaining.saturating_sub(1);
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T, A> ExactSizeIterator for Nodes<'a, T, A> {}
+
+fn leftmost<T, A>(nodes: &[ArenaNode<T, A>], id: Id) -> Id {
+    let mut id = id;
+    while let Some(left) = nodes.get(id.index()).and_then(|node| node.left) {
+        id = left;
+    }
+
+    id
+}
+
+fn successor<T, A>(nodes: &[ArenaNode<T, A>], id: Id) -> Link {
+    let node = nodes.get(id.index())?;
+    if let Some(right) = node.right {
+        return Some(leftmost(nodes, right));
+    }
+
+    let mut child = id;
+    let mut parent = node.parent;
+    while let Some(id) = parent {
+        let node = nodes.get(id.index())?;
+        if node.right != Some(child) {
+            break;
+        }
+
+        child = id;
+        parent = node.parent;
+    }
+
+    parent
+}
+
+/// Iterates over an `ArenaRedBlackTree` or a `FixedRedBlackTree` in
+/// ascending order.
+pub struct Iter<'a, T> where T: 'a {
+    nodes: Nodes<'a, T, ()>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.nodes.next().map(ArenaNode::value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use arena::ArenaRedBlackTree;
+
+    mod arena_tree {
+        use super::*;
+
+        #[test]
+        fn keeps_its_elements_in_order() {
+            let tree: ArenaRedBlackTree<_> = vec![5, 1, 4, 1, 3].into_iter().collect();
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 1, 3, 4, 5]));
+            expect!(tree.len()).to(be_equal_to(5));
+            expect!(tree.contains(&4)).to(be_true());
+            expect!(tree.contains(&2)).to(be_false());
+        }
+
+        #[test]
+        fn removes_elements_and_reuses_their_slots() {
+            let mut tree: ArenaRedBlackTree<_> = (0..100).collect();
+            for value in (0..100).filter(|value| value % 3 == 0) {
+                expect!(tree.remove(&value)).to(be_some().value(value));
+            }
+
+            expect!(tree.remove(&3)).to(be_none());
+            expect!(tree.len()).to(be_equal_to(66));
+            expect!(tree.arena.slots.len()).to(be_equal_to(66));
+            expect!(tree.iter().cloned().collect::<Vec<_>>())
+                .to(be_equal_to((0..100).filter(|value| value % 3 != 0).collect::<Vec<_>>()));
+
+            tree.extend(100..134);
+            expect!(tree.arena.slots.len()).to(be_equal_to(100));
+            expect!(tree.len()).to(be_equal_to(100));
+            tree.arena.assert_balanced();
+        }
+
+        #[test]
+        fn preallocates_room_for_elements() {
+            let mut tree = ArenaRedBlackTree::with_capacity(10);
+            let slots = tree.arena.slots.as_ptr();
+            tree.extend(0..10);
+            expect!(tree.arena.slots.as_ptr()).to(be_equal_to(slots));
+
+            tree.remove(&3);
+            tree.reserve(5);
+            expect!(tree.arena.slots.capacity() >= 14).to(be_true());
+        }
+
+        #[test]
+        fn frees_the_capacity_it_isnt_using_when_shrunk() {
+            let mut tree: ArenaRedBlackTree<_> = (0..100).collect();
+            for value in (0..100).filter(|value| value % 3 != 0) {
+                tree.remove(&value);
+            }
+
+            tree.shrink_to_fit();
+            expect!(tree.arena.slots.len()).to(be_equal_to(34));
+            expect!(tree.arena.slots.capacity() < 100).to(be_true());
+            expect!(tree.iter().cloned().collect::<Vec<_>>())
+                .to(be_equal_to((0..34).map(|value| value * 3).collect::<Vec<_>>()));
+
+            // the tree still links up correctly afterwards
+            for value in 0..34 {
+                expect!(tree.remove(&(value * 3))).to(be_some().value(value * 3));
+            }
+            tree.insert(1);
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1]));
+        }
+
+        #[test]
+        fn stays_balanced() {
+            let mut tree = ArenaRedBlackTree::new();
+            for value in 0..1000 {
+                tree.insert(value);
+            }
+
+            for value in 0..500 {
+                tree.remove(&(value * 2));
+            }
+
+            tree.arena.assert_balanced();
+            expect!(tree.iter().len()).to(be_equal_to(500));
+        }
+
+        #[test]
+        fn stays_balanced_through_mixed_inserts_and_removes() {
+            let mut tree = ArenaRedBlackTree::new();
+            let mut model = Vec::new();
+            for step in 0..600u32 {
+                let value = step * 37 % 101;
+                if step % 3 == 2 {
+                    let found = model.iter().position(|&other| other == value);
+                    expect!(tree.remove(&value)).to(be_equal_to(found.map(|index| model.remove(index))));
+                } else {
+                    tree.insert(value);
+                    let index = model.iter().position(|&other| other > value).unwrap_or(model.len());
+                    model.insert(index, value);
+                }
+
+                tree.arena.assert_balanced();
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(model.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+// A persistent tree behind an atomic pointer, for data that's read far
+// more often than it's written. Readers take a reference to whichever
+// version of the tree is current, without ever waiting on a lock or on
+// each other. A writer builds the next version by path copying, which
+// leaves the old one untouched for the readers still using it, and then
+// swaps the pointer over to it. Writers take turns through a mutex.
+//
+// The pointer is the raw form of an `Arc`. A reader loads it and then
+// raises its reference count, and the writer that swapped it out mustn't
+// drop its own reference in between the two, or the count could go to
+// zero under the reader. So readers say when they're between the two by
+// counting themselves in one of two slots, picked by the current epoch,
+// and after the swap the writer waits until each slot has been seen
+// empty. Any reader that loaded the old pointer was counted before the
+// swap and is gone from its slot by then. The writer moves new readers
+// over to the slot it isn't waiting on first, so a steady stream of reads
+// can't keep it waiting. Readers never wait at all.
+//
+// Each published version is numbered, one more than the one before, so a
+// reader holding a `Snapshot` can tell which version it's reading and
+// whether a later one has replaced it, the way a database tags the
+// versions of its rows. A snapshot keeps its version's nodes alive however
+// many versions come after it, and costs the writers nothing.
+
+use std::fmt::{self, Debug};
+use std::hint;
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use persistent::{Iter, Range, RedBlackTree};
+
+/// A sorted multiset that any number of threads can read without locking,
+/// while writers publish changes one at a time. A read works on the
+/// version of the tree that was current when it started, and never sees
+/// a change halfway through.
+pub struct AtomicRedBlackTree<T> where T: PartialOrd + Clone {
+    root: AtomicPtr<Snapshot<T>>,
+    epoch: AtomicUsize,
+    // readers between loading the root and counting their reference to
+    // it, by the epoch they started in
+    readers: [AtomicUsize; 2],
+    writer: Mutex<()>,
+    // sharing this shares the trees it hands out
+    marker: PhantomData<Arc<Snapshot<T>>>,
+}
+
+impl<T> AtomicRedBlackTree<T> where T: PartialOrd + Clone {
+    pub fn new() -> AtomicRedBlackTree<T> {
+        AtomicRedBlackTree::from(RedBlackTree::new())
+    }
+
+    /// The current version of the tree, which stays as it is however the
+    /// tree changes afterwards. Takes a fixed number of steps, whatever
+    /// other readers and writers are doing.
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot::clone(&self.load())
+    }
+
+    /// The number of the current version, which goes up by one with every
+    /// change published.
+    pub fn version(&self) -> u64 {
+        self.load().version
+    }
+
+    fn load(&self) -> Arc<Snapshot<T>> {
+        let slot = &self.readers[self.epoch.load(Ordering::SeqCst) & 1];
+        slot.fetch_add(1, Ordering::SeqCst);
+        let root = self.root.load(Ordering::SeqCst);
+        // safe because the writer that swaps this root out won't drop its
+        // reference until this reader is out of its slot
+        let current = unsafe {
+            Arc::increment_strong_count(root);
+            Arc::from_raw(root)
+        };
+        slot.fetch_sub(1, Ordering::SeqCst);
+        current
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.load().tree.contains(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.load().tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn insert(&self, value: T) {
+        self.update(|tree| tree.insert(value));
+    }
+
+    /// Takes out one element equal to `value`, and says whether there was
+    /// one.
+    pub fn remove(&self, value: &T) -> bool {
+        let mut removed = false;
+        self.update(|tree| {
+            removed = tree.contains(value);
+            tree.remove(value)
+        });
+        removed
+    }
+
+    /// Publishes the tree that `change` makes out of the current one, so
+    /// readers see all of the change or none of it.
+    pub fn update<F>(&self, change: F) where F: FnOnce(&RedBlackTree<T>) -> RedBlackTree<T> {
+        // the lock guards nothing but the turn to write, so a writer that
+        // panicked can't have left anything half done
+        let _turn = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = self.load();
+        let next = Snapshot {
+            version: current.version + 1,
+            tree: change(&current.tree),
+        };
+        self.publish(next);
+    }
+
+    fn publish(&self, next: Snapshot<T>) {
+        let old = self.root.swap(Arc::into_raw(Arc::new(next)) as *mut _, Ordering::SeqCst);
+        for _ in 0..2 {
+            let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+            while self.readers[epoch & 1].load(Ordering::SeqCst) != 0 {
+                hint::spin_loop();
+            }
+        }
+
+        // no reader can still be about to count a reference to it
+        drop(unsafe { Arc::from_raw(old) });
+    }
+}
+
+impl<T> Default for AtomicRedBlackTree<T> where T: PartialOrd + Clone {
+    fn default() -> AtomicRedBlackTree<T> {
+        AtomicRedBlackTree::new()
+    }
+}
+
+impl<T> From<RedBlackTree<T>> for AtomicRedBlackTree<T> where T: PartialOrd + Clone {
+    fn from(tree: RedBlackTree<T>) -> AtomicRedBlackTree<T> {
+        AtomicRedBlackTree {
+            root: AtomicPtr::new(Arc::into_raw(Arc::new(Snapshot { version: 0, tree })) as *mut _),
+            epoch: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writer: Mutex::new(()),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for AtomicRedBlackTree<T> where T: PartialOrd + Clone {
+    fn drop(&mut self) {
+        drop(unsafe { Arc::from_raw(*self.root.get_mut()) });
+    }
+}
+
+/// One version of an `AtomicRedBlackTree`, made by
+/// `AtomicRedBlackTree::snapshot`. It can be kept and read for as long as
+/// it's needed while writers go on changing the tree, and never sees any
+/// of their changes. Cloning one is O(1).
+pub struct Snapshot<T> where T: PartialOrd + Clone {
+    version: u64,
+    tree: RedBlackTree<T>,
+}
+
+impl<T> Snapshot<T> where T: PartialOrd + Clone {
+    /// The number of the version this is, starting from 0 for the tree the
+    /// `AtomicRedBlackTree` was made with.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.tree.contains(value)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.tree.iter()
+    }
+
+    pub fn range<R>(&self, range: R) -> Range<'_, T> where R: RangeBounds<T> {
+        self.tree.range(range)
+    }
+
+    /// The version's tree, which shares its nodes with the versions before
+    /// and after it.
+    pub fn tree(&self) -> &RedBlackTree<T> {
+        &self.tree
+    }
+}
+
+impl<T> Clone for Snapshot<T> where T: PartialOrd + Clone {
+    fn clone(&self) -> Snapshot<T> {
+        Snapshot { version: self.version, tree: self.tree.clone() }
+    }
+}
+
+impl<T> Debug for Snapshot<T> where T: PartialOrd + Clone + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Snapshot").field("version", &self.version).field("tree", &self.tree).finish()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Snapshot<T> where T: PartialOrd + Clone {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use atomic::AtomicRedBlackTree;
+    use std::thread;
+
+    mod atomic_tree {
+        use super::*;
+
+        #[test]
+        fn hands_out_versions_that_dont_change() {
+            let tree = AtomicRedBlackTree::new();
+            tree.insert(2);
+            tree.insert(1);
+            let before = tree.snapshot();
+            expect!(tree.remove(&2)).to(be_true());
+            expect!(tree.remove(&2)).to(be_false());
+            tree.insert(3);
+
+            expect!(before.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2]));
+            expect!(tree.snapshot().iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 3]));
+            expect!(tree.contains(&3)).to(be_true());
+            expect!(tree.len()).to(be_equal_to(2));
+        }
+
+        #[test]
+        fn numbers_its_versions() {
+            let tree = AtomicRedBlackTree::new();
+            let first = tree.snapshot();
+            tree.insert(1);
+            tree.insert(2);
+            let third = tree.snapshot();
+            expect!(tree.remove(&5)).to(be_false());
+
+            expect!(first.version()).to(be_equal_to(0));
+            expect!(first.is_empty()).to(be_true());
+            expect!(third.version()).to(be_equal_to(2));
+            expect!(third.contains(&2)).to(be_true());
+            // even a change that changes nothing makes a new version
+            expect!(tree.version()).to(be_equal_to(3));
+            expect!(third.clone().version()).to(be_equal_to(2));
+        }
+
+        #[test]
+        fn lets_readers_run_alongside_writers() {
+            let tree = AtomicRedBlackTree::new();
+            thread::scope(|scope| {
+                for start in 0..2 {
+                    let tree = &tree;
+                    scope.spawn(move || {
+                        for value in 0..100 {
+                            tree.insert(value * 2 + start);
+                        }
+                    });
+                }
+                for _ in 0..2 {
+                    scope.spawn(|| {
+                        for _ in 0..100 {
+                            // whichever version a reader gets is a whole one
+                            let version = tree.snapshot();
+                            let values: Vec<_> = version.range(10..20).cloned().collect();
+                            expect!(values.windows(2).all(|pair| pair[0] < pair[1])).to(be_true());
+                        }
+                    });
+                }
+            });
+
+            expect!(tree.snapshot().iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..200).collect::<Vec<_>>()));
+        }
+    }
+}
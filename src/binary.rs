@@ -0,0 +1,218 @@
+// A compact binary format that keeps the exact shape of the tree, for
+// checkpointing it and restoring it later without any rebalancing.
+//
+// The format is the element count as a little endian u64, followed by
+// every node in pre-order as a tag byte and then the node's value:
+//   bit 0: the node is red
+//   bit 1: the node has a left child
+//   bit 2: the node has a right child
+// How the values themselves are encoded is up to the caller.
+
+use std::io::{self, Read, Write};
+
+use node::{Node, Link, OwnedNode, Pool, Color, NodeHelper};
+use allocator::Global;
+use stats::Recorder;
+use trace::Tracer;
+use RedBlackTree;
+
+const RED: u8 = 1;
+const HAS_LEFT: u8 = 1 << 1;
+const HAS_RIGHT: u8 = 1 << 2;
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Writes the tree, including its shape and colors, to `writer`.
+    /// `write_value` is called to encode each element.
+    pub fn write_structure<W, F>(&self, writer: &mut W, mut write_value: F) -> io::Result<()>
+    where W: Write, F: FnMut(&T, &mut W) -> io::Result<()> {
+        writer.write_all(&(self.count as u64).to_le_bytes())?;
+        write_link(&self.root, writer, &mut write_value)
+    }
+
+    /// Reads back a tree written by `write_structure`, node for node, in
+    /// O(n). The input is checked to be a valid red-black tree, and an
+    /// `InvalidData` error is returned if it isn't.
+    pub fn read_structure<R, F>(reader: &mut R, mut read_value: F) -> io::Result<RedBlackTree<T>>
+    where R: Read, F: FnMut(&mut R) -> io::Result<T> {
+        let mut count = [0; 8];
+        reader.read_exact(&mut count)?;
+        let count = u64::from_le_bytes(count) as usize;
+
+        let root = if count == 0 {
+            Link::none()
+        } else {
+            // a valid tree is never deeper than this, which also stops
+            // corrupt input from recursing without bound
+            let max_depth = 2 * (usize::BITS - count.leading_zeros()) as usize;
+            let mut reading = Reading { reader, read_value: &mut read_value, remaining: count, max_depth };
+            let (root, _) = reading.read_link(0)?;
+            if root.is_red() {
+                return Err(invalid("the root is red"));
+            }
+
+            root
+        };
+
+        if root.size() != count {
+            return Err(invalid("the node count doesn't match the header"));
+        }
+
+        let tree = RedBlackTree { root, count, pool: Pool::new(Global), stats: Recorder::new(), trace: Tracer::new() };
+        let mut values = tree.iter();
+        if let Some(mut previous) = values.next() {
+            for value in values {
+                if *value < *previous {
+                    return Err(invalid("the elements are out of order"));
+                }
+
+                previous = value;
+            }
+        }
+
+        Ok(tree)
+    }
+}
+
+fn write_link<T, W, F>(link: &Link<T>, writer: &mut W, write_value: &mut F) -> io::Result<()>
+where T: PartialOrd, W: Write, F: FnMut(&T, &mut W) -> io::Result<()> {
+    let node = match link.as_ref() {
+        Some(node) => node,
+        None => return Ok(()),
+    };
+
+    let mut tag = 0;
+    if node.is_red() {
+        tag |= RED;
+    }
+    if node.left().is_some() {
+        tag |= HAS_LEFT;
+    }
+    if node.right().is_some() {
+        tag |= HAS_RIGHT;
+    }
+
+    writer.write_all(&[tag])?;
+    write_value(node.value(), writer)?;
+    write_link(node.left(), writer, write_value)?;
+    write_link(node.right(), writer, write_value)
+}
+
+struct Reading<'a, R: 'a, F: 'a> {
+    reader: &'a mut R,
+    read_value: &'a mut F,
+    remaining: usize,
+    max_depth: usize,
+}
+
+impl<'a, R, F> Reading<'a, R, F> where R: Read {
+    // reads a subtree, checking the colors along the way, and hands it back
+    // with its black height, so the node above can compare its two sides
+    // without walking down them again
+    fn read_link<T>(&mut self, depth: usize) -> io::Result<(Link<T>, usize)>
+    where T: PartialOrd, F: FnMut(&mut R) -> io::Result<T> {
+        if self.remaining == 0 {
+            return Err(invalid("more nodes than the header says"));
+        }
+        if depth > self.max_depth {
+            return Err(invalid("the tree is too deep"));
+        }
+        self.remaining -= 1;
+
+        let mut tag = [0];
+        self.reader.read_exact(&mut tag)?;
+        let tag = tag[0];
+        if tag & !(RED | HAS_LEFT | HAS_RIGHT) != 0 {
+            return Err(invalid("unknown tag bits"));
+        }
+
+        let value = (self.read_value)(self.reader)?;
+        let (left, left_height) = if tag & HAS_LEFT != 0 { self.read_link(depth + 1)? } else { (Link::none(), 0) };
+        let (right, right_height) = if tag & HAS_RIGHT != 0 { self.read_link(depth + 1)? } else { (Link::none(), 0) };
+
+        if left_height != right_height {
+            return Err(invalid("unequal black heights"));
+        }
+        let red = tag & RED != 0;
+        if red && (left.is_red() || right.is_red()) {
+            return Err(invalid("a red node has a red child"));
+        }
+
+        let mut node = OwnedNode::new(Node::new(value));
+        node.set_color(if red { Color::Red } else { Color::Black });
+        node.set_left(left);
+        node.set_right(right);
+        node.update();
+        Ok((node.into(), left_height + !red as usize))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use std::io::{self, Read, Write};
+    use RedBlackTree;
+
+    pub fn write_u32(value: &u32, writer: &mut Vec<u8>) -> io::Result<()> {
+        writer.write_all(&value.to_le_bytes())
+    }
+
+    pub fn read_u32(reader: &mut &[u8]) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    mod structure {
+        use super::*;
+
+        #[test]
+        fn restores_the_exact_shape_and_colors() {
+            let mut tree = RedBlackTree::new();
+            for value in vec![8u32, 3, 10, 1, 6, 14, 4, 7, 13] {
+                tree.insert(value);
+            }
+
+            let mut bytes = vec![];
+            tree.write_structure(&mut bytes, write_u32).unwrap();
+            expect!(bytes.len()).to(be_equal_to(8 + 9 * 5));
+
+            let copy = RedBlackTree::read_structure(&mut &bytes[..], read_u32).unwrap();
+            expect!(copy.to_string()).to(be_equal_to(tree.to_string()));
+            expect!(copy.count).to(be_equal_to(9));
+        }
+
+        #[test]
+        fn round_trips_an_empty_tree() {
+            let mut bytes = vec![];
+            RedBlackTree::<u32>::new().write_structure(&mut bytes, write_u32).unwrap();
+            let copy = RedBlackTree::read_structure(&mut &bytes[..], read_u32).unwrap();
+            expect!(copy.root.is_none()).to(be_true());
+        }
+
+        #[test]
+        fn rejects_input_that_isnt_a_valid_tree() {
+            // a red root with no children
+            let bytes = [1, 0, 0, 0, 0, 0, 0, 0, 1, 5, 0, 0, 0];
+            let err = RedBlackTree::read_structure(&mut &bytes[..], read_u32).err().unwrap();
+            expect!(err.kind()).to(be_equal_to(io::ErrorKind::InvalidData));
+
+            // out of order: a black 5 with a red left child 7
+            let bytes = [2, 0, 0, 0, 0, 0, 0, 0, 2, 5, 0, 0, 0, 1, 7, 0, 0, 0];
+            let err = RedBlackTree::read_structure(&mut &bytes[..], read_u32).err().unwrap();
+            expect!(err.kind()).to(be_equal_to(io::ErrorKind::InvalidData));
+
+            // a black 5 with a black left child 3 and nothing on its right
+            let bytes = [2, 0, 0, 0, 0, 0, 0, 0, 2, 5, 0, 0, 0, 0, 3, 0, 0, 0];
+            let err = RedBlackTree::read_structure(&mut &bytes[..], read_u32).err().unwrap();
+            expect!(err.to_string()).to(be_equal_to("unequal black heights".to_string()));
+
+            // truncated
+            let bytes = [2, 0, 0, 0, 0, 0, 0, 0, 2, 5, 0, 0, 0];
+            expect!(RedBlackTree::read_structure(&mut &bytes[..], read_u32).is_err()).to(be_true());
+        }
+    }
+}
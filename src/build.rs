@@ -0,0 +1,142 @@
+// Bulk construction of a tree from elements that are already in order.
+// Splitting at the midpoint fills every level except possibly the last
+// one, so making the nodes in that last level red and everything else
+// black gives a valid red-black tree without a single rotation.
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+use node::{Node, Link, OwnedNode, Color, NodeHelper};
+use RedBlackTree;
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Like `from_sorted_iter`, but checks that `values` really is in
+    /// ascending order first, still in O(n). Equal elements may repeat.
+    pub fn try_from_sorted_vec(values: Vec<T>) -> Result<RedBlackTree<T>, NotSortedError> {
+        // incomparable pairs count as out of order too
+        let in_order = |pair: &[T]| matches!(pair[0].partial_cmp(&pair[1]), Some(Ordering::Less) | Some(Ordering::Equal));
+        if let Some(index) = values.windows(2).position(|pair| !in_order(pair)) {
+            return Err(NotSortedError { index: index + 1 });
+        }
+
+        Ok(RedBlackTree::from_sorted_vec(values))
+    }
+
+    /// Copies `values` into a new tree after checking that they're in
+    /// ascending order, in O(n).
+    pub fn try_from_sorted_slice(values: &[T]) -> Result<RedBlackTree<T>, NotSortedError> where T: Clone {
+        RedBlackTree::try_from_sorted_vec(values.to_vec())
+    }
+}
+
+// Sorts with a stable merge sort of the runs already in order, which only
+// ever asks whether one element is less than another. The standard
+// library's sorts may panic when the elements aren't totally ordered, as
+// with NaN; this one can only leave them out of order, which the caller
+// has to check for.
+pub fn sort<T>(values: Vec<T>) -> Vec<T> where T: PartialOrd {
+    let mut runs: Vec<Vec<T>> = Vec::new();
+    for value in values {
+        let starts_run = match runs.last().and_then(|run| run.last()) {
+            Some(last) => value < *last,
+            None => true,
+        };
+
+        if starts_run {
+            runs.push(Vec::new());
+        }
+        if let Some(run) = runs.last_mut() {
+            run.push(value);
+        }
+    }
+
+    while runs.len() > 1 {
+        let mut merged = Vec::with_capacity(runs.len() / 2 + 1);
+        let mut pairs = runs.into_iter();
+        while let Some(left) = pairs.next() {
+            merged.push(match pairs.next() {
+                Some(right) => merge(left, right),
+                None => left,
+            });
+        }
+
+        runs = merged;
+    }
+
+    runs.pop().unwrap_or_default()
+}
+
+fn merge<T>(left: Vec<T>, right: Vec<T>) -> Vec<T> where T: PartialOrd {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        // ties go to the left run, which keeps equal elements in order
+        let next = if *r < *l { right.next() } else { left.next() };
+        merged.extend(next);
+    }
+
+    merged.extend(left);
+    merged.extend(right);
+    merged
+}
+
+impl<T> Node<T> where T: PartialOrd {
+    // builds a tree out of the next `len` sorted elements of `iter` in O(n)
+    pub fn build_sorted<I>(iter: &mut I, len: usize) -> Link<T> where I: Iterator<Item = T> {
+        let mut full_levels = 0;
+        while (1usize << (full_levels + 1)) - 1 <= len {
+            full_levels += 1;
+        }
+
+        let mut root = Self::build_level(iter, len, 0, full_levels);
+        root.set_color(Color::Black);
+        root
+    }
+
+    fn build_level<I>(iter: &mut I, len: usize, depth: usize, red_depth: usize) -> Link<T> where I: Iterator<Item = T> {
+        if len == 0 {
+            return Link::none();
+        }
+
+        let left_len = (len - 1) / 2;
+        let left = Self::build_level(iter, left_len, depth + 1, red_depth);
+        // every caller knows exactly how many elements there are, but
+        // running out early just leaves a smaller tree
+        let value = match iter.next() {
+            Some(value) => value,
+            None => return left,
+        };
+        let right = Self::build_level(iter, len - 1 - left_len, depth + 1, red_depth);
+
+        let mut node = OwnedNode::new(Node::new(value));
+        node.set_left(left);
+        node.set_right(right);
+        node.set_color(if depth == red_depth { Color::Red } else { Color::Black });
+        node.update();
+        node.into()
+    }
+}
+
+/// Returned by the checked sorted constructors when an element is smaller
+/// than, or can't be compared with, the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotSortedError {
+    index: usize,
+}
+
+impl NotSortedError {
+    /// The position of the first element that's out of order.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl fmt::Display for NotSortedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "element {} is out of order", self.index)
+    }
+}
+
+impl Error for NotSortedError {}
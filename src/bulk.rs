@@ -0,0 +1,278 @@
+// Lots of changes to a tree at once, with the rebalancing saved for the
+// end. An insert or remove on its own walks down the tree and then recolors
+// and rotates its way back up, and for a batch that's a good part of the
+// tree's size, all of that costs more than reading the elements out and
+// building the tree again. So the editor only lists the changes it's given,
+// and when the scope ends they're sorted and merged with the tree's
+// elements in one pass, which goes straight into a balanced tree. The tree
+// is never out of balance where anyone can see it: it's left as it was
+// while the scope runs, and if the scope or a comparison panics it stays
+// that way.
+
+use node::Node;
+use {build, invariants, IntoIter, RedBlackTree};
+
+/// The changes being made in a `RedBlackTree::bulk_edit` scope.
+pub struct BulkEditor<'a, T> where T: PartialOrd + 'a {
+    tree: &'a RedBlackTree<T>,
+    inserts: Vec<T>,
+    removes: Vec<T>,
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Makes all the changes `edit` gives the editor by rebuilding the tree
+    /// once they're in, in O(n + k log k) for k changes, instead of
+    /// rebalancing after each one. Once k is a fair part of n, that beats
+    /// the O(k log n) of making them one at a time. Removes only take out
+    /// elements that were in the tree when the scope began, not ones
+    /// inserted in it, and inserted elements go after any equal to them.
+    /// Ones that can't be compared even with themselves, like NaN, go after
+    /// all the rest, as `insert` would put them.
+    pub fn bulk_edit<F, R>(&mut self, edit: F) -> R where F: FnOnce(&mut BulkEditor<'_, T>) -> R {
+        let (result, inserts, mut removes) = {
+            let mut editor = BulkEditor { tree: self, inserts: Vec::new(), removes: Vec::new() };
+            let result = edit(&mut editor);
+            (result, editor.inserts, editor.removes)
+        };
+
+        if inserts.is_empty() && removes.is_empty() {
+            return result;
+        }
+
+        // every comparison is made while the tree is still whole, so one
+        // that panics leaves it as it was: first where each change falls
+        // among the elements, and only then are they moved out
+        let inserts = sort(inserts);
+        removes.retain(|remove| remove.partial_cmp(remove).is_some());
+        let removes = build::sort(removes);
+        let mut plan = Vec::with_capacity(self.count);
+        {
+            let (mut insert, mut remove) = (0, 0);
+            for value in self.iter() {
+                // removes of elements the tree doesn't hold
+                while removes.get(remove).is_some_and(|remove| *remove < *value) {
+                    remove += 1;
+                }
+
+                let removed = removes.get(remove).is_some_and(|remove| *remove <= *value);
+                if removed {
+                    remove += 1;
+                }
+
+                let before = insert;
+                while inserts.get(insert).is_some_and(|insert| *insert < *value) {
+                    insert += 1;
+                }
+
+                plan.push((insert - before, removed));
+            }
+        }
+
+        let mut inserts = inserts.into_iter();
+        let mut values = Vec::with_capacity(self.count + inserts.len());
+        for (value, (before, removed)) in IntoIter::new(self.root.take(), self.count).zip(plan) {
+            values.extend(inserts.by_ref().take(before));
+            if !removed {
+                values.push(value);
+            }
+        }
+
+        values.extend(inserts);
+        self.count = values.len();
+        self.root = Node::build_sorted(&mut values.into_iter(), self.count);
+        invariants::verify(self, "bulk_edit");
+        result
+    }
+}
+
+impl<'a, T> BulkEditor<'a, T> where T: PartialOrd {
+    pub fn insert(&mut self, value: T) {
+        self.inserts.push(value);
+    }
+
+    /// Removes one element equal to `value` when the scope ends, if the
+    /// tree held one when it began that no earlier remove takes.
+    pub fn remove(&mut self, value: &T) where T: Clone {
+        self.removes.push(value.clone());
+    }
+
+    /// The number of changes waiting to be made.
+    pub fn pending(&self) -> usize {
+        self.inserts.len() + self.removes.len()
+    }
+
+    /// The tree as it was when the scope began, without any of the
+    /// editor's changes.
+    pub fn tree(&self) -> &RedBlackTree<T> {
+        self.tree
+    }
+}
+
+// in the order inserting them one at a time would leave them, with any that
+// can't be compared even with themselves, like NaN, after all the rest
+fn sort<T>(values: Vec<T>) -> Vec<T> where T: PartialOrd {
+    let (values, incomparable): (Vec<T>, Vec<T>) = values.into_iter().partition(|value| value.partial_cmp(value).is_some());
+    let mut values = build::sort(values);
+    values.extend(incomparable);
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use std::cell::Cell;
+    use std::cmp::Ordering;
+    use std::panic;
+    use RedBlackTree;
+
+    pub fn values(tree: &RedBlackTree<u32>) -> Vec<u32> {
+        tree.iter().cloned().collect()
+    }
+
+    mod bulk_edit {
+        use super::*;
+
+        #[test]
+        fn makes_the_same_changes_as_one_at_a_time() {
+            let mut tree: RedBlackTree<u32> = (0..1000).collect();
+            let mut expected = tree.clone();
+            let pending = tree.bulk_edit(|editor| {
+                for value in (0..1000).filter(|value| value % 3 == 0) {
+                    editor.remove(&value);
+                }
+
+                for value in (500..1500).rev() {
+                    editor.insert(value);
+                }
+
+                // only one copy of 1 to take out
+                editor.remove(&1);
+                editor.remove(&1);
+                editor.remove(&2000);
+                expect!(editor.tree().contains(&0)).to(be_true());
+                editor.pending()
+            });
+
+            for value in (0..1000).filter(|value| value % 3 == 0) {
+                expected.remove(&value);
+            }
+
+            expected.extend(500..1500);
+            expected.remove(&1);
+            expect!(pending).to(be_equal_to(1337));
+            expect!(values(&tree)).to(be_equal_to(values(&expected)));
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+        }
+
+        #[test]
+        fn removes_only_what_was_there_before_the_scope() {
+            let mut tree: RedBlackTree<u32> = (0..4).collect();
+            tree.bulk_edit(|editor| {
+                editor.insert(10);
+                editor.remove(&10);
+                editor.insert(2);
+                editor.remove(&2);
+            });
+
+            expect!(values(&tree)).to(be_equal_to(vec![0, 1, 2, 3, 10]));
+            tree.bulk_edit(|_| ());
+            expect!(values(&tree)).to(be_equal_to(vec![0, 1, 2, 3, 10]));
+        }
+
+        #[test]
+        fn leaves_the_tree_alone_if_the_scope_panics() {
+            let mut tree: RedBlackTree<u32> = (0..10).collect();
+            let before = tree.clone();
+            let edited = panic::catch_unwind(panic::AssertUnwindSafe(|| tree.bulk_edit(|editor| {
+                editor.remove(&3);
+                panic!("in the middle of editing");
+            })));
+
+            expect!(edited.is_err()).to(be_true());
+            expect!(tree.structural_eq(&before)).to(be_true());
+        }
+
+        #[test]
+        fn leaves_the_tree_as_it_was_when_a_comparison_panics() {
+            thread_local!(static FUSE: Cell<Option<usize>> = const { Cell::new(None) });
+
+            #[derive(Debug, Clone, PartialEq)]
+            struct Touchy(u32);
+            impl PartialOrd for Touchy {
+                fn partial_cmp(&self, other: &Touchy) -> Option<Ordering> {
+                    FUSE.with(|fuse| match fuse.get() {
+                        Some(0) => panic!("comparison failed"),
+                        Some(left) => fuse.set(Some(left - 1)),
+                        None => {},
+                    });
+                    self.0.partial_cmp(&other.0)
+                }
+            }
+
+            let before: Vec<u32> = (0..32).map(|value| value * 2).collect();
+            let expected: Vec<u32> = (0..32).map(|value| value * 2).filter(|value| value % 3 != 0).chain((0..8).map(|value| value * 8 + 1)).collect::<RedBlackTree<_>>().iter().cloned().collect();
+            let mut edited = false;
+            for fuse in 0.. {
+                let mut tree: RedBlackTree<_> = before.iter().cloned().map(Touchy).collect();
+                FUSE.with(|cell| cell.set(Some(fuse)));
+                let result = panic::catch_unwind(panic::AssertUnwindSafe(|| tree.bulk_edit(|editor| {
+                    for value in (0..8).rev() {
+                        editor.insert(Touchy(value * 8 + 1));
+                    }
+
+                    for value in (0..32).map(|value| value * 6) {
+                        editor.remove(&Touchy(value));
+                    }
+                })));
+                FUSE.with(|cell| cell.set(None));
+
+                let values: Vec<u32> = tree.iter().map(|value| value.0).collect();
+                expect!(tree.count).to(be_equal_to(tree.iter().len()));
+                expect!(tree.check_invariants().is_ok()).to(be_true());
+                edited = result.is_ok();
+                if edited {
+                    expect!(values).to(be_equal_to(expected));
+                    break;
+                }
+
+                // checking the tree afterwards compares elements too, and a
+                // panic there comes once the edit is done
+                if !(cfg!(feature = "debug-invariants") && values == expected) {
+                    expect!(values).to(be_equal_to(before.clone()));
+                }
+            }
+
+            expect!(edited).to(be_true());
+        }
+
+        #[test]
+        fn puts_elements_that_cant_be_compared_last() {
+            let mut tree: RedBlackTree<f64> = vec![1.0, 2.0, 3.0].into_iter().collect();
+            tree.bulk_edit(|editor| {
+                editor.remove(&f64::NAN);
+                editor.remove(&2.0);
+                editor.insert(f64::NAN);
+                editor.insert(2.5);
+                editor.insert(0.5);
+            });
+
+            expect!(format!("{:?}", tree.iter().collect::<Vec<_>>())).to(be_equal_to("[0.5, 1.0, 2.5, 3.0, NaN]"));
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+
+            // enough of them that the standard library's sort would give up
+            let mut tree = RedBlackTree::new();
+            tree.bulk_edit(|editor| {
+                for value in 0..50 {
+                    editor.insert(if value % 7 == 0 { f64::NAN } else { f64::from((value * 37) % 50) });
+                }
+            });
+
+            let numbers: Vec<f64> = tree.iter().cloned().filter(|value| !value.is_nan()).collect();
+            expect!(tree.iter().len()).to(be_equal_to(50));
+            expect!(tree.iter().skip(numbers.len()).all(|value| value.is_nan())).to(be_true());
+            expect!(numbers.windows(2).all(|pair| pair[0] < pair[1])).to(be_true());
+            tree.bulk_edit(|editor| editor.remove(&1.0));
+            expect!(tree.contains(&1.0)).to(be_false());
+        }
+    }
+}
@@ -0,0 +1,168 @@
+// Changes recorded as they're made to one tree, to be made again to
+// another. A replica that started out with the same elements as the tree
+// the changes were recorded on ends up with the same elements as it after
+// applying them, so keeping a replica in step only takes sending it the
+// changes, which serialize with the `serde` feature.
+//
+// Only changes that did something get recorded: a remove that didn't find
+// anything to remove isn't, and a recorded remove holds the element that
+// was actually taken out.
+
+use std::iter::FromIterator;
+use std::slice;
+use std::vec;
+
+use RedBlackTree;
+
+/// One recorded change.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change<T> {
+    Insert(T),
+    Remove(T),
+}
+
+/// A list of changes, in the order they were made, made by
+/// `Recording::finish` and made to a tree by `RedBlackTree::apply`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeSet<T> {
+    changes: Vec<Change<T>>,
+}
+
+impl<T> ChangeSet<T> {
+    pub const fn new() -> ChangeSet<T> {
+        ChangeSet { changes: Vec::new() }
+    }
+
+    pub fn push(&mut self, change: Change<T>) {
+        self.changes.push(change);
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Change<T>> {
+        self.changes.iter()
+    }
+}
+
+impl<T> Default for ChangeSet<T> {
+    fn default() -> ChangeSet<T> {
+        ChangeSet::new()
+    }
+}
+
+impl<T> FromIterator<Change<T>> for ChangeSet<T> {
+    fn from_iter<I>(iter: I) -> ChangeSet<T> where I: IntoIterator<Item = Change<T>> {
+        ChangeSet { changes: iter.into_iter().collect() }
+    }
+}
+
+impl<T> IntoIterator for ChangeSet<T> {
+    type Item = Change<T>;
+    type IntoIter = vec::IntoIter<Change<T>>;
+
+    fn into_iter(self) -> vec::IntoIter<Change<T>> {
+        self.changes.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ChangeSet<T> {
+    type Item = &'a Change<T>;
+    type IntoIter = slice::Iter<'a, Change<T>>;
+
+    fn into_iter(self) -> slice::Iter<'a, Change<T>> {
+        self.iter()
+    }
+}
+
+/// A tree whose changes are being recorded, made by
+/// `RedBlackTree::record`. The changes are made to the tree straight away.
+pub struct Recording<'a, T> where T: PartialOrd + 'a {
+    tree: &'a mut RedBlackTree<T>,
+    changes: ChangeSet<T>,
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Starts recording changes made to the tree through the `Recording`.
+    pub fn record(&mut self) -> Recording<'_, T> {
+        Recording { tree: self, changes: ChangeSet::new() }
+    }
+
+    /// Makes the changes to this tree, in order. A remove that doesn't
+    /// find an element equal to the one removed from the recorded tree
+    /// does nothing.
+    pub fn apply(&mut self, changes: ChangeSet<T>) {
+        for change in changes {
+            match change {
+                Change::Insert(value) => self.insert(value),
+                Change::Remove(value) => {
+                    self.remove(&value);
+                },
+            }
+        }
+    }
+}
+
+impl<'a, T> Recording<'a, T> where T: PartialOrd + Clone {
+    pub fn insert(&mut self, value: T) {
+        self.tree.insert(value.clone());
+        self.changes.push(Change::Insert(value));
+    }
+
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let removed = self.tree.remove(value);
+        if let Some(ref removed) = removed {
+            self.changes.push(Change::Remove(removed.clone()));
+        }
+
+        removed
+    }
+}
+
+impl<'a, T> Recording<'a, T> where T: PartialOrd {
+    /// The tree with the changes recorded so far made to it.
+    pub fn tree(&self) -> &RedBlackTree<T> {
+        self.tree
+    }
+
+    /// Stops recording, and returns the changes.
+    pub fn finish(self) -> ChangeSet<T> {
+        self.changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use changeset::Change;
+    use RedBlackTree;
+
+    mod changeset {
+        use super::*;
+
+        #[test]
+        fn makes_the_recorded_changes_again_on_a_replica() {
+            let mut primary: RedBlackTree<_> = (0..10).collect();
+            let mut replica = primary.clone();
+
+            let changes = {
+                let mut recording = primary.record();
+                recording.insert(12);
+                expect!(recording.remove(&4)).to(be_some().value(4));
+                expect!(recording.remove(&40)).to(be_none());
+                recording.insert(4);
+                recording.finish()
+            };
+            expect!(changes.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![Change::Insert(12), Change::Remove(4), Change::Insert(4)]));
+
+            replica.apply(changes);
+            expect!(replica.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(primary.iter().cloned().collect::<Vec<_>>()));
+            expect!(replica.check_invariants().is_ok()).to(be_true());
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Insertion that refuses an element rather than guess where it goes. With
+// only `PartialOrd`, an element like NaN compares as neither less than,
+// equal to nor greater than the others, and a plain insert files it to
+// the right of them, where later lookups can miss it.
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt::{self, Debug};
+
+use allocator::Allocator;
+use node::Follow;
+use {get_dir, RedBlackTree};
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// Inserts `value` unless it can't be compared with one of the
+    /// elements it would be placed by, and hands it back in that case.
+    /// Only the elements on the path down to its place get compared, so
+    /// in a tree that already holds incomparable elements this isn't a
+    /// promise that `value` is comparable with every one of them.
+    pub fn checked_insert(&mut self, value: T) -> Result<(), IncomparableError<T>> {
+        let mut link = &self.root;
+        while let Some(node) = link.as_ref() {
+            let ordering = value.partial_cmp(node.value());
+            if ordering.is_none() {
+                return Err(IncomparableError { value });
+            }
+
+            link = node.follow(get_dir(ordering == Some(Ordering::Less)));
+        }
+
+        self.insert(value);
+        Ok(())
+    }
+}
+
+/// Returned by `RedBlackTree::checked_insert`, holding the element that
+/// couldn't be placed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct IncomparableError<T> {
+    value: T,
+}
+
+impl<T> IncomparableError<T> {
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Debug for IncomparableError<T> where T: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IncomparableError").field("value", &self.value).finish()
+    }
+}
+
+impl<T> fmt::Display for IncomparableError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the element can't be compared with the elements in the tree")
+    }
+}
+
+impl<T> Error for IncomparableError<T> where T: Debug {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod checked_insert {
+        use super::*;
+
+        #[test]
+        fn hands_back_elements_it_cant_place() {
+            let mut tree = RedBlackTree::new();
+            for value in [2.0, 1.0, 3.0, 2.0] {
+                expect!(tree.checked_insert(value)).to(be_ok());
+            }
+
+            let error = tree.checked_insert(f64::NAN).unwrap_err();
+            expect!(error.into_value().is_nan()).to(be_true());
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1.0, 2.0, 2.0, 3.0]));
+        }
+
+        #[test]
+        fn places_an_element_in_an_empty_tree() {
+            let mut tree = RedBlackTree::new();
+            expect!(tree.checked_insert(f64::NAN)).to(be_ok());
+            expect!(tree.checked_insert(1.0).unwrap_err().to_string())
+                .to(be_equal_to("the element can't be compared with the elements in the tree"));
+        }
+    }
+}
@@ -0,0 +1,550 @@
+// A red-black tree of sorted runs ("chunks") of up to N elements each, in
+// the spirit of a B-tree's leaves. Every element of a chunk's left subtree
+// is <= the chunk's elements, and every element of its right subtree is >=
+// them. A lookup walks down the tree comparing against the ends of each
+// chunk, and only binary searches within the chunk it lands in, so there
+// are about N times fewer nodes to chase than in a plain `RedBlackTree`.
+//
+// The chunks are the elements of the arena's tree, positioned by rank
+// rather than by comparison, so they all sit in one buffer, with their
+// elements inline. Each node also counts the elements in its subtree's
+// chunks, which finds an element by its rank, and the rank of a value, in
+// O(log n). A full chunk is split in two before it takes another element,
+// and the new half goes in right after it, as does taking out a chunk
+// that's been emptied. Both are O(log n).
+
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::{BitAnd, BitOr, BitXor, Bound, RangeBounds, Sub};
+use std::slice;
+
+use arena::{self, Arena, ArenaNode, Augment};
+use inline::InlineVec;
+use ops::{self, Keep};
+
+type Chunks<T, const N: usize> = Arena<Chunk<T, N>, Count, Vec<ArenaNode<Chunk<T, N>, Count>>>;
+
+/// A sorted multiset with the same interface as `RedBlackTree`, but which
+/// keeps up to `N` elements in every node.
+pub struct ChunkedRedBlackTree<T, const N: usize = 16> where T: PartialOrd {
+    chunks: Chunks<T, N>,
+}
+
+// A chunk in the tree is never empty, and its elements are in ascending
+// order.
+struct Chunk<T, const N: usize> {
+    values: InlineVec<T, N>,
+}
+
+// the elements in the chunks of a subtree
+#[derive(Clone, Copy)]
+struct Count(usize);
+
+impl<T, const N: usize> Chunk<T, N> {
+    fn single(value: T) -> Chunk<T, N> {
+        let mut values = InlineVec::new();
+        values.insert(0, value);
+        Chunk { values }
+    }
+}
+
+impl<T, const N: usize> Augment<Chunk<T, N>> for Count {
+    fn pull_up(&mut self, chunk: &Chunk<T, N>, left: Option<&Count>, right: Option<&Count>) {
+        self.0 = left.map_or(0, |left| left.0) + chunk.values.len() + right.map_or(0, |right| right.0);
+    }
+}
+
+impl<T, const N: usize> ChunkedRedBlackTree<T, N> where T: PartialOrd {
+    pub const fn new() -> ChunkedRedBlackTree<T, N> {
+        assert!(N >= 2, "a chunk has to hold at least 2 elements");
+        ChunkedRedBlackTree { chunks: Arena::new(Vec::new()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.get(self.chunks.root()).map_or(0, |root| root.augment().0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.root().is_none()
+    }
+
+    pub fn insert(&mut self, value: T) {
+        self.place(value);
+    }
+
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let (index, position) = self.find(value)?;
+        let (value, emptied) = self.chunks.update_at(index, |chunk| {
+            let value = chunk.values.remove(position);
+            (value, chunk.values.is_empty())
+        })?;
+        if emptied {
+            self.chunks.remove_at(index);
+        }
+
+        Some(value)
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.find(value).is_some()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, N> {
+        Iter {
+            chunks: self.chunks.nodes(),
+            values: [].iter(),
+            remaining: self.len(),
+        }
+    }
+
+    /// The number of elements smaller than `value`, in O(log n).
+    pub fn rank(&self, value: &T) -> usize {
+        self.count_before(|probe| *probe < *value)
+    }
+
+    /// The elements that fall within `range`, in ascending order. Finding
+    /// where it starts and ends takes O(log n).
+    pub fn range<R>(&self, range: R) -> Iter<'_, T, N> where R: RangeBounds<T> {
+        let start = match range.start_bound() {
+            Bound::Included(start) => self.count_before(|probe| *probe < *start),
+            Bound::Excluded(start) => self.count_before(|probe| *probe <= *start),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => self.count_before(|probe| *probe <= *end),
+            Bound::Excluded(end) => self.count_before(|probe| *probe < *end),
+            Bound::Unbounded => self.len(),
+        };
+
+        self.iter_between(start, end)
+    }
+
+    // the elements from the one with `start` elements before it, up to
+    // but not including the one with `end` before it
+    fn iter_between(&self, start: usize, end: usize) -> Iter<'_, T, N> {
+        // which chunk the start is in, and where in it
+        let mut chunk = 0;
+        let mut position = start;
+        let mut link = self.chunks.root();
+        while let Some(node) = self.chunks.get(link) {
+            let before = self.chunks.get(node.left()).map_or(0, |left| left.augment().0);
+            let len = node.value().values.len();
+            if position < before {
+                link = node.left();
+            } else if position < before + len {
+                chunk += self.chunks.size(node.left());
+                position -= before;
+                break;
+            } else {
+                chunk += self.chunks.size(node.left()) + 1;
+                position -= before + len;
+                link = node.right();
+            }
+        }
+
+        let mut chunks = self.chunks.nodes_from(chunk);
+        let values = match chunks.next() {
+            Some(node) if start < end => node.value().values.as_slice()[position..].iter(),
+            _ => [].iter(),
+        };
+
+        Iter { chunks, values, remaining: end.saturating_sub(start) }
+    }
+
+    // how many elements `before` holds for, where it holds for the
+    // smaller elements and not for the rest
+    fn count_before<F>(&self, before: F) -> usize where F: Fn(&T) -> bool {
+        let mut count = 0;
+        let mut link = self.chunks.root();
+        while let Some(node) = self.chunks.get(link) {
+            let left = self.chunks.get(node.left()).map_or(0, |left| left.augment().0);
+            let values = node.value().values.as_slice();
+            if !before(&values[0]) {
+                link = node.left();
+            } else if before(&values[values.len() - 1]) {
+                count += left + values.len();
+                link = node.right();
+            } else {
+                return count + left + values.partition_point(|probe| before(probe));
+            }
+        }
+
+        count
+    }
+
+    // where the value goes is worked out before anything is changed, so a
+    // comparison that panics leaves the tree as it was
+    fn place(&mut self, value: T) {
+        let index = match self.chunk_for(&value) {
+            Some(index) => index,
+            None => return self.insert_chunk(0, Chunk::single(value)),
+        };
+
+        let full = match self.chunks.select(index).and_then(|id| self.chunks.node(id)) {
+            Some(node) => node.value().values.is_full(),
+            None => return self.insert_chunk(index, Chunk::single(value)),
+        };
+        if !full {
+            self.chunks.update_at(index, |chunk| {
+                let position = Self::position_for(chunk.values.as_slice(), &value);
+                chunk.values.insert(position, value);
+            });
+            return;
+        }
+
+        // either half can take the value, as long as it stays in order
+        let upper = self.chunks.update_at(index, |chunk| {
+            let values = &mut chunk.values;
+            let (lower, upper) = values.as_slice().split_at(N / 2);
+            let in_lower = value < upper[0];
+            let position = Self::position_for(if in_lower { lower } else { upper }, &value);
+            let mut upper = values.split_off(N / 2);
+            if in_lower {
+                values.insert(position, value);
+            } else {
+                upper.insert(position, value);
+            }
+
+            upper
+        });
+
+        if let Some(upper) = upper {
+            self.insert_chunk(index + 1, Chunk { values: upper });
+        }
+    }
+
+    // the rank of the chunk `value` belongs in, which is the last chunk on
+    // the way down, one way or another next to where the value goes
+    fn chunk_for(&self, value: &T) -> Option<usize> {
+        let mut index = None;
+        let mut offset = 0;
+        let mut link = self.chunks.root();
+        while let Some(node) = self.chunks.get(link) {
+            let rank = offset + self.chunks.size(node.left());
+            let values = node.value().values.as_slice();
+            index = Some(rank);
+            if *value < values[0] {
+                link = node.left();
+            } else if value.partial_cmp(&values[values.len() - 1]) != Some(Ordering::Less) {
+                offset = rank + 1;
+                link = node.right();
+            } else {
+                break;
+            }
+        }
+
+        index
+    }
+
+    // the rank of the chunk holding an element equal to `value`, and the
+    // element's position in it
+    fn find(&self, value: &T) -> Option<(usize, usize)> {
+        let mut offset = 0;
+        let mut link = self.chunks.root();
+        while let Some(node) = self.chunks.get(link) {
+            let rank = offset + self.chunks.size(node.left());
+            let values = node.value().values.as_slice();
+            if *value < values[0] {
+                link = node.left();
+            } else if values[values.len() - 1] < *value {
+                offset = rank + 1;
+                link = node.right();
+            } else {
+                let position = values.partition_point(|probe| *probe < *value);
+                return if position < values.len() && values[position] == *value {
+                    Some((rank, position))
+                } else {
+                    None
+                };
+            }
+        }
+
+        None
+    }
+
+    // equal elements go after the ones already there, like in the tree
+    fn position_for(values: &[T], value: &T) -> usize {
+        values.partition_point(|probe| value.partial_cmp(probe) != Some(Ordering::Less))
+    }
+
+    fn insert_chunk(&mut self, index: usize, chunk: Chunk<T, N>) {
+        let count = Count(chunk.values.len());
+        self.chunks.push_at(index, chunk, count);
+    }
+
+    // fills chunks from `values`, which are in ascending order, one after
+    // the other
+    fn from_sorted_vec(values: Vec<T>) -> ChunkedRedBlackTree<T, N> {
+        let mut tree = ChunkedRedBlackTree::new();
+        let mut chunk = InlineVec::new();
+        for value in values {
+            if let Err(value) = chunk.push(value) {
+                let full = mem::replace(&mut chunk, InlineVec::new());
+                tree.insert_chunk(tree.chunks.len(), Chunk { values: full });
+                chunk.insert(0, value);
+            }
+        }
+        if !chunk.is_empty() {
+            tree.insert_chunk(tree.chunks.len(), Chunk { values: chunk });
+        }
+
+        tree
+    }
+}
+
+// the elements `keep` keeps of the two trees, as a set operator would
+fn merge<T, const N: usize>(left: &ChunkedRedBlackTree<T, N>, right: &ChunkedRedBlackTree<T, N>, keep: Keep) -> ChunkedRedBlackTree<T, N>
+where T: PartialOrd + Clone {
+    let values = ops::merge_values(left.iter(), right.iter(), keep);
+    if values.windows(2).all(|pair| pair[0] <= pair[1]) {
+        ChunkedRedBlackTree::from_sorted_vec(values)
+    } else {
+        values.into_iter().collect()
+    }
+}
+
+impl<T, const N: usize> BitOr<&ChunkedRedBlackTree<T, N>> for &ChunkedRedBlackTree<T, N> where T: PartialOrd + Clone {
+    type Output = ChunkedRedBlackTree<T, N>;
+
+    fn bitor(self, other: &ChunkedRedBlackTree<T, N>) -> ChunkedRedBlackTree<T, N> {
+        merge(self, other, Keep::UNION)
+    }
+}
+
+impl<T, const N: usize> BitAnd<&ChunkedRedBlackTree<T, N>> for &ChunkedRedBlackTree<T, N> where T: PartialOrd + Clone {
+    type Output = ChunkedRedBlackTree<T, N>;
+
+    fn bitand(self, other: &ChunkedRedBlackTree<T, N>) -> ChunkedRedBlackTree<T, N> {
+        merge(self, other, Keep::INTERSECTION)
+    }
+}
+
+impl<T, const N: usize> Sub<&ChunkedRedBlackTree<T, N>> for &ChunkedRedBlackTree<T, N> where T: PartialOrd + Clone {
+    type Output = ChunkedRedBlackTree<T, N>;
+
+    fn sub(self, other: &ChunkedRedBlackTree<T, N>) -> ChunkedRedBlackTree<T, N> {
+        merge(self, other, Keep::DIFFERENCE)
+    }
+}
+
+impl<T, const N: usize> BitXor<&ChunkedRedBlackTree<T, N>> for &ChunkedRedBlackTree<T, N> where T: PartialOrd + Clone {
+    type Output = ChunkedRedBlackTree<T, N>;
+
+    fn bitxor(self, other: &ChunkedRedBlackTree<T, N>) -> ChunkedRedBlackTree<T, N> {
+        merge(self, other, Keep::SYMMETRIC_DIFFERENCE)
+    }
+}
+
+impl<T, const N: usize> Default for ChunkedRedBlackTree<T, N> where T: PartialOrd {
+    fn default() -> ChunkedRedBlackTree<T, N> {
+        ChunkedRedBlackTree::new()
+    }
+}
+
+impl<T, const N: usize> Debug for ChunkedRedBlackTree<T, N> where T: PartialOrd + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for ChunkedRedBlackTree<T, N> where T: PartialOrd {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> ChunkedRedBlackTree<T, N> {
+        let mut tree = ChunkedRedBlackTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T, const N: usize> Extend<T> for ChunkedRedBlackTree<T, N> where T: PartialOrd {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a ChunkedRedBlackTree<T, N> where T: PartialOrd {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, N>;
+
+    fn into_iter(self) -> Iter<'a, T, N> {
+        self.iter()
+    }
+}
+
+/// Iterates over a `ChunkedRedBlackTree`, or a range of it, in ascending
+/// order.
+pub struct Iter<'a, T, const N: usize> where T: PartialOrd + 'a {
+    chunks: arena::Nodes<'a, Chunk<T, N>, Count>,
+    values: slice::Iter<'a, T>,
+    remaining: usize,
+}
+
+impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> where T: PartialOrd {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        loop {
+            if let Some(value) = self.values.next() {
+                self.remaining -= 1;
+                return Some(value);
+            }
+
+            self.values = self.chunks.next()?.value().values.as_slice().iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T, const N: usize> ExactSizeIterator for Iter<'a, T, N> where T: PartialOrd {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use chunked::ChunkedRedBlackTree;
+    use std::cell::Cell;
+    use std::cmp::Ordering;
+    use std::panic;
+
+    pub fn values<const N: usize>(tree: &ChunkedRedBlackTree<u32, N>) -> Vec<u32> {
+        tree.iter().cloned().collect()
+    }
+
+    mod chunked_tree {
+        use super::*;
+
+        #[test]
+        fn keeps_its_elements_in_order_across_chunks() {
+            let mut tree = ChunkedRedBlackTree::<_, 4>::new();
+            for value in vec![5, 1, 4, 1, 3, 9, 2, 6, 5, 3, 5] {
+                tree.insert(value);
+            }
+
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 1, 2, 3, 3, 4, 5, 5, 5, 6, 9]));
+            expect!(tree.len()).to(be_equal_to(11));
+            expect!(tree.chunks.nodes().all(|node| node.value().values.len() <= 4)).to(be_true());
+            expect!(tree.contains(&6)).to(be_true());
+            expect!(tree.contains(&7)).to(be_false());
+        }
+
+        #[test]
+        fn removes_elements_and_drops_emptied_chunks() {
+            let mut tree: ChunkedRedBlackTree<_, 4> = (0..100).collect();
+            for value in 0..100 {
+                if value % 5 != 0 {
+                    expect!(tree.remove(&value)).to(be_some().value(value));
+                }
+            }
+
+            expect!(tree.remove(&1)).to(be_none());
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..20).map(|value| value * 5).collect::<Vec<_>>()));
+            expect!(tree.chunks.nodes().all(|node| !node.value().values.is_empty())).to(be_true());
+
+            for value in 0..20 {
+                tree.remove(&(value * 5));
+            }
+            expect!(tree.is_empty()).to(be_true());
+            expect!(tree.chunks.root().is_none()).to(be_true());
+        }
+
+        #[test]
+        fn keeps_its_length_right_when_a_comparison_panics() {
+            thread_local!(static FUSE: Cell<Option<usize>> = const { Cell::new(None) });
+
+            #[derive(Debug, PartialEq)]
+            struct Touchy(u32);
+            impl PartialOrd for Touchy {
+                fn partial_cmp(&self, other: &Touchy) -> Option<Ordering> {
+                    FUSE.with(|fuse| match fuse.get() {
+                        Some(0) => panic!("comparison failed"),
+                        Some(left) => fuse.set(Some(left - 1)),
+                        None => {},
+                    });
+                    self.0.partial_cmp(&other.0)
+                }
+            }
+
+            // the copies of 31 keep going into the same chunk, which fills
+            // up and splits along the way, so the panics come both before
+            // and after a split is decided on
+            let mut tree: ChunkedRedBlackTree<_, 4> = (0..32).map(|value| Touchy(value * 2)).collect();
+            let mut expected: Vec<u32> = (0..32).map(|value| value * 2).collect();
+            for fuse in (0..12).cycle().take(48) {
+                FUSE.with(|cell| cell.set(Some(fuse)));
+                let inserted = panic::catch_unwind(panic::AssertUnwindSafe(|| tree.insert(Touchy(31))));
+                FUSE.with(|cell| cell.set(None));
+
+                if inserted.is_ok() {
+                    let position = expected.partition_point(|&value| value <= 31);
+                    expected.insert(position, 31);
+                }
+                expect!(tree.iter().map(|value| value.0).collect::<Vec<_>>()).to(be_equal_to(expected.clone()));
+                expect!(tree.len()).to(be_equal_to(expected.len()));
+            }
+        }
+    }
+
+    mod rank_and_range {
+        use super::*;
+
+        #[test]
+        fn counts_the_smaller_elements_across_chunks() {
+            let tree: ChunkedRedBlackTree<u32, 4> = (0..50).map(|value| value / 2 * 2).collect();
+            expect!(tree.rank(&0)).to(be_equal_to(0));
+            expect!(tree.rank(&1)).to(be_equal_to(2));
+            expect!(tree.rank(&10)).to(be_equal_to(10));
+            expect!(tree.rank(&11)).to(be_equal_to(12));
+            expect!(tree.rank(&100)).to(be_equal_to(50));
+        }
+
+        #[test]
+        fn iterates_over_just_the_elements_in_range() {
+            let tree: ChunkedRedBlackTree<u32, 4> = (0..50).map(|value| value / 2 * 2).collect();
+            let model: Vec<u32> = (0..50).map(|value| value / 2 * 2).collect();
+            let bounds = [0, 1, 7, 8, 9, 30, 47, 48, 60];
+            for &start in &bounds {
+                for &end in &bounds {
+                    let expected: Vec<u32> = model.iter().cloned().filter(|value| (start..end).contains(value)).collect();
+                    expect!(tree.range(start..end).cloned().collect::<Vec<_>>()).to(be_equal_to(expected.clone()));
+                    expect!(tree.range(start..end).len()).to(be_equal_to(expected.len()));
+
+                    let expected: Vec<u32> = model.iter().cloned().filter(|value| (start..=end).contains(value)).collect();
+                    expect!(tree.range(start..=end).cloned().collect::<Vec<_>>()).to(be_equal_to(expected));
+                }
+            }
+
+            expect!(tree.range(..).len()).to(be_equal_to(50));
+            expect!(ChunkedRedBlackTree::<u32, 4>::new().range(1..5).next()).to(be_none());
+        }
+    }
+
+    mod set_operators {
+        use super::*;
+
+        #[test]
+        fn pair_up_equal_elements_like_a_multiset() {
+            let left: ChunkedRedBlackTree<u32, 4> = vec![1, 2, 2, 3, 5, 8, 8, 8, 13].into_iter().collect();
+            let right: ChunkedRedBlackTree<u32, 4> = vec![2, 3, 3, 8, 21].into_iter().collect();
+            expect!(values(&(&left | &right))).to(be_equal_to(vec![1, 2, 2, 3, 3, 5, 8, 8, 8, 13, 21]));
+            expect!(values(&(&left & &right))).to(be_equal_to(vec![2, 3, 8]));
+            expect!(values(&(&left - &right))).to(be_equal_to(vec![1, 2, 5, 8, 8, 13]));
+            expect!(values(&(&left ^ &right))).to(be_equal_to(vec![1, 2, 3, 5, 8, 8, 13, 21]));
+
+            // the result is a tree like any other
+            let mut union = &left | &right;
+            union.insert(4);
+            expect!(union.len()).to(be_equal_to(12));
+            expect!(union.rank(&5)).to(be_equal_to(6));
+            union.chunks.assert_balanced();
+        }
+    }
+}
@@ -0,0 +1,773 @@
+// A stand-in for `std::collections::BTreeSet` that's backed by a
+// `RedBlackTree`. Each method has the same name, arguments and return type
+// as the std one, including the lookups that go through `Borrow`. The
+// iterators it hands out implement the same traits, and so does the set,
+// so moving code over to the tree only means changing an import:
+//
+//     use red_black_tree::compat::BTreeSet;
+//
+// It's a set, unlike the tree, which is a multiset. Like std's, inserting
+// an element equal to one that's already there leaves the set unchanged.
+// The one thing a type swap can trip over is that the struct needs
+// `T: PartialOrd` where std's needs nothing, so a generic type holding one
+// has to ask for that bound.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::iter::{FromIterator, FusedIterator, Peekable};
+use std::mem;
+use std::ops::{BitAnd, BitOr, BitXor, Bound, RangeBounds, Sub};
+use std::vec;
+
+use node::{Dir, Follow, Node, NodeHelper};
+use RedBlackTree;
+
+/// An ordered set with the API of `std::collections::BTreeSet`.
+pub struct BTreeSet<T> where T: PartialOrd {
+    tree: RedBlackTree<T>,
+}
+
+// which way to go from `node` to find `value`, or None if it's a match
+fn search<T, Q>(value: &Q, node: &Node<T>) -> Option<Dir> where T: Borrow<Q> + PartialOrd, Q: Ord + ?Sized {
+    match value.cmp(node.value().borrow()) {
+        Ordering::Less => Some(Dir::Left),
+        Ordering::Equal => None,
+        Ordering::Greater => Some(Dir::Right),
+    }
+}
+
+impl<T> BTreeSet<T> where T: PartialOrd {
+    pub const fn new() -> BTreeSet<T> {
+        BTreeSet { tree: RedBlackTree::new() }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::between(&self.tree, 0, self.tree.count)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.count == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.tree = RedBlackTree::new();
+    }
+}
+
+impl<T> BTreeSet<T> where T: Ord {
+    pub fn contains<Q>(&self, value: &Q) -> bool where T: Borrow<Q>, Q: Ord + ?Sized {
+        self.get(value).is_some()
+    }
+
+    pub fn get<Q>(&self, value: &Q) -> Option<&T> where T: Borrow<Q>, Q: Ord + ?Sized {
+        let mut link = &self.tree.root;
+        while let Some(node) = link.as_ref() {
+            match search(value, node) {
+                Some(dir) => link = node.follow(dir),
+                None => return Some(node.value()),
+            }
+        }
+
+        None
+    }
+
+    /// Adds `value` unless the set already holds an equal element, and
+    /// says whether it did.
+    pub fn insert(&mut self, value: T) -> bool {
+        if self.contains(&value) {
+            return false;
+        }
+
+        self.tree.insert(value);
+        true
+    }
+
+    /// Adds `value`, taking out and returning an equal element if there
+    /// was one.
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        let replaced = self.take(&value);
+        self.tree.insert(value);
+        replaced
+    }
+
+    pub fn remove<Q>(&mut self, value: &Q) -> bool where T: Borrow<Q>, Q: Ord + ?Sized {
+        self.take(value).is_some()
+    }
+
+    pub fn take<Q>(&mut self, value: &Q) -> Option<T> where T: Borrow<Q>, Q: Ord + ?Sized {
+        self.tree.remove_where(|node| search(value, node))
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.tree.root.as_ref().map(|root| root.leftmost().value())
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.tree.root.as_ref().map(|root| root.rightmost().value())
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.tree.remove_where(|node| if node.left().is_some() { Some(Dir::Left) } else { None })
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.tree.remove_where(|node| if node.right().is_some() { Some(Dir::Right) } else { None })
+    }
+
+    /// Keeps only the elements `f` returns true for, rebuilding the tree
+    /// in one go in O(n).
+    pub fn retain<F>(&mut self, mut f: F) where F: FnMut(&T) -> bool {
+        let tree = mem::take(&mut self.tree);
+        self.tree = RedBlackTree::from_sorted_iter(tree.into_iter().filter(|value| f(value)));
+    }
+
+    /// Moves all of `other`'s elements into this set, leaving `other`
+    /// empty. Where both hold equal elements, `other`'s is kept.
+    pub fn append(&mut self, other: &mut BTreeSet<T>) {
+        let mut mine = mem::take(&mut self.tree).into_iter().peekable();
+        let mut theirs = mem::take(&mut other.tree).into_iter().peekable();
+        let mut values = Vec::with_capacity(mine.len() + theirs.len());
+        loop {
+            let take_mine = match (mine.peek(), theirs.peek()) {
+                (Some(a), Some(b)) => match a.cmp(b) {
+                    Ordering::Less => true,
+                    Ordering::Equal => {
+                        mine.next();
+                        false
+                    },
+                    Ordering::Greater => false,
+                },
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+
+            values.extend(if take_mine { mine.next() } else { theirs.next() });
+        }
+
+        self.tree = RedBlackTree::from_sorted_vec(values);
+    }
+
+    /// Moves the elements greater than or equal to `value` into a new set,
+    /// in O(log n).
+    pub fn split_off<Q>(&mut self, value: &Q) -> BTreeSet<T> where T: Borrow<Q>, Q: Ord + ?Sized {
+        let index = self.rank(value, false);
+        if index == 0 {
+            return BTreeSet { tree: mem::take(&mut self.tree) };
+        }
+
+        BTreeSet { tree: self.tree.split_at_index(index) }
+    }
+
+    /// The elements within `range`, in ascending order. It panics like
+    /// std's does when the range starts after it ends, or when it starts
+    /// and ends at the same element and leaves it out.
+    pub fn range<K, R>(&self, range: R) -> Range<'_, T> where K: Ord + ?Sized, T: Borrow<K>, R: RangeBounds<K> {
+        match (range.start_bound(), range.end_bound()) {
+            (Bound::Excluded(start), Bound::Excluded(end)) if start == end => {
+                panic!("range start and end are equal and excluded in BTreeSet")
+            },
+            (Bound::Included(start) | Bound::Excluded(start), Bound::Included(end) | Bound::Excluded(end)) if start > end => {
+                panic!("range start is greater than range end in BTreeSet")
+            },
+            _ => (),
+        }
+
+        let start = match range.start_bound() {
+            Bound::Included(start) => self.rank(start, false),
+            Bound::Excluded(start) => self.rank(start, true),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => self.rank(end, true),
+            Bound::Excluded(end) => self.rank(end, false),
+            Bound::Unbounded => self.tree.count,
+        };
+
+        Range { iter: Iter::between(&self.tree, start, end) }
+    }
+
+    pub fn difference<'a>(&'a self, other: &'a BTreeSet<T>) -> Difference<'a, T> {
+        Difference { pairs: Pairs::new(self, other) }
+    }
+
+    pub fn symmetric_difference<'a>(&'a self, other: &'a BTreeSet<T>) -> SymmetricDifference<'a, T> {
+        SymmetricDifference { pairs: Pairs::new(self, other) }
+    }
+
+    pub fn intersection<'a>(&'a self, other: &'a BTreeSet<T>) -> Intersection<'a, T> {
+        Intersection { pairs: Pairs::new(self, other) }
+    }
+
+    pub fn union<'a>(&'a self, other: &'a BTreeSet<T>) -> Union<'a, T> {
+        Union { pairs: Pairs::new(self, other) }
+    }
+
+    pub fn is_disjoint(&self, other: &BTreeSet<T>) -> bool {
+        self.intersection(other).next().is_none()
+    }
+
+    pub fn is_subset(&self, other: &BTreeSet<T>) -> bool {
+        self.len() <= other.len() && self.difference(other).next().is_none()
+    }
+
+    pub fn is_superset(&self, other: &BTreeSet<T>) -> bool {
+        other.is_subset(self)
+    }
+
+    // the number of elements less than `value`, or with `inclusive` the
+    // number less than or equal to it
+    fn rank<Q>(&self, value: &Q, inclusive: bool) -> usize where T: Borrow<Q>, Q: Ord + ?Sized {
+        let mut rank = 0;
+        let mut link = &self.tree.root;
+        while let Some(node) = link.as_ref() {
+            let before = match node.value().borrow().cmp(value) {
+                Ordering::Less => true,
+                Ordering::Equal => inclusive,
+                Ordering::Greater => false,
+            };
+
+            if before {
+                rank += node.left().size() + 1;
+                link = node.right();
+            } else {
+                link = node.left();
+            }
+        }
+
+        rank
+    }
+}
+
+impl<T> Clone for BTreeSet<T> where T: PartialOrd + Clone {
+    fn clone(&self) -> BTreeSet<T> {
+        BTreeSet { tree: self.tree.clone() }
+    }
+}
+
+impl<T> Default for BTreeSet<T> where T: PartialOrd {
+    fn default() -> BTreeSet<T> {
+        BTreeSet::new()
+    }
+}
+
+impl<T> Debug for BTreeSet<T> where T: PartialOrd + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> PartialEq for BTreeSet<T> where T: PartialOrd {
+    fn eq(&self, other: &BTreeSet<T>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T> Eq for BTreeSet<T> where T: Ord {}
+
+impl<T> PartialOrd for BTreeSet<T> where T: PartialOrd {
+    fn partial_cmp(&self, other: &BTreeSet<T>) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T> Ord for BTreeSet<T> where T: Ord {
+    fn cmp(&self, other: &BTreeSet<T>) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T> Hash for BTreeSet<T> where T: PartialOrd + Hash {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        state.write_usize(self.len());
+        for value in self {
+            value.hash(state);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for BTreeSet<T> where T: Ord {
+    fn from_iter<I>(iter: I) -> BTreeSet<T> where I: IntoIterator<Item = T> {
+        let mut values: Vec<T> = iter.into_iter().collect();
+        // of equal elements the first is kept, since the sort is stable
+        values.sort();
+        values.dedup();
+        BTreeSet { tree: RedBlackTree::from_sorted_vec(values) }
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for BTreeSet<T> where T: Ord {
+    fn from(values: [T; N]) -> BTreeSet<T> {
+        IntoIterator::into_iter(values).collect()
+    }
+}
+
+impl<T> Extend<T> for BTreeSet<T> where T: Ord {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item = T> {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<'a, T> Extend<&'a T> for BTreeSet<T> where T: Ord + Copy + 'a {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item = &'a T> {
+        self.extend(iter.into_iter().cloned());
+    }
+}
+
+impl<T> IntoIterator for BTreeSet<T> where T: PartialOrd {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        let values: Vec<T> = self.tree.into_iter().collect();
+        IntoIter { values: values.into_iter() }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BTreeSet<T> where T: PartialOrd {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> BitOr<&BTreeSet<T>> for &BTreeSet<T> where T: Ord + Clone {
+    type Output = BTreeSet<T>;
+
+    fn bitor(self, other: &BTreeSet<T>) -> BTreeSet<T> {
+        BTreeSet { tree: RedBlackTree::from_sorted_iter(self.union(other).cloned()) }
+    }
+}
+
+impl<T> BitAnd<&BTreeSet<T>> for &BTreeSet<T> where T: Ord + Clone {
+    type Output = BTreeSet<T>;
+
+    fn bitand(self, other: &BTreeSet<T>) -> BTreeSet<T> {
+        BTreeSet { tree: RedBlackTree::from_sorted_iter(self.intersection(other).cloned()) }
+    }
+}
+
+impl<T> BitXor<&BTreeSet<T>> for &BTreeSet<T> where T: Ord + Clone {
+    type Output = BTreeSet<T>;
+
+    fn bitxor(self, other: &BTreeSet<T>) -> BTreeSet<T> {
+        BTreeSet { tree: RedBlackTree::from_sorted_iter(self.symmetric_difference(other).cloned()) }
+    }
+}
+
+impl<T> Sub<&BTreeSet<T>> for &BTreeSet<T> where T: Ord + Clone {
+    type Output = BTreeSet<T>;
+
+    fn sub(self, other: &BTreeSet<T>) -> BTreeSet<T> {
+        BTreeSet { tree: RedBlackTree::from_sorted_iter(self.difference(other).cloned()) }
+    }
+}
+
+/// Iterates over a `BTreeSet`'s elements in order, from either end.
+pub struct Iter<'a, T> where T: PartialOrd + 'a {
+    root: Option<&'a Node<T>>,
+    front: Option<&'a Node<T>>,
+    back: Option<&'a Node<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iter<'a, T> where T: PartialOrd {
+    // the elements at sorted positions `start` up to but not including
+    // `end`
+    fn between(tree: &'a RedBlackTree<T>, start: usize, end: usize) -> Iter<'a, T> {
+        let root = tree.root.as_ref();
+        match root {
+            Some(root) if start < end => Iter {
+                root: Some(root),
+                front: root.select(start),
+                back: root.select(end - 1),
+                remaining: end - start,
+            },
+            _ => Iter { root, front: None, back: None, remaining: 0 },
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> where T: PartialOrd {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.front?;
+        self.front = self.root.and_then(|root| node.successor(root));
+        self.remaining -= 1;
+        Some(node.value())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    fn last(mut self) -> Option<&'a T> {
+        self.next_back()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> where T: PartialOrd {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.back?;
+        self.back = self.root.and_then(|root| node.predecessor(root));
+        self.remaining -= 1;
+        Some(node.value())
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> where T: PartialOrd {}
+
+impl<'a, T> FusedIterator for Iter<'a, T> where T: PartialOrd {}
+
+impl<'a, T> Clone for Iter<'a, T> where T: PartialOrd {
+    fn clone(&self) -> Iter<'a, T> {
+        Iter { ..*self }
+    }
+}
+
+impl<'a, T> Debug for Iter<'a, T> where T: PartialOrd + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// Iterates over the elements of a `BTreeSet` within a range, made by
+/// `BTreeSet::range`.
+pub struct Range<'a, T> where T: PartialOrd + 'a {
+    iter: Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for Range<'a, T> where T: PartialOrd {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    fn last(self) -> Option<&'a T> {
+        self.iter.last()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Range<'a, T> where T: PartialOrd {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a, T> FusedIterator for Range<'a, T> where T: PartialOrd {}
+
+impl<'a, T> Clone for Range<'a, T> where T: PartialOrd {
+    fn clone(&self) -> Range<'a, T> {
+        Range { iter: self.iter.clone() }
+    }
+}
+
+impl<'a, T> Debug for Range<'a, T> where T: PartialOrd + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.iter.fmt(f)
+    }
+}
+
+/// Takes the elements out of a `BTreeSet` in order, from either end.
+pub struct IntoIter<T> {
+    values: vec::IntoIter<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.values.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.values.next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> Debug for IntoIter<T> where T: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.values.as_slice()).finish()
+    }
+}
+
+// Walks two sets in order at once, pairing up equal elements. Each step
+// takes the smaller of the two next elements, or both when they're equal.
+struct Pairs<'a, T> where T: PartialOrd + 'a {
+    a: Peekable<Iter<'a, T>>,
+    b: Peekable<Iter<'a, T>>,
+}
+
+impl<'a, T> Pairs<'a, T> where T: Ord {
+    fn new(a: &'a BTreeSet<T>, b: &'a BTreeSet<T>) -> Pairs<'a, T> {
+        Pairs { a: a.iter().peekable(), b: b.iter().peekable() }
+    }
+
+    fn next(&mut self) -> (Option<&'a T>, Option<&'a T>) {
+        let ordering = match (self.a.peek(), self.b.peek()) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => Ordering::Greater,
+        };
+
+        match ordering {
+            Ordering::Less => (self.a.next(), None),
+            Ordering::Equal => (self.a.next(), self.b.next()),
+            Ordering::Greater => (None, self.b.next()),
+        }
+    }
+}
+
+impl<'a, T> Clone for Pairs<'a, T> where T: PartialOrd {
+    fn clone(&self) -> Pairs<'a, T> {
+        Pairs { a: self.a.clone(), b: self.b.clone() }
+    }
+}
+
+/// The elements of one `BTreeSet` that aren't in another, made by
+/// `BTreeSet::difference`.
+pub struct Difference<'a, T> where T: PartialOrd + 'a {
+    pairs: Pairs<'a, T>,
+}
+
+/// The elements in either of two `BTreeSet`s but not both, made by
+/// `BTreeSet::symmetric_difference`.
+pub struct SymmetricDifference<'a, T> where T: PartialOrd + 'a {
+    pairs: Pairs<'a, T>,
+}
+
+/// The elements in both of two `BTreeSet`s, made by
+/// `BTreeSet::intersection`.
+pub struct Intersection<'a, T> where T: PartialOrd + 'a {
+    pairs: Pairs<'a, T>,
+}
+
+/// The elements in either of two `BTreeSet`s, made by `BTreeSet::union`.
+pub struct Union<'a, T> where T: PartialOrd + 'a {
+    pairs: Pairs<'a, T>,
+}
+
+impl<'a, T> Iterator for Difference<'a, T> where T: Ord {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match self.pairs.next() {
+                (Some(a), None) => return Some(a),
+                (None, None) => return None,
+                _ => (),
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for SymmetricDifference<'a, T> where T: Ord {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match self.pairs.next() {
+                (Some(_), Some(_)) => (),
+                (a, b) => return a.or(b),
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for Intersection<'a, T> where T: Ord {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match self.pairs.next() {
+                (Some(a), Some(_)) => return Some(a),
+                (None, None) => return None,
+                _ => (),
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for Union<'a, T> where T: Ord {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let (a, b) = self.pairs.next();
+        a.or(b)
+    }
+}
+
+macro_rules! set_operation_impls {
+    ($($name:ident),*) => {$(
+        impl<'a, T> FusedIterator for $name<'a, T> where T: Ord {}
+
+        impl<'a, T> Clone for $name<'a, T> where T: PartialOrd {
+            fn clone(&self) -> $name<'a, T> {
+                $name { pairs: self.pairs.clone() }
+            }
+        }
+
+        impl<'a, T> Debug for $name<'a, T> where T: Ord + Debug {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_list().entries(self.clone()).finish()
+            }
+        }
+    )*};
+}
+
+set_operation_impls!(Difference, SymmetricDifference, Intersection, Union);
+
+#[cfg(test)]
+mod tests {
+    use compat::BTreeSet;
+    use expectest::prelude::*;
+    use std::collections::BTreeSet as StdSet;
+    use std::ops::Bound;
+
+    pub fn both(values: &[i32]) -> (BTreeSet<i32>, StdSet<i32>) {
+        (values.iter().cloned().collect(), values.iter().cloned().collect())
+    }
+
+    pub fn elements<'a, I>(iter: I) -> Vec<i32> where I: IntoIterator<Item = &'a i32> {
+        iter.into_iter().cloned().collect()
+    }
+
+    mod compat_set {
+        use super::*;
+
+        #[test]
+        fn keeps_one_of_each_element_like_std() {
+            let (mut set, mut std) = both(&[5, 3, 5, 1, 9, 3]);
+            expect!(elements(&set)).to(be_equal_to(elements(&std)));
+
+            expect!(set.insert(4)).to(be_equal_to(std.insert(4)));
+            expect!(set.insert(4)).to(be_equal_to(std.insert(4)));
+            expect!(set.remove(&1)).to(be_equal_to(std.remove(&1)));
+            expect!(set.remove(&1)).to(be_equal_to(std.remove(&1)));
+            expect!(set.take(&9)).to(be_equal_to(std.take(&9)));
+            expect!(set.replace(5)).to(be_equal_to(std.replace(5)));
+            expect!(set.get(&4)).to(be_equal_to(std.get(&4)));
+            expect!((set.first(), set.last())).to(be_equal_to((std.first(), std.last())));
+            expect!(set.pop_first()).to(be_equal_to(std.pop_first()));
+            expect!(set.pop_last()).to(be_equal_to(std.pop_last()));
+            expect!(set.len()).to(be_equal_to(std.len()));
+            expect!(elements(&set)).to(be_equal_to(elements(&std)));
+            expect!(set.tree.check_invariants().is_ok()).to(be_true());
+        }
+
+        #[test]
+        fn looks_up_borrowed_forms_of_its_elements() {
+            let set: BTreeSet<String> = ["b", "a", "c"].iter().map(|s| s.to_string()).collect();
+            expect!(set.contains("a")).to(be_true());
+            expect!(set.range::<str, _>((Bound::Included("b"), Bound::Unbounded)).cloned().collect::<Vec<_>>()).to(be_equal_to(vec!["b".to_string(), "c".to_string()]));
+
+            let mut set = set;
+            expect!(set.remove("b")).to(be_true());
+            expect!(set.contains("b")).to(be_false());
+        }
+
+        #[test]
+        fn walks_ranges_from_both_ends_like_std() {
+            let values: Vec<i32> = (0..40).map(|value| value * 3).collect();
+            let (set, std) = both(&values);
+
+            expect!(elements(set.range(10..50))).to(be_equal_to(elements(std.range(10..50))));
+            expect!(elements(set.range(..=30))).to(be_equal_to(elements(std.range(..=30))));
+            expect!(elements(set.range(200..))).to(be_equal_to(elements(std.range(200..))));
+            expect!(elements(set.range(9..9))).to(be_equal_to(Vec::<i32>::new()));
+            expect!(elements(set.iter().rev())).to(be_equal_to(elements(std.iter().rev())));
+
+            // meeting in the middle
+            let mut iter = set.range(3..=12);
+            expect!(iter.next()).to(be_some().value(&3));
+            expect!(iter.next_back()).to(be_some().value(&12));
+            expect!(iter.next_back()).to(be_some().value(&9));
+            expect!(iter.next()).to(be_some().value(&6));
+            expect!(iter.next()).to(be_none());
+            expect!(set.into_iter().rev().take(2).collect::<Vec<_>>()).to(be_equal_to(std.into_iter().rev().take(2).collect::<Vec<_>>()));
+        }
+
+        #[test]
+        #[should_panic(expected = "range start is greater than range end in BTreeSet")]
+        fn panics_on_backwards_ranges_like_std() {
+            let (set, _) = both(&[1, 2, 3]);
+            #[allow(clippy::reversed_empty_ranges)]
+            set.range(3..1);
+        }
+
+        #[test]
+        fn combines_sets_like_std() {
+            let (a, std_a) = both(&[1, 2, 3, 5, 8]);
+            let (b, std_b) = both(&[2, 3, 4, 8, 9]);
+
+            expect!(elements(a.union(&b))).to(be_equal_to(elements(std_a.union(&std_b))));
+            expect!(elements(a.intersection(&b))).to(be_equal_to(elements(std_a.intersection(&std_b))));
+            expect!(elements(a.difference(&b))).to(be_equal_to(elements(std_a.difference(&std_b))));
+            expect!(elements(a.symmetric_difference(&b))).to(be_equal_to(elements(std_a.symmetric_difference(&std_b))));
+            expect!(elements(&(&a - &b))).to(be_equal_to(elements(&(&std_a - &std_b))));
+            expect!(a.is_disjoint(&b)).to(be_false());
+
+            let (small, _) = both(&[2, 8]);
+            expect!(small.is_subset(&a)).to(be_true());
+            expect!(a.is_superset(&small)).to(be_true());
+            expect!(small.is_subset(&b) && !b.is_subset(&small)).to(be_true());
+        }
+
+        #[test]
+        fn splits_appends_and_retains_like_std() {
+            let (mut set, mut std) = both(&(0..20).collect::<Vec<_>>());
+            let (mut rest, mut std_rest) = (set.split_off(&12), std.split_off(&12));
+            expect!(elements(&set)).to(be_equal_to(elements(&std)));
+            expect!(elements(&rest)).to(be_equal_to(elements(&std_rest)));
+
+            set.retain(|value| value % 3 != 0);
+            std.retain(|value| value % 3 != 0);
+            expect!(elements(&set)).to(be_equal_to(elements(&std)));
+
+            rest.insert(4);
+            std_rest.insert(4);
+            set.append(&mut rest);
+            std.append(&mut std_rest);
+            expect!(elements(&set)).to(be_equal_to(elements(&std)));
+            expect!(rest.is_empty()).to(be_true());
+
+            let everything = set.split_off(&0);
+            expect!(set.is_empty()).to(be_true());
+            expect!(everything.len()).to(be_equal_to(std.len()));
+        }
+    }
+}
@@ -0,0 +1,547 @@
+// A red-black tree whose clones share their nodes until one of them is
+// changed. Each node sits behind an `Rc`, so cloning a tree only bumps the
+// root's reference count. A change goes through `Rc::make_mut` on every
+// node it touches, which leaves a node that only this tree uses where it
+// is, and copies one that's shared, so the first change after a clone
+// copies the path down to the element and nothing else.
+//
+// Without parent links, both the insert and the remove fix the tree up on
+// the way back out of the recursion down to the element. The cases are the
+// same as `RedBlackTree`'s.
+
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+use std::mem;
+use std::rc::Rc;
+
+use node::{Color, Dir};
+use {get_dir, search_dir};
+
+type Link<T> = Option<Rc<Node<T>>>;
+
+#[derive(Clone)]
+struct Node<T> {
+    color: Color,
+    left: Link<T>,
+    value: T,
+    right: Link<T>,
+}
+
+impl<T> Node<T> {
+    fn child(&self, dir: Dir) -> &Link<T> {
+        match dir {
+            Dir::Left => &self.left,
+            Dir::Right => &self.right,
+        }
+    }
+
+    fn child_mut(&mut self, dir: Dir) -> &mut Link<T> {
+        match dir {
+            Dir::Left => &mut self.left,
+            Dir::Right => &mut self.right,
+        }
+    }
+}
+
+/// A sorted multiset that's cloned in O(1). The clones share their nodes,
+/// and a change to one of them copies just the nodes it touches that are
+/// still shared, O(log n) of them. Elements have to be `Clone` so those
+/// nodes can be copied.
+pub struct CowRedBlackTree<T> where T: PartialOrd + Clone {
+    root: Link<T>,
+    len: usize,
+}
+
+impl<T> CowRedBlackTree<T> where T: PartialOrd + Clone {
+    pub const fn new() -> CowRedBlackTree<T> {
+        CowRedBlackTree { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) {
+        insert(&mut self.root, value);
+        if is_red(&self.root) {
+            set_color(&mut self.root, Color::Black);
+        }
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        // looking first keeps a miss from copying the path to it
+        if !self.contains(value) {
+            return None;
+        }
+
+        let (removed, _) = remove(&mut self.root, value);
+        if is_red(&self.root) {
+            set_color(&mut self.root, Color::Black);
+        }
+        if removed.is_some() {
+            self.len -= 1;
+        }
+
+        removed
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        let mut link = &self.root;
+        while let Some(ref node) = *link {
+            match search_dir(value, &node.value) {
+                Some(dir) => link = node.child(dir),
+                None => return true,
+            }
+        }
+
+        false
+    }
+
+    /// Whether the two trees still share all of their nodes, as a tree and
+    /// its clone do until one of them changes.
+    pub fn ptr_eq(&self, other: &CowRedBlackTree<T>) -> bool {
+        match (&self.root, &other.root) {
+            (Some(root), Some(other)) => Rc::ptr_eq(root, other),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// A second tree holding the same elements, for trying out changes
+    /// that may be thrown away. It's the same as `clone`: both trees stay
+    /// changeable, each change copies only the shared nodes it touches,
+    /// and dropping either tree frees only the nodes no one else has.
+    pub fn fork(&self) -> CowRedBlackTree<T> {
+        self.clone()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter { stack: Vec::new(), remaining: self.len };
+        iter.push_left_spine(&self.root);
+        iter
+    }
+}
+
+// the node at `link` for changing, copied first if another tree shares it
+fn node_mut<T: Clone>(link: &mut Link<T>) -> Option<&mut Node<T>> {
+    link.as_mut().map(Rc::make_mut)
+}
+
+fn is_red<T>(link: &Link<T>) -> bool {
+    link.as_ref().is_some_and(|node| node.color.is_red())
+}
+
+fn is_black<T>(link: &Link<T>) -> bool {
+    !is_red(link)
+}
+
+fn set_color<T: Clone>(link: &mut Link<T>, color: Color) {
+    if let Some(node) = node_mut(link) {
+        node.color = color;
+    }
+}
+
+// equal elements go to the right, like in `RedBlackTree`
+fn insert<T: PartialOrd + Clone>(link: &mut Link<T>, value: T) {
+    let node = match node_mut(link) {
+        Some(node) => node,
+        None => {
+            *link = Some(Rc::new(Node { color: Color::Red, left: None, value, right: None }));
+            return;
+        },
+    };
+
+    let dir = get_dir(value < node.value);
+    insert(node.child_mut(dir), value);
+    if node.color.is_black() {
+        fix_red_red(link, dir);
+    }
+}
+
+// rotates a red child on the `dir` side of the black node at `link` over
+// it, if that child has a red child of its own. That ends up as a red
+// node with two black children, whose parent gets checked the same way.
+fn fix_red_red<T: Clone>(link: &mut Link<T>, dir: Dir) {
+    let grandchild_dir = {
+        let child = match link.as_ref().map(|node| node.child(dir)) {
+            Some(child) if is_red(child) => child,
+            _ => return,
+        };
+        let child = match child.as_ref() {
+            Some(child) => child,
+            None => return,
+        };
+
+        if is_red(child.child(dir)) {
+            dir
+        } else if is_red(child.child(dir.opposite())) {
+            dir.opposite()
+        } else {
+            return;
+        }
+    };
+
+    if let Some(node) = node_mut(link) {
+        // an inner grandchild gets rotated to the outside first
+        if grandchild_dir != dir {
+            rotate(node.child_mut(dir), dir);
+        }
+    }
+    rotate(link, dir.opposite());
+
+    if let Some(top) = node_mut(link) {
+        top.color = Color::Red;
+        set_color(&mut top.left, Color::Black);
+        set_color(&mut top.right, Color::Black);
+    }
+}
+
+// takes an element equal to `value` out of the subtree at `link`, and says
+// whether the subtree is left a black node short on every path down
+fn remove<T: PartialOrd + Clone>(link: &mut Link<T>, value: &T) -> (Option<T>, bool) {
+    let node = match node_mut(link) {
+        Some(node) => node,
+        None => return (None, false),
+    };
+
+    let dir = match search_dir(value, &node.value) {
+        Some(dir) => dir,
+        // a node with two children takes over the largest value in its
+        // left subtree, and that value's node gets removed instead
+        None if node.left.is_some() && node.right.is_some() => {
+            let (largest, is_short) = remove_largest(&mut node.left);
+            let removed = largest.map(|largest| mem::replace(&mut node.value, largest));
+            return (removed, is_short && fix_short_side(link, Dir::Left));
+        },
+        None => return unlink(link),
+    };
+
+    let (removed, is_short) = remove(node.child_mut(dir), value);
+    (removed, is_short && fix_short_side(link, dir))
+}
+
+fn remove_largest<T: Clone>(link: &mut Link<T>) -> (Option<T>, bool) {
+    let node = match node_mut(link) {
+        Some(node) => node,
+        None => return (None, false),
+    };
+
+    if node.right.is_none() {
+        return unlink(link);
+    }
+
+    let (removed, is_short) = remove_largest(&mut node.right);
+    (removed, is_short && fix_short_side(link, Dir::Right))
+}
+
+// replaces the node at `link`, which has at most one child, with that child
+fn unlink<T: Clone>(link: &mut Link<T>) -> (Option<T>, bool) {
+    let node = match link.take() {
+        Some(node) => Rc::unwrap_or_clone(node),
+        None => return (None, false),
+    };
+
+    *link = if node.left.is_some() { node.left } else { node.right };
+    // taking out a black node leaves its side a black node short, unless
+    // there is a red child to recolor in its place
+    let is_short = node.color.is_black() && is_black(link);
+    if node.color.is_black() {
+        set_color(link, Color::Black);
+    }
+
+    (Some(node.value), is_short)
+}
+
+// rebalances the node at `link` when its `n_dir` side has one black node
+// fewer than the other side, and says whether the whole subtree is still
+// a black node short, which moves the problem up to its parent
+fn fix_short_side<T: Clone>(link: &mut Link<T>, n_dir: Dir) -> bool {
+    let s_dir = n_dir.opposite();
+    let parent = match node_mut(link) {
+        Some(parent) => parent,
+        None => return false,
+    };
+
+    // a red sibling gets rotated above the parent, which leaves the parent
+    // red, one level down, with a black sibling
+    if is_red(parent.child(s_dir)) {
+        set_color(parent.child_mut(s_dir), Color::Black);
+        parent.color = Color::Red;
+        rotate(link, n_dir);
+        return match node_mut(link) {
+            Some(top) => fix_short_side(top.child_mut(n_dir), n_dir),
+            None => false,
+        };
+    }
+
+    let parent_color = parent.color;
+    let sibling = match node_mut(parent.child_mut(s_dir)) {
+        Some(sibling) => sibling,
+        None => return false,
+    };
+
+    if is_black(&sibling.left) && is_black(&sibling.right) {
+        // with no red nephews the sibling's side can be shortened to
+        // match, unless the parent is red and can make up for both sides
+        sibling.color = Color::Red;
+        if parent_color.is_red() {
+            parent.color = Color::Black;
+            return false;
+        }
+
+        return true;
+    }
+
+    if is_black(sibling.child(s_dir)) {
+        // move the red nephew to the far side
+        sibling.color = Color::Red;
+        set_color(sibling.child_mut(n_dir), Color::Black);
+        rotate(parent.child_mut(s_dir), s_dir);
+    }
+
+    if let Some(sibling) = node_mut(parent.child_mut(s_dir)) {
+        sibling.color = parent_color;
+        set_color(sibling.child_mut(s_dir), Color::Black);
+    }
+
+    // the sibling takes the parent's place and color, and the parent
+    // becomes the extra black node on the short side
+    parent.color = Color::Black;
+    rotate(link, n_dir);
+    false
+}
+
+// moves the node at `link` down to its `dir` side, bringing up its other
+// child in its place. Without that child there's nothing to do.
+fn rotate<T: Clone>(link: &mut Link<T>, dir: Dir) {
+    let mut down = match link.take() {
+        Some(down) => down,
+        None => return,
+    };
+
+    let mut up = match Rc::make_mut(&mut down).child_mut(dir.opposite()).take() {
+        Some(up) => up,
+        None => {
+            *link = Some(down);
+            return;
+        },
+    };
+
+    let up_node = Rc::make_mut(&mut up);
+    *Rc::make_mut(&mut down).child_mut(dir.opposite()) = up_node.child_mut(dir).take();
+    *up_node.child_mut(dir) = Some(down);
+    *link = Some(up);
+}
+
+// the clone shares every node with the original
+impl<T> Clone for CowRedBlackTree<T> where T: PartialOrd + Clone {
+    fn clone(&self) -> CowRedBlackTree<T> {
+        CowRedBlackTree { root: self.root.clone(), len: self.len }
+    }
+}
+
+impl<T> Default for CowRedBlackTree<T> where T: PartialOrd + Clone {
+    fn default() -> CowRedBlackTree<T> {
+        CowRedBlackTree::new()
+    }
+}
+
+impl<T> Debug for CowRedBlackTree<T> where T: PartialOrd + Clone + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> FromIterator<T> for CowRedBlackTree<T> where T: PartialOrd + Clone {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> CowRedBlackTree<T> {
+        let mut tree = CowRedBlackTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T> Extend<T> for CowRedBlackTree<T> where T: PartialOrd + Clone {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a CowRedBlackTree<T> where T: PartialOrd + Clone {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Iterates over a `CowRedBlackTree` in ascending order.
+pub struct Iter<'a, T> where T: 'a {
+    // the nodes whose elements are still to come, each after the ones
+    // above it
+    stack: Vec<&'a Node<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left_spine(&mut self, link: &'a Link<T>) {
+        let mut link = link;
+        while let Some(ref node) = *link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(&node.right);
+        self.remaining -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use cow::CowRedBlackTree;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+    use cow::{Link, Node};
+
+    // the black height of the subtree, after checking it's a valid
+    // red-black tree with its elements in order
+    pub fn check<T: PartialOrd>(link: &Link<T>, low: Option<&T>, high: Option<&T>) -> usize {
+        let node = match *link {
+            Some(ref node) => node,
+            None => return 1,
+        };
+
+        assert!(low.is_none_or(|low| *low <= node.value) && high.is_none_or(|high| node.value <= *high));
+        if node.color.is_red() {
+            assert!(node.left.as_ref().is_none_or(|left| left.color.is_black()));
+            assert!(node.right.as_ref().is_none_or(|right| right.color.is_black()));
+        }
+
+        let left = check(&node.left, low, Some(&node.value));
+        let right = check(&node.right, Some(&node.value), high);
+        assert_eq!(left, right);
+        left + if node.color.is_black() { 1 } else { 0 }
+    }
+
+    // where each of the nodes is in memory
+    pub fn nodes<T>(link: &Link<T>) -> HashSet<*const Node<T>> {
+        let mut nodes = HashSet::new();
+        let mut stack = vec![link];
+        while let Some(link) = stack.pop() {
+            if let Some(ref node) = *link {
+                nodes.insert(Rc::as_ptr(node));
+                stack.push(&node.left);
+                stack.push(&node.right);
+            }
+        }
+
+        nodes
+    }
+
+    mod cow_tree {
+        use super::*;
+
+        #[test]
+        fn stays_valid_and_in_order_through_inserts_and_removals() {
+            let mut tree = CowRedBlackTree::new();
+            let mut expected = vec![];
+            for value in 0..300u32 {
+                let value = value * 37 % 101;
+                tree.insert(value);
+                expected.insert(expected.partition_point(|&probe| probe <= value), value);
+                check(&tree.root, None, None);
+            }
+            for value in 0..300u32 {
+                let value = value * 13 % 101;
+                let removed = tree.remove(&value);
+                match expected.binary_search(&value) {
+                    Ok(position) => expect!(removed).to(be_some().value(expected.remove(position))),
+                    Err(_) => expect!(removed).to(be_none()),
+                };
+                check(&tree.root, None, None);
+            }
+
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(expected.clone()));
+            expect!(tree.len()).to(be_equal_to(expected.len()));
+        }
+
+        #[test]
+        fn shares_its_nodes_with_its_clones_until_one_of_them_changes() {
+            let mut tree: CowRedBlackTree<_> = (0..256).collect();
+            let snapshot = tree.clone();
+            expect!(tree.ptr_eq(&snapshot)).to(be_true());
+
+            tree.insert(1000);
+            tree.remove(&0);
+            expect!(tree.ptr_eq(&snapshot)).to(be_false());
+            expect!(snapshot.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..256).collect::<Vec<_>>()));
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((1..256).chain(Some(1000)).collect::<Vec<_>>()));
+
+            // only the nodes near the two paths were copied
+            let copied = nodes(&tree.root).difference(&nodes(&snapshot.root)).count();
+            expect!(copied > 0 && copied < 40).to(be_true());
+            check(&tree.root, None, None);
+            check(&snapshot.root, None, None);
+        }
+
+        #[test]
+        fn forks_into_two_trees_that_change_independently() {
+            let mut tree: CowRedBlackTree<_> = (0..32).collect();
+            let mut fork = tree.fork();
+            fork.remove(&5);
+            fork.insert(100);
+            tree.insert(-1);
+
+            expect!(fork.iter().cloned().collect::<Vec<_>>())
+                .to(be_equal_to((0..32).filter(|&value| value != 5).chain(Some(100)).collect::<Vec<_>>()));
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((-1..32).collect::<Vec<_>>()));
+
+            // throwing the fork away leaves the original with every node to itself
+            drop(fork);
+            let mut stack = vec![&tree.root];
+            while let Some(link) = stack.pop() {
+                if let Some(node) = link {
+                    expect!(Rc::strong_count(node)).to(be_equal_to(1));
+                    stack.push(&node.left);
+                    stack.push(&node.right);
+                }
+            }
+        }
+
+        #[test]
+        fn changes_nodes_in_place_once_they_arent_shared() {
+            let mut tree: CowRedBlackTree<_> = (0..64).collect();
+            let before = nodes(&tree.root);
+            tree.insert(1000);
+            tree.insert(30);
+            expect!(before.is_subset(&nodes(&tree.root))).to(be_true());
+
+            let before = nodes(&tree.root);
+            tree.remove(&7);
+            tree.remove(&40);
+            expect!(nodes(&tree.root).is_subset(&before)).to(be_true());
+        }
+    }
+}
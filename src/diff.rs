@@ -0,0 +1,96 @@
+// The differences between two trees, found by walking both in order at
+// once, the way a merge would. Nothing is copied or collected, and the
+// walk takes O(n + m) between the two, only as far as it's been asked to
+// go. The trees are multisets, so an element that's in one tree twice and
+// in the other once shows up once, as only in the first.
+
+use std::iter::{FusedIterator, Peekable};
+
+use allocator::{Allocator, Global};
+use {RedBlackIterator, RedBlackTree};
+
+/// An element in one of the two trees a `Diff` compares and not the other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffItem<'a, T> where T: 'a {
+    OnlyInSelf(&'a T),
+    OnlyInOther(&'a T),
+}
+
+/// Iterates over the differences between two trees, made by
+/// `RedBlackTree::diff`, in ascending order.
+pub struct Diff<'a, T, A = Global> where T: PartialOrd + 'a, A: Allocator + Clone + 'a {
+    mine: Peekable<RedBlackIterator<'a, T, A>>,
+    theirs: Peekable<RedBlackIterator<'a, T, A>>,
+}
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// The elements that are in only one of this tree and `other`. An
+    /// element that can't be compared with the one it's up against in the
+    /// other tree counts as a difference.
+    pub fn diff<'a>(&'a self, other: &'a RedBlackTree<T, A>) -> Diff<'a, T, A> {
+        Diff { mine: self.iter().peekable(), theirs: other.iter().peekable() }
+    }
+}
+
+impl<'a, T, A> Iterator for Diff<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    type Item = DiffItem<'a, T>;
+
+    fn next(&mut self) -> Option<DiffItem<'a, T>> {
+        loop {
+            let (mine, theirs) = match (self.mine.peek(), self.theirs.peek()) {
+                (Some(&mine), Some(&theirs)) => (mine, theirs),
+                (Some(_), None) => return self.mine.next().map(DiffItem::OnlyInSelf),
+                (None, _) => return self.theirs.next().map(DiffItem::OnlyInOther),
+            };
+
+            if theirs < mine {
+                self.theirs.next();
+                return Some(DiffItem::OnlyInOther(theirs));
+            }
+
+            self.mine.next();
+            if mine == theirs {
+                self.theirs.next();
+            } else {
+                return Some(DiffItem::OnlyInSelf(mine));
+            }
+        }
+    }
+}
+
+impl<'a, T, A> FusedIterator for Diff<'a, T, A> where T: PartialOrd, A: Allocator + Clone {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use diff::DiffItem;
+    use RedBlackTree;
+
+    mod diff {
+        use super::*;
+
+        #[test]
+        fn yields_the_elements_in_only_one_of_the_trees_in_order() {
+            let mine: RedBlackTree<_> = vec![1, 2, 2, 4, 6, 9].into_iter().collect();
+            let theirs: RedBlackTree<_> = vec![2, 3, 4, 7, 9, 10].into_iter().collect();
+
+            expect!(mine.diff(&theirs).collect::<Vec<_>>()).to(be_equal_to(vec![
+                DiffItem::OnlyInSelf(&1),
+                DiffItem::OnlyInSelf(&2),
+                DiffItem::OnlyInOther(&3),
+                DiffItem::OnlyInSelf(&6),
+                DiffItem::OnlyInOther(&7),
+                DiffItem::OnlyInOther(&10),
+            ]));
+            expect!(mine.diff(&mine.clone()).next()).to(be_none());
+        }
+
+        #[test]
+        fn counts_an_element_it_cant_compare_as_a_difference() {
+            let mine: RedBlackTree<f64> = vec![1.0, f64::NAN].into_iter().collect();
+            let theirs: RedBlackTree<f64> = vec![1.0].into_iter().collect();
+            expect!(mine.diff(&theirs).count()).to(be_equal_to(1));
+            expect!(mine.diff(&mine.clone()).count()).to(be_equal_to(2));
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// The few smallest or largest elements of a tree, for leaderboards and the
+// like where only the top of a big tree is ever looked at. Both start from
+// the end of the tree they want and walk inwards, so taking k elements out
+// of n costs O(log n + k), and the rest of the tree is never visited.
+
+use std::iter::Take;
+
+use allocator::{Allocator, Global};
+use node::Node;
+use {RedBlackIterator, RedBlackTree};
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// The `k` smallest elements, smallest first, or all of them if there
+    /// are fewer than `k`.
+    pub fn bottom_k(&self, k: usize) -> Take<RedBlackIterator<'_, T, A>> {
+        self.iter().take(k)
+    }
+
+    /// The `k` largest elements, largest first, or all of them if there
+    /// are fewer than `k`.
+    pub fn top_k(&self, k: usize) -> TopK<'_, T, A> {
+        let root = self.root.as_ref();
+        TopK {
+            root,
+            current: root.map(|root| root.rightmost()),
+            remaining: k.min(self.count),
+        }
+    }
+}
+
+/// Iterates over a tree's largest elements, from the largest down, by
+/// following the parent pointers back up like `RedBlackIterator`.
+pub struct TopK<'a, T, A = Global> where T: PartialOrd + 'a, A: Allocator + Clone + 'a {
+    root: Option<&'a Node<T, A>>,
+    current: Option<&'a Node<T, A>>,
+    remaining: usize,
+}
+
+impl<'a, T, A> Iterator for TopK<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let node = self.current?;
+        self.current = self.root.and_then(|root| node.predecessor(root));
+        self.remaining -= 1;
+        Some(node.value())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T, A> ExactSizeIterator for TopK<'a, T, A> where T: PartialOrd, A: Allocator + Clone {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod top_and_bottom_k {
+        use super::*;
+
+        #[test]
+        fn take_the_elements_from_either_end() {
+            let tree: RedBlackTree<_> = (0..100).rev().collect();
+            expect!(tree.top_k(3).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![99, 98, 97]));
+            expect!(tree.bottom_k(3).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![0, 1, 2]));
+            expect!(tree.top_k(3).len()).to(be_equal_to(3));
+            expect!(tree.top_k(0).next()).to(be_none());
+        }
+
+        #[test]
+        fn stop_at_the_end_of_a_small_tree() {
+            let tree: RedBlackTree<_> = vec![2, 1, 2].into_iter().collect();
+            expect!(tree.top_k(10).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![2, 2, 1]));
+            expect!(tree.top_k(10).len()).to(be_equal_to(3));
+            expect!(tree.bottom_k(10).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 2]));
+            expect!(RedBlackTree::<u32>::new().top_k(1).next()).to(be_none());
+        }
+    }
+}
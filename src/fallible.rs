@@ -0,0 +1,175 @@
+// Inserting and reserving that report running out of memory instead of
+// aborting, for programs that would rather turn a request away than die
+// when memory is tight. Every allocation they make goes through the fallible
+// APIs, the node from the tree's allocator and the path down to its place
+// from the global one, and all of them are made before the tree is touched,
+// so a failure leaves the tree as it was.
+
+use std::error::Error;
+use std::fmt::{self, Debug};
+
+use allocator::Allocator;
+use RedBlackTree;
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// Inserts `value`, unless there isn't the memory for it, and hands it
+    /// back in that case. A node left from a removal is used first, so
+    /// after `try_reserve` this can't fail.
+    pub fn try_insert(&mut self, value: T) -> Result<(), TryInsertError<T>> {
+        // a red-black tree of n nodes is at most 2 * log2(n + 1) high
+        let height = 2 * (usize::BITS - (self.count + 1).leading_zeros()) as usize;
+        let mut path = Vec::new();
+        if path.try_reserve(height).is_err() {
+            return Err(TryInsertError { value });
+        }
+
+        match self.pool.try_node(value) {
+            Ok(node) => {
+                self.insert_node(node, path);
+                Ok(())
+            },
+            Err(value) => Err(TryInsertError { value }),
+        }
+    }
+
+    /// Like `reserve`, but reports running out of memory. The nodes that
+    /// were allocated before it ran out are kept.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if self.pool.try_reserve(additional) {
+            Ok(())
+        } else {
+            Err(TryReserveError(()))
+        }
+    }
+}
+
+/// Returned by `RedBlackTree::try_insert`, holding the element there
+/// wasn't the memory for.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TryInsertError<T> {
+    value: T,
+}
+
+impl<T> TryInsertError<T> {
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Debug for TryInsertError<T> where T: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TryInsertError").field("value", &self.value).finish()
+    }
+}
+
+impl<T> fmt::Display for TryInsertError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "there wasn't the memory to insert the element")
+    }
+}
+
+impl<T> Error for TryInsertError<T> where T: Debug {}
+
+/// Returned by `RedBlackTree::try_reserve` when it runs out of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TryReserveError(());
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "there wasn't the memory to reserve the nodes")
+    }
+}
+
+impl Error for TryReserveError {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod try_insert {
+        use super::*;
+
+        #[test]
+        fn inserts_like_insert_while_there_is_memory() {
+            let mut tree = RedBlackTree::new();
+            expect!(tree.try_reserve(4)).to(be_ok());
+            for value in (0..100).rev() {
+                expect!(tree.try_insert(value)).to(be_ok());
+            }
+
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..100).collect::<Vec<_>>()));
+            expect!(tree.pooled_nodes()).to(be_equal_to(0));
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+        }
+    }
+
+    #[cfg(feature = "allocator_api")]
+    mod out_of_memory {
+        use super::*;
+        use std::alloc::{AllocError, Allocator, Global, Layout};
+        use std::cell::Cell;
+        use std::ptr::NonNull;
+        use std::rc::Rc;
+
+        // fails every allocation once the allocations it has left run out
+        #[derive(Clone)]
+        struct Limited(Rc<Cell<usize>>);
+
+        unsafe impl Allocator for Limited {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                match self.0.get() {
+                    0 => Err(AllocError),
+                    left => {
+                        self.0.set(left - 1);
+                        Global.allocate(layout)
+                    },
+                }
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        #[test]
+        fn hands_back_elements_there_is_no_memory_for() {
+            // enough for three nodes and a copy of them
+            let left = Rc::new(Cell::new(6));
+            let mut tree = RedBlackTree::new_in(Limited(left.clone()));
+            for value in 0..3 {
+                expect!(tree.try_insert(value)).to(be_ok());
+            }
+
+            let before = tree.clone();
+            expect!(left.get()).to(be_equal_to(0));
+            let error = tree.try_insert(3).unwrap_err();
+            expect!(error.into_value()).to(be_equal_to(3));
+            expect!(tree.structural_eq(&before)).to(be_true());
+
+            // a removed element's node is reused without allocating
+            tree.remove(&0);
+            expect!(tree.try_insert(3)).to(be_ok());
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3]));
+        }
+
+        #[test]
+        fn keeps_what_it_managed_to_reserve() {
+            let left = Rc::new(Cell::new(2));
+            let mut tree = RedBlackTree::<u32, _>::new_in(Limited(left.clone()));
+            expect!(tree.try_reserve(3)).to(be_err());
+            expect!(tree.pooled_nodes()).to(be_equal_to(2));
+
+            left.set(1);
+            expect!(tree.try_reserve(3)).to(be_ok());
+            expect!(tree.try_insert(1)).to(be_ok());
+            expect!(tree.try_insert(2)).to(be_ok());
+            expect!(tree.try_insert(3)).to(be_ok());
+            expect!(tree.try_insert(4)).to(be_err());
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// A red-black tree with room for at most N elements, kept in an array
+// inside the tree itself. It's `ArenaRedBlackTree`'s tree with its slots
+// in an `InlineVec` instead of a Vec, so it never allocates: an empty one
+// takes all the memory it will ever need, wherever it's put, in a static
+// as easily as on the stack. Inserting into a full tree fails and hands
+// the element back instead of growing.
+//
+// That keeps the tree itself off the heap, but the crate links std, so it
+// won't build for a microcontroller or any other target without one.
+
+use std::error::Error;
+use std::fmt::{self, Debug};
+
+use arena::{Arena, ArenaNode, Iter};
+use inline::InlineVec;
+
+/// A sorted multiset of at most `N` elements, which never allocates. The
+/// crate still needs std, though.
+pub struct FixedRedBlackTree<T, const N: usize> where T: PartialOrd {
+    arena: Arena<T, (), InlineVec<ArenaNode<T, ()>, N>>,
+}
+
+impl<T, const N: usize> FixedRedBlackTree<T, N> where T: PartialOrd {
+    pub const fn new() -> FixedRedBlackTree<T, N> {
+        FixedRedBlackTree { arena: Arena::new(InlineVec::new()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.arena.len() == N
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Inserts `value`, unless the tree is full, and hands it back in that
+    /// case.
+    pub fn insert(&mut self, value: T) -> Result<(), CapacityError<T>> {
+        let index = self.arena.insert_position(&value);
+        self.arena.insert_at(index, value, ()).map_err(|(value, ())| CapacityError { value })
+    }
+
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let index = self.arena.position(value)?;
+        self.arena.remove_at(index).map(|(value, ())| value)
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.arena.position(value).is_some()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.arena.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.arena.clear();
+    }
+}
+
+impl<T, const N: usize> Clone for FixedRedBlackTree<T, N> where T: PartialOrd + Clone {
+    fn clone(&self) -> FixedRedBlackTree<T, N> {
+        FixedRedBlackTree { arena: self.arena.clone() }
+    }
+}
+
+impl<T, const N: usize> Default for FixedRedBlackTree<T, N> where T: PartialOrd {
+    fn default() -> FixedRedBlackTree<T, N> {
+        FixedRedBlackTree::new()
+    }
+}
+
+impl<T, const N: usize> Debug for FixedRedBlackTree<T, N> where T: PartialOrd + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a FixedRedBlackTree<T, N> where T: PartialOrd {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Returned by `FixedRedBlackTree::insert` when the tree is full, holding
+/// the element that didn't fit.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CapacityError<T> {
+    value: T,
+}
+
+impl<T> CapacityError<T> {
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Debug for CapacityError<T> where T: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CapacityError").field("value", &self.value).finish()
+    }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the tree is full")
+    }
+}
+
+impl<T> Error for CapacityError<T> where T: Debug {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use fixed::FixedRedBlackTree;
+
+    mod fixed_tree {
+        use super::*;
+
+        #[test]
+        fn hands_back_elements_once_full() {
+            let mut tree = FixedRedBlackTree::<u32, 4>::new();
+            for value in [3, 1, 4, 1] {
+                expect!(tree.insert(value)).to(be_ok());
+            }
+
+            expect!(tree.is_full()).to(be_true());
+            let error = tree.insert(5).unwrap_err();
+            expect!(error.into_value()).to(be_equal_to(5));
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 1, 3, 4]));
+
+            // a removal makes room again
+            expect!(tree.remove(&1)).to(be_some().value(1));
+            expect!(tree.insert(5)).to(be_ok());
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 3, 4, 5]));
+        }
+
+        #[test]
+        fn stays_balanced() {
+            let mut tree = FixedRedBlackTree::<u32, 256>::new();
+            for round in 0..4 {
+                for value in 0..256 {
+                    expect!(tree.insert(value * 7 % 256 + round)).to(be_ok());
+                }
+                for value in 0..200 {
+                    tree.remove(&(value + round));
+                }
+
+                tree.arena.assert_balanced();
+                tree.clear();
+            }
+            expect!(tree.is_empty()).to(be_true());
+        }
+
+        #[test]
+        fn can_initialize_a_static() {
+            static TREE: ::std::sync::Mutex<FixedRedBlackTree<u8, 8>> = ::std::sync::Mutex::new(FixedRedBlackTree::new());
+            TREE.lock().unwrap().insert(2).unwrap();
+            expect!(TREE.lock().unwrap().contains(&2)).to(be_true());
+        }
+    }
+}
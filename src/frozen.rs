@@ -0,0 +1,142 @@
+// A read-only snapshot of a tree, stored as the sorted array of its
+// elements. Lookups binary search the array instead of chasing pointers
+// from node to node, and range and rank queries come down to finding
+// positions in it. Freezing and thawing are both O(n).
+
+use std::cmp::Ordering;
+use std::ops::{Bound, Index, RangeBounds};
+use std::slice;
+
+use RedBlackTree;
+
+/// An immutable, flat copy of a `RedBlackTree`, made by
+/// `RedBlackTree::freeze`, that answers queries faster than the tree.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FrozenRedBlackTree<T> where T: PartialOrd {
+    values: Vec<T>,
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Turns the tree into a `FrozenRedBlackTree`, in O(n).
+    pub fn freeze(self) -> FrozenRedBlackTree<T> {
+        FrozenRedBlackTree { values: self.into_iter().collect() }
+    }
+}
+
+impl<T> FrozenRedBlackTree<T> where T: PartialOrd {
+    /// Turns the snapshot back into a tree that can be changed, in O(n).
+    pub fn thaw(self) -> RedBlackTree<T> {
+        RedBlackTree::from_sorted_vec(self.values)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The elements, in ascending order.
+    pub fn as_slice(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.values.iter()
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.get(value).is_some()
+    }
+
+    /// The stored element equal to `value`, if there is one, in O(log n).
+    pub fn get(&self, value: &T) -> Option<&T> {
+        self.values
+            .binary_search_by(|probe| probe.partial_cmp(value).unwrap_or(Ordering::Less))
+            .ok()
+            .map(|index| &self.values[index])
+    }
+
+    /// The number of elements smaller than `value`, in O(log n).
+    pub fn rank(&self, value: &T) -> usize {
+        self.values.partition_point(|probe| *probe < *value)
+    }
+
+    /// The elements that fall within `range`, in ascending order.
+    pub fn range<R>(&self, range: R) -> &[T] where R: RangeBounds<T> {
+        let values = &self.values[..];
+        let start = match range.start_bound() {
+            Bound::Included(start) => values.partition_point(|value| *value < *start),
+            Bound::Excluded(start) => values.partition_point(|value| *value <= *start),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => values.partition_point(|value| *value <= *end),
+            Bound::Excluded(end) => values.partition_point(|value| *value < *end),
+            Bound::Unbounded => values.len(),
+        };
+
+        if start < end { &values[start..end] } else { &[] }
+    }
+}
+
+// indexes by rank, so frozen[0] is the smallest element
+impl<T> Index<usize> for FrozenRedBlackTree<T> where T: PartialOrd {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.values[index]
+    }
+}
+
+impl<'a, T> IntoIterator for &'a FrozenRedBlackTree<T> where T: PartialOrd {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.values.iter()
+    }
+}
+
+impl<T> From<RedBlackTree<T>> for FrozenRedBlackTree<T> where T: PartialOrd {
+    fn from(tree: RedBlackTree<T>) -> FrozenRedBlackTree<T> {
+        tree.freeze()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod frozen {
+        use super::*;
+
+        #[test]
+        fn answers_queries_like_the_tree_it_was_made_from() {
+            let tree: RedBlackTree<u32> = vec![40, 10, 30, 20, 30, 50].into_iter().collect();
+            let frozen = tree.freeze();
+
+            expect!(frozen.len()).to(be_equal_to(6));
+            expect!(frozen.contains(&30)).to(be_true());
+            expect!(frozen.get(&35)).to(be_none());
+            expect!(frozen.rank(&30)).to(be_equal_to(2));
+            expect!(frozen.rank(&31)).to(be_equal_to(4));
+            expect!(frozen[0]).to(be_equal_to(10));
+            expect!(frozen.range(20..40).to_vec()).to(be_equal_to(vec![20, 30, 30]));
+            expect!(frozen.range(..=20).to_vec()).to(be_equal_to(vec![10, 20]));
+            expect!(frozen.range(60..).len()).to(be_equal_to(0));
+        }
+
+        #[test]
+        fn thaws_back_into_a_tree() {
+            let tree: RedBlackTree<u32> = (0..100).collect();
+            let mut thawed = tree.clone().freeze().thaw();
+            expect!(thawed == tree).to(be_true());
+
+            thawed.insert(100);
+            expect!(thawed.contains(&100)).to(be_true());
+        }
+    }
+}
@@ -0,0 +1,293 @@
+// A sorted multiset of integers that finds the smallest value from some
+// point on that it doesn't hold, in O(log n), for handing out the lowest
+// free id and the like. Each node keeps the smallest and largest values in
+// its subtree and how many distinct values there are, and a subtree whose
+// distinct values cover everything from its smallest to its largest has no
+// gap in it, so a search can step over it without looking inside. The
+// nodes live in the arena's tree, with that as their augmentation.
+
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+
+use arena::{self, Arena, ArenaNode, Augment, Link};
+use integer::Integer;
+
+type Nodes<T> = Arena<T, Span<T>, Vec<ArenaNode<T, Span<T>>>>;
+
+#[derive(Clone, Copy)]
+struct Span<T> {
+    min: T,
+    max: T,
+    // counting repeats once
+    distinct: usize,
+}
+
+impl<T> Span<T> where T: Integer {
+    fn new(value: T) -> Span<T> {
+        Span { min: value, max: value, distinct: 1 }
+    }
+
+    // whether every value from `min` to `max` is there
+    fn is_contiguous(&self) -> bool {
+        self.min.checked_add_usize(self.distinct - 1) == Some(self.max)
+    }
+}
+
+impl<T> Augment<T> for Span<T> where T: Integer {
+    fn pull_up(&mut self, value: &T, left: Option<&Span<T>>, right: Option<&Span<T>>) {
+        let mut distinct = 1;
+        if let Some(left) = left {
+            distinct += left.distinct - (left.max == *value) as usize;
+        }
+        if let Some(right) = right {
+            distinct += right.distinct - (right.min == *value) as usize;
+        }
+
+        *self = Span {
+            min: left.map_or(*value, |left| left.min),
+            max: right.map_or(*value, |right| right.max),
+            distinct,
+        };
+    }
+}
+
+// how far a search for the first missing value has got
+enum Search<T> {
+    // every value from the start up to, but not including, this one is
+    // there, as far as the search has looked
+    Upto(T),
+    // the answer, where `None` means every value up to the type's maximum
+    // is taken
+    Found(Option<T>),
+}
+
+/// A sorted multiset of integers that can find the smallest value it
+/// doesn't hold in O(log n).
+#[derive(Clone)]
+pub struct GapRedBlackTree<T> where T: Integer {
+    arena: Nodes<T>,
+}
+
+impl<T> GapRedBlackTree<T> where T: Integer {
+    pub const fn new() -> GapRedBlackTree<T> {
+        GapRedBlackTree { arena: Arena::new(Vec::new()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.len() == 0
+    }
+
+    /// Adds `value`, after any values equal to it.
+    pub fn insert(&mut self, value: T) {
+        let index = self.arena.insert_position(&value);
+        self.arena.push_at(index, value, Span::new(value));
+    }
+
+    /// Takes out one value equal to `value`, if there is one.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let index = self.arena.position(value)?;
+        self.arena.remove_at(index).map(|(value, _)| value)
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.arena.position(value).is_some()
+    }
+
+    /// The values in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { nodes: self.arena.nodes() }
+    }
+
+    pub fn clear(&mut self) {
+        self.arena.clear();
+    }
+
+    /// Returns the smallest value `>= from` that is not in the tree, or
+    /// `None` if every value from `from` up to the type's maximum is taken.
+    ///
+    /// Only the nodes on the way down to `from` and to the answer are
+    /// looked at, so this takes O(log n).
+    pub fn first_missing(&self, from: T) -> Option<T> {
+        match self.search(self.arena.root(), from) {
+            Search::Upto(missing) => Some(missing),
+            Search::Found(missing) => missing,
+        }
+    }
+
+    // carries on a search that has found everything from the start up to
+    // `missing` into the subtree at `link`. A subtree holds every value
+    // of the tree between its smallest and largest, so one with a gap
+    // that the search doesn't step over has the answer in it, and the
+    // search only ever goes into the subtrees on the way down to the
+    // start and to the answer.
+    fn search(&self, link: Link, missing: T) -> Search<T> {
+        let node = match self.arena.get(link) {
+            Some(node) => node,
+            None => return Search::Upto(missing),
+        };
+
+        let span = node.augment();
+        if span.max < missing {
+            // all before the start, or repeats of values already found
+            return Search::Upto(missing);
+        }
+        if missing < span.min {
+            return Search::Found(Some(missing));
+        }
+        if span.is_contiguous() {
+            return match span.max.checked_add_usize(1) {
+                Some(next) => Search::Upto(next),
+                None => Search::Found(None),
+            };
+        }
+
+        let missing = match self.search(node.left(), missing) {
+            Search::Upto(missing) => missing,
+            found => return found,
+        };
+
+        let value = *node.value();
+        let missing = if missing < value {
+            return Search::Found(Some(missing));
+        } else if value == missing {
+            match missing.checked_add_usize(1) {
+                Some(next) => next,
+                None => return Search::Found(None),
+            }
+        } else {
+            missing
+        };
+
+        self.search(node.right(), missing)
+    }
+}
+
+impl<T> Default for GapRedBlackTree<T> where T: Integer {
+    fn default() -> GapRedBlackTree<T> {
+        GapRedBlackTree::new()
+    }
+}
+
+impl<T> Debug for GapRedBlackTree<T> where T: Integer + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> FromIterator<T> for GapRedBlackTree<T> where T: Integer {
+    fn from_iter<I>(iter: I) -> GapRedBlackTree<T> where I: IntoIterator<Item = T> {
+        let mut tree = GapRedBlackTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T> Extend<T> for GapRedBlackTree<T> where T: Integer {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item = T> {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a GapRedBlackTree<T> where T: Integer {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Iterates over the values of a `GapRedBlackTree` in ascending order.
+pub struct Iter<'a, T> where T: 'a {
+    nodes: arena::Nodes<'a, T, Span<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.nodes.next().map(ArenaNode::value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use super::GapRedBlackTree;
+
+    mod first_missing {
+        use super::*;
+
+        #[test]
+        fn returns_the_smallest_value_not_in_the_tree() {
+            let tree = GapRedBlackTree::<u32>::new();
+            expect!(tree.first_missing(0)).to(be_some().value(0));
+
+            let tree: GapRedBlackTree<u32> = vec![0, 1, 2, 4, 5, 7].into_iter().collect();
+            expect!(tree.first_missing(0)).to(be_some().value(3));
+            expect!(tree.first_missing(3)).to(be_some().value(3));
+            expect!(tree.first_missing(4)).to(be_some().value(6));
+            expect!(tree.first_missing(7)).to(be_some().value(8));
+            expect!(tree.first_missing(100)).to(be_some().value(100));
+        }
+
+        #[test]
+        fn returns_none_when_every_value_up_to_the_maximum_is_taken() {
+            let tree: GapRedBlackTree<u8> = vec![253, 254, 255].into_iter().collect();
+            expect!(tree.first_missing(253)).to(be_none());
+            expect!(tree.first_missing(252)).to(be_some().value(252));
+        }
+
+        #[test]
+        fn counts_repeated_values_once() {
+            let tree: GapRedBlackTree<u32> = vec![1, 1, 2].into_iter().collect();
+            expect!(tree.first_missing(1)).to(be_some().value(3));
+
+            let tree: GapRedBlackTree<u32> = vec![1, 1, 3, 3, 3, 4].into_iter().collect();
+            expect!(tree.first_missing(1)).to(be_some().value(2));
+            expect!(tree.first_missing(3)).to(be_some().value(5));
+
+            let tree: GapRedBlackTree<u8> = vec![254, 255, 255].into_iter().collect();
+            expect!(tree.first_missing(254)).to(be_none());
+        }
+
+        #[test]
+        fn agrees_with_a_scan_through_inserts_and_removes() {
+            let mut tree = GapRedBlackTree::new();
+            let mut model = Vec::new();
+            for step in 0..400u32 {
+                let value = step * 37 % 101;
+                if step % 3 == 2 {
+                    let found = model.iter().position(|&other| other == value);
+                    expect!(tree.remove(&value).is_some()).to(be_equal_to(found.is_some()));
+                    if let Some(index) = found {
+                        model.remove(index);
+                    }
+                } else {
+                    tree.insert(value);
+                    model.push(value);
+                    model.sort();
+                }
+
+                tree.arena.assert_balanced();
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(model.clone()));
+                for from in (0..110).step_by(7) {
+                    let missing = (from..).find(|value| !model.contains(value));
+                    expect!(tree.first_missing(from)).to(be_equal_to(missing));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// Changing an element in place. Elements are usually records ordered by a
+// key, where most of what can change doesn't move them, so `get_mut` hands
+// out the element itself behind a guard instead of making the caller take
+// it out and put it back. When the guard goes, it checks the element
+// against its neighbors, and only if the change has put it out of order
+// does it take it out and insert it again where it now belongs. Its node
+// is reused for that, so nothing is allocated.
+
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use allocator::{Allocator, Global};
+use node::{Dir, Follow, Node};
+use stats::{self, Op};
+use {search_dir, trace, RedBlackTree};
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// Mutable access to an element equal to `value`. If it's changed so
+    /// that it no longer sorts between its neighbors, it's moved to where
+    /// it belongs when the guard is dropped, after any elements equal to
+    /// it.
+    pub fn get_mut(&mut self, value: &T) -> Option<ValueGuard<'_, T, A>> {
+        let _span = trace::span(Op::Contains, self.count);
+        stats::start();
+        let mut path = vec![];
+        {
+            let mut link = &self.root;
+            let mut found = false;
+            while let Some(node) = link.as_ref() {
+                stats::comparison();
+                match search_dir(value, node.value()) {
+                    Some(dir) => {
+                        path.push(dir);
+                        link = node.follow(dir);
+                    },
+                    None => {
+                        found = true;
+                        break;
+                    },
+                }
+            }
+
+            self.stats.finish(Op::Contains);
+            if !found {
+                return None;
+            }
+        }
+
+        let mut node = self.root.as_mut()?;
+        for &dir in &path {
+            node = node.follow_mut(dir).as_mut()?;
+        }
+
+        let node = NonNull::from(node);
+        Some(ValueGuard { tree: self, path, node })
+    }
+}
+
+/// An element of a tree that can be changed in place, made by
+/// `RedBlackTree::get_mut`. It puts the element back in order when it's
+/// dropped.
+pub struct ValueGuard<'a, T, A = Global> where T: PartialOrd + 'a, A: Allocator + Clone + 'a {
+    tree: &'a mut RedBlackTree<T, A>,
+    // the way down to the element's node
+    path: Vec<Dir>,
+    node: NonNull<Node<T, A>>,
+}
+
+impl<'a, T, A> Deref for ValueGuard<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // the tree is borrowed for as long as the guard lives, so the node
+        // stays where it was
+        unsafe { self.node.as_ref().value() }
+    }
+}
+
+impl<'a, T, A> DerefMut for ValueGuard<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.node.as_mut().value_mut() }
+    }
+}
+
+impl<'a, T, A> Drop for ValueGuard<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn drop(&mut self) {
+        let in_order = {
+            let root = match self.tree.root.as_ref() {
+                Some(root) => root,
+                None => return,
+            };
+
+            let node = unsafe { self.node.as_ref() };
+            let after_previous = node.predecessor(root).is_none_or(|previous| previous.value() <= node.value());
+            let before_next = node.successor(root).is_none_or(|next| node.value() <= next.value());
+            after_previous && before_next
+        };
+
+        if in_order {
+            return;
+        }
+
+        let mut path = self.path.drain(..);
+        if let Some(value) = self.tree.remove_where(|_| path.next()) {
+            self.tree.insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use std::cmp::Ordering;
+    use RedBlackTree;
+
+    // ordered by the key alone, with a payload that doesn't affect the
+    // order
+    #[derive(Debug, Clone, Copy)]
+    pub struct Entry {
+        key: u32,
+        payload: char,
+    }
+
+    impl PartialEq for Entry {
+        fn eq(&self, other: &Entry) -> bool {
+            self.key == other.key
+        }
+    }
+
+    impl PartialOrd for Entry {
+        fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+            self.key.partial_cmp(&other.key)
+        }
+    }
+
+    pub fn entry(key: u32) -> Entry {
+        Entry { key, payload: ' ' }
+    }
+
+    pub fn contents(tree: &RedBlackTree<Entry>) -> Vec<(u32, char)> {
+        tree.iter().map(|entry| (entry.key, entry.payload)).collect()
+    }
+
+    mod get_mut {
+        use super::*;
+
+        #[test]
+        fn changes_an_element_in_place() {
+            let mut tree: RedBlackTree<_> = (0..10).map(entry).collect();
+            tree.get_mut(&entry(4)).unwrap().payload = 'x';
+            expect!(contents(&tree)[4]).to(be_equal_to((4, 'x')));
+            expect!(tree.get_mut(&entry(10)).is_none()).to(be_true());
+            expect!(tree.pooled_nodes()).to(be_equal_to(0));
+        }
+
+        #[test]
+        fn moves_an_element_that_is_put_out_of_order() {
+            let mut tree: RedBlackTree<_> = (0..10).map(|key| entry(key * 10)).collect();
+            {
+                let mut guard = tree.get_mut(&entry(20)).unwrap();
+                guard.key = 75;
+                guard.payload = 'x';
+            }
+
+            let keys: Vec<_> = tree.iter().map(|entry| entry.key).collect();
+            expect!(keys).to(be_equal_to(vec![0, 10, 30, 40, 50, 60, 70, 75, 80, 90]));
+            expect!(contents(&tree)[7]).to(be_equal_to((75, 'x')));
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+
+            // a move to a tie goes after the equal elements
+            {
+                let mut guard = tree.get_mut(&entry(0)).unwrap();
+                guard.key = 50;
+                guard.payload = 'z';
+            }
+
+            expect!(contents(&tree)[3..5].to_vec()).to(be_equal_to(vec![(50, ' '), (50, 'z')]));
+            tree.get_mut(&entry(90)).unwrap().payload = 'y';
+            expect!(contents(&tree).last().cloned()).to(be_some().value((90, 'y')));
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+        }
+    }
+}
@@ -0,0 +1,259 @@
+// A tree that remembers its last few changes, so they can be undone and
+// redone. Each change is kept as the change that reverses it: an insert
+// as removing the element again, and a remove as putting the removed
+// element back. Undoing makes the reverse change and keeps the original to
+// redo, and making a new change forgets whatever there was to redo, like
+// an editor's history. Only the most recent `limit` changes are kept, the
+// oldest being forgotten first.
+//
+// Undoing brings back the same elements, but not necessarily the same
+// shape. An insert is undone by taking out the element at the position
+// the insert put it in, without comparing anything, so even an element
+// that can't be found by searching for it, like NaN, can be taken back
+// out. Since the tree can hold several equal elements, that's only sure
+// to be the inserted element itself if equal elements can't be told
+// apart, as undoing a remove puts the element back after any equal ones.
+
+use std::collections::VecDeque;
+
+use node::{Node, NodeHelper};
+use RedBlackTree;
+
+/// A `RedBlackTree` that can undo and redo its last `limit` changes, made
+/// by `RedBlackTree::with_history`. Removed elements are cloned into the
+/// history, so they have to be `Clone`.
+pub struct HistoryRedBlackTree<T> where T: PartialOrd + Clone {
+    tree: RedBlackTree<T>,
+    undo: VecDeque<Change<T>>,
+    redo: Vec<Change<T>>,
+    limit: usize,
+}
+
+// a change, kept as the change that reverses it in `undo` and as itself in
+// `redo`. Removes are kept as the position of the element to take out.
+enum Change<T> {
+    Insert(T),
+    Remove(usize),
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd + Clone {
+    /// Starts keeping a history of the last `limit` changes to the tree.
+    pub fn with_history(self, limit: usize) -> HistoryRedBlackTree<T> {
+        HistoryRedBlackTree { tree: self, undo: VecDeque::new(), redo: Vec::new(), limit }
+    }
+}
+
+impl<T> HistoryRedBlackTree<T> where T: PartialOrd + Clone {
+    pub fn new(limit: usize) -> HistoryRedBlackTree<T> {
+        RedBlackTree::new().with_history(limit)
+    }
+
+    pub fn insert(&mut self, value: T) {
+        let index = self.tree.insert_position(&value);
+        self.tree.insert(value);
+        self.record(Change::Remove(index));
+    }
+
+    /// Removes one element equal to `value`, if there is one. Nothing is
+    /// recorded when there isn't.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let removed = self.tree.remove(value);
+        if let Some(ref removed) = removed {
+            self.record(Change::Insert(removed.clone()));
+        }
+
+        removed
+    }
+
+    /// Reverses the most recent change that hasn't been undone already,
+    /// and says whether there was one.
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop_back().and_then(|change| self.apply(change)) {
+            Some(redo) => {
+                self.redo.push(redo);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Makes the most recently undone change again, and says whether there
+    /// was one. Any change made since the undo forgets what there was to
+    /// redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop().and_then(|change| self.apply(change)) {
+            Some(undo) => {
+                self.push_undo(undo);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forgets the history, keeping the tree as it is.
+    pub fn clear_history(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn tree(&self) -> &RedBlackTree<T> {
+        &self.tree
+    }
+
+    /// Stops keeping a history and hands back the tree.
+    pub fn into_tree(self) -> RedBlackTree<T> {
+        self.tree
+    }
+
+    fn record(&mut self, undo: Change<T>) {
+        self.redo.clear();
+        self.push_undo(undo);
+    }
+
+    fn push_undo(&mut self, undo: Change<T>) {
+        if self.limit == 0 {
+            return;
+        }
+
+        if self.undo.len() == self.limit {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(undo);
+    }
+
+    // makes the change and returns the one that reverses it. Every change
+    // after the one being reversed has been reversed already, so the
+    // position a remove was given still holds the element it's for; if
+    // there's nothing there, nothing is done.
+    fn apply(&mut self, change: Change<T>) -> Option<Change<T>> {
+        match change {
+            Change::Insert(value) => {
+                let index = self.tree.insert_position(&value);
+                self.tree.insert(value);
+                Some(Change::Remove(index))
+            },
+            Change::Remove(index) => self.tree.remove_at(index).map(Change::Insert),
+        }
+    }
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    // the position `insert` puts `value` in, after every element it
+    // doesn't sort before
+    fn insert_position(&self, value: &T) -> usize {
+        let mut index = 0;
+        let mut link = &self.root;
+        while let Some(node) = link.as_ref() {
+            if *value < *node.value() {
+                link = node.left();
+            } else {
+                index += node.left().size() + 1;
+                link = node.right();
+            }
+        }
+
+        index
+    }
+
+    fn remove_at(&mut self, index: usize) -> Option<T> {
+        if index >= self.count {
+            return None;
+        }
+
+        let (left, rest) = Node::split_at_rank(self.root.take(), index);
+        let (removed, right) = Node::split_at_rank(rest, 1);
+        // joining needs a node to go in between the two halves
+        let (pivot, right) = Node::split_at_rank(right, 1);
+        let mut root = match pivot.into_owned() {
+            Some(pivot) => Node::join(left, pivot, right),
+            None => left,
+        };
+
+        Node::ensure_root_black(&mut root);
+        self.root = root;
+        self.count -= 1;
+        removed.into_node().map(Node::into_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use history::HistoryRedBlackTree;
+
+    pub fn elements(tree: &HistoryRedBlackTree<i32>) -> Vec<i32> {
+        tree.tree().iter().cloned().collect()
+    }
+
+    mod history {
+        use super::*;
+
+        #[test]
+        fn undoes_and_redoes_its_changes_in_order() {
+            let mut tree = HistoryRedBlackTree::new(8);
+            tree.insert(1);
+            tree.insert(2);
+            expect!(tree.remove(&1)).to(be_some().value(1));
+            expect!(tree.remove(&5)).to(be_none());
+
+            expect!(tree.undo()).to(be_true());
+            expect!(elements(&tree)).to(be_equal_to(vec![1, 2]));
+            expect!(tree.undo()).to(be_true());
+            expect!(elements(&tree)).to(be_equal_to(vec![1]));
+            expect!(tree.redo()).to(be_true());
+            expect!(tree.redo()).to(be_true());
+            expect!(tree.redo()).to(be_false());
+            expect!(elements(&tree)).to(be_equal_to(vec![2]));
+
+            tree.undo();
+            // a new change leaves nothing to redo
+            tree.insert(3);
+            expect!(tree.can_redo()).to(be_false());
+            expect!(elements(&tree)).to(be_equal_to(vec![1, 2, 3]));
+            expect!(tree.tree().check_invariants().is_ok()).to(be_true());
+        }
+
+        #[test]
+        fn forgets_its_oldest_changes_past_its_limit() {
+            let mut tree = HistoryRedBlackTree::new(3);
+            for value in 0..5 {
+                tree.insert(value);
+            }
+
+            while tree.undo() {}
+            expect!(elements(&tree)).to(be_equal_to(vec![0, 1]));
+            while tree.redo() {}
+            expect!(elements(&tree)).to(be_equal_to(vec![0, 1, 2, 3, 4]));
+
+            let mut forgetful = HistoryRedBlackTree::new(0);
+            forgetful.insert(1);
+            expect!(forgetful.can_undo()).to(be_false());
+        }
+
+        #[test]
+        fn undoes_inserting_an_element_that_cant_be_found() {
+            let mut tree = HistoryRedBlackTree::new(8);
+            tree.insert(1.0);
+            tree.insert(f64::NAN);
+            tree.insert(0.5);
+            expect!(tree.tree().contains(&f64::NAN)).to(be_false());
+
+            expect!(tree.undo()).to(be_true());
+            expect!(tree.undo()).to(be_true());
+            expect!(tree.tree().iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1.0]));
+            expect!(tree.redo()).to(be_true());
+            expect!(tree.tree().iter().filter(|value| value.is_nan()).count()).to(be_equal_to(1));
+            expect!(tree.undo()).to(be_true());
+            expect!(tree.undo()).to(be_true());
+            expect!(tree.tree().iter().next()).to(be_none());
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// A vector with a fixed capacity of N, whose elements are stored inline
+// rather than on the heap. It's what the chunks of a `ChunkedRedBlackTree`
+// and the elements of a small `SmallRedBlackTree` are kept in.
+
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::slice;
+
+// the first `len` values are always initialized
+pub struct InlineVec<T, const N: usize> {
+    len: usize,
+    values: [MaybeUninit<T>; N],
+}
+
+impl<T, const N: usize> InlineVec<T, N> {
+    pub const fn new() -> InlineVec<T, N> {
+        InlineVec { len: 0, values: [const { MaybeUninit::uninit() }; N] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.values.as_ptr() as *const T, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.values.as_mut_ptr() as *mut T, self.len) }
+    }
+
+    // adds `value` at the end, or hands it back when there's no room
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+
+        self.values[self.len].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        self.len -= 1;
+        unsafe { Some(self.values[self.len].assume_init_read()) }
+    }
+
+    pub fn clear(&mut self) {
+        let len = self.len;
+        // a panicking drop leaks the values after it rather than letting
+        // them be dropped twice
+        self.len = 0;
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.values.as_mut_ptr() as *mut T, len)) }
+    }
+
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len && self.len < N);
+        unsafe {
+            let slot = self.values.as_mut_ptr().add(index);
+            ptr::copy(slot, slot.add(1), self.len - index);
+            ptr::write(slot as *mut T, value);
+        }
+
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len);
+        self.len -= 1;
+        unsafe {
+            let slot = self.values.as_mut_ptr().add(index);
+            let value = ptr::read(slot as *const T);
+            ptr::copy(slot.add(1), slot, self.len - index);
+            value
+        }
+    }
+
+    // moves the values from `index` on into a new vector
+    pub fn split_off(&mut self, index: usize) -> InlineVec<T, N> {
+        assert!(index <= self.len);
+        let mut other = InlineVec::new();
+        other.len = self.len - index;
+        unsafe {
+            ptr::copy_nonoverlapping(self.values.as_ptr().add(index), other.values.as_mut_ptr(), other.len);
+        }
+
+        self.len = index;
+        other
+    }
+
+    // moves the values out onto the heap
+    pub fn into_vec(mut self) -> Vec<T> {
+        let mut vec = Vec::with_capacity(self.len);
+        unsafe {
+            ptr::copy_nonoverlapping(self.values.as_ptr() as *const T, vec.as_mut_ptr(), self.len);
+            vec.set_len(self.len);
+        }
+
+        // the values belong to `vec` now
+        self.len = 0;
+        vec
+    }
+}
+
+impl<T, const N: usize> Clone for InlineVec<T, N> where T: Clone {
+    fn clone(&self) -> InlineVec<T, N> {
+        let mut other = InlineVec::new();
+        for value in self.as_slice() {
+            // bumping `len` one value at a time means a panicking clone
+            // only drops the values cloned so far
+            other.values[other.len].write(value.clone());
+            other.len += 1;
+        }
+
+        other
+    }
+}
+
+impl<T, const N: usize> Drop for InlineVec<T, N> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.values.as_mut_ptr() as *mut T, self.len)) }
+    }
+}
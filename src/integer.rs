@@ -0,0 +1,25 @@
+// The element types of a `GapRedBlackTree`, which finds the values it's
+// missing. They only need to know how to step forward without overflowing.
+pub trait Integer: Copy + PartialOrd {
+    /// Returns `self + n`, or `None` if the result would overflow.
+    fn checked_add_usize(self, n: usize) -> Option<Self>;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Integer for $t {
+                fn checked_add_usize(self, n: usize) -> Option<Self> {
+                    let sum = self as i128 + n as i128;
+                    if sum > <$t>::MAX as i128 {
+                        None
+                    } else {
+                        Some(sum as $t)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
@@ -0,0 +1,270 @@
+// A full check of everything the tree relies on being true of its nodes,
+// for tests that want to catch a corrupted tree as soon as it happens
+// rather than through a lookup going wrong much later. It walks every
+// node once, in order, so it's O(n).
+
+use std::error::Error;
+use std::fmt::{self, Debug};
+
+use allocator::Allocator;
+use node::{Node, Link, Parent, NodeHelper};
+#[cfg(feature = "debug-invariants")]
+use render;
+use RedBlackTree;
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// Checks that the elements are in order, that no red node has a red
+    /// child, that every path down from the root passes the same number
+    /// of black nodes, and that the length of the tree matches the number
+    /// of nodes in it. The error names the first element found where one
+    /// of those doesn't hold.
+    pub fn check_invariants(&self) -> Result<(), InvariantError> where T: Debug {
+        find_violation(self).map_err(|violation| InvariantError {
+            invariant: violation.invariant,
+            value: violation.node.map(|node| format!("{:?}", node.value())),
+        })
+    }
+}
+
+// What's wrong with a tree, and the node it's wrong at unless it's the
+// tree as a whole.
+struct Violation<'a, T: 'a, A: 'a> where T: PartialOrd, A: Allocator + Clone {
+    invariant: Invariant,
+    node: Option<&'a Node<T, A>>,
+}
+
+fn find_violation<T, A>(tree: &RedBlackTree<T, A>) -> Result<(), Violation<'_, T, A>>
+where T: PartialOrd, A: Allocator + Clone {
+    let mut walk = Walk { previous: None, nodes: 0 };
+    walk.check(&tree.root, None)?;
+
+    if let Some(root) = tree.root.as_ref() {
+        if root.is_red() {
+            return Err(Violation { invariant: Invariant::BlackRoot, node: Some(root) });
+        }
+    }
+    if walk.nodes != tree.count {
+        return Err(Violation { invariant: Invariant::Count, node: None });
+    }
+
+    Ok(())
+}
+
+// Runs the full check after an operation that changes the tree, when the
+// `debug-invariants` feature is on, and panics if the tree has been left
+// broken. The elements don't have to be printable, so the panic shows
+// the shape and colors of the tree with the offending node starred.
+#[cfg(feature = "debug-invariants")]
+pub fn verify<T, A>(tree: &RedBlackTree<T, A>, operation: &str) where T: PartialOrd, A: Allocator + Clone {
+    let violation = match find_violation(tree) {
+        Ok(()) => return,
+        Err(violation) => violation,
+    };
+
+    let marked = violation.node.map(|node| node as *const Node<T, A>);
+    let label = |node: &Node<T, A>| {
+        let star = if Some(node as *const Node<T, A>) == marked { "*" } else { "" };
+        format!("{:?}{}", node.color(), star)
+    };
+    let error = InvariantError { invariant: violation.invariant, value: None };
+    panic!("{} after {}:\n{}", error, operation, render::unicode_tree_with(&tree.root, &label));
+}
+
+#[cfg(not(feature = "debug-invariants"))]
+#[inline(always)]
+pub fn verify<T, A>(_tree: &RedBlackTree<T, A>, _operation: &str) where T: PartialOrd, A: Allocator + Clone {}
+
+struct Walk<'a, T: 'a> {
+    // the element just before the subtree being checked
+    previous: Option<&'a T>,
+    nodes: usize,
+}
+
+impl<'a, T> Walk<'a, T> where T: PartialOrd {
+    // checks the subtree under `link`, returning its black height
+    fn check<A: Allocator + Clone>(&mut self, link: &'a Link<T, A>, parent: Parent<T, A>) -> Result<usize, Violation<'a, T, A>> {
+        let node = match link.as_ref() {
+            Some(node) => node,
+            None => return Ok(1),
+        };
+        let error = |invariant| Violation { invariant, node: Some(node) };
+
+        if node.parent() != parent {
+            return Err(error(Invariant::Parent));
+        }
+
+        let left_height = self.check(node.left(), link.as_parent())?;
+        if let Some(previous) = self.previous {
+            if *node.value() < *previous {
+                return Err(error(Invariant::Order));
+            }
+        }
+        self.previous = Some(node.value());
+        self.nodes += 1;
+        let right_height = self.check(node.right(), link.as_parent())?;
+
+        if node.is_red() && (node.left().is_red() || node.right().is_red()) {
+            return Err(error(Invariant::RedChild));
+        }
+        if left_height != right_height {
+            return Err(error(Invariant::BlackHeight));
+        }
+        if node.size() != 1 + node.left().size() + node.right().size() {
+            return Err(error(Invariant::Size));
+        }
+
+        Ok(left_height + if node.is_black() { 1 } else { 0 })
+    }
+}
+
+/// The rules a red-black tree has to follow, as checked by
+/// `RedBlackTree::check_invariants`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Invariant {
+    /// No element is smaller than the one before it.
+    Order,
+    /// The root is black.
+    BlackRoot,
+    /// No red node has a red child.
+    RedChild,
+    /// Both children of a node have the same number of black nodes on
+    /// every path down from them.
+    BlackHeight,
+    /// Each node knows how many nodes its subtree holds.
+    Size,
+    /// Each node points back at its parent.
+    Parent,
+    /// The tree's length is the number of nodes in it.
+    Count,
+}
+
+/// Returned by `RedBlackTree::check_invariants` when the tree breaks one
+/// of the rules it keeps to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvariantError {
+    invariant: Invariant,
+    value: Option<String>,
+}
+
+impl InvariantError {
+    /// The rule that was broken.
+    pub fn invariant(&self) -> Invariant {
+        self.invariant
+    }
+
+    /// The `Debug` output of the element where the rule was broken, unless
+    /// it's the tree as a whole that's wrong.
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rule = match self.invariant {
+            Invariant::Order => "the elements are out of order",
+            Invariant::BlackRoot => "the root is red",
+            Invariant::RedChild => "a red node has a red child",
+            Invariant::BlackHeight => "unequal black heights",
+            Invariant::Size => "a subtree has the wrong size",
+            Invariant::Parent => "a node doesn't point back at its parent",
+            Invariant::Count => "the length doesn't match the number of nodes",
+        };
+
+        match self.value {
+            Some(ref value) => write!(f, "invalid red-black tree: {} at {}", rule, value),
+            None => write!(f, "invalid red-black tree: {}", rule),
+        }
+    }
+}
+
+impl Error for InvariantError {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use invariants::Invariant;
+    use node::{Color, NodeHelper};
+    use RedBlackTree;
+
+    mod check_invariants {
+        use super::*;
+
+        #[test]
+        fn accepts_every_tree_the_operations_leave_behind() {
+            let mut tree = RedBlackTree::new();
+            expect!(tree.check_invariants()).to(be_ok());
+            for value in 0..200u32 {
+                tree.insert(value * 7 % 101);
+                expect!(tree.check_invariants()).to(be_ok());
+            }
+            for value in 0..150u32 {
+                tree.remove(&(value * 3 % 101));
+                expect!(tree.check_invariants()).to(be_ok());
+            }
+
+            let rest = tree.split_at_index(20);
+            expect!(tree.check_invariants()).to(be_ok());
+            expect!(rest.check_invariants()).to(be_ok());
+        }
+
+        #[test]
+        fn names_the_element_where_a_rule_is_broken() {
+            let mut tree: RedBlackTree<_> = (1..8).collect();
+            tree.root.set_color(Color::Red);
+            let error = tree.check_invariants().unwrap_err();
+            expect!(error.invariant()).to(be_equal_to(Invariant::BlackRoot));
+            expect!(error.value()).to(be_some().value("4"));
+            expect!(error.to_string()).to(be_equal_to("invalid red-black tree: the root is red at 4"));
+
+            let mut tree: RedBlackTree<_> = (1..8).collect();
+            *tree.root.as_mut().unwrap().value_mut() = 10;
+            let error = tree.check_invariants().unwrap_err();
+            expect!(error.invariant()).to(be_equal_to(Invariant::Order));
+            expect!(error.value()).to(be_some().value("5"));
+
+            let mut tree: RedBlackTree<_> = (1..8).collect();
+            tree.root.as_mut().unwrap().left_mut().set_color(Color::Red);
+            expect!(tree.check_invariants().unwrap_err().invariant()).to(be_equal_to(Invariant::BlackHeight));
+
+            let mut tree: RedBlackTree<_> = (1..8).collect();
+            tree.count = 6;
+            let error = tree.check_invariants().unwrap_err();
+            expect!(error.invariant()).to(be_equal_to(Invariant::Count));
+            expect!(error.value()).to(be_none());
+        }
+    }
+}
+
+#[cfg(all(test, feature = "debug-invariants"))]
+mod verify_tests {
+    use expectest::prelude::*;
+    use node::Color;
+    use RedBlackTree;
+
+    mod debug_invariants {
+        use super::*;
+
+        #[test]
+        fn lets_every_operation_on_a_valid_tree_through() {
+            let mut tree: RedBlackTree<_> = (0..50).collect();
+            for value in 50..100 {
+                tree.insert(value);
+            }
+            for value in 0..30 {
+                tree.remove(&(value * 3));
+            }
+            let rest = tree.split_at_index(40);
+            tree.rebuild();
+            expect!(tree.count + rest.count).to(be_equal_to(70));
+        }
+
+        #[test]
+        #[should_panic]
+        fn panics_at_the_operation_that_finds_the_tree_broken() {
+            let mut tree: RedBlackTree<_> = (1..8).collect();
+            tree.root.as_mut().unwrap().left_mut().as_mut().unwrap().set_color(Color::Red);
+            tree.insert(8);
+        }
+    }
+}
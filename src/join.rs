@@ -0,0 +1,158 @@
+// Split and join primitives, based off "Just Join for Parallel Ordered Sets"
+// (Blelloch, Ferizovic and Sun). Joining two trees around a pivot only walks
+// down the spine of the taller tree, so both operations are O(log n). A split
+// carries the black heights of its pieces along with them rather than
+// measuring them again at every level, which would make it O(log² n).
+
+use node::{Node, Link, OwnedNode, Color, Dir, NodeHelper, Follow};
+
+impl<T> Node<T> where T: PartialOrd {
+    // number of black nodes from the root down to the leaves
+    pub fn black_height(link: &Link<T>) -> usize {
+        let mut height = 0;
+        let mut link = link;
+        while let Some(node) = link.as_ref() {
+            if node.is_black() {
+                height += 1;
+            }
+
+            link = node.left();
+        }
+
+        height
+    }
+
+    // joins `left` and `right` around the detached `pivot`, where every
+    // element of `left` is <= pivot and every element of `right` is >= pivot
+    pub fn join(left: Link<T>, pivot: OwnedNode<T>, right: Link<T>) -> Link<T> {
+        let left_height = Self::black_height(&left);
+        let right_height = Self::black_height(&right);
+        Self::join_with_heights(left, left_height, pivot, right, right_height).0
+    }
+
+    // `join`, for trees whose black heights are already known, which also
+    // hands back the black height of the joined tree
+    fn join_with_heights(left: Link<T>, left_height: usize, pivot: OwnedNode<T>, right: Link<T>, right_height: usize) -> (Link<T>, usize) {
+        let (mut tree, height) = if left_height == right_height {
+            let color = if left.is_black() && right.is_black() {
+                Color::Red
+            } else {
+                Color::Black
+            };
+
+            (Self::attach(left, pivot, right, color), left_height + color.is_black() as usize)
+        } else {
+            Self::join_unequal(left, left_height, pivot, right, right_height)
+        };
+
+        // whichever node ends up on top still points at where it came from
+        tree.set_parent(None);
+        (tree, height)
+    }
+
+    // joins `left` and `right` with nothing in between, using the first
+    // node of `right` as the pivot
+    pub fn concat(left: Link<T>, right: Link<T>) -> Link<T> {
+        let (pivot, right) = Self::split_at_rank(right, 1);
+        match pivot.into_owned() {
+            Some(pivot) => Self::join(left, pivot, right),
+            None => left,
+        }
+    }
+
+    // hangs the shorter tree off the side of the taller one. Only blackening
+    // the root makes the joined tree any taller than the taller of the two.
+    fn join_unequal(left: Link<T>, left_height: usize, pivot: OwnedNode<T>, right: Link<T>, right_height: usize) -> (Link<T>, usize) {
+        let dir = if left_height > right_height { Dir::Right } else { Dir::Left };
+        let mut tree = if left_height > right_height {
+            Self::join_towards(left, left_height, pivot, right, right_height, dir)
+        } else {
+            Self::join_towards(right, right_height, pivot, left, left_height, dir)
+        };
+
+        let mut height = left_height.max(right_height);
+        if let Some(root) = tree.as_mut() {
+            if root.is_red() && root.follow(dir).is_red() {
+                root.set_color(Color::Black);
+                height += 1;
+            }
+        }
+
+        (tree, height)
+    }
+
+    // walks down the `dir` spine of the taller tree until the black heights
+    // match, hangs `short` there, and fixes any red-red pair on the way up
+    fn join_towards(tall: Link<T>, tall_height: usize, pivot: OwnedNode<T>, short: Link<T>, short_height: usize, dir: Dir) -> Link<T> {
+        let mut node = match tall.into_owned() {
+            Some(node) if node.is_red() || tall_height != short_height => node,
+            // the black heights match by the time the spine runs out, if
+            // not before
+            tall => {
+                let tall = Link::from(tall);
+                return match dir {
+                    Dir::Right => Self::attach(tall, pivot, short, Color::Red),
+                    Dir::Left => Self::attach(short, pivot, tall, Color::Red),
+                };
+            },
+        };
+
+        let child_height = if node.is_black() { tall_height.saturating_sub(1) } else { tall_height };
+        let child = node.follow_mut(dir).take();
+        *node.follow_mut(dir) = Self::join_towards(child, child_height, pivot, short, short_height, dir);
+
+        let red_pair = node.follow(dir).as_ref().is_some_and(|child| child.is_red() && child.follow(dir).is_red());
+        if node.is_black() && red_pair {
+            if let Some(child) = node.follow_mut(dir).as_mut() {
+                child.follow_mut(dir).set_color(Color::Black);
+            }
+
+            let mut tall = Link::from(node);
+            Self::rotate(&mut tall, dir.opposite());
+            tall
+        } else {
+            node.update();
+            node.into()
+        }
+    }
+
+    fn attach(left: Link<T>, pivot: OwnedNode<T>, right: Link<T>, color: Color) -> Link<T> {
+        let mut node = pivot;
+        node.set_left(left);
+        node.set_right(right);
+        node.set_color(color);
+        node.update();
+        node.into()
+    }
+
+    // splits off the first `index` elements of the subtree into the left tree
+    pub fn split_at_rank(link: Link<T>, index: usize) -> (Link<T>, Link<T>) {
+        let height = Self::black_height(&link);
+        let ((left, _), (right, _)) = Self::split_with_heights(link, height, index);
+        (left, right)
+    }
+
+    // `split_at_rank` for a subtree of black height `height`, handing back
+    // the black heights of both halves along with them. Each level works
+    // out its children's black heights from its own, so the pieces can be
+    // joined on the way back up without walking down them to measure.
+    fn split_with_heights(link: Link<T>, height: usize, index: usize) -> ((Link<T>, usize), (Link<T>, usize)) {
+        let mut node = match link.into_owned() {
+            Some(node) => node,
+            None => return ((Link::none(), 0), (Link::none(), 0)),
+        };
+
+        let child_height = height.saturating_sub(node.is_black() as usize);
+        let left = node.left_mut().take();
+        let right = node.right_mut().take();
+        let left_size = left.size();
+
+        if index <= left_size {
+            let (left_left, (left_right, middle_height)) = Self::split_with_heights(left, child_height, index);
+            (left_left, Self::join_with_heights(left_right, middle_height, node, right, child_height))
+        } else {
+            let ((right_left, middle_height), right_right) = Self::split_with_heights(right, child_height, index - left_size - 1);
+            (Self::join_with_heights(left, child_height, node, right_left, middle_height), right_right)
+        }
+    }
+}
@@ -0,0 +1,194 @@
+// A flat interchange format for the shape of a tree: the nodes in
+// breadth-first order, with a `None` wherever a node is missing a child,
+// and no trailing `None`s. This is the usual array notation for binary
+// trees, so a tree can be written down in a test, or handed to another
+// language, as a plain list:
+//   [B.2, B.1, R.4, None, None, B.3, B.5, None, None, None, R.6]
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+
+use node::{Node, Link, OwnedNode, Pool, Color, NodeHelper};
+use allocator::Global;
+use stats::Recorder;
+use trace::Tracer;
+use RedBlackTree;
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Lists the nodes of the tree, colors included, in level order, with
+    /// `None` standing in for each missing child.
+    pub fn to_level_order(&self) -> Vec<Option<(Color, T)>> where T: Clone {
+        let mut nodes = vec![];
+        let mut queue = VecDeque::new();
+        queue.push_back(&self.root);
+        while let Some(link) = queue.pop_front() {
+            match link.as_ref() {
+                Some(node) => {
+                    nodes.push(Some((node.color(), node.value().clone())));
+                    queue.push_back(node.left());
+                    queue.push_back(node.right());
+                },
+                None => nodes.push(None),
+            }
+        }
+
+        while let Some(&None) = nodes.last() {
+            nodes.pop();
+        }
+
+        nodes
+    }
+
+    /// Rebuilds a tree, node for node, from the output of `to_level_order`.
+    /// Fails if the nodes don't form a valid red-black tree.
+    pub fn from_level_order(nodes: Vec<Option<(Color, T)>>) -> Result<RedBlackTree<T>, LevelOrderError> {
+        let mut nodes: Vec<_> = nodes.into_iter().map(|node| node.map(|(color, value)| (color, value, None, None))).collect();
+
+        // hand out the entries after the root to the nodes in the order
+        // they are reached, the same way they were written
+        let mut count: usize = 0;
+        let mut queue = VecDeque::new();
+        if let Some(&Some(_)) = nodes.first() {
+            queue.push_back((0, 0));
+        }
+        let mut next = 1;
+        let mut max_depth = 0;
+        while let Some((index, depth)) = queue.pop_front() {
+            count += 1;
+            max_depth = max_depth.max(depth);
+            for &left in &[true, false] {
+                if next < nodes.len() && nodes[next].is_some() {
+                    queue.push_back((next, depth + 1));
+                    if let Some(ref mut node) = nodes[index] {
+                        if left { node.2 = Some(next) } else { node.3 = Some(next) }
+                    }
+                }
+                next += 1;
+            }
+        }
+
+        if nodes.iter().skip(next).any(|node| node.is_some()) {
+            return Err(LevelOrderError("there are nodes without a parent"));
+        }
+        // a valid tree is never deeper than this, which also keeps the
+        // recursion below in check
+        if max_depth > 2 * (usize::BITS - count.leading_zeros()) as usize {
+            return Err(LevelOrderError("the tree is too deep"));
+        }
+
+        let root = if count == 0 { Link::none() } else { build(&mut nodes, 0)? };
+        if root.is_red() {
+            return Err(LevelOrderError("the root is red"));
+        }
+
+        let tree = RedBlackTree { root, count, pool: Pool::new(Global), stats: Recorder::new(), trace: Tracer::new() };
+        let mut values = tree.iter();
+        if let Some(mut previous) = values.next() {
+            for value in values {
+                if *value < *previous {
+                    return Err(LevelOrderError("the elements are out of order"));
+                }
+
+                previous = value;
+            }
+        }
+
+        Ok(tree)
+    }
+}
+
+type Entry<T> = Option<(Color, T, Option<usize>, Option<usize>)>;
+
+// assembles the subtree at `index`, checking the colors along the way
+fn build<T: PartialOrd>(nodes: &mut Vec<Entry<T>>, index: usize) -> Result<Link<T>, LevelOrderError> {
+    let (color, value, left, right) = match nodes[index].take() {
+        Some(entry) => entry,
+        None => return Err(LevelOrderError("there are nodes without a parent")),
+    };
+    let left = match left { Some(left) => build(nodes, left)?, None => Link::none() };
+    let right = match right { Some(right) => build(nodes, right)?, None => Link::none() };
+
+    if Node::black_height(&left) != Node::black_height(&right) {
+        return Err(LevelOrderError("unequal black heights"));
+    }
+    if color.is_red() && (left.is_red() || right.is_red()) {
+        return Err(LevelOrderError("a red node has a red child"));
+    }
+
+    let mut node = OwnedNode::new(Node::new(value));
+    node.set_color(color);
+    node.set_left(left);
+    node.set_right(right);
+    node.update();
+    Ok(node.into())
+}
+
+/// Returned by `RedBlackTree::from_level_order` when the nodes it was
+/// given don't make up a valid red-black tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelOrderError(&'static str);
+
+impl fmt::Display for LevelOrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid level order tree: {}", self.0)
+    }
+}
+
+impl Error for LevelOrderError {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use node::Color::{Red, Black};
+    use RedBlackTree;
+
+    mod level_order {
+        use super::*;
+
+        #[test]
+        fn lists_the_nodes_breadth_first() {
+            let mut tree = RedBlackTree::new();
+            for value in 1..7 {
+                tree.insert(value);
+            }
+            expect!(tree.to_level_order()).to(be_equal_to(vec![
+                Some((Black, 2)),
+                Some((Black, 1)), Some((Red, 4)),
+                None, None, Some((Black, 3)), Some((Black, 5)),
+                None, None, None, Some((Red, 6)),
+            ]));
+            expect!(RedBlackTree::<u8>::new().to_level_order().is_empty()).to(be_true());
+        }
+
+        #[test]
+        fn restores_the_exact_shape_and_colors() {
+            for count in 0..40 {
+                let tree: RedBlackTree<_> = (0..count).collect();
+                let nodes = tree.to_level_order();
+                let copy = RedBlackTree::from_level_order(nodes.clone()).unwrap();
+                expect!(copy.count).to(be_equal_to(tree.count));
+                expect!(copy.to_level_order()).to(be_equal_to(nodes));
+            }
+
+            let mut padded = RedBlackTree::from(vec![2, 1, 3]).to_level_order();
+            padded.extend(vec![None, None]);
+            expect!(RedBlackTree::from_level_order(padded).unwrap().count).to(be_equal_to(3));
+        }
+
+        #[test]
+        fn rejects_nodes_that_arent_a_valid_tree() {
+            let invalid = vec![
+                vec![Some((Red, 1))],
+                vec![Some((Black, 2)), Some((Black, 1))],
+                vec![Some((Black, 2)), Some((Red, 3)), Some((Red, 1))],
+                vec![Some((Black, 2)), Some((Red, 1)), None, Some((Red, 0))],
+                vec![Some((Black, 1)), None, None, Some((Black, 2))],
+                vec![None, Some((Black, 1))],
+            ];
+            for nodes in invalid {
+                expect!(RedBlackTree::from_level_order(nodes).is_err()).to(be_true());
+            }
+        }
+    }
+}
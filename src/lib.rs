@@ -1,448 +1,1056 @@
-#![feature(plugin)]
-#![plugin(stainless)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+// the tree code reaches nodes through references that can't be null
+// rather than by unwrapping links, so keep it that way
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used))]
 
 // Note: the algorithm for this tree is based off the algorithm in
 // https://en.wikipedia.org/wiki/Red%E2%80%93black_tree
 
-#[macro_use]
 #[cfg(not(test))]
 mod node;
 
-#[macro_use]
 #[cfg(test)]
 pub mod node;
 
-use node::{Node, Link, Color, Dir, NodeHelper, Follow};
+mod allocator;
+mod arena;
+mod atomic;
+mod binary;
+mod build;
+mod bulk;
+mod changeset;
+mod checked;
+mod diff;
+mod extremes;
+mod chunked;
+mod cow;
+mod fallible;
+mod fixed;
+mod frozen;
+mod gaps;
+mod guard;
+mod history;
+mod inline;
+mod integer;
+mod invariants;
+mod join;
+mod level_order;
+mod list;
+mod merge;
+mod merkle;
+mod nearest;
+mod observe;
+mod ops;
+mod prefix;
+mod queue;
+mod range_map;
+mod range_set;
+mod render;
+mod small;
+mod shift;
+mod sorted;
+mod splice;
+mod stats;
+mod sync;
+mod timer;
+mod trace;
+mod transform;
+mod transaction;
+mod weak;
+mod weighted;
+
+pub mod compat;
+pub mod persistent;
+
+#[cfg(feature = "svg")]
+mod svg;
+
+#[cfg(feature = "rand")]
+extern crate rand;
+
+#[cfg(feature = "serde")]
+extern crate serde;
+
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+#[cfg(feature = "postcard")]
+extern crate postcard;
+
+#[cfg(feature = "postcard")]
+mod postcard_impls;
+
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls;
+
+#[cfg(feature = "proptest")]
+extern crate proptest;
+
+#[cfg(feature = "proptest")]
+pub mod strategies;
+
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+#[cfg(feature = "test_support")]
+pub mod test_support;
+
+// the in-crate tests use `verify!` too
+#[cfg(any(test, feature = "test-macros"))]
+#[doc(hidden)]
+#[macro_use]
+pub mod test_macros;
+
+#[cfg(feature = "rkyv")]
+extern crate rkyv;
+
+#[cfg(feature = "rkyv")]
+pub mod archive;
+
+#[cfg(feature = "wasm")]
+extern crate js_sys;
+
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "python")]
+extern crate pyo3;
+
+// the code pyo3's macros generate refers to `::core`, which this edition
+// only resolves if the crate is declared at the root
+#[cfg(feature = "python")]
+extern crate core;
 
+#[cfg(feature = "python")]
+pub mod python;
+
+// Kani sets `cfg(kani)` itself when it builds the crate
+#[cfg(kani)]
+extern crate kani;
+
+#[cfg(kani)]
+mod proofs;
+
+pub use allocator::{Allocator, Global};
+pub use arena::ArenaRedBlackTree;
+pub use atomic::{AtomicRedBlackTree, Snapshot};
+pub use integer::Integer;
+pub use invariants::{Invariant, InvariantError};
+pub use build::NotSortedError;
+pub use bulk::BulkEditor;
+pub use changeset::{Change, ChangeSet, Recording};
+pub use checked::IncomparableError;
+pub use chunked::ChunkedRedBlackTree;
+pub use cow::CowRedBlackTree;
+pub use diff::{Diff, DiffItem};
+pub use extremes::TopK;
+pub use fallible::{TryInsertError, TryReserveError};
+pub use fixed::{CapacityError, FixedRedBlackTree};
+pub use frozen::FrozenRedBlackTree;
+pub use gaps::GapRedBlackTree;
+pub use guard::ValueGuard;
+pub use history::HistoryRedBlackTree;
+pub use level_order::LevelOrderError;
+pub use list::TreeList;
+pub use merge::{MergeItem, MergeIter};
+pub use merkle::Digests;
+pub use node::Color;
+pub use observe::{Event, ObservedRedBlackTree, OwnedEvent, Subscription};
+pub use shift::{ShiftError, ShiftRedBlackTree};
+pub use prefix::Prefixed;
+pub use queue::RbPriorityQueue;
+pub use range_map::RangeMap;
+pub use range_set::RangeSet;
+pub use small::SmallRedBlackTree;
+pub use sorted::SortedSet;
+pub use sync::SyncRedBlackTree;
+pub use timer::TimerQueue;
+pub use transaction::Transaction;
+pub use weak::WeakValueMap;
+#[cfg(feature = "stats")]
+pub use stats::{OpStats, Stats};
+pub use weighted::WeightedRedBlackTree;
+
+use node::{Node, Link, OwnedNode, Pool, Dir, NodeHelper, Follow};
+use stats::{Op, Recorder};
+use trace::Tracer;
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::{self, Debug, Display};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::iter::FromIterator;
 use std::mem;
 
-pub struct RedBlackTree<T> where T: PartialOrd {
-    root: Link<T>,
+#[cfg(feature = "rand")]
+use rand::Rng;
+
+/// A sorted multiset, kept balanced as a red-black tree.
+///
+/// No operation on the tree panics by itself. The only panics that can
+/// come out of one are those raised by the elements' own `PartialOrd`,
+/// `Clone` or `Hash` impls, by the allocator running out of memory, or by
+/// the `rb_tree_sorted!` macro when its elements are out of order. When
+/// a comparison panics, it's before the tree has been changed, so the
+/// tree is left as it was, minus the element that was being inserted.
+pub struct RedBlackTree<T, A = Global> where T: PartialOrd, A: Allocator + Clone {
+    root: Link<T, A>,
     count: usize,
+    // removed nodes waiting to be reused, which also holds the allocator
+    pool: Pool<T, A>,
+    stats: Recorder,
+    trace: Tracer,
 }
 
 impl<T> RedBlackTree<T> where T: PartialOrd {
-    pub fn new() -> RedBlackTree<T> {
+    /// Creates an empty tree. It doesn't allocate, so it can be used to
+    /// initialize a `static`.
+    pub const fn new() -> RedBlackTree<T> {
+        RedBlackTree::new_in(Global)
+    }
+    
+    /// Creates an empty tree with nodes already allocated for `capacity`
+    /// elements.
+    pub fn with_capacity(capacity: usize) -> RedBlackTree<T> {
+        let mut tree = RedBlackTree::new();
+        tree.reserve(capacity);
+        tree
+    }
+}
+
+// The basic operations work with any allocator. Everything else in the
+// crate is only implemented for trees in the global allocator.
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// Creates an empty tree whose nodes will be allocated with `alloc`.
+    /// Any allocator other than `Global` needs the `allocator_api` feature.
+    pub const fn new_in(alloc: A) -> RedBlackTree<T, A> {
         RedBlackTree {
-            root: None,
+            root: Link::none(),
             count: 0,
+            pool: Pool::new(alloc),
+            stats: Recorder::new(),
+            trace: Tracer::new(),
         }
     }
     
+    pub fn allocator(&self) -> &A {
+        self.pool.allocator()
+    }
+    
     pub fn insert(&mut self, value: T) {
-        Node::insert_n(value, &mut self.root);
+        let node = self.pool.node(value);
+        self.insert_node(node, Vec::new());
+    }
+    
+    // `path` is where the way down to the node's place is kept, and is
+    // empty, but can already have room for it
+    fn insert_node(&mut self, node: OwnedNode<T, A>, path: Vec<Dir>) {
+        let _span = trace::span(Op::Insert, self.count);
+        stats::start();
+        Node::insert_n(node, &mut self.root, path, &self.trace);
         self.count += 1;
+        self.stats.finish(Op::Insert);
+        invariants::verify(self, "insert");
     }
 
     pub fn remove(&mut self, value: &T) -> Option<T> {
-        let ret = Node::remove_n(value, &mut self.root);
+        self.remove_where(|node| {
+            stats::comparison();
+            search_dir(value, node.value())
+        })
+    }
+    
+    // removes the node `find` leads to, going from the root the way it
+    // says at each node until it says that's the one
+    fn remove_where<F>(&mut self, find: F) -> Option<T> where F: FnMut(&Node<T, A>) -> Option<Dir> {
+        let _span = trace::span(Op::Remove, self.count);
+        stats::start();
+        let ret = Node::remove_n(find, &mut self.root, &mut self.pool, &self.trace);
         if ret.is_some() {
             self.count -= 1;
         }
         
+        self.stats.finish(Op::Remove);
+        invariants::verify(self, "remove");
         ret
     }
     
-    pub fn iter(&self) -> RedBlackIterator<T> {
+    pub fn contains(&self, value: &T) -> bool {
+        let _span = trace::span(Op::Contains, self.count);
+        stats::start();
+        let mut link = &self.root;
+        let mut found = false;
+        while let Some(node) = link.as_ref() {
+            stats::comparison();
+            match search_dir(value, node.value()) {
+                Some(dir) => link = node.follow(dir),
+                None => {
+                    found = true;
+                    break;
+                },
+            }
+        }
+        
+        self.stats.finish(Op::Contains);
+        found
+    }
+    
+    /// Whether the two trees hold equal elements in the same shape, down
+    /// to the color of every node. `==` only looks at the elements, and
+    /// this is for checking that something which promises to rebuild a
+    /// tree exactly, like a round trip through serialization, really does.
+    pub fn structural_eq(&self, other: &RedBlackTree<T, A>) -> bool {
+        self.count == other.count && Node::structural_eq(&self.root, &other.root)
+    }
+    
+    pub fn iter(&self) -> RedBlackIterator<'_, T, A> {
         RedBlackIterator::new(self)
     }
+    
+    /// The number of removed nodes the tree is holding on to. Removing an
+    /// element keeps its node around, and inserting uses up those nodes
+    /// before allocating new ones.
+    pub fn pooled_nodes(&self) -> usize {
+        self.pool.free_nodes()
+    }
+    
+    /// Frees the removed nodes the tree is holding on to, all but `keep`
+    /// of them. `trim_pool(0)` gives all of their memory back.
+    pub fn trim_pool(&mut self, keep: usize) {
+        self.pool.trim(keep);
+    }
+    
+    /// Allocates nodes up front, so that the next `additional` inserts
+    /// don't have to. They're kept with the removed nodes.
+    pub fn reserve(&mut self, additional: usize) {
+        self.pool.reserve(additional);
+    }
+    
+    /// The rotations, recolorings and comparisons the tree's inserts,
+    /// removals and lookups have done so far.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.stats.stats()
+    }
+    
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
+    }
+    
+    /// Shows the elements in the rotations and recolorings the tree traces
+    /// from now on, which otherwise leave them out. Copies and trees split
+    /// off from this one show them too.
+    #[cfg(feature = "tracing")]
+    pub fn trace_values(&mut self) where T: Debug {
+        self.trace.print_values::<T>();
+    }
 }
 
-pub struct RedBlackIterator<'a, T> where T: PartialOrd + 'a {
-    parents: Vec<&'a Link<T>>,
-    current: Option<&'a Link<T>>,
-}
-
-impl<'a, T> RedBlackIterator<'a, T> where T: PartialOrd {
-    fn new(tree: &RedBlackTree<T>) -> RedBlackIterator<T> {
-        let mut parents = vec![];
-        let mut node = &tree.root;
-        
-        if node.is_none() {
-            return RedBlackIterator {
-                parents: parents,
-                current: None,
-            }
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Splits the tree so that `self` keeps its `index` smallest elements
+    /// and the rest are moved into the returned tree, in O(log n).
+    pub fn split_at_index(&mut self, index: usize) -> RedBlackTree<T> {
+        if index >= self.count {
+            return RedBlackTree::new();
         }
         
-        while node.left().is_some() {
-            let old_node = node;
-            node = node.left();
-            parents.push(old_node);
-        }
+        let (mut left, mut right) = Node::split_at_rank(self.root.take(), index);
+        Node::ensure_root_black(&mut left);
+        Node::ensure_root_black(&mut right);
         
-        RedBlackIterator {
-            parents: parents,
-            current: Some(node),
+        let rest = RedBlackTree {
+            root: right,
+            count: self.count - index,
+            pool: Pool::new(Global),
+            stats: Recorder::new(),
+            trace: self.trace,
+        };
+        
+        self.root = left;
+        self.count = index;
+        invariants::verify(self, "split_at_index");
+        invariants::verify(&rest, "split_at_index");
+        rest
+    }
+    
+    /// Draws the shape of the tree, one line per level, with each node's
+    /// color and value. This is the same output as the `Display` impl.
+    pub fn render_ascii(&self) -> String where T: Display {
+        self.to_string()
+    }
+    
+    /// Draws the tree top down, one node per line, with box drawing lines
+    /// from each node to its children. Reads better than `render_ascii`
+    /// for deep trees.
+    pub fn render_unicode(&self) -> String where T: Display {
+        render::unicode_tree(&self.root)
+    }
+    
+    /// Writes the tree out as a Mermaid `graph TD` flowchart, for pasting
+    /// into anything that renders Mermaid diagrams.
+    pub fn to_mermaid(&self) -> String where T: Display {
+        render::mermaid_tree(&self.root)
+    }
+    
+    /// Dumps the shape of the tree as nested `{value, color, left, right}`
+    /// JSON objects, for visualizers and tests that check the exact shape.
+    /// Values are written as strings of their `Display` output. Unlike the
+    /// serde support, which only keeps the elements, this keeps the shape.
+    pub fn to_structure_json(&self) -> String where T: Display {
+        render::json_tree(&self.root)
+    }
+    
+    /// Draws the tree as an SVG picture, with a circle filled red or black
+    /// for every node and a row for every level.
+    #[cfg(feature = "svg")]
+    pub fn to_svg(&self) -> String where T: Display {
+        svg::svg_tree(&self.root)
+    }
+    
+    /// Builds a tree out of elements that are already in ascending order
+    /// in O(n), without comparing them or rebalancing. Equal elements may
+    /// repeat. If the input isn't actually sorted, the tree keeps it in the
+    /// order given, and lookups can miss elements that are there.
+    pub fn from_sorted_iter<I>(iter: I) -> RedBlackTree<T> where I: IntoIterator<Item = T> {
+        RedBlackTree::from_sorted_vec(iter.into_iter().collect())
+    }
+    
+    /// Rebuilds the tree into the shallowest shape that holds its
+    /// elements, in O(n). Lots of inserts and removals can leave the tree
+    /// up to twice as deep as it needs to be, and this gets lookups back to
+    /// the best case. The old nodes are freed as the new ones are made, so
+    /// it doesn't need much more memory than the tree already uses.
+    pub fn rebuild(&mut self) {
+        let mut values = IntoIter::new(self.root.take(), self.count);
+        self.root = Node::build_sorted(&mut values, self.count);
+        invariants::verify(self, "rebuild");
+    }
+    
+    fn from_sorted_vec(values: Vec<T>) -> RedBlackTree<T> {
+        let count = values.len();
+        RedBlackTree {
+            root: Node::build_sorted(&mut values.into_iter(), count),
+            count,
+            pool: Pool::new(Global),
+            stats: Recorder::new(),
+            trace: Tracer::new(),
         }
     }
+    
+    // element at the given sorted position
+    fn select(&self, index: usize) -> Option<&T> {
+        self.root.as_ref()?.select(index).map(Node::value)
+    }
 }
 
-impl<'a, T> Iterator for RedBlackIterator<'a, T> where T: PartialOrd {
-    type Item = &'a T;
-    
-    fn next(&mut self) -> Option<&'a T> {
-        if self.current.is_none() {
+#[cfg(feature = "rand")]
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Picks an element uniformly at random in O(log n), by choosing a
+    /// random rank and walking down using the subtree sizes. To pick by
+    /// weight, keep the elements in a `WeightedRedBlackTree`.
+    pub fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        if self.count == 0 {
             return None;
         }
         
-        let value = self.current.unwrap().value();
-        match *self.current.unwrap().right() {
-            None => {
-                self.current = self.parents.pop();
-            },
-            Some(_) => {
-                self.current = self.current.map(|n| n.right());
-                
-                while self.current.unwrap().left().is_some() {
-                    let old_current = self.current.unwrap();
-                    self.current = self.current.map(|n| n.left());
-                    self.parents.push(old_current);
-                }
-            },
-        }
-        
-        Some(value)
+        self.select(rng.gen_range(0..self.count))
     }
 }
 
-fn get_dir(is_less: bool) -> Dir {
-    if is_less { Dir::Left } else { Dir::Right }
+// Walks the tree in order by following the parent pointers back up, so it
+// doesn't need any memory of its own.
+pub struct RedBlackIterator<'a, T, A = Global> where T: PartialOrd + 'a, A: Allocator + Clone + 'a {
+    root: Option<&'a Node<T, A>>,
+    current: Option<&'a Node<T, A>>,
+    remaining: usize,
 }
 
-impl<T> Node<T> where T: PartialOrd {
-    fn insert_n(value: T, node: &mut Link<T>) {
-        if node.is_none() {
-            *node = Some(Box::new(Node::new(value)));
-            Self::ensure_root_black(node);
-        } else {
-            let dir = get_dir(value < *node.value());
-            if let Some(0) = Self::insert_p(value, node, dir) {
-                Self::ensure_root_black(node);
-            }
+impl<'a, T, A> RedBlackIterator<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn new(tree: &'a RedBlackTree<T, A>) -> RedBlackIterator<'a, T, A> {
+        let root = tree.root.as_ref();
+        RedBlackIterator {
+            root,
+            current: root.map(|root| root.leftmost()),
+            remaining: tree.count,
         }
     }
+}
+
+impl<'a, T, A> Iterator for RedBlackIterator<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    type Item = &'a T;
     
-    fn insert_p(value: T, parent: &mut Link<T>, n_dir: Dir) -> Option<usize> {
-        if parent.follow(n_dir).is_none() {
-            let mut node = parent.follow_mut(n_dir);
-            *node = Some(Box::new(Node::new(value)));
-            None
-        } else {
-            let dir = get_dir(value < *parent.follow(n_dir).value());
-            match Self::insert_g(value, parent, n_dir, dir) {
-                None | Some(0) => None,
-                Some(rest) => Some(rest - 1),
-            }
-        }
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.current?;
+        self.current = self.root.and_then(|root| node.successor(root));
+        self.remaining -= 1;
+        Some(node.value())
     }
     
-    fn insert_g(value: T, grandparent: &mut Link<T>, p_dir: Dir, n_dir: Dir) -> Option<usize> {
-        if follow!(grandparent, p_dir, n_dir).is_none() {
-            {
-                let mut node = follow_mut!(grandparent, p_dir, n_dir);
-                *node = Some(Box::new(Node::new(value)));
-            }
-            match Self::ensure_parent_black(grandparent, p_dir, n_dir) {
-                None | Some(0) => None,
-                Some(rest) => Some(rest - 1),
-            }
-        } else {
-            let dir = get_dir(value < *follow!(grandparent, p_dir, n_dir).value());
-            match Self::insert_g(value, grandparent.follow_mut(p_dir), n_dir, dir) {
-                Some(0) => Self::ensure_parent_black(grandparent, p_dir, n_dir),
-                Some(rest) => Some(rest - 1),
-                None => None,
-            }
-        }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
     }
     
-    fn ensure_root_black(node: &mut Link<T>) {
-        node.set_color(Color::Black);
+    fn count(self) -> usize {
+        self.remaining
     }
     
-    fn ensure_parent_black(grandparent: &mut Link<T>, p_dir: Dir, n_dir: Dir) -> Option<usize> {
-        match *grandparent.follow(p_dir).color() {
-            Color::Red => Self::ensure_balanced_when_uncle_red(grandparent, p_dir, n_dir),
-            Color::Black => None,
-        }
+    // the last element is the same however far along the iterator is,
+    // unless it's already been passed
+    fn last(self) -> Option<&'a T> {
+        self.current.and(self.root).map(|root| root.rightmost().value())
     }
     
-    fn ensure_balanced_when_uncle_red(grandparent: &mut Link<T>, p_dir: Dir, n_dir: Dir) -> Option<usize> {
-        let uncle_red = {
-            let uncle = grandparent.follow_mut(p_dir.opposite());
-            match *uncle {
-                None => false,
-                _ => match *uncle.color() {
-                    Color::Black => false,
-                    Color::Red => {
-                        uncle.set_color(Color::Black);
-                        true
-                    },
-                },
-            }
-        };
-
-        if uncle_red {
-            grandparent.follow_mut(p_dir).set_color(Color::Black);
-            grandparent.set_color(Color::Red);
-            Some(2)
-        } else {
-            Self::ensure_balanced_when_uncle_black(grandparent, p_dir, n_dir);
-            None
+    // jumps straight to the element using the subtree sizes, rather than
+    // stepping through the ones in between
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        if n >= self.remaining {
+            self.current = None;
+            self.remaining = 0;
+            return None;
         }
+        
+        let root = self.root?;
+        let node = root.select(root.size() - self.remaining + n)?;
+        self.current = node.successor(root);
+        self.remaining -= n + 1;
+        Some(node.value())
     }
+}
+
+impl<'a, T, A> ExactSizeIterator for RedBlackIterator<'a, T, A> where T: PartialOrd, A: Allocator + Clone {}
+
+impl<'a, T, A> IntoIterator for &'a RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    type Item = &'a T;
+    type IntoIter = RedBlackIterator<'a, T, A>;
     
-    fn ensure_balanced_when_uncle_black(grandparent: &mut Link<T>, p_dir: Dir, n_dir: Dir) {
-        match (p_dir, n_dir) {
-            (Dir::Left, Dir::Right) => {
-                Self::rotate_left(grandparent.follow_mut(p_dir));
-            },
-            (Dir::Right, Dir::Left) => {
-                Self::rotate_right(grandparent.follow_mut(p_dir));
-            },
-            _ => (),
-        }
+    fn into_iter(self) -> RedBlackIterator<'a, T, A> {
+        self.iter()
+    }
+}
+
+// Consumes the tree in ascending order, taking nodes apart as it goes.
+pub struct IntoIter<T> where T: PartialOrd {
+    parents: Vec<OwnedNode<T>>,
+    remaining: usize,
+}
+
+impl<T> IntoIter<T> where T: PartialOrd {
+    fn new(root: Link<T>, count: usize) -> IntoIter<T> {
+        let mut iter = IntoIter {
+            parents: vec![],
+            remaining: count,
+        };
         
-        grandparent.set_color(Color::Red);
-        grandparent.follow_mut(p_dir).set_color(Color::Black);
-        match n_dir {
-            Dir::Left => Self::rotate_right(grandparent),
-            Dir::Right => Self::rotate_left(grandparent),
-        }
+        iter.push_left_spine(root);
+        iter
     }
     
-    fn rotate_left(parent: &mut Link<T>) {
-        let mut parent = parent;
-        let mut node = parent.right_mut().take();
-        let node_left = node.left_mut().take();
-        parent.set_right(node_left);
-        mem::swap(parent, &mut node);
-        
-        // so as to avoid confusion
-        let new_node = parent;
-        let new_parent = node;
-        new_node.set_left(new_parent);
+    fn push_left_spine(&mut self, link: Link<T>) {
+        let mut link = link;
+        while let Some(mut node) = link.into_owned() {
+            link = node.left_mut().take();
+            self.parents.push(node);
+        }
     }
+}
+
+impl<T> Iterator for IntoIter<T> where T: PartialOrd {
+    type Item = T;
     
-    fn rotate_right(parent: &mut Link<T>) {
-        let mut parent = parent;
-        let mut node = parent.left_mut().take();
-        let node_right = node.right_mut().take();
-        parent.set_left(node_right);
-        mem::swap(parent, &mut node);
-        
-        // so as to avoid confusion
-        let new_node = parent;
-        let new_parent = node;
-        new_node.set_right(new_parent);
+    fn next(&mut self) -> Option<T> {
+        let mut node = self.parents.pop()?;
+        let right = node.right_mut().take();
+        self.push_left_spine(right);
+        self.remaining -= 1;
+        Some(node.into_node().into_value())
     }
     
-    fn remove_n(value: &T, node: &mut Link<T>) -> Option<T> {
-        if node.is_none() {
-            return None;
-        }
-        
-        if *node.value() == *value {
-            Some(Self::find_child_to_delete(node).0)
-        } else {
-            let dir = get_dir(*node.value() < *value);
-            Self::remove_p(value, node, dir).0
-        }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
     }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> where T: PartialOrd {}
+
+impl<T> IntoIterator for RedBlackTree<T> where T: PartialOrd {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
     
-    fn remove_p(value: &T, parent: &mut Link<T>, n_dir: Dir) -> (Option<T>, bool) {
-        if parent.follow(n_dir).is_none() {
-            return (None, false);
-        }
-        
-        if *parent.follow(n_dir).value() == *value {
-            let (value, should_fix_parent) = Self::find_child_to_delete(parent.follow_mut(n_dir));
-            (Some(value), should_fix_parent)
-        } else {
-            let dir = get_dir(*parent.follow(n_dir).value() < *value);
-            let (ret, mut should_fix_parent) = Self::remove_p(value, parent.follow_mut(n_dir), dir);
-            if should_fix_parent {
-                should_fix_parent = Self::delete_case2(parent, n_dir);
-            }
-            
-            (ret, should_fix_parent)
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter::new(self.root, self.count)
+    }
+}
+
+impl<T, A> Clone for RedBlackTree<T, A> where T: PartialOrd + Clone, A: Allocator + Clone {
+    fn clone(&self) -> RedBlackTree<T, A> {
+        let mut root = self.root.clone();
+        Node::update_parents(&mut root);
+        RedBlackTree {
+            root,
+            count: self.count,
+            pool: self.pool.clone(),
+            stats: Recorder::new(),
+            trace: self.trace,
         }
     }
     
-    fn find_child_to_delete(node: &mut Link<T>) -> (T, bool) {
-        if node.left().is_none() || node.right().is_none() {
-            return Self::delete_one_child(node);
-        }
-        
-        let (mut value, mut should_fix_parent) = {
-            let left = node.left_mut();
-            Self::find_largest_child_to_delete(left)
-        };
-        
-        mem::swap(&mut value, node.value_mut());
-        
-        if should_fix_parent {
-            should_fix_parent = Self::delete_case2(node, Dir::Left);
+    fn clone_from(&mut self, source: &RedBlackTree<T, A>) {
+        self.root.clone_from(&source.root);
+        Node::update_parents(&mut self.root);
+        self.count = source.count;
+    }
+}
+
+impl<T> Default for RedBlackTree<T> where T: PartialOrd {
+    fn default() -> RedBlackTree<T> {
+        RedBlackTree::new()
+    }
+}
+
+// two trees are equal when they hold the same elements, however
+// differently they happen to be balanced
+impl<T> PartialEq for RedBlackTree<T> where T: PartialOrd {
+    fn eq(&self, other: &RedBlackTree<T>) -> bool {
+        self.count == other.count && self.iter().eq(other.iter())
+    }
+}
+
+impl<T> Eq for RedBlackTree<T> where T: Eq + PartialOrd {}
+
+impl<T> PartialOrd for RedBlackTree<T> where T: PartialOrd {
+    fn partial_cmp(&self, other: &RedBlackTree<T>) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T> Ord for RedBlackTree<T> where T: Ord {
+    fn cmp(&self, other: &RedBlackTree<T>) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+// hashes the elements in order, so equal trees hash the same
+// regardless of their shape
+impl<T> Hash for RedBlackTree<T> where T: PartialOrd + Hash {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.count);
+        for value in self.iter() {
+            value.hash(state);
         }
-        
-        (value, should_fix_parent)
     }
-    
-    fn find_largest_child_to_delete(node: &mut Link<T>) -> (T, bool) {
-        if node.right().is_none() {
-            return Self::delete_one_child(node);
+}
+
+impl<T, A> Debug for RedBlackTree<T, A> where T: PartialOrd + Debug, A: Allocator + Clone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+// draws the shape of the tree with the color of every node,
+// see the render module for the layout
+impl<T> Display for RedBlackTree<T> where T: PartialOrd + Display {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        render::fmt_tree(&self.root, f)
+    }
+}
+
+impl<T> FromIterator<T> for RedBlackTree<T> where T: PartialOrd {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> RedBlackTree<T> {
+        // sorting up front lets the tree be built in one pass instead
+        // of rebalancing after every insert
+        let values = build::sort(iter.into_iter().collect());
+        if values.windows(2).all(|pair| pair[0] <= pair[1]) {
+            return RedBlackTree::from_sorted_vec(values);
         }
         
-        let (value, mut should_fix_parent) = {
-            let right = node.right_mut();
-            Self::find_largest_child_to_delete(right)
-        };
-        
-        if should_fix_parent {
-            should_fix_parent = Self::delete_case2(node, Dir::Right);
+        // elements that can't be compared can't be sorted either, so
+        // they're inserted one at a time, and go wherever `insert` puts
+        // them
+        let mut tree = RedBlackTree::new();
+        tree.extend(values);
+        tree
+    }
+}
+
+impl<T> Extend<T> for RedBlackTree<T> where T: PartialOrd {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
         }
-        
-        (value, should_fix_parent)
     }
-    
-    fn delete_one_child(node: &mut Link<T>) -> (T, bool) {
-        let child = (if node.right().is_none() {
-            node.left_mut()
-        } else {
-            node.right_mut()
-        }).take();
-        
-        let mut new_is_black = false;
-        let original_node = mem::replace(node, child);
-        if original_node.is_black() {
-            new_is_black = node.is_black();
-            if !new_is_black {
-                node.set_color(Color::Black);
-            }
+}
+
+impl<T> From<Vec<T>> for RedBlackTree<T> where T: PartialOrd {
+    fn from(values: Vec<T>) -> RedBlackTree<T> {
+        RedBlackTree::from_iter(values)
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for RedBlackTree<T> where T: PartialOrd {
+    fn from(values: [T; N]) -> RedBlackTree<T> {
+        RedBlackTree::from_iter(values)
+    }
+}
+
+impl<T> From<BTreeSet<T>> for RedBlackTree<T> where T: Ord {
+    fn from(set: BTreeSet<T>) -> RedBlackTree<T> {
+        // already sorted, so there's no need to go through from_iter
+        let count = set.len();
+        RedBlackTree {
+            root: Node::build_sorted(&mut set.into_iter(), count),
+            count,
+            pool: Pool::new(Global),
+            stats: Recorder::new(),
+            trace: Tracer::new(),
         }
-        
-        (original_node.take_value(), new_is_black)
     }
-    
-    fn delete_case2(parent: &mut Link<T>, n_dir: Dir) -> bool {
-        let mut is_red = false;
+}
+
+impl<T, S> From<HashSet<T, S>> for RedBlackTree<T> where T: PartialOrd {
+    fn from(set: HashSet<T, S>) -> RedBlackTree<T> {
+        RedBlackTree::from_iter(set)
+    }
+}
+
+impl<T> From<RedBlackTree<T>> for BTreeSet<T> where T: Ord {
+    fn from(tree: RedBlackTree<T>) -> BTreeSet<T> {
+        tree.into_iter().collect()
+    }
+}
+
+impl<T, S> From<RedBlackTree<T>> for HashSet<T, S> where T: PartialOrd + Eq + Hash, S: BuildHasher + Default {
+    fn from(tree: RedBlackTree<T>) -> HashSet<T, S> {
+        tree.into_iter().collect()
+    }
+}
+
+impl<T> From<RedBlackTree<T>> for Vec<T> where T: PartialOrd {
+    fn from(tree: RedBlackTree<T>) -> Vec<T> {
+        tree.into_iter().collect()
+    }
+}
+
+// marks each case of rebalancing after a removal, so the Kani proofs can
+// check that every one of them gets reached. Otherwise it's nothing.
+macro_rules! delete_case {
+    ($case:expr) => {
+        #[cfg(kani)]
+        kani::cover!(true, $case);
+    };
+}
+
+fn get_dir(is_less: bool) -> Dir {
+    if is_less { Dir::Left } else { Dir::Right }
+}
+
+// which way to go from a node holding `other` to find `value`, or None if
+// that node is a match. It costs a single comparison, and values that
+// can't be compared go right, the same as on insert.
+fn search_dir<T: PartialOrd>(value: &T, other: &T) -> Option<Dir> {
+    match value.partial_cmp(other) {
+        Some(Ordering::Equal) => None,
+        Some(Ordering::Less) => Some(Dir::Left),
+        _ => Some(Dir::Right),
+    }
+}
+
+impl<T, A> Node<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn insert_n(node: OwnedNode<T, A>, root: &mut Link<T, A>, path: Vec<Dir>, trace: &Tracer) {
+        let mut node = node;
+        // all the comparing is done before anything changes, so a
+        // comparison that panics leaves the tree as it was
+        let mut path = path;
         {
-            let sibling = parent.follow_mut(n_dir.opposite());
-            if sibling.is_red() {
-                is_red = true;
-                sibling.set_color(Color::Black);
+            let mut link = &*root;
+            while let Some(current) = link.as_ref() {
+                stats::comparison();
+                let dir = get_dir(*node.value() < *current.value());
+                path.push(dir);
+                link = current.follow(dir);
             }
         }
         
-        if is_red {
-            parent.set_color(Color::Red);
-            match n_dir {
-                Dir::Left => Self::rotate_left(parent),
-                Dir::Right => Self::rotate_right(parent),
+        {
+            // every node on the way down gains a descendant
+            let mut link = &mut *root;
+            let mut parent = None;
+            for &dir in &path {
+                let here = link.as_parent();
+                let current = match link.as_mut() {
+                    Some(current) => current,
+                    None => return,
+                };
+                
+                current.set_size(current.size() + 1);
+                parent = here;
+                link = current.follow_mut(dir);
             }
+            
+            node.set_parent(parent);
+            *link = node.into();
         }
         
-        Self::delete_case3(parent, n_dir)
+        let _lent = trace.lend();
+        Self::fix_red_red(root, &path);
     }
     
-    fn delete_case3(parent: &mut Link<T>, n_dir: Dir) -> bool{
-        if parent.is_red() {
-           Self::delete_case4(parent, n_dir);
-           return false;
-        }
-        
-        let sibling_needs_change: bool;
-        {
-            let sibling = parent.follow_mut(n_dir.opposite());
-            sibling_needs_change = sibling.is_black()
-                && sibling.left().is_black()
-                && sibling.right().is_black();
+    // walks back up from the red node at the end of `path`, recoloring
+    // while its uncle is red, and rotating once when the uncle is black
+    fn fix_red_red(root: &mut Link<T, A>, path: &[Dir]) {
+        let mut depth = path.len();
+        while depth >= 2 {
+            let p_dir = path[depth - 2];
+            let n_dir = path[depth - 1];
+            let grandparent = match Self::follow_path(root, &path[..depth - 2]) {
+                Some(grandparent) => grandparent,
+                None => return,
+            };
+            let node = match grandparent.as_mut() {
+                Some(node) => node,
+                None => return,
+            };
             
-            if sibling_needs_change {
-                sibling.set_color(Color::Red);
+            if node.follow(p_dir).is_black() {
+                return;
             }
+            
+            if node.follow(p_dir.opposite()).is_black() {
+                Self::ensure_balanced_when_uncle_black(grandparent, p_dir, n_dir);
+                return;
+            }
+            
+            node.follow_mut(p_dir.opposite()).set_color(Color::Black);
+            node.follow_mut(p_dir).set_color(Color::Black);
+            node.set_color(Color::Red);
+            depth -= 2;
         }
         
-        if !sibling_needs_change {
-            Self::delete_case4(parent, n_dir);
-        }
-        
-        sibling_needs_change
+        // either the root itself was made red, or it's the parent, and
+        // a black root can't be part of a red-red pair
+        Self::ensure_root_black(root);
     }
     
-    fn delete_case4(parent: &mut Link<T>, n_dir: Dir) {
-        if parent.is_red()
-            && parent.follow(n_dir.opposite()).is_black()
-            && parent.follow(n_dir.opposite()).left().is_black()
-            && parent.follow(n_dir.opposite()).right().is_black() {
-            parent.follow_mut(n_dir.opposite()).set_color(Color::Red);
-            parent.set_color(Color::Black);
-        } else {
-            Self::delete_case5(parent, n_dir);
-            Self::delete_case6(parent, n_dir);
-        }
+    // the nodes on the path are only borrowed one step at a time, so going
+    // back up means following the path down again from the root. Comes up
+    // empty if the path runs off the bottom of the tree.
+    fn follow_path<'a>(link: &'a mut Link<T, A>, path: &[Dir]) -> Option<&'a mut Link<T, A>> {
+        path.iter().try_fold(link, |link, &dir| link.as_mut().map(|node| node.follow_mut(dir)))
     }
     
-    fn delete_case5(parent: &mut Link<T>, n_dir: Dir) {
-        if parent.follow(n_dir.opposite()).is_black() {
-            let mut rotate_right = false;
-            let mut rotate_left = false;
-            
-            {
-                let sibling = parent.follow_mut(n_dir.opposite());
-                match n_dir {
-                    Dir::Left => {
-                        if sibling.right().is_black() && sibling.left().is_red() {
-                            sibling.set_color(Color::Red);
-                            sibling.left_mut().set_color(Color::Black);
-                            rotate_right = true;
-                        }
-                    },
-                    Dir::Right => {
-                        if sibling.left().is_black() && sibling.right().is_red() {
-                            sibling.set_color(Color::Red);
-                            sibling.right_mut().set_color(Color::Black);
-                            rotate_left = true;
-                        }
-                    },
-                }
+    fn ensure_root_black(node: &mut Link<T, A>) {
+        node.set_color(Color::Black);
+    }
+    
+    fn ensure_balanced_when_uncle_black(grandparent: &mut Link<T, A>, p_dir: Dir, n_dir: Dir) {
+        if let Some(node) = grandparent.as_mut() {
+            // an inner grandchild gets rotated to the outside first
+            if n_dir != p_dir {
+                Self::rotate(node.follow_mut(p_dir), p_dir);
             }
             
-            if rotate_right {
-                Self::rotate_right(parent);
-            } else if rotate_left {
-                Self::rotate_left(parent);
-            }
+            node.set_color(Color::Red);
+            node.follow_mut(p_dir).set_color(Color::Black);
         }
+        
+        Self::rotate(grandparent, p_dir.opposite());
     }
     
-    fn delete_case6(parent: &mut Link<T>, n_dir: Dir) {
-        let parent_color = *parent.color();
-        parent.set_color(Color::Black);
-        let mut rotate_left = false;
-        let mut rotate_right = false;
-        
-        {
-            let sibling = parent.follow_mut(n_dir.opposite());
-            sibling.set_color(parent_color);
+    fn remove_n<F>(mut find: F, root: &mut Link<T, A>, pool: &mut Pool<T, A>, trace: &Tracer) -> Option<T> where F: FnMut(&Node<T, A>) -> Option<Dir> {
+        let mut path = vec![];
+        let found = {
+            let mut link = &*root;
+            let node = loop {
+                let node = link.as_ref()?;
+                let dir = match find(node) {
+                    Some(dir) => dir,
+                    None => break node,
+                };
+                path.push(dir);
+                link = node.follow(dir);
+            };
+            
+            // a node with two children trades values with the largest node
+            // in its left subtree, which has at most one child, and that
+            // node gets removed instead
+            let found = path.len();
+            if let (Some(left), true) = (node.left().as_ref(), node.right().is_some()) {
+                path.push(Dir::Left);
+                let mut node = left;
+                while let Some(right) = node.right().as_ref() {
+                    path.push(Dir::Right);
+                    node = right;
+                }
+            }
+            
+            found
+        };
         
-            match n_dir {
-                Dir::Left => {
-                    sibling.right_mut().set_color(Color::Black);
-                    rotate_left = true;
-                },
-                Dir::Right => {
-                    sibling.left_mut().set_color(Color::Black);
-                    rotate_right = true;
+        // the comparing is over
+        let _lent = trace.lend();
+        Self::remove_at(root, path, found, pool)
+    }
+    
+    // unlinks the node at the end of `path`, which can have at most one
+    // child, into the pool, and returns the value of the node `found` steps
+    // down the path, which takes over the unlinked node's value when it
+    // isn't that node
+    fn remove_at(root: &mut Link<T, A>, path: Vec<Dir>, found: usize, pool: &mut Pool<T, A>) -> Option<T> {
+        let (mut value, is_short) = {
+            // every node on the way down loses a descendant
+            let mut link = &mut *root;
+            for &dir in &path {
+                let node = link.as_mut()?;
+                node.set_size(node.size() - 1);
+                link = node.follow_mut(dir);
+            }
+            
+            let mut removed = link.take().into_owned()?;
+            let child = if removed.left().is_some() {
+                removed.left_mut().take()
+            } else {
+                removed.right_mut().take()
+            };
+            *link = child;
+            link.set_parent(removed.parent());
+            
+            // taking out a black node leaves its side a black node short,
+            // unless there is a red child to recolor in its place
+            let is_short = removed.is_black() && link.is_black();
+            if removed.is_black() {
+                link.set_color(Color::Black);
+            }
+            
+            (pool.recycle(removed), is_short)
+        };
+        
+        if found < path.len() {
+            if let Some(node) = Self::follow_path(root, &path[..found]).and_then(Link::as_mut) {
+                mem::swap(&mut value, node.value_mut());
+            }
+        }
+        
+        if is_short {
+            Self::fix_short_side(root, path);
+        }
+        
+        Some(value)
+    }
+    
+    // walks back up from the end of `path`, whose subtree (possibly empty)
+    // has one black node fewer than its sibling's, until the black heights
+    // are balanced again
+    fn fix_short_side(root: &mut Link<T, A>, mut path: Vec<Dir>) {
+        while let Some(&n_dir) = path.last() {
+            let s_dir = n_dir.opposite();
+            let depth = path.len();
+            let link = match Self::follow_path(root, &path[..depth - 1]) {
+                Some(link) => link,
+                None => return,
+            };
+            let parent = match link.as_mut() {
+                Some(parent) => parent,
+                None => return,
+            };
+            
+            // a red sibling gets rotated above the parent, which leaves the
+            // parent red, one level down, with a black sibling
+            if parent.follow(s_dir).is_red() {
+                delete_case!("red sibling");
+                parent.follow_mut(s_dir).set_color(Color::Black);
+                parent.set_color(Color::Red);
+                Self::rotate(link, n_dir);
+                path.insert(depth - 1, n_dir);
+                continue;
+            }
+            
+            // the short side has a black height of at least one less than
+            // the sibling's, so there is a sibling
+            let parent_color = parent.color();
+            let sibling = match parent.follow_mut(s_dir).as_mut() {
+                Some(sibling) => sibling,
+                None => return,
+            };
+            
+            if sibling.left().is_black() && sibling.right().is_black() {
+                // with no red nephews the sibling's side can be shortened
+                // to match, which moves the problem up to the parent,
+                // unless the parent is red and can make up for both sides
+                sibling.set_color(Color::Red);
+                if parent_color.is_red() {
+                    delete_case!("red parent, black sibling and nephews");
+                    parent.set_color(Color::Black);
+                    return;
                 }
+                
+                delete_case!("black parent, sibling and nephews");
+                path.pop();
+                continue;
+            }
+            
+            if sibling.follow(s_dir).is_black() {
+                delete_case!("red near nephew");
+                // move the red nephew to the far side
+                sibling.set_color(Color::Red);
+                sibling.follow_mut(n_dir).set_color(Color::Black);
+                Self::rotate(parent.follow_mut(s_dir), s_dir);
+            }
+            
+            if let Some(sibling) = parent.follow_mut(s_dir).as_mut() {
+                sibling.set_color(parent_color);
+                sibling.follow_mut(s_dir).set_color(Color::Black);
             }
+            
+            // the sibling takes the parent's place and color, and the
+            // parent becomes the extra black node on the short side
+            delete_case!("red far nephew");
+            parent.set_color(Color::Black);
+            Self::rotate(link, n_dir);
+            return;
         }
+    }
+    
+    // moves the node at `link` down to its `dir` side, bringing up its
+    // other child in its place. Without that child there's nothing to do.
+    fn rotate(link: &mut Link<T, A>, dir: Dir) {
+        let mut down = match link.take().into_owned() {
+            Some(down) => down,
+            None => return,
+        };
+        let mut up = match down.follow_mut(dir.opposite()).take().into_owned() {
+            Some(up) => up,
+            None => {
+                *link = down.into();
+                return;
+            },
+        };
         
-        if rotate_left {
-            Self::rotate_left(parent);
-        } else if rotate_right {
-            Self::rotate_right(parent);
+        stats::rotation();
+        trace::rotation(dir, down.value(), up.value());
+        let above = down.parent();
+        *down.follow_mut(dir.opposite()) = up.follow_mut(dir).take();
+        down.update();
+        *up.follow_mut(dir) = down.into();
+        up.update();
+        up.set_parent(above);
+        *link = up.into();
+    }
+    
+    fn structural_eq(link: &Link<T, A>, other: &Link<T, A>) -> bool {
+        match (link.as_ref(), other.as_ref()) {
+            (Some(node), Some(other)) => {
+                node.color() == other.color() && node.value() == other.value()
+                    && Self::structural_eq(node.left(), other.left())
+                    && Self::structural_eq(node.right(), other.right())
+            },
+            (None, None) => true,
+            _ => false,
         }
     }
 }
@@ -456,6 +1064,18 @@ macro_rules! rb_tree [
     });
 ];
 
+// like rb_tree!, but bulk builds the tree from elements that have to be
+// listed in ascending order, and panics if they aren't
+#[macro_export]
+macro_rules! rb_tree_sorted [
+    ($($item:expr),*) => ({
+        match RedBlackTree::try_from_sorted_vec(vec![$($item),*]) {
+            Ok(tree) => tree,
+            Err(error) => panic!("rb_tree_sorted!: {}", error),
+        }
+    });
+];
+
 
 #[cfg(test)]
 #[macro_use(expect)]
@@ -463,11 +1083,16 @@ extern crate expectest;
 
 #[cfg(test)]
 mod unit_tests {
-    pub use expectest::prelude::*;
+    use expectest::prelude::*;
     
-    use std::fmt::Debug;
-    pub use super::*;
-    pub use super::node::*;
+    use std::cmp::Ordering;
+    use std::collections::{BTreeSet, HashSet};
+    use std::collections::hash_map::DefaultHasher;
+    use std::fmt::Display;
+    use std::hash::{Hash, Hasher};
+    use std::sync::Mutex;
+    use super::*;
+    use super::node::*;
     
     // print before each insert so we can see how the tree
     // is being constructed
@@ -483,195 +1108,71 @@ mod unit_tests {
         })
     ];
     
-    impl PartialEq for Color {
-        fn eq(&self, other: &Self) -> bool {
-            match *self {
-                Color::Red => match *other {
-                    Color::Red => true,
-                    _ => false,
-                },
-                Color::Black => match *other {
-                    Color::Black => true,
-                    _ => false,
-                }
-            }
-        }
-    }
-    
-    // Allows you to verify a tree's structure
-    // by using the same format as the output
-    // of the print_tree function:
-    // verify!{ tree =>
-    //         < B.2 >
-    //     B.1   < R.4 >
-    //         B.3   < B.6 >
-    //             R.5     R.7
-    // };
-    macro_rules! verify {
-        // helper rule
-        (@expr $e:expr) => {$e};
-        
-        (@color R) => { Color::Red };
-        (@color B) => { Color::Black };
-        
-        (@expect $node:expr => $c:tt.$v:tt) => {{
-            expect!($node.as_ref()).to(be_some()
-                .value(&Box::new(node(
-                    verify!(@color $c),
-                    verify!(@expr $v)))));
-        }};
-        (@expect $node:expr => None) => {{
-            expect!($node.as_ref()).to(be_none());
-        }};
-        
-        // edge case where root is None
-        (@match_node [$node:expr] => None) => {
-            verify!(@expect $node => None);
-        };
-        // two children
-        (@match_node [$node:expr, $($stack:expr),+] => <$c:tt.$v:tt> $($nodes:tt)+) => {{
-            verify!(@expect $node => $c.$v);
-            verify!(@match_node [$($stack),+, $node.left(), $node.right()] => $($nodes)+);
-        }};
-        // two children and one stack item
-        (@match_node [$node:expr] => <$c:tt.$v:tt> $($nodes:tt)+) => {{
-            verify!(@expect $node => $c.$v);
-            verify!(@match_node [$node.left(), $node.right()] => $($nodes)+);
-        }};
-        // only right child
-        (@match_node [$node:expr, $($stack:expr),+] => $c:tt.$v:tt> $($nodes:tt)+) => {{
-            verify!(@expect $node => $c.$v);
-            verify!(@expect $node.left() => None);
-            verify!(@match_node [$($stack),+, $node.right()] => $($nodes)+);
-        }};
-        // only right child and one stack item
-        (@match_node [$node:expr] => $c:tt.$v:tt> $($nodes:tt)+) => {{
-            verify!(@expect $node => $c.$v);
-            verify!(@expect $node.left() => None);
-            verify!(@match_node [$node.right()] => $($nodes)+);
-        }};
-        // only left child
-        (@match_node [$node:expr, $($stack:expr),+] => <$c:tt.$v:tt $($nodes:tt)+) => {{
-            verify!(@expect $node => $c.$v);
-            verify!(@expect $node.right() => None);
-            verify!(@match_node [$($stack),+, $node.left()] => $($nodes)+);
-        }};
-        // only left child and one stack item
-        (@match_node [$node:expr] => <$c:tt.$v:tt $($nodes:tt)+) => {{
-            verify!(@expect $node => $c.$v);
-            verify!(@expect $node.right() => None);
-            verify!(@match_node [$node.left()] => $($nodes)+);
-        }};
-        // no children
-        (@match_node [$node:expr, $($stack:expr),+] => $c:tt.$v:tt $($nodes:tt)+) => {{
-            verify!(@expect $node => $c.$v);
-            verify!(@expect $node.right() => None);
-            verify!(@expect $node.left() => None);
-            verify!(@match_node [$($stack),+] => $($nodes)+);
-        }};
-        // no children and one stack item
-        (@match_node [$node:expr] => $c:tt.$v:tt) => {{
-            verify!(@expect $node => $c.$v);
-            verify!(@expect $node.left() => None);
-            verify!(@expect $node.right() => None);
-        }};
-    
-        // initial macro call
-        ($tree:expr => $($nodes:tt)+) => {
-            verify!(@match_node [$tree.root] => $($nodes)+);
-        };
-    }
-    
     // Will print out a tree like the following:
     //   <B.2>
     // B.1     <R.4>
     //       B.3   B.5>
     //                R.6
     #[allow(dead_code)]
-    pub fn print_tree<T: PartialOrd + Debug> (tree: &RedBlackTree<T>) {
-        let link = &tree.root;
-        if link.is_none() {
+    pub fn print_tree<T: PartialOrd + Display> (tree: &RedBlackTree<T>) {
+        if tree.root.is_none() {
             println!("None");
             return;
         }
         
-        let mut print_queue = vec![(0, link)];
-        while !print_queue.is_empty() {
-            let mut new_print_queue = vec![];
-            let mut printed_offset = 0;
-            for (link_offset, link) in print_queue {
-                printed_offset += print_link(
-                    link,
-                    link_offset,
-                    link_offset - printed_offset,
-                    &mut new_print_queue);
-            }
-            
-            print_queue = new_print_queue;
-            println!("\n");
-        }
+        println!("{}\n", tree.render_ascii());
     }
     
-    fn tree_width<T: PartialOrd + Debug>(link: &Link<T>) -> usize {
-        match *link {
-            None => 0,
-            _ => tree_width(link.left())
-                + link_str(link).len()
-                + tree_width(link.right())
-        }
-    }
-    
-    fn link_str<T: PartialOrd + Debug> (link: &Link<T>) -> String {
-        format!("{:?}", link.as_ref().unwrap())
-    }
-    
-    fn print_link<'a, 'b, T>(link: &'a Link<T>, offset: usize, printed_offset: usize, print_queue: &'b mut Vec<(usize, &'a Link<T>)>) -> usize
-    where T: PartialOrd + Debug {
-        let right = link.right();
-        let left = link.left();
-        
-        if left.is_some() {
-            print_queue.push((offset, left));
-        }
+    mod the_red_black_tree {
+        use super::*;
         
-        let link_str = link_str(link);
-        let link_str_len = link_str.len();
-        let link_offset = tree_width(left) + link_str_len;
-        if right.is_some() {
-            print_queue.push((offset + link_offset, right));
+        mod new_constructor {
+            use super::*;
+            
+            #[test]
+            fn creates_a_tree_with_no_root_and_with_a_count_of_0() {
+                let tree = RedBlackTree::<usize>::new();
+                expect!(tree.root.as_ref()).to(be_none());
+                expect!(tree.count).to(be_equal_to(0));
+            }
+            
+            #[test]
+            fn can_initialize_a_static() {
+                static TREE: Mutex<RedBlackTree<u32>> = Mutex::new(RedBlackTree::new());
+                const EMPTY: CowRedBlackTree<u32> = CowRedBlackTree::new();
+                
+                TREE.lock().unwrap().insert(3);
+                expect!(TREE.lock().unwrap().contains(&3)).to(be_true());
+                expect!(EMPTY.len()).to(be_equal_to(0));
+            }
         }
-        let link_with_arrows = format!("{}{}{}",
-            left.as_ref().map_or("", |_| "<"),
-            link_str,
-            right.as_ref().map_or("", |_| ">")
-        );
-        let right_offset = right.as_ref().map_or(0, |_| 1);
-        let arrow_offset = left.as_ref().map_or(0, |_| 1) + right_offset;
         
-        print!("{0:>1$}", link_with_arrows, link_offset + printed_offset + right_offset);
-        link_offset + offset + arrow_offset
-    }
-    
-    describe! the_red_black_tree {
-        describe! new_constructor {
-            it "creates a tree with no root and with a count of 0" {
-                let tree = RedBlackTree::<usize>::new();
-                expect!(tree.root).to(be_none());
+        mod default {
+            use super::*;
+            
+            #[test]
+            fn creates_an_empty_tree() {
+                let tree: RedBlackTree<usize> = Default::default();
+                expect!(tree.root.as_ref()).to(be_none());
                 expect!(tree.count).to(be_equal_to(0));
             }
         }
         
-        describe! insert {
-            it "creates a black root when the first item is inserted" {
+        mod insert {
+            use super::*;
+            
+            #[test]
+            fn creates_a_black_root_when_the_first_item_is_inserted() {
                 let tree = rb_tree![1];
                 assert!(tree.root.is_some());
                 
-                expect!(tree.root.value()).to(be_equal_to(&1));
+                expect!(tree.root.as_ref().unwrap().value()).to(be_equal_to(&1));
                 expect!(tree.count).to(be_equal_to(1));
-                expect!(tree.root.color().is_black()).to(be_true());
+                expect!(tree.root.as_ref().unwrap().color().is_black()).to(be_true());
             }
             
-            it "correctly rotates the tree when it becomes unbalanced on the third insert" {
+            #[test]
+            fn correctly_rotates_the_tree_when_it_becomes_unbalanced_on_the_third_insert() {
                 // right heavy
                 let tree = rb_tree![1, 2, 3];
                 verify!{ tree =>
@@ -687,7 +1188,8 @@ mod unit_tests {
                 };
             }
             
-            it "fixes uncles when an insert leaves an imbalance in the number of black nodes" {
+            #[test]
+            fn fixes_uncles_when_an_insert_leaves_an_imbalance_in_the_number_of_black_nodes() {
                 // right-right
                 let tree = rb_tree![1, 2, 3, 4];
                 verify!{ tree =>
@@ -721,7 +1223,8 @@ mod unit_tests {
                 };
             }
             
-            it "makes grandparents red when parents and uncles were red" {
+            #[test]
+            fn makes_grandparents_red_when_parents_and_uncles_were_red() {
                 let mut tree = rb_tree_print![1, 2, 3, 4, 5, 6];
                 verify!{ tree =>
                       < B.2 >
@@ -740,14 +1243,18 @@ mod unit_tests {
             }
         }
         
-        describe! remove {
-            it "returns None when the tree is empty" {
+        mod remove {
+            use super::*;
+            
+            #[test]
+            fn returns_none_when_the_tree_is_empty() {
                 let mut tree: RedBlackTree<usize> = rb_tree![];
                 let value = 1;
                 expect!(tree.remove(&value)).to(be_none());
             }
             
-            it "returns Some item matching value when a match is found, and removes the item from the tree" {
+            #[test]
+            fn returns_some_item_matching_value_when_a_match_is_found_and_removes_the_item_from_the_tree() {
                 let mut tree = rb_tree![1];
                 let value = 1;
                 
@@ -756,7 +1263,8 @@ mod unit_tests {
                 verify!{ tree => None };
             }
             
-            it "can remove a node that has two children" {
+            #[test]
+            fn can_remove_a_node_that_has_two_children() {
                 let mut tree = rb_tree![1, 2, 3];
                 verify!{tree =>
                       < B.2 >
@@ -771,7 +1279,8 @@ mod unit_tests {
                 };
             }
             
-            it "rebalances when a deep removal is made" {
+            #[test]
+            fn rebalances_when_a_deep_removal_is_made() {
                 let mut tree = rb_tree_print![1, 2, 3, 4, 5, 6];
                 verify!{ tree =>
                       < B.2 >
@@ -784,15 +1293,800 @@ mod unit_tests {
                 expect!(tree.remove(&value)).to(be_some().value(4));
                 verify!{tree =>
                       < B.2 >
+                    B.1   < R.5 >
+                        B.3     B.6
+                };
+            }
+            
+            #[test]
+            fn keeps_every_element_and_subtree_size_right_through_many_removals() {
+                let mut tree: RedBlackTree<_> = (0..200).map(|i| i % 50).collect();
+                for i in 0..50 {
+                    expect!(tree.remove(&(i * 7 % 50))).to(be_some().value(i * 7 % 50));
+                    expect!(tree.root.size()).to(be_equal_to(tree.count));
+                }
+                
+                expect!(tree.remove(&50)).to(be_none());
+                expect!(tree.count).to(be_equal_to(150));
+                let expected: Vec<_> = (0..50).flat_map(|i| vec![i; 3]).collect();
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(expected));
+            }
+            
+            #[test]
+            fn compares_against_each_node_on_the_way_down_only_once() {
+                thread_local!(static COMPARISONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) });
+                
+                #[derive(Debug)]
+                struct Counted(u32);
+                impl PartialEq for Counted {
+                    fn eq(&self, other: &Counted) -> bool {
+                        COMPARISONS.with(|count| count.set(count.get() + 1));
+                        self.0 == other.0
+                    }
+                }
+                impl PartialOrd for Counted {
+                    fn partial_cmp(&self, other: &Counted) -> Option<Ordering> {
+                        COMPARISONS.with(|count| count.set(count.get() + 1));
+                        self.0.partial_cmp(&other.0)
+                    }
+                }
+                
+                // 4 at the root, then 2 and 6, then the rest
+                let mut tree: RedBlackTree<_> = (1..8).map(Counted).collect();
+                COMPARISONS.with(|count| count.set(0));
+                expect!(tree.contains(&Counted(7))).to(be_true());
+                expect!(COMPARISONS.with(|count| count.get())).to(be_equal_to(3));
+                
+                COMPARISONS.with(|count| count.set(0));
+                expect!(tree.remove(&Counted(5)).map(|value| value.0)).to(be_some().value(5));
+                // checking the tree afterwards compares each element with the one before it
+                let checking = if cfg!(feature = "debug-invariants") { tree.count - 1 } else { 0 };
+                expect!(COMPARISONS.with(|count| count.get())).to(be_equal_to(3 + checking));
+            }
+        }
+        
+        mod structural_eq {
+            use super::*;
+            
+            #[test]
+            fn tells_apart_trees_that_hold_the_same_elements_in_different_shapes() {
+                let inserted = rb_tree![1, 2, 3, 4, 5, 6, 7];
+                let built = rb_tree_sorted![1, 2, 3, 4, 5, 6, 7];
+                expect!(inserted == built).to(be_true());
+                expect!(inserted.structural_eq(&built)).to(be_false());
+                expect!(inserted.structural_eq(&inserted.clone())).to(be_true());
+                
+                // the same shape, but with one node's color flipped
+                let mut recolored = built.clone();
+                recolored.root.as_mut().unwrap().left_mut().set_color(Color::Red);
+                expect!(built.structural_eq(&recolored)).to(be_false());
+                
+                let mut rebuilt = inserted.clone();
+                rebuilt.rebuild();
+                expect!(rebuilt.structural_eq(&built)).to(be_true());
+                expect!(rb_tree![1, 2].structural_eq(&rb_tree![1])).to(be_false());
+            }
+        }
+        
+        mod panic_safety {
+            use super::*;
+            
+            #[test]
+            fn leaves_the_tree_as_it_was_when_a_comparison_panics() {
+                thread_local! {
+                    // how many more comparisons may happen before one panics
+                    static FUSE: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) };
+                    static DROPS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+                }
+                
+                #[derive(Debug)]
+                struct Touchy(u32);
+                impl Touchy {
+                    fn burn() {
+                        FUSE.with(|fuse| match fuse.get() {
+                            Some(0) => panic!("comparison failed"),
+                            Some(left) => fuse.set(Some(left - 1)),
+                            None => {},
+                        });
+                    }
+                }
+                impl Drop for Touchy {
+                    fn drop(&mut self) {
+                        DROPS.with(|drops| drops.set(drops.get() + 1));
+                    }
+                }
+                impl PartialEq for Touchy {
+                    fn eq(&self, other: &Touchy) -> bool {
+                        Touchy::burn();
+                        self.0 == other.0
+                    }
+                }
+                impl PartialOrd for Touchy {
+                    fn partial_cmp(&self, other: &Touchy) -> Option<Ordering> {
+                        Touchy::burn();
+                        self.0.partial_cmp(&other.0)
+                    }
+                }
+                
+                let mut tree: RedBlackTree<_> = (0..64).map(|value| Touchy(value * 2)).collect();
+                let expected: Vec<u32> = (0..64).map(|value| value * 2).collect();
+                // 40 is four levels down, so each of these panics on the way to it
+                for fuse in 0..4 {
+                    DROPS.with(|drops| drops.set(0));
+                    FUSE.with(|cell| cell.set(Some(fuse)));
+                    let inserted = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tree.insert(Touchy(41))));
+                    let removed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tree.remove(&Touchy(40))));
+                    FUSE.with(|cell| cell.set(None));
+                    
+                    expect!(inserted.is_err()).to(be_true());
+                    expect!(removed.is_err()).to(be_true());
+                    // only the two elements that never made it in were dropped
+                    expect!(DROPS.with(|drops| drops.get())).to(be_equal_to(2));
+                    expect!(tree.check_invariants()).to(be_ok());
+                    expect!(tree.iter().map(|value| value.0).collect::<Vec<_>>()).to(be_equal_to(expected.clone()));
+                }
+                
+                tree.insert(Touchy(41));
+                expect!(tree.remove(&Touchy(40)).map(|value| value.0)).to(be_some().value(40));
+                expect!(tree.check_invariants()).to(be_ok());
+            }
+        }
+        
+        mod subtree_sizes {
+            use super::*;
+            
+            #[test]
+            fn keeps_the_size_of_every_subtree_up_to_date() {
+                let mut tree = rb_tree![5, 2, 8, 1, 9, 3, 7, 4, 6];
+                expect!(tree.root.size()).to(be_equal_to(9));
+                let root = tree.root.as_ref().unwrap();
+                expect!(root.left().size() + root.right().size()).to(be_equal_to(8));
+                
+                tree.remove(&5);
+                expect!(tree.root.size()).to(be_equal_to(tree.count));
+            }
+        }
+        
+        mod select {
+            use super::*;
+            
+            #[test]
+            fn finds_elements_by_their_sorted_position() {
+                let tree = rb_tree![50, 20, 80, 10, 90, 30, 70];
+                expect!(tree.select(0)).to(be_some().value(&10));
+                expect!(tree.select(3)).to(be_some().value(&50));
+                expect!(tree.select(6)).to(be_some().value(&90));
+                expect!(tree.select(7)).to(be_none());
+            }
+        }
+        
+        mod from_iter {
+            use super::*;
+            
+            #[test]
+            fn builds_a_tree_from_any_iterator() {
+                let tree: RedBlackTree<_> = (0..1000).rev().collect();
+                expect!(tree.count).to(be_equal_to(1000));
+                expect!(tree.root.size()).to(be_equal_to(1000));
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..1000).collect::<Vec<_>>()));
+            }
+            
+            #[test]
+            fn colors_the_incomplete_bottom_level_red() {
+                let tree: RedBlackTree<_> = vec![3, 1, 4, 2].into_iter().collect();
+                verify!{ tree =>
+                      < B.2 >
                     B.1     B.3 >
-                                B.5 >
+                                R.4
+                };
+                
+                let tree: RedBlackTree<usize> = vec![].into_iter().collect();
+                verify!{ tree => None };
+            }
+            
+            #[test]
+            fn inserts_elements_that_cant_be_compared() {
+                let tree: RedBlackTree<f64> = vec![3.0, f64::NAN, 1.0, 2.0].into_iter().collect();
+                let mut inserted = RedBlackTree::new();
+                for value in [3.0, f64::NAN, 1.0, 2.0] {
+                    inserted.insert(value);
+                }
+                
+                expect!(tree.contains(&3.0)).to(be_true());
+                expect!(format!("{:?}", tree)).to(be_equal_to(format!("{:?}", inserted)));
+                
+                // big enough for the standard library's sorts to notice
+                // the missing total order
+                let values: Vec<f64> = (0..50).map(|value| if value % 7 == 0 { f64::NAN } else { ((value * 37) % 50) as f64 }).collect();
+                let tree: RedBlackTree<f64> = values.into_iter().collect();
+                expect!(tree.iter().count()).to(be_equal_to(50));
+                expect!(tree.iter().filter(|value| value.is_nan()).count()).to(be_equal_to(8));
+                expect!(tree.check_invariants().is_ok()).to(be_true());
+            }
+        }
+        
+        mod from_sorted_iter {
+            use super::*;
+            
+            #[test]
+            fn builds_the_same_balanced_shape_as_from_iter() {
+                let tree = RedBlackTree::from_sorted_iter(vec![1, 2, 3, 4]);
+                verify!{ tree =>
+                      < B.2 >
+                    B.1     B.3 >
+                                R.4
+                };
+                
+                let tree = RedBlackTree::from_sorted_iter((0..1000).map(|value| value / 2));
+                expect!(tree.root.size()).to(be_equal_to(1000));
+                expect!(tree.contains(&499)).to(be_true());
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..1000).map(|value| value / 2).collect::<Vec<_>>()));
+            }
+        }
+        
+        mod rebuild {
+            use super::*;
+            
+            #[test]
+            fn packs_the_tree_into_a_minimal_height_shape() {
+                let mut tree = RedBlackTree::new();
+                for value in 0..1000 {
+                    tree.insert(value);
+                }
+                for value in 0..500 {
+                    tree.remove(&(value * 2));
+                }
+                
+                tree.rebuild();
+                let packed = RedBlackTree::from_sorted_iter((0..500).map(|value| value * 2 + 1));
+                expect!(tree.to_level_order()).to(be_equal_to(packed.to_level_order()));
+                expect!(tree.root.size()).to(be_equal_to(500));
+                
+                let mut tree = RedBlackTree::<u8>::new();
+                tree.rebuild();
+                verify!{ tree => None };
+            }
+        }
+        
+        mod sorted_constructors {
+            use super::*;
+            
+            #[test]
+            fn builds_a_tree_from_sorted_input() {
+                let tree = RedBlackTree::try_from_sorted_slice(&[1, 2, 2, 5]).unwrap();
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 2, 5]));
+                
+                let tree = rb_tree_sorted![1.5, 2.5];
+                expect!(tree.contains(&2.5)).to(be_true());
+                expect!(RedBlackTree::<u8>::try_from_sorted_vec(vec![]).is_ok()).to(be_true());
+            }
+            
+            #[test]
+            fn points_at_the_first_element_out_of_order() {
+                let error = RedBlackTree::try_from_sorted_vec(vec![1, 3, 2, 0]).unwrap_err();
+                expect!(error.index()).to(be_equal_to(2));
+                expect!(error.to_string()).to(be_equal_to("element 2 is out of order".to_string()));
+                
+                let error = RedBlackTree::try_from_sorted_slice(&[1.0, f64::NAN]).unwrap_err();
+                expect!(error.index()).to(be_equal_to(1));
+            }
+            
+            #[test]
+            #[should_panic]
+            fn makes_the_macro_panic_on_unsorted_input() {
+                rb_tree_sorted![2, 1];
+            }
+        }
+        
+        mod extend {
+            use super::*;
+            
+            #[test]
+            fn inserts_every_element_of_the_iterator() {
+                let mut tree = rb_tree![5, 1];
+                tree.extend(vec![3, 5, 2]);
+                expect!(tree.count).to(be_equal_to(5));
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3, 5, 5]));
+            }
+        }
+        
+        mod from {
+            use super::*;
+            
+            #[test]
+            fn builds_a_tree_from_a_vector_or_an_array() {
+                let tree = RedBlackTree::from(vec![3, 1, 2]);
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3]));
+                
+                let tree = RedBlackTree::from([3, 1, 2]);
+                expect!(tree.count).to(be_equal_to(3));
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3]));
+            }
+        }
+        
+        mod clone {
+            use super::*;
+            
+            #[test]
+            fn copies_the_exact_shape_and_colors_of_the_tree() {
+                let mut tree = rb_tree![1, 2, 3, 4, 5, 6];
+                let copy = tree.clone();
+                tree.insert(7);
+                
+                verify!{ copy =>
+                      < B.2 >
+                    B.1   < R.4 >
+                        B.3     B.5 >
                                     R.6
                 };
+                expect!(copy.count).to(be_equal_to(6));
+                expect!(copy.root.size()).to(be_equal_to(6));
+            }
+        }
+        
+        mod clone_from {
+            use super::*;
+            
+            #[test]
+            fn reuses_the_nodes_of_the_tree_being_overwritten() {
+                let source = rb_tree![10, 20, 30];
+                let mut tree = rb_tree![1, 2, 3];
+                let root_before = tree.root.as_ref().unwrap() as *const Node<_>;
+                
+                tree.clone_from(&source);
+                let root_after = tree.root.as_ref().unwrap() as *const Node<_>;
+                expect!(root_after).to(be_equal_to(root_before));
+                verify!{ tree =>
+                      < B.20 >
+                    R.10     R.30
+                };
+            }
+            
+            #[test]
+            fn grows_and_shrinks_the_tree_to_match_the_source() {
+                let mut tree = rb_tree![1, 2, 3];
+                tree.clone_from(&rb_tree![1, 2, 3, 4, 5, 6]);
+                expect!(tree.count).to(be_equal_to(6));
+                verify!{ tree =>
+                      < B.2 >
+                    B.1   < R.4 >
+                        B.3     B.5 >
+                                    R.6
+                };
+                
+                tree.clone_from(&rb_tree![7]);
+                verify!{ tree => B.7 };
+                expect!(tree.count).to(be_equal_to(1));
+            }
+        }
+        
+        mod debug {
+            use super::*;
+            
+            #[test]
+            fn prints_the_elements_in_order_as_a_set() {
+                expect!(format!("{:?}", rb_tree![2, 3, 1])).to(be_equal_to("{1, 2, 3}".to_string()));
+                expect!(format!("{:?}", RedBlackTree::<usize>::new())).to(be_equal_to("{}".to_string()));
+            }
+        }
+        
+        mod equality {
+            use super::*;
+            
+            #[test]
+            fn compares_the_contents_regardless_of_the_shape_of_the_tree() {
+                let inserted = rb_tree![4, 3, 2, 1];
+                let built: RedBlackTree<_> = (1..5).collect();
+                expect!(inserted.root.as_ref().unwrap().value()).to_not(be_equal_to(built.root.as_ref().unwrap().value()));
+                expect!(inserted == built).to(be_true());
+                
+                expect!(rb_tree![1, 2] == rb_tree![1, 2, 3]).to(be_false());
+                expect!(rb_tree![1, 2, 4] == rb_tree![1, 2, 3]).to(be_false());
+            }
+        }
+        
+        mod ordering {
+            use super::*;
+            
+            #[test]
+            fn compares_the_elements_lexicographically() {
+                expect!(rb_tree![1, 2, 3] < rb_tree![1, 3]).to(be_true());
+                expect!(rb_tree![1, 2] < rb_tree![1, 2, 3]).to(be_true());
+                expect!(rb_tree![2] > rb_tree![1, 5]).to(be_true());
+                expect!(rb_tree![3, 1, 2].cmp(&(1..4).collect())).to(be_equal_to(Ordering::Equal));
+                
+                let mut trees = vec![rb_tree![2], rb_tree![1, 3], rb_tree![1, 2]];
+                trees.sort();
+                expect!(trees).to(be_equal_to(vec![rb_tree![1, 2], rb_tree![1, 3], rb_tree![2]]));
+            }
+        }
+        
+        mod hash {
+            use super::*;
+            
+            #[test]
+            // the stats counters are the only thing that changes behind a
+            // shared reference, and they're left out of the hash
+            #[allow(clippy::mutable_key_type)]
+            fn hashes_equal_trees_the_same_regardless_of_shape() {
+                fn hash_of<T: Hash>(value: &T) -> u64 {
+                    let mut hasher = DefaultHasher::new();
+                    value.hash(&mut hasher);
+                    hasher.finish()
+                }
+                
+                let inserted = rb_tree![4, 3, 2, 1];
+                let built: RedBlackTree<_> = (1..5).collect();
+                expect!(hash_of(&inserted)).to(be_equal_to(hash_of(&built)));
+                
+                let mut seen = HashSet::new();
+                expect!(seen.insert(inserted)).to(be_true());
+                expect!(seen.insert(built)).to(be_false());
+                expect!(seen.insert(rb_tree![1, 2, 3])).to(be_true());
+            }
+        }
+        
+        mod display {
+            use super::*;
+            
+            #[test]
+            fn draws_the_shape_of_the_tree() {
+                let tree = rb_tree![1, 2, 3, 4, 5, 6];
+                expect!(tree.to_string()).to(be_equal_to(concat!(
+                    "  <B.2>\n",
+                    "B.1     <R.4>\n",
+                    "      B.3   B.5>\n",
+                    "               R.6").to_string()));
+                
+                expect!(RedBlackTree::<usize>::new().to_string()).to(be_equal_to(String::new()));
+            }
+            
+            #[test]
+            fn can_be_rendered_to_a_string_directly() {
+                let tree = rb_tree![1, 2, 3];
+                expect!(tree.render_ascii()).to(be_equal_to(concat!(
+                    "  <B.2>\n",
+                    "R.1   R.3").to_string()));
+            }
+            
+            #[test]
+            fn draws_a_vertical_tree_with_box_drawing_lines() {
+                let tree = rb_tree![1, 2, 3, 4, 5, 6];
+                expect!(tree.render_unicode()).to(be_equal_to(concat!(
+                    "B.2\n",
+                    "├── B.1\n",
+                    "└── R.4\n",
+                    "    ├── B.3\n",
+                    "    └── B.5\n",
+                    "        ├── ·\n",
+                    "        └── R.6").to_string()));
+                
+                expect!(rb_tree![1].render_unicode()).to(be_equal_to("B.1".to_string()));
+                expect!(RedBlackTree::<usize>::new().render_unicode()).to(be_equal_to(String::new()));
+            }
+            
+            #[test]
+            fn exports_a_mermaid_flowchart() {
+                let tree = rb_tree![1, 2, 3, 4];
+                expect!(tree.to_mermaid()).to(be_equal_to(concat!(
+                    "graph TD\n",
+                    "    n0[\"2\"]:::black\n",
+                    "    n0 -->|L| n1\n",
+                    "    n1[\"1\"]:::black\n",
+                    "    n0 -->|R| n2\n",
+                    "    n2[\"3\"]:::black\n",
+                    "    n2 -->|R| n3\n",
+                    "    n3[\"4\"]:::red\n",
+                    "    classDef red fill:#d22,stroke:#800,color:#fff\n",
+                    "    classDef black fill:#222,stroke:#000,color:#fff").to_string()));
+                
+                let mut quoted = RedBlackTree::new();
+                quoted.insert("say \"hi\"");
+                expect!(quoted.to_mermaid().contains("n0[\"say #quot;hi#quot;\"]:::black")).to(be_true());
+            }
+            
+            #[test]
+            fn dumps_the_structure_as_json() {
+                let tree = rb_tree![1, 2, 3];
+                expect!(tree.to_structure_json()).to(be_equal_to(concat!(
+                    r#"{"value":"2","color":"black","#,
+                    r#""left":{"value":"1","color":"red","left":null,"right":null},"#,
+                    r#""right":{"value":"3","color":"red","left":null,"right":null}}"#).to_string()));
+                
+                let mut escaped = RedBlackTree::new();
+                escaped.insert("a\"b\\c\n\u{1}");
+                expect!(escaped.to_structure_json()).to(be_equal_to(
+                    r#"{"value":"a\"b\\c\n\u0001","color":"black","left":null,"right":null}"#.to_string()));
+                
+                expect!(RedBlackTree::<u8>::new().to_structure_json()).to(be_equal_to("null".to_string()));
+            }
+        }
+        
+        mod std_conversions {
+            use super::*;
+            
+            #[test]
+            fn converts_from_sets() {
+                let tree = RedBlackTree::from((0..50).collect::<BTreeSet<_>>());
+                expect!(tree.count).to(be_equal_to(50));
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..50).collect::<Vec<_>>()));
+                
+                let tree = RedBlackTree::from(vec![3, 1, 2].into_iter().collect::<HashSet<_>>());
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3]));
+            }
+            
+            #[test]
+            fn converts_into_sets_and_vectors() {
+                let set: BTreeSet<_> = rb_tree![3, 1, 2].into();
+                expect!(set.into_iter().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3]));
+                
+                let set: HashSet<_> = rb_tree![3, 1, 3].into();
+                expect!(set.len()).to(be_equal_to(2));
+                
+                let values: Vec<_> = rb_tree![3, 1, 2].into();
+                expect!(values).to(be_equal_to(vec![1, 2, 3]));
+            }
+        }
+        
+        mod into_iter {
+            use super::*;
+            
+            #[test]
+            fn consumes_the_tree_in_ascending_order() {
+                let tree: RedBlackTree<_> = vec![5, 3, 8, 1, 4].into_iter().collect();
+                let mut iter = tree.into_iter();
+                expect!(iter.len()).to(be_equal_to(5));
+                expect!(iter.next()).to(be_some().value(1));
+                expect!(iter.collect::<Vec<_>>()).to(be_equal_to(vec![3, 4, 5, 8]));
+            }
+            
+            #[test]
+            fn iterates_over_a_borrowed_tree() {
+                let tree = rb_tree![2, 1];
+                let mut values = vec![];
+                for value in &tree {
+                    values.push(*value);
+                }
+                expect!(values).to(be_equal_to(vec![1, 2]));
+            }
+        }
+        
+        mod set_operators {
+            use super::*;
+            
+            #[test]
+            fn computes_the_union_intersection_difference_and_symmetric_difference() {
+                let a = rb_tree![1, 2, 3, 4];
+                let b = rb_tree![3, 4, 5];
+                expect!((&a | &b).iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3, 4, 5]));
+                expect!((&a & &b).iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![3, 4]));
+                expect!((&a - &b).iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2]));
+                expect!((&a ^ &b).iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 5]));
+                expect!((&a - &b).count).to(be_equal_to(2));
+            }
+            
+            #[test]
+            fn pairs_up_duplicates_one_to_one() {
+                let a = rb_tree![1, 1, 2];
+                let b = rb_tree![1, 2, 2];
+                expect!((&a | &b).iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 1, 2, 2]));
+                expect!((&a & &b).iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2]));
+                expect!((&a ^ &b).iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2]));
+            }
+            
+            #[test]
+            fn never_pairs_up_elements_that_cant_be_compared() {
+                let nan = rb_tree![f64::NAN];
+                let b = rb_tree![1.0, 2.0];
+                let union = &nan | &b;
+                expect!(union.iter().filter(|value| value.is_nan()).count()).to(be_equal_to(1));
+                expect!(union.contains(&1.0) && union.contains(&2.0)).to(be_true());
+                expect!(union.count).to(be_equal_to(3));
+                expect!((&nan & &b).iter().next()).to(be_none());
+                expect!((&b - &nan).iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1.0, 2.0]));
+                expect!((&nan ^ &nan).count).to(be_equal_to(2));
             }
         }
         
-        describe! tree_iterator {
-            it "iterates in ascending order" {
+        mod merge_with {
+            use super::*;
+            
+            #[test]
+            fn lets_the_caller_pick_what_each_pair_of_equal_elements_becomes() {
+                // ordered by the key alone, so elements from the two trees can be told apart
+                #[derive(Debug, Clone, Copy)]
+                struct Tagged(u32, char);
+                impl PartialEq for Tagged {
+                    fn eq(&self, other: &Tagged) -> bool {
+                        self.0 == other.0
+                    }
+                }
+                impl PartialOrd for Tagged {
+                    fn partial_cmp(&self, other: &Tagged) -> Option<Ordering> {
+                        self.0.partial_cmp(&other.0)
+                    }
+                }
+                
+                let mut tree = rb_tree![Tagged(1, 'a'), Tagged(2, 'a'), Tagged(2, 'a'), Tagged(4, 'a')];
+                let other = rb_tree![Tagged(2, 'b'), Tagged(3, 'b'), Tagged(4, 'b')];
+                let mut pairs = vec![];
+                tree.merge_with(other, |mine, theirs| {
+                    pairs.push((mine.1, theirs.1));
+                    theirs
+                });
+                
+                expect!(pairs).to(be_equal_to(vec![('a', 'b'), ('a', 'b')]));
+                let merged: Vec<_> = tree.iter().map(|value| (value.0, value.1)).collect();
+                expect!(merged).to(be_equal_to(vec![(1, 'a'), (2, 'b'), (2, 'a'), (3, 'b'), (4, 'b')]));
+                expect!(tree.count).to(be_equal_to(5));
+            }
+            
+            #[test]
+            fn still_sorts_what_the_closure_returns() {
+                let mut tree = rb_tree![1, 5];
+                tree.merge_with(rb_tree![5, 9], |mine, theirs| mine + theirs);
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 9, 10]));
+                expect!(tree.check_invariants().is_ok()).to(be_true());
+            }
+            
+            #[test]
+            fn only_resolves_elements_that_compare_equal() {
+                let mut tree = rb_tree![f64::NAN];
+                let mut resolved = 0;
+                tree.merge_with(rb_tree![1.0, 2.0], |mine, _| {
+                    resolved += 1;
+                    mine
+                });
+                
+                expect!(resolved).to(be_equal_to(0));
+                expect!(tree.count).to(be_equal_to(3));
+                expect!(tree.contains(&1.0) && tree.contains(&2.0)).to(be_true());
+            }
+            
+            #[test]
+            fn leaves_the_tree_as_it_was_when_a_comparison_panics() {
+                thread_local!(static FUSE: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) });
+                
+                #[derive(Debug, Clone, PartialEq)]
+                struct Touchy(u32);
+                impl PartialOrd for Touchy {
+                    fn partial_cmp(&self, other: &Touchy) -> Option<Ordering> {
+                        FUSE.with(|fuse| match fuse.get() {
+                            Some(0) => panic!("comparison failed"),
+                            Some(left) => fuse.set(Some(left - 1)),
+                            None => {},
+                        });
+                        self.0.partial_cmp(&other.0)
+                    }
+                }
+                
+                let mut tree: RedBlackTree<_> = (0..10).map(|value| Touchy(value * 2)).collect();
+                let before = tree.clone();
+                let other = (0..10).map(|value| Touchy(value * 3)).collect();
+                FUSE.with(|fuse| fuse.set(Some(5)));
+                let merged = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tree.merge_with(other, |mine, _| mine)));
+                FUSE.with(|fuse| fuse.set(None));
+                
+                expect!(merged.is_err()).to(be_true());
+                expect!(tree.structural_eq(&before)).to(be_true());
+            }
+            
+            #[test]
+            fn keeps_everything_but_the_pair_when_resolve_panics() {
+                let mut tree = rb_tree![1, 3, 5, 7];
+                let merged = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tree.merge_with(rb_tree![2, 5, 6, 8], |_, _| {
+                    panic!("in the middle of resolving");
+                })));
+                
+                expect!(merged.is_err()).to(be_true());
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3, 6, 7, 8]));
+                expect!(tree.count).to(be_equal_to(6));
+                expect!(tree.check_invariants()).to(be_ok());
+            }
+        }
+        
+        mod merge_all {
+            use super::*;
+            
+            #[test]
+            fn keeps_every_element_of_every_tree() {
+                let shards = vec![rb_tree![1, 4, 7, 7], RedBlackTree::new(), rb_tree![2, 7, 9], rb_tree![0, 3]];
+                let tree = RedBlackTree::merge_all(shards);
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![0, 1, 2, 3, 4, 7, 7, 7, 9]));
+                expect!(tree.count).to(be_equal_to(9));
+                expect!(tree.check_invariants().is_ok()).to(be_true());
+                
+                let none: Vec<RedBlackTree<u32>> = vec![];
+                expect!(RedBlackTree::merge_all(none).count).to(be_equal_to(0));
+            }
+        }
+        
+        mod split_at_index {
+            use super::*;
+            
+            #[test]
+            fn keeps_the_smallest_elements_and_returns_the_rest() {
+                let mut tree = RedBlackTree::new();
+                for i in 0..100 {
+                    tree.insert(i);
+                }
+                
+                let rest = tree.split_at_index(40);
+                expect!(tree.count).to(be_equal_to(40));
+                expect!(tree.root.size()).to(be_equal_to(40));
+                expect!(tree.root.as_ref().unwrap().color().is_black()).to(be_true());
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..40).collect::<Vec<_>>()));
+                
+                expect!(rest.count).to(be_equal_to(60));
+                expect!(rest.root.size()).to(be_equal_to(60));
+                expect!(rest.root.as_ref().unwrap().color().is_black()).to(be_true());
+                expect!(rest.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((40..100).collect::<Vec<_>>()));
+            }
+            
+            #[test]
+            fn returns_an_empty_tree_when_splitting_past_the_end() {
+                let mut tree = rb_tree![1, 2, 3];
+                let rest = tree.split_at_index(3);
+                expect!(rest.root.as_ref()).to(be_none());
+                expect!(tree.count).to(be_equal_to(3));
+                
+                let rest = tree.split_at_index(0);
+                expect!(tree.root.as_ref()).to(be_none());
+                expect!(rest.count).to(be_equal_to(3));
+            }
+            
+            #[test]
+            fn leaves_both_halves_balanced_wherever_it_splits() {
+                // the removals leave red nodes all through the tree, so the
+                // black heights the split carries along change from level to
+                // level
+                let mut whole = RedBlackTree::new();
+                for i in 0..200 {
+                    whole.insert(i * 7 % 200);
+                }
+                for i in 0..200 {
+                    if i % 3 == 0 {
+                        whole.remove(&i);
+                    }
+                }
+                
+                for index in 0..whole.len() {
+                    let mut tree = whole.clone();
+                    let rest = tree.split_at_index(index);
+                    expect!(tree.check_invariants()).to(be_ok());
+                    expect!(rest.check_invariants()).to(be_ok());
+                    expect!(tree.len() + rest.len()).to(be_equal_to(whole.len()));
+                }
+            }
+        }
+        
+        #[cfg(feature = "rand")]
+        mod random {
+            use super::*;
+            use rand::rngs::StdRng;
+            use rand::SeedableRng;
+            
+            #[test]
+            fn picks_every_element_uniformly() {
+                let tree: RedBlackTree<u32> = (0..10).collect();
+                let mut rng = StdRng::seed_from_u64(7);
+                let mut counts = [0; 10];
+                for _ in 0..10_000 {
+                    if let Some(value) = tree.random(&mut rng) {
+                        counts[*value as usize] += 1;
+                    }
+                }
+                
+                expect!(counts.iter().all(|&count| (800..1200).contains(&count))).to(be_true());
+                expect!(RedBlackTree::<u32>::new().random(&mut rng)).to(be_none());
+            }
+        }
+        
+        mod tree_iterator {
+            use super::*;
+            
+            #[test]
+            fn iterates_in_ascending_order() {
                 let mut tree = rb_tree![3];
                 {
                     let mut iter = tree.iter();
@@ -851,6 +2145,147 @@ mod unit_tests {
                     expect!(iter.next()).to(be_none());
                 }
             }
+            
+            #[test]
+            fn knows_how_many_elements_are_left() {
+                let tree = rb_tree![3, 1, 2];
+                let mut iter = tree.iter();
+                expect!(iter.len()).to(be_equal_to(3));
+                iter.next();
+                expect!(iter.len()).to(be_equal_to(2));
+                iter.next();
+                iter.next();
+                expect!(iter.len()).to(be_equal_to(0));
+            }
+            
+            #[test]
+            fn keeps_no_state_beyond_where_it_is_in_the_tree() {
+                // the root, the current node and the count: there's no
+                // room for a stack, so making one never allocates
+                expect!(std::mem::size_of::<RedBlackIterator<u64>>()).to(be_equal_to(3 * std::mem::size_of::<usize>()));
+                
+                let tree: RedBlackTree<_> = (0..1000).collect();
+                expect!(tree.iter().cloned().eq(0..1000)).to(be_true());
+            }
+            
+            #[test]
+            fn skips_ahead_without_stepping_through_every_element() {
+                let tree: RedBlackTree<_> = (0..100).collect();
+                let mut iter = tree.iter();
+                expect!(iter.nth(1)).to(be_some().value(&1));
+                expect!(iter.nth(9)).to(be_some().value(&11));
+                expect!(iter.next()).to(be_some().value(&12));
+                expect!(iter.len()).to(be_equal_to(87));
+                expect!(iter.nth(86)).to(be_some().value(&99));
+                expect!(iter.next()).to(be_none());
+                
+                let mut iter = tree.iter();
+                expect!(iter.nth(100)).to(be_none());
+                expect!(iter.next()).to(be_none());
+                expect!(tree.iter().step_by(25).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![0, 25, 50, 75]));
+            }
+            
+            #[test]
+            fn finds_the_last_element_and_the_count_directly() {
+                let tree = rb_tree![5, 1, 9, 3];
+                expect!(tree.iter().last()).to(be_some().value(&9));
+                expect!(tree.iter().count()).to(be_equal_to(4));
+                
+                let mut iter = tree.iter();
+                iter.nth(2);
+                expect!(iter.count()).to(be_equal_to(1));
+                let mut iter = tree.iter();
+                iter.nth(2);
+                expect!(iter.last()).to(be_some().value(&9));
+                let mut iter = tree.iter();
+                iter.nth(3);
+                expect!(iter.last()).to(be_none());
+                expect!(RedBlackTree::<u8>::new().iter().last()).to(be_none());
+            }
+        }
+        
+        mod node_pool {
+            use super::*;
+            
+            #[test]
+            fn reuses_removed_nodes_before_allocating() {
+                fn last(tree: &RedBlackTree<i32>) -> *const Node<i32> {
+                    let mut node = tree.root.as_ref().unwrap();
+                    while let Some(right) = node.right().as_ref() {
+                        node = right;
+                    }
+                    
+                    node
+                }
+                
+                let mut tree: RedBlackTree<_> = (0..10).collect();
+                let freed = last(&tree);
+                tree.remove(&9);
+                tree.remove(&42);
+                expect!(tree.pooled_nodes()).to(be_equal_to(1));
+                
+                tree.insert(20);
+                expect!(tree.pooled_nodes()).to(be_equal_to(0));
+                expect!(last(&tree)).to(be_equal_to(freed));
+                
+                for value in 0..5 {
+                    tree.remove(&value);
+                }
+                tree.extend(vec![3, 1, 2]);
+                expect!(tree.pooled_nodes()).to(be_equal_to(2));
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3, 5, 6, 7, 8, 20]));
+            }
+            
+            #[test]
+            fn allocates_reserved_nodes_up_front() {
+                let mut tree = RedBlackTree::with_capacity(5);
+                expect!(tree.pooled_nodes()).to(be_equal_to(5));
+                tree.extend(0..5);
+                expect!(tree.pooled_nodes()).to(be_equal_to(0));
+                
+                tree.remove(&0);
+                tree.reserve(3);
+                expect!(tree.pooled_nodes()).to(be_equal_to(3));
+                tree.reserve(1);
+                expect!(tree.pooled_nodes()).to(be_equal_to(3));
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3, 4]));
+            }
+            
+            #[test]
+            fn frees_as_many_nodes_as_asked() {
+                let mut tree: RedBlackTree<_> = (0..10).collect();
+                for value in 0..10 {
+                    tree.remove(&value);
+                }
+                
+                tree.trim_pool(4);
+                expect!(tree.pooled_nodes()).to(be_equal_to(4));
+                tree.trim_pool(8);
+                expect!(tree.pooled_nodes()).to(be_equal_to(4));
+                tree.trim_pool(0);
+                expect!(tree.pooled_nodes()).to(be_equal_to(0));
+                
+                tree.insert(1);
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1]));
+            }
+        }
+        
+        mod node_layout {
+            use super::*;
+            
+            #[test]
+            fn keeps_the_color_in_with_the_parent_pointer() {
+                expect!(std::mem::size_of::<Node<u64>>()).to(be_equal_to(5 * std::mem::size_of::<usize>()));
+                
+                let tree = rb_tree![1, 2, 3, 4, 5, 6];
+                expect!(tree.root.as_ref().unwrap().left().as_ref().unwrap().parent()).to(be_equal_to(tree.root.as_ref().map(std::ptr::NonNull::from)));
+                verify!{ tree =>
+                        <B.2>
+                    B.1      <R.4>
+                          B.3     B.5>
+                                     R.6
+                };
+            }
         }
     }
 }
@@ -0,0 +1,325 @@
+// A sequence kept as a red-black tree, with the elements in the order they
+// were put in rather than sorted, like a rope. Every node already knows
+// the size of its subtree, which is all it takes to find the element at a
+// position, so getting, inserting and removing anywhere are O(log n), as
+// are splitting the list in two and joining two lists end to end, where a
+// `Vec` would be O(n) for all but the last.
+//
+// The elements go in an ordinary tree, positioned by rank with a split and
+// a join, and taken out by walking down the subtree sizes. The elements don't have to be
+// comparable, since nothing ever compares them.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+use std::mem;
+
+use node::{Dir, Node, NodeHelper};
+use {RedBlackIterator, RedBlackTree};
+
+/// A list that can be changed anywhere in O(log n).
+pub struct TreeList<T> {
+    tree: RedBlackTree<Item<T>>,
+}
+
+// the tree needs its elements to be comparable, but it never compares
+// them, since they're only ever placed by rank
+#[derive(Clone, Debug)]
+struct Item<T>(T);
+
+impl<T> PartialEq for Item<T> {
+    fn eq(&self, _other: &Item<T>) -> bool {
+        true
+    }
+}
+
+impl<T> PartialOrd for Item<T> {
+    fn partial_cmp(&self, _other: &Item<T>) -> Option<Ordering> {
+        Some(Ordering::Equal)
+    }
+}
+
+impl<T> TreeList<T> {
+    pub const fn new() -> TreeList<T> {
+        TreeList { tree: RedBlackTree::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.count == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.tree.root.as_ref()?.select(index).map(|node| &node.value().0)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let mut node = self.tree.root.as_mut()?;
+        let mut index = index;
+        loop {
+            let left_size = node.left().size();
+            if index < left_size {
+                node = node.left_mut().as_mut()?;
+            } else if index == left_size {
+                return Some(&mut node.value_mut().0);
+            } else {
+                index -= left_size + 1;
+                node = node.right_mut().as_mut()?;
+            }
+        }
+    }
+
+    /// Inserts `value` at `index`, moving the elements from there on one
+    /// place along.
+    ///
+    /// Panics if `index` is greater than the list's length.
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.tree.count, "insertion index (is {}) should be <= len (is {})", index, self.tree.count);
+        let (left, right) = Node::split_at_rank(self.tree.root.take(), index);
+        let mut root = Node::join(left, self.tree.pool.node(Item(value)), right);
+        Node::ensure_root_black(&mut root);
+        self.tree.root = root;
+        self.tree.count += 1;
+    }
+
+    /// Takes out the element at `index`, moving the elements after it one
+    /// place back, or returns None if there's no such element.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.tree.count {
+            return None;
+        }
+
+        let mut index = index;
+        let item = self.tree.remove_where(|node| {
+            let left_size = node.left().size();
+            match index.cmp(&left_size) {
+                Ordering::Less => Some(Dir::Left),
+                Ordering::Equal => None,
+                Ordering::Greater => {
+                    index -= left_size + 1;
+                    Some(Dir::Right)
+                },
+            }
+        });
+
+        item.map(|item| item.0)
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.insert(self.tree.count, value);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.remove(self.tree.count.wrapping_sub(1))
+    }
+
+    /// Splits the list in two at `index`, keeping the elements before it
+    /// and returning the rest, in O(log n).
+    ///
+    /// Panics if `index` is greater than the list's length.
+    pub fn split_off(&mut self, index: usize) -> TreeList<T> {
+        assert!(index <= self.tree.count, "`at` split index (is {}) should be <= len (is {})", index, self.tree.count);
+        TreeList { tree: self.tree.split_at_index(index) }
+    }
+
+    /// Moves all of `other`'s elements onto the end of this list, leaving
+    /// `other` empty, in O(log n).
+    pub fn append(&mut self, other: &mut TreeList<T>) {
+        let mut other = mem::take(&mut other.tree);
+        let count = self.tree.count + other.count;
+        let mut root = Node::concat(self.tree.root.take(), other.root.take());
+
+        Node::ensure_root_black(&mut root);
+        self.tree.root = root;
+        self.tree.count = count;
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { items: self.tree.iter() }
+    }
+}
+
+impl<T> Default for TreeList<T> {
+    fn default() -> TreeList<T> {
+        TreeList::new()
+    }
+}
+
+impl<T> Clone for TreeList<T> where T: Clone {
+    fn clone(&self) -> TreeList<T> {
+        TreeList { tree: self.tree.clone() }
+    }
+}
+
+impl<T> Debug for TreeList<T> where T: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> PartialEq for TreeList<T> where T: PartialEq {
+    fn eq(&self, other: &TreeList<T>) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<T> Eq for TreeList<T> where T: Eq {}
+
+impl<T> FromIterator<T> for TreeList<T> {
+    // the elements are already in order, so the tree can be built in
+    // one go
+    fn from_iter<I>(iter: I) -> TreeList<T> where I: IntoIterator<Item = T> {
+        TreeList { tree: RedBlackTree::from_sorted_iter(iter.into_iter().map(Item)) }
+    }
+}
+
+impl<T> Extend<T> for TreeList<T> {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item = T> {
+        let mut rest = iter.into_iter().collect();
+        self.append(&mut rest);
+    }
+}
+
+impl<T> From<Vec<T>> for TreeList<T> {
+    fn from(values: Vec<T>) -> TreeList<T> {
+        values.into_iter().collect()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a TreeList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for TreeList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { items: self.tree.into_iter() }
+    }
+}
+
+/// Iterates over the elements of a `TreeList` from the front.
+pub struct Iter<'a, T> where T: 'a {
+    items: RedBlackIterator<'a, Item<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.items.next().map(|item| &item.0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        self.items.nth(n).map(|item| &item.0)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+/// Moves the elements out of a `TreeList` from the front.
+pub struct IntoIter<T> {
+    items: ::IntoIter<Item<T>>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.items.next().map(|item| item.0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use TreeList;
+
+    pub fn elements(list: &TreeList<u32>) -> Vec<u32> {
+        list.iter().cloned().collect()
+    }
+
+    mod tree_list {
+        use super::*;
+
+        #[test]
+        fn keeps_elements_where_they_were_put() {
+            let mut list = TreeList::new();
+            list.push(3);
+            list.insert(0, 1);
+            list.insert(1, 2);
+            list.insert(3, 4);
+            list.insert(0, 0);
+            expect!(elements(&list)).to(be_equal_to(vec![0, 1, 2, 3, 4]));
+            expect!(list.get(2)).to(be_some().value(&2));
+            expect!(list.get(5)).to(be_none());
+
+            *list.get_mut(4).unwrap() = 40;
+            expect!(list.remove(1)).to(be_some().value(1));
+            expect!(list.remove(4)).to(be_none());
+            expect!(list.pop()).to(be_some().value(40));
+            expect!(elements(&list)).to(be_equal_to(vec![0, 2, 3]));
+            expect!(list.tree.check_invariants().is_ok()).to(be_true());
+        }
+
+        #[test]
+        fn stays_balanced_through_many_changes() {
+            let mut list = TreeList::new();
+            let mut vec = Vec::new();
+            for value in 0..500 {
+                let index = (value * 7) as usize % (vec.len() + 1);
+                list.insert(index, value);
+                vec.insert(index, value);
+            }
+            for step in 0..200 {
+                let index = (step * 13) % vec.len();
+                expect!(list.remove(index)).to(be_some().value(vec.remove(index)));
+            }
+
+            expect!(elements(&list)).to(be_equal_to(vec));
+            expect!(list.tree.check_invariants().is_ok()).to(be_true());
+        }
+
+        #[test]
+        fn splits_and_appends() {
+            let mut list: TreeList<_> = (0..100).collect();
+            let mut rest = list.split_off(40);
+            expect!(elements(&list)).to(be_equal_to((0..40).collect::<Vec<_>>()));
+            expect!(elements(&rest)).to(be_equal_to((40..100).collect::<Vec<_>>()));
+
+            rest.append(&mut list);
+            expect!(list.is_empty()).to(be_true());
+            expect!(rest.len()).to(be_equal_to(100));
+            expect!(rest.get(60)).to(be_some().value(&0));
+            expect!(rest.tree.check_invariants().is_ok()).to(be_true());
+
+            rest.extend(vec![7, 8]);
+            expect!(rest.into_iter().skip(99).collect::<Vec<_>>()).to(be_equal_to(vec![39, 7, 8]));
+        }
+
+        #[test]
+        #[should_panic]
+        fn refuses_to_insert_past_the_end() {
+            TreeList::new().insert(1, 0);
+        }
+    }
+}
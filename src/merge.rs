@@ -0,0 +1,100 @@
+// The elements of two trees in one sorted sequence, made by walking
+// both in order at once and always taking the smaller of the two next
+// elements, like the merge step of a merge sort. Neither tree is
+// changed or copied. Every element of both comes out, duplicates
+// included, each one tagged with the tree it came from.
+
+use std::iter::{FusedIterator, Peekable};
+
+use allocator::{Allocator, Global};
+use {RedBlackIterator, RedBlackTree};
+
+/// An element of one of the two trees a `MergeIter` walks, and which one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeItem<'a, T> where T: 'a {
+    FromSelf(&'a T),
+    FromOther(&'a T),
+}
+
+impl<'a, T> MergeItem<'a, T> {
+    pub fn value(self) -> &'a T {
+        match self {
+            MergeItem::FromSelf(value) | MergeItem::FromOther(value) => value,
+        }
+    }
+}
+
+/// Iterates over the elements of two trees in ascending order, made by
+/// `RedBlackTree::merge_iter`.
+pub struct MergeIter<'a, T, A = Global> where T: PartialOrd + 'a, A: Allocator + Clone + 'a {
+    mine: Peekable<RedBlackIterator<'a, T, A>>,
+    theirs: Peekable<RedBlackIterator<'a, T, A>>,
+}
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// Every element of this tree and `other`, in ascending order. Of
+    /// equal elements, this tree's come first, and so do elements that
+    /// can't be compared with the next one in `other`.
+    pub fn merge_iter<'a>(&'a self, other: &'a RedBlackTree<T, A>) -> MergeIter<'a, T, A> {
+        MergeIter { mine: self.iter().peekable(), theirs: other.iter().peekable() }
+    }
+}
+
+impl<'a, T, A> Iterator for MergeIter<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    type Item = MergeItem<'a, T>;
+
+    fn next(&mut self) -> Option<MergeItem<'a, T>> {
+        let theirs_first = match (self.mine.peek(), self.theirs.peek()) {
+            (Some(mine), Some(theirs)) => theirs < mine,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        if theirs_first {
+            self.theirs.next().map(MergeItem::FromOther)
+        } else {
+            self.mine.next().map(MergeItem::FromSelf)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.mine.len() + self.theirs.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, T, A> ExactSizeIterator for MergeIter<'a, T, A> where T: PartialOrd, A: Allocator + Clone {}
+
+impl<'a, T, A> FusedIterator for MergeIter<'a, T, A> where T: PartialOrd, A: Allocator + Clone {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use merge::MergeItem;
+    use RedBlackTree;
+
+    mod merge_iter {
+        use super::*;
+
+        #[test]
+        fn interleaves_both_trees_in_order_keeping_duplicates() {
+            let mine: RedBlackTree<_> = vec![1, 3, 3, 8].into_iter().collect();
+            let theirs: RedBlackTree<_> = vec![0, 3, 9].into_iter().collect();
+
+            let merged = mine.merge_iter(&theirs);
+            expect!(merged.len()).to(be_equal_to(7));
+            expect!(merged.collect::<Vec<_>>()).to(be_equal_to(vec![
+                MergeItem::FromOther(&0),
+                MergeItem::FromSelf(&1),
+                MergeItem::FromSelf(&3),
+                MergeItem::FromSelf(&3),
+                MergeItem::FromOther(&3),
+                MergeItem::FromSelf(&8),
+                MergeItem::FromOther(&9),
+            ]));
+
+            let values: Vec<_> = mine.merge_iter(&RedBlackTree::new()).map(MergeItem::value).cloned().collect();
+            expect!(values).to(be_equal_to(vec![1, 3, 3, 8]));
+        }
+    }
+}
@@ -0,0 +1,179 @@
+// Hashes of a tree's subtrees, for finding where two replicas of a set
+// differ without comparing every element. A subtree's hash is the sum of
+// its elements' hashes, so it's the same whatever shape the subtree has,
+// and two replicas that got to the same elements by different routes hash
+// the same even though their trees don't look alike. That makes every
+// range of elements a subtree in this sense, and its hash comes out of
+// the prefix sums of the elements' hashes in O(log n).
+//
+// Finding the differences works from the top down. A range whose hash and
+// number of elements are the same on both sides is taken to be the same
+// and skipped. Otherwise it's split around its middle element and both
+// halves are compared, down to ranges of a couple of elements. So each
+// difference costs O(log n) comparisons of ranges, rather than the two
+// sides being scanned from end to end.
+//
+// The elements are hashed with `DefaultHasher`, whose results are only the
+// same between builds made with the same version of Rust, so both replicas
+// need to be built with the same one. And a sum of hashes can't stand up
+// to someone picking elements to collide on purpose; this is for catching
+// replicas drifting apart, not for checking data from someone untrusted.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::{Bound, RangeBounds};
+
+use RedBlackTree;
+
+/// The hashes of every range of a `RedBlackTree`'s elements, made by
+/// `RedBlackTree::digests` in O(n). The tree can't change while they're
+/// kept.
+pub struct Digests<'a, T> where T: PartialOrd + 'a {
+    values: Vec<&'a T>,
+    // sums[i] is the sum of the hashes of the first i elements
+    sums: Vec<u64>,
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd + Hash {
+    /// Hashes the elements, so the tree can be compared with its replicas.
+    pub fn digests(&self) -> Digests<'_, T> {
+        let values: Vec<_> = self.iter().collect();
+        let mut sums = Vec::with_capacity(values.len() + 1);
+        sums.push(0);
+        let mut sum = 0u64;
+        for value in &values {
+            sum = sum.wrapping_add(hash(*value));
+            sums.push(sum);
+        }
+
+        Digests { values, sums }
+    }
+}
+
+fn hash<T>(value: &T) -> u64 where T: Hash {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<'a, T> Digests<'a, T> where T: PartialOrd {
+    /// The hash of the elements in `range`, which is the same for any tree
+    /// with the same elements in that range, in O(log n).
+    pub fn digest<R>(&self, range: R) -> u64 where R: RangeBounds<T> {
+        self.sum(self.span(range.start_bound(), range.end_bound()))
+    }
+
+    /// The hash of every element.
+    pub fn root(&self) -> u64 {
+        self.sums[self.values.len()]
+    }
+
+    /// The ranges in which this tree and `other` hold different elements,
+    /// in ascending order. Every difference falls in one of them, and each
+    /// one holds at least one.
+    pub fn divergent_ranges(&self, other: &Digests<'_, T>) -> Vec<(Bound<T>, Bound<T>)> where T: Clone {
+        let mut ranges = Vec::new();
+        self.compare(other, Bound::Unbounded, Bound::Unbounded, &mut ranges);
+        ranges
+    }
+
+    fn compare(&self, other: &Digests<'_, T>, start: Bound<T>, end: Bound<T>, ranges: &mut Vec<(Bound<T>, Bound<T>)>) where T: Clone {
+        let mine = self.span(start.as_ref(), end.as_ref());
+        let theirs = other.span(start.as_ref(), end.as_ref());
+        let (len, other_len) = (mine.1 - mine.0, theirs.1 - theirs.0);
+        if len == other_len && self.sum(mine) == other.sum(theirs) {
+            return;
+        }
+
+        if len == 0 || other_len == 0 || len + other_len <= 2 {
+            ranges.push((start, end));
+            return;
+        }
+
+        // split around the middle element of the bigger side, which takes
+        // that element and any equal to it out of both halves, so each
+        // half holds fewer elements between the two sides than this range
+        let middle = if len >= other_len {
+            self.values[mine.0 + len / 2].clone()
+        } else {
+            other.values[theirs.0 + other_len / 2].clone()
+        };
+
+        self.compare(other, start, Bound::Excluded(middle.clone()), ranges);
+        let point = (Bound::Included(&middle), Bound::Included(&middle));
+        let (mine, theirs) = (self.span(point.0, point.1), other.span(point.0, point.1));
+        if mine.1 - mine.0 != theirs.1 - theirs.0 || self.sum(mine) != other.sum(theirs) {
+            ranges.push((Bound::Included(middle.clone()), Bound::Included(middle.clone())));
+        }
+        self.compare(other, Bound::Excluded(middle), end, ranges);
+    }
+
+    fn sum(&self, (start, end): (usize, usize)) -> u64 {
+        self.sums[end].wrapping_sub(self.sums[start])
+    }
+
+    // the positions of the first element in the range and of the first
+    // one after it
+    fn span(&self, start: Bound<&T>, end: Bound<&T>) -> (usize, usize) {
+        let values = &self.values[..];
+        let start = match start {
+            Bound::Included(start) => values.partition_point(|value| **value < *start),
+            Bound::Excluded(start) => values.partition_point(|value| **value <= *start),
+            Bound::Unbounded => 0,
+        };
+        let end = match end {
+            Bound::Included(end) => values.partition_point(|value| **value <= *end),
+            Bound::Excluded(end) => values.partition_point(|value| **value < *end),
+            Bound::Unbounded => values.len(),
+        };
+
+        (start, end.max(start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use std::ops::{Bound, RangeBounds};
+    use RedBlackTree;
+
+    mod digests {
+        use super::*;
+
+        #[test]
+        fn hashes_the_same_elements_the_same_whatever_the_shape() {
+            let built: RedBlackTree<u32> = (0..64).collect();
+            let sorted = RedBlackTree::from_sorted_iter(0..64);
+            let (built, sorted) = (built.digests(), sorted.digests());
+
+            expect!(built.root()).to(be_equal_to(sorted.root()));
+            expect!(built.digest(10..20)).to(be_equal_to(sorted.digest(10..20)));
+            expect!(built.digest(10..20)).not_to(be_equal_to(built.digest(10..21)));
+            expect!(built.divergent_ranges(&sorted).is_empty()).to(be_true());
+        }
+
+        #[test]
+        fn finds_the_ranges_in_which_two_replicas_differ() {
+            let primary: RedBlackTree<u32> = (0..1000).collect();
+            let mut replica = primary.clone();
+            replica.remove(&10);
+            replica.insert(500);
+            replica.insert(2000);
+            let (primary, replica) = (primary.digests(), replica.digests());
+
+            let ranges = primary.divergent_ranges(&replica);
+            let covers = |value: u32| ranges.iter().any(|range| range.contains(&value));
+            expect!(covers(10) && covers(500) && covers(2000)).to(be_true());
+            // narrowed down to a handful of elements each
+            expect!(ranges.len()).to(be_equal_to(3));
+            for range in &ranges {
+                let (start, end) = primary.span(range.0.as_ref(), range.1.as_ref());
+                expect!(end - start <= 2).to(be_true());
+            }
+
+            let empty = RedBlackTree::new();
+            let empty = empty.digests();
+            expect!(primary.divergent_ranges(&empty)).to(be_equal_to(vec![(Bound::Unbounded, Bound::Unbounded)]));
+        }
+    }
+}
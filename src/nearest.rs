@@ -0,0 +1,183 @@
+// The elements nearest to a value that needn't be in the tree: the largest
+// one below it, or at or below it, and the smallest one above it, or at or
+// above it. Each walks down from the root once, remembering the last node
+// that was on the right side of the value, so they're O(log n) where a
+// range scan or skipping through an iterator would be O(n). `nearest` picks
+// between the floor and the ceiling by a distance of the caller's, which only
+// gives the closest element when the distance grows moving away from the
+// value in either direction, as it does for the usual ones.
+
+use allocator::Allocator;
+use RedBlackTree;
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// The largest element less than or equal to `value`. Of several equal
+    /// ones it's the last in order.
+    pub fn floor(&self, value: &T) -> Option<&T> {
+        self.last_where(|other| *other <= *value)
+    }
+
+    /// The smallest element greater than or equal to `value`. Of several
+    /// equal ones it's the first in order.
+    pub fn ceiling(&self, value: &T) -> Option<&T> {
+        self.first_where(|other| *other >= *value)
+    }
+
+    /// The smallest element strictly greater than `value`, whether or not
+    /// `value` is in the tree.
+    pub fn next_after(&self, value: &T) -> Option<&T> {
+        self.first_where(|other| *other > *value)
+    }
+
+    /// The largest element strictly less than `value`, whether or not
+    /// `value` is in the tree.
+    pub fn prev_before(&self, value: &T) -> Option<&T> {
+        self.last_where(|other| *other < *value)
+    }
+
+    /// The element closest to `target` by `dist`, for snapping a value to
+    /// the nearest one stored. Only the floor and the ceiling of `target`
+    /// are measured, and of the two the floor wins a tie.
+    pub fn nearest<D, F>(&self, target: &T, dist: F) -> Option<&T> where D: PartialOrd, F: Fn(&T, &T) -> D {
+        match (self.floor(target), self.ceiling(target)) {
+            (Some(floor), Some(ceiling)) => {
+                if dist(ceiling, target) < dist(floor, target) {
+                    Some(ceiling)
+                } else {
+                    Some(floor)
+                }
+            },
+            (floor, ceiling) => floor.or(ceiling),
+        }
+    }
+
+    // the last element `below` holds for, where it holds for every element
+    // up to some point in order and for none after
+    fn last_where<F>(&self, below: F) -> Option<&T> where F: Fn(&T) -> bool {
+        let mut found = None;
+        let mut link = &self.root;
+        while let Some(node) = link.as_ref() {
+            if below(node.value()) {
+                found = Some(node.value());
+                link = node.right();
+            } else {
+                link = node.left();
+            }
+        }
+
+        found
+    }
+
+    // the first element `above` holds for, where it holds for none of the
+    // elements up to some point in order and for every one after
+    fn first_where<F>(&self, above: F) -> Option<&T> where F: Fn(&T) -> bool {
+        let mut found = None;
+        let mut link = &self.root;
+        while let Some(node) = link.as_ref() {
+            if above(node.value()) {
+                found = Some(node.value());
+                link = node.left();
+            } else {
+                link = node.right();
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod floor_and_ceiling {
+        use super::*;
+
+        #[test]
+        fn find_the_nearest_elements_either_side() {
+            let tree: RedBlackTree<_> = (0..50).map(|value| value * 10).collect();
+            expect!(tree.floor(&35)).to(be_some().value(&30));
+            expect!(tree.ceiling(&35)).to(be_some().value(&40));
+            // an element that's there is its own floor and ceiling
+            expect!(tree.floor(&120)).to(be_some().value(&120));
+            expect!(tree.ceiling(&120)).to(be_some().value(&120));
+
+            expect!(tree.floor(&-1)).to(be_none());
+            expect!(tree.ceiling(&-1)).to(be_some().value(&0));
+            expect!(tree.floor(&1000)).to(be_some().value(&490));
+            expect!(tree.ceiling(&491)).to(be_none());
+            expect!(RedBlackTree::new().floor(&0)).to(be_none());
+        }
+
+        #[test]
+        fn pick_the_outermost_of_equal_elements() {
+            let tree: RedBlackTree<_> = vec![Keyed(1, 'a'), Keyed(2, 'b'), Keyed(2, 'c'), Keyed(3, 'd')].into_iter().collect();
+            expect!(tree.floor(&Keyed(2, ' ')).map(|keyed| keyed.1)).to(be_some().value('c'));
+            expect!(tree.ceiling(&Keyed(2, ' ')).map(|keyed| keyed.1)).to(be_some().value('b'));
+        }
+
+        // compares by the number alone, so that equal elements can still be
+        // told apart
+        #[derive(Debug)]
+        pub struct Keyed(u32, char);
+
+        impl PartialEq for Keyed {
+            fn eq(&self, other: &Keyed) -> bool {
+                self.0 == other.0
+            }
+        }
+
+        impl PartialOrd for Keyed {
+            fn partial_cmp(&self, other: &Keyed) -> Option<::std::cmp::Ordering> {
+                self.0.partial_cmp(&other.0)
+            }
+        }
+    }
+
+    mod next_after_and_prev_before {
+        use super::*;
+
+        #[test]
+        fn skip_past_the_value_whether_or_not_its_there() {
+            let tree: RedBlackTree<_> = vec![10, 20, 20, 30].into_iter().collect();
+            expect!(tree.next_after(&20)).to(be_some().value(&30));
+            expect!(tree.prev_before(&20)).to(be_some().value(&10));
+            expect!(tree.next_after(&15)).to(be_some().value(&20));
+            expect!(tree.prev_before(&15)).to(be_some().value(&10));
+
+            expect!(tree.next_after(&30)).to(be_none());
+            expect!(tree.prev_before(&10)).to(be_none());
+            expect!(tree.next_after(&0)).to(be_some().value(&10));
+            expect!(tree.prev_before(&99)).to(be_some().value(&30));
+        }
+    }
+
+    mod nearest {
+        use super::*;
+
+        #[test]
+        fn snaps_to_the_closest_element() {
+            let tree: RedBlackTree<i32> = vec![0, 10, 25, 100].into_iter().collect();
+            let dist = |a: &i32, b: &i32| (a - b).abs();
+            expect!(tree.nearest(&7, dist)).to(be_some().value(&10));
+            expect!(tree.nearest(&3, dist)).to(be_some().value(&0));
+            expect!(tree.nearest(&25, dist)).to(be_some().value(&25));
+            // halfway between the two, so the lower one
+            expect!(tree.nearest(&5, dist)).to(be_some().value(&0));
+
+            expect!(tree.nearest(&-50, dist)).to(be_some().value(&0));
+            expect!(tree.nearest(&1000, dist)).to(be_some().value(&100));
+            expect!(RedBlackTree::new().nearest(&0, dist)).to(be_none());
+        }
+
+        #[test]
+        fn measures_with_the_given_distance() {
+            // anything above counts as twice as far as it is
+            let tree: RedBlackTree<f64> = vec![0.0, 1.0].into_iter().collect();
+            let dist = |a: &f64, b: &f64| if a > b { (a - b) * 2.0 } else { b - a };
+            expect!(tree.nearest(&0.6, dist)).to(be_some().value(&0.0));
+            expect!(tree.nearest(&0.7, dist)).to(be_some().value(&1.0));
+        }
+    }
+}
@@ -1,6 +1,14 @@
 use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
 
-#[derive(Copy, Clone)]
+use allocator::{self, Allocator, Global};
+use stats;
+use trace;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Color {
     Red,
     Black,
@@ -33,7 +41,7 @@ impl Debug for Color {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Dir {
     Left,
     Right
@@ -48,164 +56,625 @@ impl Dir {
     }
 }
 
-pub struct Node<T> where T: PartialOrd {
-    color: Color,
+pub struct Node<T, A = Global> where T: PartialOrd, A: Allocator + Clone {
     value: T,
-    left: Link<T>,
-    right: Link<T>,
+    left: Link<T, A>,
+    right: Link<T, A>,
+    // number of nodes in the subtree rooted at this node,
+    // used for the order statistic queries
+    size: usize,
+    // the node whose child this is, so iterators can walk back up without
+    // keeping a stack. It's only kept up to date below the root; the
+    // root's is left as whatever it was and must never be followed.
+    // Nodes are at least word aligned, so the low bit of the pointer is
+    // always free and holds the node's color instead of a field of its own.
+    parent_color: *mut Node<T, A>,
+    // what the node was allocated with, and gets freed with. Each node
+    // keeps its own copy so that links know how to free their nodes.
+    alloc: A,
 }
 
-pub type Link<T> = Option<Box<Node<T>>>;
+pub type Parent<T, A = Global> = Option<NonNull<Node<T, A>>>;
 
-impl<T> Node<T> where T: PartialOrd {
-    pub fn new(value: T) -> Node<T> {
-        Node::<T> {
-            value: value,
-            color: Color::Red,
-            left: None,
-            right: None,
+// set in `parent_color` for black nodes, so a fresh node
+// with no parent yet starts out red
+const BLACK: usize = 1;
+
+// a node holds a usize, so this is enough for the bit to be free
+const _: () = assert!(mem::align_of::<usize>() > BLACK);
+
+// An owned subtree, which may be empty. The nodes are allocated as Boxes
+// but held as raw pointers, since a Box promises that nothing else points
+// into it, and every node is also pointed at by its children.
+pub struct Link<T, A = Global> where T: PartialOrd, A: Allocator + Clone {
+    node: Parent<T, A>,
+    marker: PhantomData<Node<T, A>>,
+}
+
+impl<T, A> Link<T, A> where T: PartialOrd, A: Allocator + Clone {
+    pub const fn none() -> Link<T, A> {
+        Link { node: None, marker: PhantomData }
+    }
+
+    // allocates the node with its own allocator
+    pub fn new(node: Node<T, A>) -> Link<T, A> {
+        OwnedNode::new(node).into()
+    }
+
+    pub fn is_some(&self) -> bool {
+        self.node.is_some()
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.node.is_none()
+    }
+
+    pub fn as_ref(&self) -> Option<&Node<T, A>> {
+        // the link owns the node, so it lives as long as the link is borrowed
+        self.node.map(|node| unsafe { &*node.as_ptr() })
+    }
+
+    pub fn as_mut(&mut self) -> Option<&mut Node<T, A>> {
+        // and only the link can hand out a mutable borrow of it
+        self.node.map(|node| unsafe { &mut *node.as_ptr() })
+    }
+
+    pub fn take(&mut self) -> Link<T, A> {
+        mem::take(self)
+    }
+
+    // the node, if there is one, as a subtree that can't be empty
+    pub fn into_owned(self) -> Option<OwnedNode<T, A>> {
+        let node = self.node.map(|node| OwnedNode { node, marker: PhantomData });
+        mem::forget(self);
+        node
+    }
+
+    pub fn into_node(self) -> Option<Node<T, A>> {
+        self.into_owned().map(OwnedNode::into_node)
+    }
+}
+
+impl<T, A> Drop for Link<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn drop(&mut self) {
+        drop(self.take().into_node());
+    }
+}
+
+impl<T, A> Default for Link<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn default() -> Link<T, A> {
+        Link::none()
+    }
+}
+
+impl<T, A> Clone for Link<T, A> where T: PartialOrd + Clone, A: Allocator + Clone {
+    fn clone(&self) -> Link<T, A> {
+        match self.as_ref() {
+            Some(node) => Link::new(node.clone()),
+            None => Link::none(),
+        }
+    }
+
+    // wherever both trees have a node in the same position the existing
+    // allocation gets overwritten instead of being freed and reallocated
+    fn clone_from(&mut self, source: &Link<T, A>) {
+        match (self.as_mut(), source.as_ref()) {
+            (Some(node), Some(source)) => return node.clone_from(source),
+            (None, None) => return,
+            _ => (),
         }
+
+        *self = source.clone();
     }
 }
 
-impl<T> Debug for Node<T> where T: PartialOrd + Debug {
+impl<T, A> Debug for Link<T, A> where T: PartialOrd + Debug, A: Allocator + Clone {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}.{:?}", self.color, self.value)
+        self.as_ref().fmt(f)
     }
 }
 
-pub trait NodeHelper<T> where T: PartialOrd {
-    fn color(&self) -> &Color;
-    fn set_color(&mut self, color: Color);
+// An owned subtree that's known not to be empty, for the code that works on
+// a node it has taken out of the tree, like a rotation or a join. It's a
+// `Link` minus the `None`, so it derefs straight to its node.
+pub struct OwnedNode<T, A = Global> where T: PartialOrd, A: Allocator + Clone {
+    node: NonNull<Node<T, A>>,
+    marker: PhantomData<Node<T, A>>,
+}
+
+impl<T, A> OwnedNode<T, A> where T: PartialOrd, A: Allocator + Clone {
+    // allocates the node with its own allocator
+    pub fn new(node: Node<T, A>) -> OwnedNode<T, A> {
+        let alloc = node.alloc.clone();
+        OwnedNode { node: allocator::allocate(node, alloc), marker: PhantomData }
+    }
+
+    pub fn as_parent(&self) -> Parent<T, A> {
+        Some(self.node)
+    }
+
+    // recomputes the size of the node from its children's,
+    // and points the children back at the node
+    pub fn update(&mut self) {
+        let parent = self.as_parent();
+        self.size = 1 + self.left.size() + self.right.size();
+        self.left.set_parent(parent);
+        self.right.set_parent(parent);
+    }
+
+    pub fn into_node(self) -> Node<T, A> {
+        let node = self.node;
+        mem::forget(self);
+        unsafe {
+            let alloc = (*node.as_ptr()).alloc.clone();
+            allocator::deallocate(node, alloc)
+        }
+    }
+}
+
+impl<T, A> Deref for OwnedNode<T, A> where T: PartialOrd, A: Allocator + Clone {
+    type Target = Node<T, A>;
+
+    fn deref(&self) -> &Node<T, A> {
+        // the same ownership rules as for `Link::as_ref` and `as_mut`
+        unsafe { &*self.node.as_ptr() }
+    }
+}
+
+impl<T, A> DerefMut for OwnedNode<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn deref_mut(&mut self) -> &mut Node<T, A> {
+        unsafe { &mut *self.node.as_ptr() }
+    }
+}
+
+impl<T, A> Drop for OwnedNode<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn drop(&mut self) {
+        unsafe {
+            let alloc = (*self.node.as_ptr()).alloc.clone();
+            drop(allocator::deallocate(self.node, alloc));
+        }
+    }
+}
+
+impl<T, A> From<OwnedNode<T, A>> for Link<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn from(node: OwnedNode<T, A>) -> Link<T, A> {
+        let link = Link { node: Some(node.node), marker: PhantomData };
+        mem::forget(node);
+        link
+    }
+}
+
+impl<T, A> From<Option<OwnedNode<T, A>>> for Link<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn from(node: Option<OwnedNode<T, A>>) -> Link<T, A> {
+        node.map_or(Link::none(), Link::from)
+    }
+}
+
+// Nodes removed from a tree are kept here to be reused by later insertions,
+// so a tree that keeps growing and shrinking around the same size stops
+// going to the allocator. The free nodes are chained together through
+// their own memory, which is otherwise unused, starting from `head`.
+pub struct Pool<T, A = Global> where T: PartialOrd, A: Allocator + Clone {
+    head: Parent<T, A>,
+    len: usize,
+    alloc: A,
+}
+
+impl<T, A> Pool<T, A> where T: PartialOrd, A: Allocator + Clone {
+    pub const fn new(alloc: A) -> Pool<T, A> {
+        Pool { head: None, len: 0, alloc }
+    }
     
-    fn left(&self) -> &Link<T>;
-    fn left_mut(&mut self) -> &mut Link<T>;
-    fn set_left(&mut self, left: Link<T>);
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
     
-    fn right(&self) -> &Link<T>;
-    fn right_mut(&mut self) -> &mut Link<T>;
-    fn set_right(&mut self, right: Link<T>);
+    pub fn free_nodes(&self) -> usize {
+        self.len
+    }
     
-    fn value(&self) -> &T;
-    fn value_mut(&mut self) -> &mut T;
-    fn set_value(&mut self, value: T);
-    fn take_value(self) -> T;
+    // a new red node holding `value`, in a free node if there is one
+    pub fn node(&mut self, value: T) -> OwnedNode<T, A> {
+        let node = Node::new_in(value, self.alloc.clone());
+        match self.pop() {
+            Some(free) => unsafe {
+                ptr::write(free.as_ptr(), node);
+                OwnedNode { node: free, marker: PhantomData }
+            },
+            None => OwnedNode::new(node),
+        }
+    }
     
-    fn is_black(&self) -> bool;
-    fn is_red(&self) -> bool;
+    // like `node`, but hands `value` back when there's no free node and
+    // allocating one fails
+    pub fn try_node(&mut self, value: T) -> Result<OwnedNode<T, A>, T> {
+        let free = match self.pop() {
+            Some(free) => free,
+            None => match allocator::try_allocate(MaybeUninit::<Node<T, A>>::uninit(), self.alloc.clone()) {
+                Ok(free) => free.cast(),
+                Err(_) => return Err(value),
+            },
+        };
+        
+        unsafe { ptr::write(free.as_ptr(), Node::new_in(value, self.alloc.clone())) };
+        Ok(OwnedNode { node: free, marker: PhantomData })
+    }
+    
+    // takes the value out of a node that's been unlinked from the tree,
+    // and keeps the node for later. Its children have to be gone already.
+    pub fn recycle(&mut self, node: OwnedNode<T, A>) -> T {
+        let free = node.node;
+        mem::forget(node);
+        let node = unsafe { ptr::read(free.as_ptr()) };
+        self.push(free);
+        node.value
+    }
+    
+    // allocates free nodes until there are at least `len` of them
+    pub fn reserve(&mut self, len: usize) {
+        while self.len < len {
+            let free = allocator::allocate(MaybeUninit::<Node<T, A>>::uninit(), self.alloc.clone());
+            self.push(free.cast());
+        }
+    }
+    
+    // like `reserve`, but stops at the first allocation that fails, and
+    // says whether there are `len` free nodes now. The ones it did manage
+    // to allocate are kept.
+    pub fn try_reserve(&mut self, len: usize) -> bool {
+        while self.len < len {
+            match allocator::try_allocate(MaybeUninit::<Node<T, A>>::uninit(), self.alloc.clone()) {
+                Ok(free) => self.push(free.cast()),
+                Err(_) => return false,
+            }
+        }
+        
+        true
+    }
+    
+    // frees all but `keep` of the nodes
+    pub fn trim(&mut self, keep: usize) {
+        while self.len > keep {
+            match self.pop() {
+                Some(free) => unsafe { allocator::free(free, self.alloc.clone()) },
+                None => break,
+            }
+        }
+    }
+    
+    // `free` has to be node memory from `alloc` that holds no value
+    fn push(&mut self, free: NonNull<Node<T, A>>) {
+        unsafe { ptr::write(free.as_ptr() as *mut Parent<T, A>, self.head) };
+        self.head = Some(free);
+        self.len += 1;
+    }
+    
+    fn pop(&mut self) -> Parent<T, A> {
+        let free = self.head?;
+        // a node is at least as big and as aligned as a pointer
+        self.head = unsafe { ptr::read(free.as_ptr() as *const Parent<T, A>) };
+        self.len -= 1;
+        Some(free)
+    }
 }
 
-impl<T> NodeHelper<T> for Link<T> where T: PartialOrd {
-    fn color(&self) -> &Color {
-        &self.as_ref().unwrap().color
+impl<T, A> Drop for Pool<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn drop(&mut self) {
+        self.trim(0);
     }
-    fn set_color(&mut self, color: Color) {
-        self.as_mut().map(|n| n.color = color);
+}
+
+// the copy starts out empty
+impl<T, A> Clone for Pool<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn clone(&self) -> Pool<T, A> {
+        Pool::new(self.alloc.clone())
+    }
+}
+
+// The raw pointers only ever point at nodes of the same tree, which is
+// owned as a whole, so they don't stop it from being shared or sent
+// anywhere its elements can be.
+unsafe impl<T, A> Send for Link<T, A> where T: PartialOrd + Send, A: Allocator + Clone + Send {}
+unsafe impl<T, A> Sync for Link<T, A> where T: PartialOrd + Sync, A: Allocator + Clone + Sync {}
+unsafe impl<T, A> Send for Pool<T, A> where T: PartialOrd + Send, A: Allocator + Clone + Send {}
+unsafe impl<T, A> Sync for Pool<T, A> where T: PartialOrd + Sync, A: Allocator + Clone + Sync {}
+unsafe impl<T, A> Send for Node<T, A> where T: PartialOrd + Send, A: Allocator + Clone + Send {}
+unsafe impl<T, A> Sync for Node<T, A> where T: PartialOrd + Sync, A: Allocator + Clone + Sync {}
+unsafe impl<T, A> Send for OwnedNode<T, A> where T: PartialOrd + Send, A: Allocator + Clone + Send {}
+unsafe impl<T, A> Sync for OwnedNode<T, A> where T: PartialOrd + Sync, A: Allocator + Clone + Sync {}
+
+impl<T> Node<T> where T: PartialOrd {
+    pub fn new(value: T) -> Node<T> {
+        Node::new_in(value, Global)
+    }
+}
+
+impl<T, A> Node<T, A> where T: PartialOrd, A: Allocator + Clone {
+    pub fn new_in(value: T, alloc: A) -> Node<T, A> {
+        Node {
+            value,
+            left: Link::none(),
+            right: Link::none(),
+            size: 1,
+            parent_color: ptr::null_mut(),
+            alloc,
+        }
     }
     
-    fn left(&self) -> &Link<T> {
-        &self.as_ref().unwrap().left
+    pub fn color(&self) -> Color {
+        if self.parent_color.addr() & BLACK == 0 {
+            Color::Red
+        } else {
+            Color::Black
+        }
     }
-    fn left_mut(&mut self) -> &mut Link<T> {
-        &mut self.as_mut().unwrap().left
+    
+    pub fn set_color(&mut self, color: Color) {
+        let bit = match color {
+            Color::Red => 0,
+            Color::Black => BLACK,
+        };
+        
+        if self.parent_color.addr() & BLACK != bit {
+            stats::recoloring();
+            trace::recoloring(&self.value, color);
+        }
+        self.parent_color = self.parent_color.map_addr(|addr| (addr & !BLACK) | bit);
     }
-    fn set_left(&mut self, left: Link<T>) {
-        self.as_mut().map(|n| n.left = left);
+    
+    pub fn is_red(&self) -> bool {
+        self.color().is_red()
     }
     
-    fn right(&self) -> &Link<T> {
-        &self.as_ref().unwrap().right
+    pub fn is_black(&self) -> bool {
+        self.color().is_black()
     }
-    fn right_mut(&mut self) -> &mut Link<T> {
-        &mut self.as_mut().unwrap().right
+    
+    pub fn parent(&self) -> Parent<T, A> {
+        NonNull::new(self.parent_color.map_addr(|addr| addr & !BLACK))
     }
-    fn set_right(&mut self, right: Link<T>) {
-        self.as_mut().map(|n| n.right = right);
+    
+    pub fn set_parent(&mut self, parent: Parent<T, A>) {
+        let bit = self.parent_color.addr() & BLACK;
+        let parent = parent.map_or(ptr::null_mut(), NonNull::as_ptr);
+        self.parent_color = parent.map_addr(|addr| addr | bit);
     }
     
-    fn value(&self) -> &T {
-        &self.as_ref().unwrap().value
+    pub fn value(&self) -> &T {
+        &self.value
     }
-    fn value_mut(&mut self) -> &mut T {
-        &mut self.as_mut().unwrap().value
+    
+    pub fn value_mut(&mut self) -> &mut T {
+        &mut self.value
     }
-    fn set_value(&mut self, value: T) {
-        self.as_mut().map(|n| n.value = value);
+    
+    pub fn left(&self) -> &Link<T, A> {
+        &self.left
     }
-    fn take_value(self) -> T {
-        self.unwrap().value
+    
+    pub fn left_mut(&mut self) -> &mut Link<T, A> {
+        &mut self.left
     }
     
-    fn is_black(&self) -> bool {
-        self.as_ref().map_or(true, |n| n.color.is_black())
+    pub fn set_left(&mut self, left: Link<T, A>) {
+        self.left = left;
     }
-    fn is_red(&self) -> bool {
-        self.as_ref().map_or(false, |n| n.color.is_red())
+    
+    pub fn right(&self) -> &Link<T, A> {
+        &self.right
     }
-}
-
-impl<'a, T> NodeHelper<T> for Option<&'a mut Link<T>> where T: PartialOrd {
-    fn color(&self) -> &Color {
-        self.as_ref().unwrap().color()
+    
+    pub fn right_mut(&mut self) -> &mut Link<T, A> {
+        &mut self.right
     }
-    fn set_color(&mut self, color: Color) {
-        self.as_mut().map(|n| n.set_color(color));
+    
+    pub fn set_right(&mut self, right: Link<T, A>) {
+        self.right = right;
     }
     
-    fn left(&self) -> &Link<T> {
-        self.as_ref().unwrap().left()
+    pub fn into_value(self) -> T {
+        self.value
     }
-    fn left_mut(&mut self) -> &mut Link<T> {
-        (**self.as_mut().unwrap()).left_mut()
+
+    pub fn size(&self) -> usize {
+        self.size
     }
-    fn set_left(&mut self, left: Link<T>) {
-        self.as_mut().map(|n| n.set_left(left));
+    
+    pub fn set_size(&mut self, size: usize) {
+        self.size = size;
     }
     
-    fn right(&self) -> &Link<T> {
-        self.as_ref().unwrap().right()
+    pub fn leftmost(&self) -> &Node<T, A> {
+        let mut node = self;
+        while let Some(left) = node.left.as_ref() {
+            node = left;
+        }
+        
+        node
     }
-    fn right_mut(&mut self) -> &mut Link<T> {
-        (**self.as_mut().unwrap()).right_mut()
+    
+    pub fn rightmost(&self) -> &Node<T, A> {
+        let mut node = self;
+        while let Some(right) = node.right.as_ref() {
+            node = right;
+        }
+        
+        node
     }
-    fn set_right(&mut self, right: Link<T>) {
-        self.as_mut().map(|n| n.set_right(right));
+    
+    // the node at the given sorted position in this subtree
+    pub fn select(&self, index: usize) -> Option<&Node<T, A>> {
+        let mut node = self;
+        let mut index = index;
+        loop {
+            let left_size = node.left.size();
+            if index < left_size {
+                node = node.left.as_ref()?;
+            } else if index == left_size {
+                return Some(node);
+            } else {
+                index -= left_size + 1;
+                node = node.right.as_ref()?;
+            }
+        }
     }
     
-    fn value(&self) -> &T {
-        self.as_ref().unwrap().value()
+    // the next node in order, or None after the last one. `root` has to be
+    // the root of the tree this node is in, since that's where the parent
+    // pointers stop being valid.
+    pub fn successor<'a>(&'a self, root: &Node<T, A>) -> Option<&'a Node<T, A>> {
+        if let Some(right) = self.right.as_ref() {
+            return Some(right.leftmost());
+        }
+        
+        let mut node = self;
+        while !ptr::eq(node, root) {
+            // every node below the root points at the node it hangs from,
+            // and that node lives for as long as the tree is borrowed
+            let parent = unsafe { &*node.parent()?.as_ptr() };
+            if parent.left.as_ref().is_some_and(|left| ptr::eq(left, node)) {
+                return Some(parent);
+            }
+            
+            node = parent;
+        }
+        
+        None
     }
-    fn value_mut(&mut self) -> &mut T {
-        self.as_mut().unwrap().value_mut()
+    
+    // the node before this one in order, or None before the first one,
+    // with the same rule for `root` as `successor`
+    pub fn predecessor<'a>(&'a self, root: &Node<T, A>) -> Option<&'a Node<T, A>> {
+        if let Some(left) = self.left.as_ref() {
+            return Some(left.rightmost());
+        }
+        
+        let mut node = self;
+        while !ptr::eq(node, root) {
+            let parent = unsafe { &*node.parent()?.as_ptr() };
+            if parent.right.as_ref().is_some_and(|right| ptr::eq(right, node)) {
+                return Some(parent);
+            }
+            
+            node = parent;
+        }
+        
+        None
+    }
+    
+    // points every node of the subtree back at its parent, for subtrees
+    // that were copied or moved around without going through `update`
+    pub fn update_parents(link: &mut Link<T, A>) {
+        link.update();
+        if let Some(node) = link.as_mut() {
+            Self::update_parents(node.left_mut());
+            Self::update_parents(node.right_mut());
+        }
+    }
+}
+
+impl<T, A> Clone for Node<T, A> where T: PartialOrd + Clone, A: Allocator + Clone {
+    fn clone(&self) -> Self {
+        let mut node = Node {
+            value: self.value.clone(),
+            left: self.left.clone(),
+            right: self.right.clone(),
+            size: self.size,
+            // the copy can't know where it will end up, so the tree being
+            // cloned fixes up the parent pointers afterwards
+            parent_color: ptr::null_mut(),
+            alloc: self.alloc.clone(),
+        };
+        
+        node.set_color(self.color());
+        node
     }
-    fn set_value(&mut self, value: T) {
-        self.as_mut().map(|n| n.set_value(value));
+    
+    // links forward clone_from to their nodes, to reuse their allocations
+    fn clone_from(&mut self, source: &Self) {
+        self.set_color(source.color());
+        self.value.clone_from(&source.value);
+        self.left.clone_from(&source.left);
+        self.right.clone_from(&source.right);
+        self.size = source.size;
     }
-    fn take_value(self) -> T {
-        unimplemented!()
+}
+
+impl<T, A> Debug for Node<T, A> where T: PartialOrd + Debug, A: Allocator + Clone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}.{:?}", self.color(), self.value)
     }
+}
+
+// What can be asked of, or done to, a link whether or not it has a node.
+// Anything that needs the node itself goes through `as_ref` or `as_mut`
+// first, so a missing node can't be mistaken for a present one.
+pub trait NodeHelper<T, A = Global> where T: PartialOrd, A: Allocator + Clone {
+    // missing nodes count as black
+    fn is_black(&self) -> bool;
+    fn is_red(&self) -> bool;
+    // does nothing to an empty link
+    fn set_color(&mut self, color: Color);
     
+    fn size(&self) -> usize;
+    
+    fn set_parent(&mut self, parent: Parent<T, A>);
+    fn as_parent(&self) -> Parent<T, A>;
+    
+    // recomputes the size of the node from its children's,
+    // and points the children back at the node
+    fn update(&mut self);
+}
+
+impl<T, A> NodeHelper<T, A> for Link<T, A> where T: PartialOrd, A: Allocator + Clone {
     fn is_black(&self) -> bool {
-        self.as_ref().unwrap().is_black()
+        self.as_ref().is_none_or(Node::is_black)
     }
     fn is_red(&self) -> bool {
-        self.as_ref().unwrap().is_red()
+        self.as_ref().is_some_and(Node::is_red)
+    }
+    fn set_color(&mut self, color: Color) {
+        if let Some(node) = self.as_mut() {
+            node.set_color(color);
+        }
+    }
+    
+    fn size(&self) -> usize {
+        self.as_ref().map_or(0, Node::size)
+    }
+    
+    fn set_parent(&mut self, parent: Parent<T, A>) {
+        if let Some(node) = self.as_mut() {
+            node.set_parent(parent);
+        }
+    }
+    fn as_parent(&self) -> Parent<T, A> {
+        self.node
+    }
+    
+    fn update(&mut self) {
+        let parent = self.as_parent();
+        if let Some(node) = self.as_mut() {
+            node.size = 1 + node.left.size() + node.right.size();
+            node.left.set_parent(parent);
+            node.right.set_parent(parent);
+        }
     }
 }
 
-pub trait Follow<T> where T: PartialOrd {
-    fn follow(&self, direction: Dir) -> &Link<T>;
-    fn follow_mut(&mut self, direction: Dir) -> &mut Link<T>;
+pub trait Follow<T, A = Global> where T: PartialOrd, A: Allocator + Clone {
+    fn follow(&self, direction: Dir) -> &Link<T, A>;
+    fn follow_mut(&mut self, direction: Dir) -> &mut Link<T, A>;
 }
 
-impl<T> Follow<T> for Node<T> where T: PartialOrd {
-    fn follow(&self, direction: Dir) -> &Link<T> {
+impl<T, A> Follow<T, A> for Node<T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn follow(&self, direction: Dir) -> &Link<T, A> {
         match direction {
             Dir::Left => &self.left,
             Dir::Right => &self.right,
         }
     }
-    fn follow_mut(&mut self, direction: Dir) -> &mut Link<T> {
+    fn follow_mut(&mut self, direction: Dir) -> &mut Link<T, A> {
         match direction {
             Dir::Left => &mut self.left,
             Dir::Right => &mut self.right,
@@ -213,59 +682,17 @@ impl<T> Follow<T> for Node<T> where T: PartialOrd {
     }
 }
 
-impl<T> Follow<T> for Link<T> where T: PartialOrd {
-    fn follow(&self, direction: Dir) -> &Link<T> {
-        self.as_ref().unwrap().follow(direction)
-    }
-    fn follow_mut(&mut self, direction: Dir) -> &mut Link<T> {
-        self.as_mut().unwrap().follow_mut(direction)
-    }
-}
-
-impl<'a, T> Follow<T> for Option<&'a mut Link<T>> where T: PartialOrd {
-    fn follow(&self, direction: Dir) -> &Link<T> {
-        self.as_ref().unwrap().follow(direction)
-    }
-    fn follow_mut(&mut self, direction: Dir) -> &mut Link<T> {
-        self.as_mut().unwrap().follow_mut(direction)
-    }
-}
-
-// variadic versions of follow
-#[macro_export]
-macro_rules! follow {
-    ($node:expr, $dir:ident) => {
-        $node.follow($dir)
-    };
-    ($node:expr, $dir:ident, $($dirs:ident),+) => {
-        follow!($node.follow($dir), $($dirs),+)
-    };
-}
-
-#[macro_export]
-macro_rules! follow_mut {
-    ($node:expr, $dir:ident) => {
-        $node.follow_mut($dir)
-    };
-    ($node:expr, $dir:ident, $($dirs:ident),+) => {
-        follow_mut!($node.follow_mut($dir), $($dirs),+)
-    };
-}
-
 #[cfg(test)]
 impl<T: PartialOrd> PartialEq for Node<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.color == other.color
+        self.color() == other.color()
             && self.value == other.value
     }
 }
 
 #[cfg(test)]
 pub fn node<T: PartialOrd>(color: Color, value: T) -> Node<T> {
-    Node::<T> {
-        color: color,
-        value: value,
-        left: None,
-        right: None,
-    }
+    let mut node = Node::new(value);
+    node.set_color(color);
+    node
 }
\ No newline at end of file
@@ -0,0 +1,183 @@
+// A tree that tells whoever's subscribed to it about each change as it's
+// made, so a cache or a second index built from the tree can be kept up
+// to date change by change rather than by comparing the whole tree with
+// the last copy it saw.
+//
+// Subscribers are called in the order they subscribed, after the change
+// has been made to the tree. A removed element is lent to them in the
+// event before it's handed back to whoever removed it. A subscriber can
+// also be the sending end of a channel, which gets its own copy of each
+// event, and is dropped once the receiving end has hung up.
+
+use std::sync::mpsc::Sender;
+
+use RedBlackTree;
+
+/// A change made to an `ObservedRedBlackTree`.
+#[derive(Debug, PartialEq)]
+pub enum Event<'a, T> where T: 'a {
+    Inserted(&'a T),
+    Removed(T),
+}
+
+/// An `Event` with its own copy of the inserted element, for sending to
+/// another thread.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnedEvent<T> {
+    Inserted(T),
+    Removed(T),
+}
+
+impl<'a, T> Event<'a, T> where T: Clone {
+    pub fn to_owned(&self) -> OwnedEvent<T> {
+        match *self {
+            Event::Inserted(value) => OwnedEvent::Inserted(value.clone()),
+            Event::Removed(ref value) => OwnedEvent::Removed(value.clone()),
+        }
+    }
+}
+
+/// Identifies a subscriber to an `ObservedRedBlackTree`, for
+/// unsubscribing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Subscription(usize);
+
+// a subscriber returns whether it wants to hear about any more changes
+type Subscriber<T> = Box<dyn FnMut(&Event<'_, T>) -> bool>;
+
+/// A `RedBlackTree` that calls its subscribers with every change made to
+/// it, made by `RedBlackTree::observed`.
+pub struct ObservedRedBlackTree<T> where T: PartialOrd {
+    tree: RedBlackTree<T>,
+    subscribers: Vec<(Subscription, Subscriber<T>)>,
+    next: usize,
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Turns the tree into one that takes subscribers, without any yet.
+    pub fn observed(self) -> ObservedRedBlackTree<T> {
+        ObservedRedBlackTree { tree: self, subscribers: Vec::new(), next: 0 }
+    }
+}
+
+impl<T> ObservedRedBlackTree<T> where T: PartialOrd {
+    pub fn new() -> ObservedRedBlackTree<T> {
+        RedBlackTree::new().observed()
+    }
+
+    /// Calls `subscriber` with every change from now on, until it's
+    /// unsubscribed.
+    pub fn subscribe<F>(&mut self, mut subscriber: F) -> Subscription where F: FnMut(&Event<'_, T>) + 'static {
+        self.add(Box::new(move |event| {
+            subscriber(event);
+            true
+        }))
+    }
+
+    /// Sends a copy of every change from now on down `sender`, until it's
+    /// unsubscribed or the receiver is dropped.
+    pub fn subscribe_channel(&mut self, sender: Sender<OwnedEvent<T>>) -> Subscription where T: Clone + 'static {
+        self.add(Box::new(move |event| sender.send(event.to_owned()).is_ok()))
+    }
+
+    /// Stops calling a subscriber, and says whether it was still
+    /// subscribed.
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> bool {
+        let before = self.subscribers.len();
+        self.subscribers.retain(|&(id, _)| id != subscription);
+        self.subscribers.len() != before
+    }
+
+    pub fn insert(&mut self, value: T) where T: Clone {
+        self.tree.insert(value.clone());
+        self.notify(&Event::Inserted(&value));
+    }
+
+    /// Removes one element equal to `value`, if there is one, and tells
+    /// the subscribers before handing it back.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        let removed = self.tree.remove(value)?;
+        let event = Event::Removed(removed);
+        self.notify(&event);
+        match event {
+            Event::Removed(removed) => Some(removed),
+            Event::Inserted(_) => unreachable!(),
+        }
+    }
+
+    pub fn tree(&self) -> &RedBlackTree<T> {
+        &self.tree
+    }
+
+    /// Drops the subscribers and hands back the tree.
+    pub fn into_tree(self) -> RedBlackTree<T> {
+        self.tree
+    }
+
+    fn add(&mut self, subscriber: Subscriber<T>) -> Subscription {
+        let subscription = Subscription(self.next);
+        self.next += 1;
+        self.subscribers.push((subscription, subscriber));
+        subscription
+    }
+
+    fn notify(&mut self, event: &Event<'_, T>) {
+        self.subscribers.retain_mut(|&mut (_, ref mut subscriber)| subscriber(event));
+    }
+}
+
+impl<T> Default for ObservedRedBlackTree<T> where T: PartialOrd {
+    fn default() -> ObservedRedBlackTree<T> {
+        ObservedRedBlackTree::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use observe::{Event, ObservedRedBlackTree, OwnedEvent};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    mod observed_tree {
+        use super::*;
+
+        #[test]
+        fn tells_its_subscribers_about_each_change() {
+            let mut tree = ObservedRedBlackTree::new();
+            let seen = Rc::new(RefCell::new(Vec::new()));
+            let subscription = {
+                let seen = seen.clone();
+                tree.subscribe(move |event: &Event<i32>| seen.borrow_mut().push(event.to_owned()))
+            };
+
+            tree.insert(2);
+            tree.insert(1);
+            expect!(tree.remove(&2)).to(be_some().value(2));
+            // nothing to tell about
+            expect!(tree.remove(&7)).to(be_none());
+            expect!(tree.unsubscribe(subscription)).to(be_true());
+            tree.insert(3);
+
+            expect!(seen.borrow().clone()).to(be_equal_to(vec![OwnedEvent::Inserted(2), OwnedEvent::Inserted(1), OwnedEvent::Removed(2)]));
+            expect!(tree.unsubscribe(subscription)).to(be_false());
+        }
+
+        #[test]
+        fn sends_changes_down_a_channel_until_the_receiver_hangs_up() {
+            let mut tree = ObservedRedBlackTree::new();
+            let (sender, receiver) = mpsc::channel();
+            tree.subscribe_channel(sender);
+
+            tree.insert(1);
+            tree.remove(&1);
+            expect!(receiver.try_iter().collect::<Vec<_>>()).to(be_equal_to(vec![OwnedEvent::Inserted(1), OwnedEvent::Removed(1)]));
+
+            drop(receiver);
+            tree.insert(2);
+            expect!(tree.subscribers.len()).to(be_equal_to(0));
+            expect!(tree.tree().contains(&2)).to(be_true());
+        }
+    }
+}
@@ -0,0 +1,315 @@
+// Set operators, matching the ones BTreeSet provides. Each one walks both
+// trees in order at the same time and bulk builds the result, so they're
+// all O(n + m). Equal elements are paired up one to one, so duplicates
+// behave like a multiset (union keeps the larger count, intersection the
+// smaller one, and so on). Elements that can't be compared, like NaN, are
+// never equal to anything, so they're never paired up. `ChunkedRedBlackTree`
+// builds its own operators on the same merge.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::iter;
+use std::mem;
+use std::ops::{BitAnd, BitOr, BitXor, Sub};
+use std::vec;
+
+use {IntoIter, RedBlackTree};
+
+// which elements a set operator keeps: those only in the left tree, those
+// only in the right one, and those in both
+pub struct Keep {
+    left_only: bool,
+    right_only: bool,
+    both: bool,
+}
+
+impl Keep {
+    pub const UNION: Keep = Keep { left_only: true, right_only: true, both: true };
+    pub const INTERSECTION: Keep = Keep { left_only: false, right_only: false, both: true };
+    pub const DIFFERENCE: Keep = Keep { left_only: true, right_only: false, both: false };
+    pub const SYMMETRIC_DIFFERENCE: Keep = Keep { left_only: true, right_only: true, both: false };
+}
+
+// which side a merge takes its next element from
+enum Step {
+    Left,
+    Right,
+    Both,
+}
+
+// only elements that compare equal are paired up. Of two that can't be
+// compared, the left one is taken first, as `insert` would put the right
+// one after it.
+fn step<T>(left: &T, right: &T) -> Step where T: PartialOrd {
+    match left.partial_cmp(right) {
+        Some(Ordering::Equal) => Step::Both,
+        Some(Ordering::Greater) => Step::Right,
+        _ => Step::Left,
+    }
+}
+
+// the elements `keep` keeps of two sequences in ascending order, cloned
+// into a Vec in the order they were taken
+pub fn merge_values<'a, T, L, R>(left: L, right: R, keep: Keep) -> Vec<T>
+where T: PartialOrd + Clone + 'a, L: Iterator<Item = &'a T>, R: Iterator<Item = &'a T> {
+    let mut values = vec![];
+    let mut left = left.peekable();
+    let mut right = right.peekable();
+
+    loop {
+        let (value, kept) = match (left.peek(), right.peek()) {
+            (None, None) => break,
+            (Some(_), None) => (left.next(), keep.left_only),
+            (None, Some(_)) => (right.next(), keep.right_only),
+            (Some(l), Some(r)) => match step(*l, *r) {
+                Step::Left => (left.next(), keep.left_only),
+                Step::Right => (right.next(), keep.right_only),
+                Step::Both => {
+                    right.next();
+                    (left.next(), keep.both)
+                },
+            },
+        };
+
+        if kept {
+            values.extend(value.cloned());
+        }
+    }
+
+    values
+}
+
+fn merge<T>(left: &RedBlackTree<T>, right: &RedBlackTree<T>, keep: Keep) -> RedBlackTree<T> where T: PartialOrd + Clone {
+    let values = merge_values(left.iter(), right.iter(), keep);
+    if values.windows(2).all(|pair| pair[0] <= pair[1]) {
+        RedBlackTree::from_sorted_vec(values)
+    } else {
+        values.into_iter().collect()
+    }
+}
+
+impl<T> BitOr<&RedBlackTree<T>> for &RedBlackTree<T> where T: PartialOrd + Clone {
+    type Output = RedBlackTree<T>;
+
+    fn bitor(self, other: &RedBlackTree<T>) -> RedBlackTree<T> {
+        merge(self, other, Keep::UNION)
+    }
+}
+
+impl<T> BitAnd<&RedBlackTree<T>> for &RedBlackTree<T> where T: PartialOrd + Clone {
+    type Output = RedBlackTree<T>;
+
+    fn bitand(self, other: &RedBlackTree<T>) -> RedBlackTree<T> {
+        merge(self, other, Keep::INTERSECTION)
+    }
+}
+
+impl<T> Sub<&RedBlackTree<T>> for &RedBlackTree<T> where T: PartialOrd + Clone {
+    type Output = RedBlackTree<T>;
+
+    fn sub(self, other: &RedBlackTree<T>) -> RedBlackTree<T> {
+        merge(self, other, Keep::DIFFERENCE)
+    }
+}
+
+impl<T> BitXor<&RedBlackTree<T>> for &RedBlackTree<T> where T: PartialOrd + Clone {
+    type Output = RedBlackTree<T>;
+
+    fn bitxor(self, other: &RedBlackTree<T>) -> RedBlackTree<T> {
+        merge(self, other, Keep::SYMMETRIC_DIFFERENCE)
+    }
+}
+
+// where an element of the left tree goes in a merge: after how many more
+// elements of the right tree, and whether the one after those is equal to
+// it, making a pair for `resolve`
+struct Place {
+    before: usize,
+    paired: bool,
+}
+
+// A `merge_with` that's under way. `kept` holds the elements of both trees
+// that haven't been handed to `resolve`, in the order the plan puts them,
+// which is sorted without comparing anything again. If `resolve` or a
+// comparison panics before the merge is done, dropping this puts those
+// back in the tree, along with the rest of both trees in the same order.
+struct Merging<'a, T> where T: PartialOrd {
+    tree: &'a mut RedBlackTree<T>,
+    plan: vec::IntoIter<Place>,
+    left: IntoIter<T>,
+    right: IntoIter<T>,
+    kept: Vec<T>,
+    done: bool,
+}
+
+impl<'a, T> Merging<'a, T> where T: PartialOrd {
+    // hands each pair to `resolve`, and returns what it made of them, each
+    // with how many of the kept elements go before it
+    fn resolve<F>(&mut self, resolve: &mut F) -> Vec<(usize, T)> where F: FnMut(T, T) -> T {
+        let mut resolved = vec![];
+        while let Some((place, left)) = self.plan.next().zip(self.left.next()) {
+            self.kept.extend(self.right.by_ref().take(place.before));
+            let right = if place.paired { self.right.next() } else { None };
+            match right {
+                Some(right) => resolved.push((self.kept.len(), resolve(left, right))),
+                None => self.kept.push(left),
+            }
+        }
+
+        self.kept.extend(self.right.by_ref());
+        resolved
+    }
+
+    // whether the resolved elements fall in order among the kept ones
+    fn in_order(&self, resolved: &[(usize, T)]) -> bool {
+        let resolved = resolved.iter().map(|(before, value)| (*before, value));
+        interleave(self.kept.iter(), resolved).is_sorted_by(|previous, value| previous <= value)
+    }
+
+    fn finish(mut self, resolved: Vec<(usize, T)>, in_order: bool) {
+        self.done = true;
+        let kept = mem::take(&mut self.kept);
+        let values = interleave(kept.into_iter(), resolved.into_iter());
+        *self.tree = if in_order {
+            RedBlackTree::from_sorted_vec(values.collect())
+        } else {
+            values.collect()
+        };
+    }
+}
+
+// the kept elements of a merge, with each resolved one before the kept
+// element whose index it came with
+fn interleave<V, K, R>(kept: K, resolved: R) -> impl Iterator<Item = V> where K: Iterator<Item = V>, R: Iterator<Item = (usize, V)> {
+    let mut kept = kept.enumerate().peekable();
+    let mut resolved = resolved.peekable();
+    iter::from_fn(move || {
+        let next = kept.peek().map(|(index, _)| *index);
+        match resolved.next_if(|(before, _)| next.is_none_or(|index| *before <= index)) {
+            Some((_, value)) => Some(value),
+            None => kept.next().map(|(_, value)| value),
+        }
+    })
+}
+
+impl<'a, T> Drop for Merging<'a, T> where T: PartialOrd {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        let mut kept = mem::take(&mut self.kept);
+        for (place, left) in self.plan.by_ref().zip(self.left.by_ref()) {
+            kept.extend(self.right.by_ref().take(place.before));
+            kept.push(left);
+            if place.paired {
+                kept.extend(self.right.next());
+            }
+        }
+        kept.extend(self.right.by_ref());
+        *self.tree = RedBlackTree::from_sorted_vec(kept);
+    }
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Moves the elements of `other` into this tree, like a union, except
+    /// that each pair of equal elements, one from each tree, is replaced
+    /// with whatever `resolve` makes of the two, this tree's element
+    /// first. Elements with no equal in the other tree are kept as they
+    /// are. O(n + m), unless `resolve` returns elements that don't sort
+    /// where the pairs it was given did, which costs a rebuild by
+    /// inserting.
+    ///
+    /// The two trees are compared before anything is moved, so a
+    /// comparison that panics then leaves this tree as it was. If
+    /// `resolve` panics, or a comparison after it, this tree ends up with
+    /// every element of both that wasn't handed to `resolve`.
+    pub fn merge_with<F>(&mut self, other: RedBlackTree<T>, mut resolve: F) where F: FnMut(T, T) -> T {
+        let mut plan = Vec::with_capacity(self.count);
+        let mut right = other.iter().peekable();
+        for left in self.iter() {
+            let mut place = Place { before: 0, paired: false };
+            loop {
+                match right.peek().map(|right| step(left, *right)) {
+                    Some(Step::Right) => place.before += 1,
+                    Some(Step::Both) => place.paired = true,
+                    Some(Step::Left) | None => break,
+                }
+
+                right.next();
+                if place.paired {
+                    break;
+                }
+            }
+
+            plan.push(place);
+        }
+
+        let left = mem::take(self).into_iter();
+        let kept = Vec::with_capacity(left.len() + other.count);
+        let mut merging = Merging { tree: self, plan: plan.into_iter(), left, right: other.into_iter(), kept, done: false };
+        let resolved = merging.resolve(&mut resolve);
+        let in_order = merging.in_order(&resolved);
+        merging.finish(resolved, in_order);
+    }
+}
+
+// the smallest element left in one of the trees `merge_all` is merging,
+// ordered so that `BinaryHeap`, a max heap, pops the smallest first, and
+// of equal ones the one from the earliest tree
+struct Head<T> {
+    value: T,
+    source: usize,
+}
+
+impl<T> PartialEq for Head<T> where T: PartialOrd {
+    fn eq(&self, other: &Head<T>) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Head<T> where T: PartialOrd {}
+
+impl<T> PartialOrd for Head<T> where T: PartialOrd {
+    fn partial_cmp(&self, other: &Head<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Head<T> where T: PartialOrd {
+    fn cmp(&self, other: &Head<T>) -> Ordering {
+        // incomparable elements come out in some order or other, and the
+        // result gets sorted properly afterwards
+        let by_value = other.value.partial_cmp(&self.value).unwrap_or(Ordering::Equal);
+        by_value.then(other.source.cmp(&self.source))
+    }
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Builds one tree out of all the elements of all of `trees`, keeping
+    /// every one of them the way `extend` would. The trees are merged
+    /// through a heap of their smallest elements and the result built
+    /// from the merge directly, in O(n log k) for k trees. Of equal
+    /// elements, those from earlier trees come first.
+    pub fn merge_all<I>(trees: I) -> RedBlackTree<T> where I: IntoIterator<Item = RedBlackTree<T>> {
+        let mut sources: Vec<IntoIter<T>> = trees.into_iter().map(RedBlackTree::into_iter).collect();
+        let mut heads = BinaryHeap::with_capacity(sources.len());
+        let mut len = 0;
+        for (source, values) in sources.iter_mut().enumerate() {
+            len += values.len();
+            heads.extend(values.next().map(|value| Head { value, source }));
+        }
+
+        let mut values = Vec::with_capacity(len);
+        while let Some(Head { value, source }) = heads.pop() {
+            values.push(value);
+            heads.extend(sources[source].next().map(|value| Head { value, source }));
+        }
+
+        if values.windows(2).all(|pair| pair[0] <= pair[1]) {
+            RedBlackTree::from_sorted_vec(values)
+        } else {
+            values.into_iter().collect()
+        }
+    }
+}
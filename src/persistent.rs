@@ -0,0 +1,525 @@
+// A red-black tree that's never changed in place. Its nodes are shared
+// through `Arc`s, and `insert` and `remove` return a new tree, copying only
+// the nodes on the path down to the element and sharing every other
+// subtree with the old tree. So both trees stay usable, a copy of a tree
+// is just a reference count going up, and keeping a snapshot of each
+// version, say for undo, costs O(log n) space per change.
+//
+// Shared nodes can't have parent links, so the balancing isn't
+// `RedBlackTree`'s. It's the functional version, which rebuilds the path
+// on the way back up from the element: Okasaki's for insertion and Kahrs'
+// for removal.
+
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+use node::{Color, Dir};
+use {get_dir, search_dir};
+
+type Link<T> = Option<Arc<Node<T>>>;
+
+struct Node<T> {
+    color: Color,
+    left: Link<T>,
+    value: T,
+    right: Link<T>,
+}
+
+/// A sorted multiset whose `insert` and `remove` leave it unchanged and
+/// return a new tree instead, which shares all but O(log n) of its nodes
+/// with this one. Cloning one is O(1). The elements have to be `Clone`,
+/// since the elements on the path to a change get copied into the new
+/// tree's nodes.
+pub struct RedBlackTree<T> where T: PartialOrd + Clone {
+    root: Link<T>,
+    len: usize,
+}
+
+impl<T> RedBlackTree<T> where T: PartialOrd + Clone {
+    pub const fn new() -> RedBlackTree<T> {
+        RedBlackTree { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// A tree with `value` added, after any elements equal to it.
+    pub fn insert(&self, value: T) -> RedBlackTree<T> {
+        RedBlackTree { root: blacken(insert(&self.root, value)), len: self.len + 1 }
+    }
+
+    /// A tree with one element equal to `value` taken out, or a copy of
+    /// this one if there isn't one.
+    pub fn remove(&self, value: &T) -> RedBlackTree<T> {
+        // the rebalancing on the way back up counts on a node having been
+        // taken out below, so it can't be run when there's nothing to take
+        if !self.contains(value) {
+            return self.clone();
+        }
+
+        RedBlackTree { root: blacken(remove(&self.root, value)), len: self.len - 1 }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        let mut link = &self.root;
+        while let Some(ref node) = *link {
+            match search_dir(value, &node.value) {
+                Some(Dir::Left) => link = &node.left,
+                Some(Dir::Right) => link = &node.right,
+                None => return true,
+            }
+        }
+
+        false
+    }
+
+    /// Whether the two trees are the same version, so that they're sure to
+    /// hold the same elements without comparing any of them.
+    pub fn ptr_eq(&self, other: &RedBlackTree<T>) -> bool {
+        match (&self.root, &other.root) {
+            (Some(root), Some(other)) => Arc::ptr_eq(root, other),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter { stack: Vec::new(), remaining: self.len };
+        iter.push_left_spine(&self.root);
+        iter
+    }
+
+    /// Iterates in ascending order over the elements that fall in `range`.
+    /// Finding the first one takes O(log n).
+    pub fn range<R>(&self, range: R) -> Range<'_, T> where R: RangeBounds<T> {
+        // the path down to the first element in range, keeping each node
+        // that's still to come
+        let mut stack = Vec::new();
+        let mut link = &self.root;
+        while let Some(ref node) = *link {
+            let in_range = match range.start_bound() {
+                Bound::Included(start) => *start <= node.value,
+                Bound::Excluded(start) => *start < node.value,
+                Bound::Unbounded => true,
+            };
+
+            if in_range {
+                stack.push(&**node);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+
+        Range { stack, end: range.end_bound().cloned() }
+    }
+}
+
+fn node<T>(color: Color, left: Link<T>, value: T, right: Link<T>) -> Link<T> {
+    Some(Arc::new(Node { color, left, value, right }))
+}
+
+// the node at `link`, if it's red
+fn red<T>(link: &Link<T>) -> Option<&Node<T>> {
+    link.as_ref().map(|node| &**node).filter(|node| node.color.is_red())
+}
+
+// the node at `link`, if there is one and it's black
+fn black<T>(link: &Link<T>) -> Option<&Node<T>> {
+    link.as_ref().map(|node| &**node).filter(|node| node.color.is_black())
+}
+
+fn recolor<T: Clone>(node: &Node<T>, color: Color) -> Link<T> {
+    Some(Arc::new(Node { color, left: node.left.clone(), value: node.value.clone(), right: node.right.clone() }))
+}
+
+fn blacken<T: Clone>(link: Link<T>) -> Link<T> {
+    match red(&link) {
+        Some(node) => recolor(node, Color::Black),
+        None => link,
+    }
+}
+
+// makes a black node out of the three, fixing up a red child that has a
+// red child of its own by rotating the three nodes involved into a red
+// node with two black children
+fn balance<T: Clone>(left: Link<T>, value: T, right: Link<T>) -> Link<T> {
+    if let (Some(l), Some(r)) = (red(&left), red(&right)) {
+        return node(Color::Red, recolor(l, Color::Black), value, recolor(r, Color::Black));
+    }
+
+    if let Some(l) = red(&left) {
+        if let Some(ll) = red(&l.left) {
+            return node(Color::Red,
+                        recolor(ll, Color::Black),
+                        l.value.clone(),
+                        node(Color::Black, l.right.clone(), value, right));
+        }
+        if let Some(lr) = red(&l.right) {
+            return node(Color::Red,
+                        node(Color::Black, l.left.clone(), l.value.clone(), lr.left.clone()),
+                        lr.value.clone(),
+                        node(Color::Black, lr.right.clone(), value, right));
+        }
+    }
+
+    if let Some(r) = red(&right) {
+        if let Some(rr) = red(&r.right) {
+            return node(Color::Red,
+                        node(Color::Black, left, value, r.left.clone()),
+                        r.value.clone(),
+                        recolor(rr, Color::Black));
+        }
+        if let Some(rl) = red(&r.left) {
+            return node(Color::Red,
+                        node(Color::Black, left, value, rl.left.clone()),
+                        rl.value.clone(),
+                        node(Color::Black, rl.right.clone(), r.value.clone(), r.right.clone()));
+        }
+    }
+
+    node(Color::Black, left, value, right)
+}
+
+// equal elements go to the right, like in `RedBlackTree`
+fn insert<T: PartialOrd + Clone>(link: &Link<T>, value: T) -> Link<T> {
+    let current = match *link {
+        Some(ref current) => current,
+        None => return node(Color::Red, None, value, None),
+    };
+
+    let dir = get_dir(value < current.value);
+    match (dir, current.color) {
+        (Dir::Left, Color::Black) => balance(insert(&current.left, value), current.value.clone(), current.right.clone()),
+        (Dir::Left, Color::Red) => node(Color::Red, insert(&current.left, value), current.value.clone(), current.right.clone()),
+        (Dir::Right, Color::Black) => balance(current.left.clone(), current.value.clone(), insert(&current.right, value)),
+        (Dir::Right, Color::Red) => node(Color::Red, current.left.clone(), current.value.clone(), insert(&current.right, value)),
+    }
+}
+
+// the subtree with `value` taken out of it, which has to be there. Taking
+// it out of a black subtree leaves that subtree one black node short, and
+// the parent makes up for it with `balance_left` or `balance_right`.
+fn remove<T: PartialOrd + Clone>(link: &Link<T>, value: &T) -> Link<T> {
+    let current = link.as_ref()?;
+    match search_dir(value, &current.value) {
+        None => fuse(&current.left, &current.right),
+        Some(Dir::Left) => {
+            let left = remove(&current.left, value);
+            if black(&current.left).is_some() {
+                balance_left(left, current.value.clone(), current.right.clone())
+            } else {
+                node(Color::Red, left, current.value.clone(), current.right.clone())
+            }
+        },
+        Some(Dir::Right) => {
+            let right = remove(&current.right, value);
+            if black(&current.right).is_some() {
+                balance_right(current.left.clone(), current.value.clone(), right)
+            } else {
+                node(Color::Red, current.left.clone(), current.value.clone(), right)
+            }
+        },
+    }
+}
+
+// puts the three back together when `left` has one black node fewer on
+// every path down than `right`
+fn balance_left<T: Clone>(left: Link<T>, value: T, right: Link<T>) -> Link<T> {
+    if let Some(l) = red(&left) {
+        return node(Color::Red, recolor(l, Color::Black), value, right);
+    }
+    if let Some(r) = black(&right) {
+        return balance(left, value, recolor(r, Color::Red));
+    }
+    if let Some(r) = red(&right) {
+        if let Some(rl) = black(&r.left) {
+            return node(Color::Red,
+                        node(Color::Black, left, value, rl.left.clone()),
+                        rl.value.clone(),
+                        balance(rl.right.clone(), r.value.clone(), redden(&r.right)));
+        }
+    }
+
+    // only reached if the heights weren't as promised
+    node(Color::Black, left, value, right)
+}
+
+fn balance_right<T: Clone>(left: Link<T>, value: T, right: Link<T>) -> Link<T> {
+    if let Some(r) = red(&right) {
+        return node(Color::Red, left, value, recolor(r, Color::Black));
+    }
+    if let Some(l) = black(&left) {
+        return balance(recolor(l, Color::Red), value, right);
+    }
+    if let Some(l) = red(&left) {
+        if let Some(lr) = black(&l.right) {
+            return node(Color::Red,
+                        balance(redden(&l.left), l.value.clone(), lr.left.clone()),
+                        lr.value.clone(),
+                        node(Color::Black, lr.right.clone(), value, right));
+        }
+    }
+
+    node(Color::Black, left, value, right)
+}
+
+// takes a black node off the paths through a black subtree
+fn redden<T: Clone>(link: &Link<T>) -> Link<T> {
+    match black(link) {
+        Some(node) => recolor(node, Color::Red),
+        None => link.clone(),
+    }
+}
+
+// joins the two children of a removed node, every element of `left` being
+// no greater than every element of `right`. The two have the same black
+// height, and the result has one black node fewer if the removed node was
+// black.
+fn fuse<T: Clone>(left: &Link<T>, right: &Link<T>) -> Link<T> {
+    let (l, r) = match (left, right) {
+        (Some(l), Some(r)) => (l, r),
+        (None, _) => return right.clone(),
+        (_, None) => return left.clone(),
+    };
+
+    match (l.color, r.color) {
+        (Color::Red, Color::Red) => {
+            let middle = fuse(&l.right, &r.left);
+            match red(&middle) {
+                Some(m) => node(Color::Red,
+                                node(Color::Red, l.left.clone(), l.value.clone(), m.left.clone()),
+                                m.value.clone(),
+                                node(Color::Red, m.right.clone(), r.value.clone(), r.right.clone())),
+                None => node(Color::Red,
+                             l.left.clone(),
+                             l.value.clone(),
+                             node(Color::Red, middle, r.value.clone(), r.right.clone())),
+            }
+        },
+        (Color::Black, Color::Black) => {
+            let middle = fuse(&l.right, &r.left);
+            match red(&middle) {
+                Some(m) => node(Color::Red,
+                                node(Color::Black, l.left.clone(), l.value.clone(), m.left.clone()),
+                                m.value.clone(),
+                                node(Color::Black, m.right.clone(), r.value.clone(), r.right.clone())),
+                None => balance_left(l.left.clone(),
+                                     l.value.clone(),
+                                     node(Color::Black, middle, r.value.clone(), r.right.clone())),
+            }
+        },
+        (Color::Black, Color::Red) => node(Color::Red, fuse(left, &r.left), r.value.clone(), r.right.clone()),
+        (Color::Red, Color::Black) => node(Color::Red, l.left.clone(), l.value.clone(), fuse(&l.right, right)),
+    }
+}
+
+impl<T> Clone for RedBlackTree<T> where T: PartialOrd + Clone {
+    fn clone(&self) -> RedBlackTree<T> {
+        RedBlackTree { root: self.root.clone(), len: self.len }
+    }
+}
+
+impl<T> Default for RedBlackTree<T> where T: PartialOrd + Clone {
+    fn default() -> RedBlackTree<T> {
+        RedBlackTree::new()
+    }
+}
+
+impl<T> Debug for RedBlackTree<T> where T: PartialOrd + Clone + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> PartialEq for RedBlackTree<T> where T: PartialOrd + Clone {
+    fn eq(&self, other: &RedBlackTree<T>) -> bool {
+        self.len == other.len && (self.ptr_eq(other) || self.iter().eq(other.iter()))
+    }
+}
+
+impl<T> FromIterator<T> for RedBlackTree<T> where T: PartialOrd + Clone {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> RedBlackTree<T> {
+        iter.into_iter().fold(RedBlackTree::new(), |tree, value| tree.insert(value))
+    }
+}
+
+impl<'a, T> IntoIterator for &'a RedBlackTree<T> where T: PartialOrd + Clone {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Iterates over a persistent `RedBlackTree` in ascending order.
+pub struct Iter<'a, T> where T: 'a {
+    // the nodes whose elements are still to come, each after the ones
+    // above it
+    stack: Vec<&'a Node<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left_spine(&mut self, link: &'a Link<T>) {
+        let mut link = link;
+        while let Some(ref node) = *link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(&node.right);
+        self.remaining -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+/// Iterates over the elements of a persistent `RedBlackTree` in a range,
+/// in ascending order.
+pub struct Range<'a, T> where T: 'a {
+    stack: Vec<&'a Node<T>>,
+    end: Bound<T>,
+}
+
+impl<'a, T> Iterator for Range<'a, T> where T: PartialOrd {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        let in_range = match self.end {
+            Bound::Included(ref end) => node.value <= *end,
+            Bound::Excluded(ref end) => node.value < *end,
+            Bound::Unbounded => true,
+        };
+        if !in_range {
+            self.stack.clear();
+            return None;
+        }
+
+        let mut link = &node.right;
+        while let Some(ref node) = *link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+
+        Some(&node.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use persistent::RedBlackTree;
+    use std::ops::Bound;
+    use std::sync::Arc;
+    use persistent::Link;
+
+    // the black height of the subtree, after checking it's a valid
+    // red-black tree with its elements in order
+    pub fn check<T: PartialOrd>(link: &Link<T>, low: Option<&T>, high: Option<&T>) -> usize {
+        let node = match *link {
+            Some(ref node) => node,
+            None => return 1,
+        };
+
+        assert!(low.is_none_or(|low| *low <= node.value) && high.is_none_or(|high| node.value <= *high));
+        if node.color.is_red() {
+            assert!(node.left.as_ref().is_none_or(|left| left.color.is_black()));
+            assert!(node.right.as_ref().is_none_or(|right| right.color.is_black()));
+        }
+
+        let left = check(&node.left, low, Some(&node.value));
+        let right = check(&node.right, Some(&node.value), high);
+        assert_eq!(left, right);
+        left + if node.color.is_black() { 1 } else { 0 }
+    }
+
+    mod persistent_tree {
+        use super::*;
+
+        #[test]
+        fn stays_valid_and_in_order_through_inserts_and_removals() {
+            let mut tree = RedBlackTree::new();
+            let mut expected = vec![];
+            for value in 0..300u32 {
+                let value = value * 37 % 101;
+                tree = tree.insert(value);
+                expected.insert(expected.partition_point(|&probe| probe <= value), value);
+                check(&tree.root, None, None);
+            }
+            for value in 0..300u32 {
+                let value = value * 13 % 101;
+                tree = tree.remove(&value);
+                if let Ok(position) = expected.binary_search(&value) {
+                    expected.remove(position);
+                }
+                check(&tree.root, None, None);
+                expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(expected.clone()));
+            }
+
+            expect!(tree.len()).to(be_equal_to(expected.len()));
+            expect!(tree.root.as_ref().is_none_or(|root| root.color.is_black())).to(be_true());
+        }
+
+        #[test]
+        fn leaves_the_old_versions_as_they_were() {
+            let versions: Vec<RedBlackTree<u32>> = (0..20).scan(RedBlackTree::new(), |tree, value| {
+                *tree = tree.insert(value);
+                Some(tree.clone())
+            }).collect();
+
+            let removed = versions[19].remove(&7);
+            expect!(removed.contains(&7)).to(be_false());
+            for (index, version) in versions.iter().enumerate() {
+                expect!(version.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..index as u32 + 1).collect::<Vec<_>>()));
+            }
+
+            // taking out something that isn't there shares the whole tree
+            expect!(versions[3].remove(&10).ptr_eq(&versions[3])).to(be_true());
+            expect!(removed == versions[19]).to(be_false());
+        }
+
+        #[test]
+        fn iterates_over_a_range_of_its_elements() {
+            let tree: RedBlackTree<_> = (0..50).map(|value| value / 2).collect();
+            expect!(tree.range(3..6).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![3, 3, 4, 4, 5, 5]));
+            expect!(tree.range(20..=20).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![20, 20]));
+            expect!(tree.range((Bound::Excluded(22), Bound::Unbounded)).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![23, 23, 24, 24]));
+            expect!(tree.range(..2).count()).to(be_equal_to(4));
+            expect!(tree.range(30..).next()).to(be_none());
+        }
+
+        #[test]
+        fn shares_the_subtrees_a_change_doesnt_touch() {
+            let tree: RedBlackTree<_> = (0..64).collect();
+            let changed = tree.insert(100);
+            let (old, new) = (tree.root.as_ref().unwrap(), changed.root.as_ref().unwrap());
+            expect!(Arc::ptr_eq(old, new)).to(be_false());
+
+            // 100 goes in at the far right, so the whole left side is shared
+            expect!(Arc::ptr_eq(old.left.as_ref().unwrap(), new.left.as_ref().unwrap())).to(be_true());
+            expect!(Arc::strong_count(old.left.as_ref().unwrap())).to(be_equal_to(2));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+// postcard encodes a tree as a varint length followed by its elements, and
+// can write straight into a fixed buffer, so a tree can be saved to a flash
+// page without allocating anything. Reading one back does allocate: the
+// elements are decoded into a Vec first, as the serde impls do for every
+// format, so that a sorted one can be built in O(n), and then the tree
+// allocates its nodes.
+
+use postcard;
+use serde::{Deserialize, Serialize};
+
+use RedBlackTree;
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Encodes the tree into `buf`, returning the part of `buf` that was
+    /// written to. Fails if `buf` is too small to hold the whole tree.
+    pub fn write_postcard<'a>(&self, buf: &'a mut [u8]) -> postcard::Result<&'a mut [u8]> where T: Serialize {
+        postcard::to_slice(self, buf)
+    }
+
+    /// Decodes a tree written by `write_postcard` from the start of
+    /// `bytes`, returning it along with whatever input follows it. The
+    /// elements are collected into a Vec before the tree is built.
+    pub fn read_postcard<'de>(bytes: &'de [u8]) -> postcard::Result<(RedBlackTree<T>, &'de [u8])> where T: Deserialize<'de> {
+        postcard::take_from_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use {Change, ChangeSet, RedBlackTree};
+
+    mod postcard {
+        use super::*;
+
+        #[test]
+        fn round_trips_through_a_fixed_buffer() {
+            let tree: RedBlackTree<u16> = vec![300, 1, 20, 4000].into_iter().collect();
+            let mut buf = [0; 32];
+            let len = tree.write_postcard(&mut buf).unwrap().len();
+            // a length byte, then the elements as varints
+            expect!(len).to(be_equal_to(1 + 1 + 1 + 2 + 2));
+
+            let (copy, rest) = RedBlackTree::<u16>::read_postcard(&buf[..len + 2]).unwrap();
+            expect!(copy == tree).to(be_true());
+            expect!(rest.len()).to(be_equal_to(2));
+        }
+
+        #[test]
+        fn fails_cleanly_when_the_buffer_is_too_small() {
+            let tree: RedBlackTree<u32> = (0..100).collect();
+            let mut buf = [0; 16];
+            expect!(tree.write_postcard(&mut buf).is_err()).to(be_true());
+
+            let truncated = [5, 1, 2];
+            expect!(RedBlackTree::<u32>::read_postcard(&truncated).is_err()).to(be_true());
+        }
+
+        #[test]
+        fn encodes_the_kind_of_each_change_by_its_index() {
+            let changes: ChangeSet<u8> = vec![Change::Insert(7), Change::Remove(9)].into_iter().collect();
+            let mut buf = [0; 8];
+            let bytes = ::postcard::to_slice(&changes, &mut buf).unwrap();
+            expect!(bytes.to_vec()).to(be_equal_to(vec![2, 0, 7, 1, 9]));
+            expect!(::postcard::from_bytes::<ChangeSet<u8>>(bytes).unwrap()).to(be_equal_to(changes));
+        }
+    }
+}
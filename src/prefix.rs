@@ -0,0 +1,87 @@
+// The elements of a tree of strings that start with a given prefix, for
+// autocompletion. Strings starting with a prefix sort together, straight
+// after the prefix itself, so this walks down to the first string at or
+// after the prefix and goes on in order until one doesn't start with it.
+// That stops exactly where a range up to the prefix's successor would,
+// without having to work the successor out, which is fiddly once the last
+// character is `char::MAX` or the prefix is empty.
+//
+// The elements only have to borrow as `str`, which promises that they sort
+// the same way their strings do, so `String`, `&str`, `Box<str>` and the
+// like all work.
+
+use std::borrow::Borrow;
+
+use allocator::{Allocator, Global};
+use node::Node;
+use RedBlackTree;
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd + Borrow<str>, A: Allocator + Clone {
+    /// The elements that start with `prefix`, in order.
+    pub fn range_prefix<'a>(&'a self, prefix: &'a str) -> Prefixed<'a, T, A> {
+        let mut first = None;
+        let mut link = &self.root;
+        while let Some(node) = link.as_ref() {
+            if node.value().borrow() >= prefix {
+                first = Some(node);
+                link = node.left();
+            } else {
+                link = node.right();
+            }
+        }
+
+        Prefixed { root: self.root.as_ref(), current: first, prefix }
+    }
+}
+
+/// Iterates over the elements of a tree that start with a prefix. Made by
+/// `RedBlackTree::range_prefix`.
+pub struct Prefixed<'a, T, A = Global> where T: PartialOrd + 'a, A: Allocator + Clone + 'a {
+    root: Option<&'a Node<T, A>>,
+    current: Option<&'a Node<T, A>>,
+    prefix: &'a str,
+}
+
+impl<'a, T, A> Iterator for Prefixed<'a, T, A> where T: PartialOrd + Borrow<str>, A: Allocator + Clone {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.current?;
+        if !node.value().borrow().starts_with(self.prefix) {
+            self.current = None;
+            return None;
+        }
+
+        self.current = self.root.and_then(|root| node.successor(root));
+        Some(node.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod range_prefix {
+        use super::*;
+
+        #[test]
+        fn finds_the_strings_starting_with_the_prefix() {
+            let tree: RedBlackTree<_> = vec!["car", "ca", "cat", "cb", "c", "cart", "dog", "b"].into_iter().collect();
+            expect!(tree.range_prefix("ca").cloned().collect::<Vec<_>>()).to(be_equal_to(vec!["ca", "car", "cart", "cat"]));
+            expect!(tree.range_prefix("car").cloned().collect::<Vec<_>>()).to(be_equal_to(vec!["car", "cart"]));
+            expect!(tree.range_prefix("d").cloned().collect::<Vec<_>>()).to(be_equal_to(vec!["dog"]));
+            expect!(tree.range_prefix("cu").next()).to(be_none());
+            expect!(tree.range_prefix("z").next()).to(be_none());
+            expect!(tree.range_prefix("").count()).to(be_equal_to(8));
+        }
+
+        #[test]
+        fn handles_prefixes_without_an_easy_successor() {
+            let last = format!("a{}", char::MAX);
+            let tree: RedBlackTree<String> = vec![format!("{}z", last), last.clone(), "b".to_string(), "a".to_string()].into_iter().collect();
+            expect!(tree.range_prefix(&last).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![last.clone(), format!("{}z", last)]));
+            expect!(tree.range_prefix("é").next()).to(be_none());
+        }
+    }
+}
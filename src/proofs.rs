@@ -0,0 +1,65 @@
+// Kani proofs that inserting and removing keep a tree valid, over every
+// tree up to a few elements built out of every possible sequence of
+// inserts. Run them with `cargo kani`. Each case of rebalancing after a
+// removal is marked with `delete_case!`, and Kani reports whether the
+// removal proof reaches each one, so a proof that passes without going
+// through all of them stands out.
+
+use RedBlackTree;
+
+// enough elements for every removal case to come up, since the red
+// sibling case needs a tree three levels deep
+const ELEMENTS: usize = 6;
+
+fn any_tree() -> RedBlackTree<u8> {
+    let mut tree = RedBlackTree::new();
+    for _ in 0..ELEMENTS {
+        // a few values is enough to get every shape, plus duplicates
+        let value: u8 = kani::any_where(|value: &u8| *value < 8);
+        tree.insert(value);
+    }
+
+    tree
+}
+
+#[kani::proof]
+#[kani::unwind(8)]
+fn insert_keeps_the_tree_valid() {
+    let mut tree = any_tree();
+    let value: u8 = kani::any();
+    tree.insert(value);
+
+    assert!(tree.check_invariants().is_ok());
+    assert!(tree.contains(&value));
+    assert!(tree.count == ELEMENTS + 1);
+}
+
+#[kani::proof]
+#[kani::unwind(8)]
+fn remove_keeps_the_tree_valid() {
+    let mut tree = any_tree();
+    let value: u8 = kani::any_where(|value: &u8| *value < 8);
+    let was_there = tree.contains(&value);
+
+    let removed = tree.remove(&value);
+    assert!(tree.check_invariants().is_ok());
+    assert!(removed == if was_there { Some(value) } else { None });
+    assert!(tree.count == if was_there { ELEMENTS - 1 } else { ELEMENTS });
+}
+
+#[kani::proof]
+#[kani::unwind(8)]
+fn removing_everything_leaves_an_empty_tree() {
+    let mut tree = any_tree();
+    for _ in 0..ELEMENTS {
+        let value = match tree.iter().next() {
+            Some(&value) => value,
+            None => break,
+        };
+
+        assert!(tree.remove(&value) == Some(value));
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    assert!(tree.root.is_none());
+}
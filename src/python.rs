@@ -0,0 +1,247 @@
+// Python bindings. The exported class holds arbitrary Python objects and
+// orders them with Python's own comparisons, so any values that can be
+// compared with each other can go in it. A comparison that raises can't
+// hand the exception back through `PartialOrd`, so it's kept aside until
+// the tree is done, and then raised as a TypeError, with the original as
+// its cause if it wasn't one already. An object that can't be compared
+// with what's already there is never added.
+//
+// To build an importable extension module, also enable pyo3's
+// `extension-module` feature.
+
+// the wrappers pyo3 generates around the methods trip this one
+#![allow(clippy::useless_conversion)]
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PySlice};
+
+use RedBlackTree;
+
+struct PyValue(PyObject);
+
+thread_local! {
+    // the first exception a comparison raised since `comparing` started
+    static COMPARE_ERROR: Cell<Option<PyErr>> = const { Cell::new(None) };
+}
+
+// runs `f`, and fails with the first exception that a comparison in it
+// raised, if there was one
+fn comparing<R, F>(py: Python, f: F) -> PyResult<R> where F: FnOnce() -> R {
+    COMPARE_ERROR.with(|error| error.set(None));
+    let result = f();
+    match COMPARE_ERROR.with(Cell::take) {
+        Some(error) if error.is_instance_of::<PyTypeError>(py) => Err(error),
+        Some(error) => {
+            let type_error = PyTypeError::new_err("comparing the values raised an exception");
+            type_error.set_cause(py, Some(error));
+            Err(type_error)
+        },
+        None => Ok(result),
+    }
+}
+
+impl PartialEq for PyValue {
+    fn eq(&self, other: &PyValue) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for PyValue {
+    fn partial_cmp(&self, other: &PyValue) -> Option<Ordering> {
+        Python::with_gil(|py| match self.0.bind(py).compare(other.0.bind(py)) {
+            Ok(ordering) => Some(ordering),
+            Err(error) => {
+                COMPARE_ERROR.with(|first| {
+                    let first_error = first.take().unwrap_or(error);
+                    first.set(Some(first_error));
+                });
+                None
+            },
+        })
+    }
+}
+
+#[pyclass(name = "RedBlackTree")]
+pub struct PyRedBlackTree {
+    tree: RedBlackTree<PyValue>,
+}
+
+impl PyRedBlackTree {
+    fn list(&self, py: Python) -> Py<PyList> {
+        PyList::new_bound(py, self.tree.iter().map(|value| value.0.clone_ref(py))).unbind()
+    }
+
+    // takes out one copy of `value`, and says whether there was one. It's
+    // looked for first, so a comparison that raises leaves the tree alone.
+    fn take(&mut self, py: Python, value: PyObject) -> PyResult<bool> {
+        let value = PyValue(value);
+        if !comparing(py, || self.tree.contains(&value))? {
+            return Ok(false);
+        }
+
+        comparing(py, || self.tree.remove(&value).is_some())
+    }
+}
+
+#[pymethods]
+impl PyRedBlackTree {
+    #[new]
+    #[pyo3(signature = (values=None))]
+    fn new(py: Python, values: Option<&Bound<PyAny>>) -> PyResult<PyRedBlackTree> {
+        let mut tree = PyRedBlackTree { tree: RedBlackTree::new() };
+        if let Some(values) = values {
+            for value in values.iter()? {
+                tree.add(py, value?.unbind())?;
+            }
+        }
+
+        Ok(tree)
+    }
+
+    /// Adds `value`, raising TypeError, and leaving the tree alone, if it
+    /// can't be compared with the values it would go between.
+    fn add(&mut self, py: Python, value: PyObject) -> PyResult<()> {
+        match comparing(py, || self.tree.checked_insert(PyValue(value)))? {
+            Ok(()) => Ok(()),
+            Err(_) => Err(PyTypeError::new_err("the value can't be compared with the ones in the tree")),
+        }
+    }
+
+    /// Removes one copy of `value`, raising KeyError if there isn't one.
+    fn remove(&mut self, py: Python, value: PyObject) -> PyResult<()> {
+        if self.take(py, value)? {
+            Ok(())
+        } else {
+            Err(PyKeyError::new_err("value not in tree"))
+        }
+    }
+
+    /// Removes one copy of `value`, if there is one.
+    fn discard(&mut self, py: Python, value: PyObject) -> PyResult<()> {
+        self.take(py, value).map(|_| ())
+    }
+
+    fn __contains__(&self, py: Python, value: PyObject) -> PyResult<bool> {
+        comparing(py, || self.tree.contains(&PyValue(value)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.tree.count
+    }
+
+    // iterates over a snapshot, so changing the tree while iterating over
+    // it is safe, if not very useful
+    fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+        Ok(self.list(py).bind(py).as_any().iter()?.into_any().unbind())
+    }
+
+    /// Indexes the values by rank, with negative indices counting from the
+    /// largest. Slices return a list, and only look up the values in them.
+    fn __getitem__(&self, py: Python, index: &Bound<PyAny>) -> PyResult<PyObject> {
+        if let Ok(slice) = index.downcast::<PySlice>() {
+            // Python works out where the slice starts and goes, the same as
+            // it would for a list
+            let indices = slice.indices(self.tree.count as isize)?;
+            let values = (0..indices.slicelength as isize)
+                .filter_map(|i| self.tree.select((indices.start + i * indices.step) as usize))
+                .map(|value| value.0.clone_ref(py))
+                .collect::<Vec<_>>();
+            return Ok(PyList::new_bound(py, values).into_any().unbind());
+        }
+
+        let index: isize = index.extract()?;
+        let len = self.tree.count as isize;
+        let rank = if index < 0 { index + len } else { index };
+        if rank < 0 || rank >= len {
+            return Err(PyIndexError::new_err("tree index out of range"));
+        }
+
+        let entry = self.tree.select(rank as usize).ok_or_else(|| PyIndexError::new_err("tree index out of range"))?;
+        Ok(entry.0.clone_ref(py))
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("RedBlackTree({})", self.list(py).bind(py).repr()?))
+    }
+}
+
+#[pymodule]
+fn red_black_tree(module: &Bound<PyModule>) -> PyResult<()> {
+    module.add_class::<PyRedBlackTree>()
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::prelude::*;
+    use pyo3::types::PyDict;
+    use super::PyRedBlackTree;
+
+    mod python {
+        use super::*;
+
+        #[test]
+        fn behaves_like_an_ordered_python_collection() {
+            pyo3::prepare_freethreaded_python();
+            Python::with_gil(|py| {
+                let globals = PyDict::new_bound(py);
+                globals.set_item("RedBlackTree", py.get_type_bound::<PyRedBlackTree>()).unwrap();
+                py.run_bound(r#"
+tree = RedBlackTree([5, 1, 4])
+tree.add(3)
+assert len(tree) == 4
+assert 4 in tree and 2 not in tree
+assert list(tree) == [1, 3, 4, 5]
+assert tree[0] == 1 and tree[-1] == 5
+assert tree[1:3] == [3, 4] and tree[::-2] == [5, 3]
+tree.remove(4)
+tree.discard(10)
+assert list(tree) == [1, 3, 5]
+assert repr(tree) == "RedBlackTree([1, 3, 5])"
+
+try:
+    tree[3]
+    assert False
+except IndexError:
+    pass
+
+try:
+    tree.remove(10)
+    assert False
+except KeyError:
+    pass
+
+strings = RedBlackTree(["b", "c", "a"])
+assert list(strings) == ["a", "b", "c"]
+
+numbers = RedBlackTree(range(10))
+assert numbers[2:8:3] == [2, 5] and numbers[8:2:-2] == [8, 6, 4]
+assert numbers[-3:] == [7, 8, 9] and numbers[20:] == []
+
+try:
+    numbers.add("a")
+    assert False
+except TypeError:
+    pass
+assert len(numbers) == 10
+
+class Raises:
+    def __eq__(self, other):
+        raise ValueError("no")
+    __lt__ = __gt__ = __eq__
+
+for attempt in [lambda: numbers.add(Raises()), lambda: Raises() in numbers, lambda: numbers.remove(Raises())]:
+    try:
+        attempt()
+        assert False
+    except TypeError as error:
+        assert isinstance(error.__cause__, ValueError)
+assert list(numbers) == list(range(10))
+"#, Some(&globals), None).unwrap();
+            });
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// A priority queue on top of the tree, for code that would use
+// `BinaryHeap` but also needs to take out or reprioritize an element that
+// isn't at the top. The tree keeps everything in order, so both ends are
+// O(log n) to pop, any element is O(log n) to find and take out, and the
+// queue can be walked in priority order without draining it.
+//
+// Like `BinaryHeap`, the elements are their own priorities, and `pop`
+// takes the greatest. `pop_min` takes from the other end, so the queue
+// serves either order, or both at once.
+
+use std::iter::FromIterator;
+use std::mem;
+
+use node::Dir;
+use {RedBlackIterator, RedBlackTree};
+
+/// A double-ended priority queue that can also remove or reprioritize any
+/// of its elements.
+#[derive(Clone)]
+pub struct RbPriorityQueue<T> where T: PartialOrd {
+    tree: RedBlackTree<T>,
+}
+
+impl<T> RbPriorityQueue<T> where T: PartialOrd {
+    pub const fn new() -> RbPriorityQueue<T> {
+        RbPriorityQueue { tree: RedBlackTree::new() }
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.tree.insert(value);
+    }
+
+    /// Takes out the greatest element. Of several equal ones it's the
+    /// last pushed.
+    pub fn pop(&mut self) -> Option<T> {
+        self.tree.remove_where(|node| if node.right().is_some() { Some(Dir::Right) } else { None })
+    }
+
+    /// Takes out the smallest element. Of several equal ones it's the
+    /// first pushed.
+    pub fn pop_min(&mut self) -> Option<T> {
+        self.tree.remove_where(|node| if node.left().is_some() { Some(Dir::Left) } else { None })
+    }
+
+    /// The element `pop` would take out.
+    pub fn peek(&self) -> Option<&T> {
+        self.tree.root.as_ref().map(|root| root.rightmost().value())
+    }
+
+    /// The element `pop_min` would take out.
+    pub fn peek_min(&self) -> Option<&T> {
+        self.tree.root.as_ref().map(|root| root.leftmost().value())
+    }
+
+    /// Takes out an element equal to `value`, wherever it is in the queue.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        self.tree.remove(value)
+    }
+
+    /// Takes out an element equal to `value` and pushes `priority` in its
+    /// place, handing back the element taken out. If there's no such
+    /// element the queue is left alone, and `priority` is handed back
+    /// instead.
+    pub fn change_priority(&mut self, value: &T, priority: T) -> Result<T, T> {
+        match self.tree.remove(value) {
+            Some(value) => {
+                self.tree.insert(priority);
+                Ok(value)
+            },
+            None => Err(priority),
+        }
+    }
+
+    /// Moves all of `other`'s elements into this queue, leaving `other`
+    /// empty. The smaller queue's elements are pushed into the bigger one.
+    pub fn append(&mut self, other: &mut RbPriorityQueue<T>) {
+        if other.len() > self.len() {
+            mem::swap(self, other);
+        }
+
+        let other = mem::take(&mut other.tree);
+        self.tree.extend(other);
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.count == 0
+    }
+
+    /// The elements from the smallest up, leaving them in the queue.
+    pub fn iter(&self) -> RedBlackIterator<'_, T> {
+        self.tree.iter()
+    }
+
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.tree.into_iter().collect()
+    }
+}
+
+impl<T> Default for RbPriorityQueue<T> where T: PartialOrd {
+    fn default() -> RbPriorityQueue<T> {
+        RbPriorityQueue::new()
+    }
+}
+
+impl<T> FromIterator<T> for RbPriorityQueue<T> where T: PartialOrd {
+    fn from_iter<I>(iter: I) -> RbPriorityQueue<T> where I: IntoIterator<Item = T> {
+        RbPriorityQueue { tree: iter.into_iter().collect() }
+    }
+}
+
+impl<T> Extend<T> for RbPriorityQueue<T> where T: PartialOrd {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item = T> {
+        self.tree.extend(iter);
+    }
+}
+
+impl<'a, T> IntoIterator for &'a RbPriorityQueue<T> where T: PartialOrd {
+    type Item = &'a T;
+    type IntoIter = RedBlackIterator<'a, T>;
+
+    fn into_iter(self) -> RedBlackIterator<'a, T> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RbPriorityQueue;
+
+    mod priority_queue {
+        use super::*;
+
+        #[test]
+        fn pops_from_either_end() {
+            let mut queue: RbPriorityQueue<_> = vec![5, 1, 9, 3, 7].into_iter().collect();
+            expect!(queue.peek()).to(be_some().value(&9));
+            expect!(queue.peek_min()).to(be_some().value(&1));
+            expect!(queue.pop()).to(be_some().value(9));
+            expect!(queue.pop_min()).to(be_some().value(1));
+            expect!(queue.pop()).to(be_some().value(7));
+            expect!(queue.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![3, 5]));
+
+            queue.pop();
+            queue.pop();
+            expect!(queue.pop()).to(be_none());
+            expect!(queue.peek()).to(be_none());
+            expect!(queue.is_empty()).to(be_true());
+        }
+
+        #[test]
+        fn removes_and_reprioritizes_any_element() {
+            let mut queue: RbPriorityQueue<_> = vec![(2, 'a'), (4, 'b'), (6, 'c')].into_iter().collect();
+            expect!(queue.change_priority(&(2, 'a'), (8, 'a'))).to(be_ok().value((2, 'a')));
+            expect!(queue.peek()).to(be_some().value(&(8, 'a')));
+            expect!(queue.change_priority(&(3, 'x'), (1, 'x'))).to(be_err().value((1, 'x')));
+            expect!(queue.len()).to(be_equal_to(3));
+
+            expect!(queue.remove(&(4, 'b'))).to(be_some().value((4, 'b')));
+            expect!(queue.into_sorted_vec()).to(be_equal_to(vec![(6, 'c'), (8, 'a')]));
+        }
+
+        #[test]
+        fn appends_another_queue() {
+            let mut small: RbPriorityQueue<_> = vec![4, 2].into_iter().collect();
+            let mut big: RbPriorityQueue<_> = vec![1, 3, 5].into_iter().collect();
+            small.append(&mut big);
+            expect!(big.is_empty()).to(be_true());
+            expect!(small.into_sorted_vec()).to(be_equal_to(vec![1, 2, 3, 4, 5]));
+        }
+    }
+}
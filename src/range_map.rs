@@ -0,0 +1,404 @@
+// A map from half-open ranges of keys to values, for memory maps, address
+// tables and the like, where whole stretches of keys share a value. The
+// ranges in the map never overlap, and two that touch never have equal
+// values, so every stretch of keys with the same value is a single range.
+// Inserting a range cuts back or splits whatever it overlaps, and merges
+// with neighbours that end up touching it with an equal value. Removing a
+// range cuts back or splits what it overlaps the same way.
+//
+// The ranges go in the tree ordered by where they start. They don't
+// overlap, so they're ordered by where they end as well, and the first
+// range that could overlap a key is the first one ending after it. Finding
+// it is a walk down the tree, so a lookup is O(log n), and changing k
+// ranges is O(k log n).
+
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+use std::ops::Range;
+
+use node::{Dir, Node};
+use {RedBlackIterator, RedBlackTree};
+
+// a range in the tree, which orders the ranges by their starts alone
+#[derive(Clone)]
+struct Span<K, V> {
+    range: Range<K>,
+    value: V,
+}
+
+impl<K, V> PartialEq for Span<K, V> where K: PartialEq {
+    fn eq(&self, other: &Span<K, V>) -> bool {
+        self.range.start == other.range.start
+    }
+}
+
+impl<K, V> PartialOrd for Span<K, V> where K: PartialOrd {
+    fn partial_cmp(&self, other: &Span<K, V>) -> Option<Ordering> {
+        self.range.start.partial_cmp(&other.range.start)
+    }
+}
+
+/// A map from non-overlapping ranges of keys to values, which merges
+/// touching ranges whose values are equal.
+#[derive(Clone)]
+pub struct RangeMap<K, V> where K: Ord {
+    tree: RedBlackTree<Span<K, V>>,
+}
+
+impl<K, V> RangeMap<K, V> where K: Ord {
+    pub const fn new() -> RangeMap<K, V> {
+        RangeMap { tree: RedBlackTree::new() }
+    }
+
+    /// The value of the range `key` falls in, if any.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    /// The range `key` falls in and its value, if any.
+    pub fn get_key_value(&self, key: &K) -> Option<(&Range<K>, &V)> {
+        let span = self.first_ending_after(key)?.value();
+        if span.range.start <= *key {
+            Some((&span.range, &span.value))
+        } else {
+            None
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// The number of ranges, where touching ranges with equal values count
+    /// as one.
+    pub fn len(&self) -> usize {
+        self.tree.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.count == 0
+    }
+
+    /// The ranges and their values, in order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { spans: self.tree.iter() }
+    }
+
+    /// The ranges that overlap `range`, in order, whole, along with their
+    /// values.
+    pub fn overlapping(&self, range: Range<K>) -> Overlapping<'_, K, V> {
+        let current = if range.start < range.end { self.first_ending_after(&range.start) } else { None };
+        Overlapping { root: self.tree.root.as_ref(), current, end: range.end }
+    }
+
+    // the node of the first range that ends after `key`, which contains
+    // `key` if any range does
+    fn first_ending_after(&self, key: &K) -> Option<&Node<Span<K, V>>> {
+        let mut found = None;
+        let mut link = &self.tree.root;
+        while let Some(node) = link.as_ref() {
+            if node.value().range.end > *key {
+                found = Some(node);
+                link = node.left();
+            } else {
+                link = node.right();
+            }
+        }
+
+        found
+    }
+
+    // takes out the range that starts at `start`
+    fn take(&mut self, start: &K) -> Option<Span<K, V>> {
+        self.tree.remove_where(|node| match start.cmp(&node.value().range.start) {
+            Ordering::Less => Some(Dir::Left),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(Dir::Right),
+        })
+    }
+
+    // the starts of the ranges that `keep` picks out of the ones that
+    // overlap or touch `range`
+    fn starts_near<F>(&self, range: &Range<K>, keep: F) -> Vec<K> where K: Clone, F: Fn(&Span<K, V>) -> bool {
+        let mut starts = vec![];
+        let mut link = &self.tree.root;
+        let mut current = None;
+        while let Some(node) = link.as_ref() {
+            if node.value().range.end >= range.start {
+                current = Some(node);
+                link = node.left();
+            } else {
+                link = node.right();
+            }
+        }
+
+        while let (Some(node), Some(root)) = (current, self.tree.root.as_ref()) {
+            if node.value().range.start > range.end {
+                break;
+            }
+
+            if keep(node.value()) {
+                starts.push(node.value().range.start.clone());
+            }
+            current = node.successor(root);
+        }
+
+        starts
+    }
+}
+
+impl<K, V> RangeMap<K, V> where K: Ord + Clone, V: PartialEq + Clone {
+    /// Maps every key in `range` to `value`, cutting back or splitting the
+    /// ranges it overlaps, and merging with the ones it touches that have
+    /// an equal value. An empty range changes nothing.
+    pub fn insert(&mut self, range: Range<K>, value: V) {
+        if range.start >= range.end {
+            return;
+        }
+
+        let starts = self.starts_near(&range, |span| {
+            let overlaps = span.range.start < range.end && span.range.end > range.start;
+            overlaps || span.value == value
+        });
+
+        let mut merged = range.clone();
+        for start in starts {
+            let span = match self.take(&start) {
+                Some(span) => span,
+                None => continue,
+            };
+
+            if span.value == value {
+                if span.range.start < merged.start {
+                    merged.start = span.range.start;
+                }
+                if span.range.end > merged.end {
+                    merged.end = span.range.end;
+                }
+            } else {
+                self.put_back_outside(span, &range);
+            }
+        }
+
+        self.tree.insert(Span { range: merged, value });
+    }
+
+    /// Unmaps every key in `range`, cutting back or splitting the ranges it
+    /// overlaps.
+    pub fn remove(&mut self, range: Range<K>) {
+        if range.start >= range.end {
+            return;
+        }
+
+        let starts = self.starts_near(&range, |span| span.range.start < range.end && span.range.end > range.start);
+        for start in starts {
+            if let Some(span) = self.take(&start) {
+                self.put_back_outside(span, &range);
+            }
+        }
+    }
+
+    // puts back the parts of `span` on either side of `range`
+    fn put_back_outside(&mut self, span: Span<K, V>, range: &Range<K>) {
+        if span.range.start < range.start {
+            let left = span.range.start.clone()..range.start.clone();
+            self.tree.insert(Span { range: left, value: span.value.clone() });
+        }
+        if span.range.end > range.end {
+            let right = range.end.clone()..span.range.end;
+            self.tree.insert(Span { range: right, value: span.value });
+        }
+    }
+}
+
+impl<K, V> Default for RangeMap<K, V> where K: Ord {
+    fn default() -> RangeMap<K, V> {
+        RangeMap::new()
+    }
+}
+
+impl<K, V> Debug for RangeMap<K, V> where K: Ord + Debug, V: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> PartialEq for RangeMap<K, V> where K: Ord, V: PartialEq {
+    fn eq(&self, other: &RangeMap<K, V>) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<K, V> Eq for RangeMap<K, V> where K: Ord, V: Eq {}
+
+impl<K, V> FromIterator<(Range<K>, V)> for RangeMap<K, V> where K: Ord + Clone, V: PartialEq + Clone {
+    fn from_iter<I>(iter: I) -> RangeMap<K, V> where I: IntoIterator<Item = (Range<K>, V)> {
+        let mut map = RangeMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V> Extend<(Range<K>, V)> for RangeMap<K, V> where K: Ord + Clone, V: PartialEq + Clone {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item = (Range<K>, V)> {
+        for (range, value) in iter {
+            self.insert(range, value);
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a RangeMap<K, V> where K: Ord {
+    type Item = (&'a Range<K>, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+/// Iterates over the ranges of a `RangeMap` and their values, in order.
+pub struct Iter<'a, K, V> where K: Ord + 'a, V: 'a {
+    spans: RedBlackIterator<'a, Span<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> where K: Ord {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<(&'a Range<K>, &'a V)> {
+        self.spans.next().map(|span| (&span.range, &span.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.spans.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> where K: Ord {}
+
+/// Iterates over the ranges of a `RangeMap` that overlap a range, made by
+/// `RangeMap::overlapping`.
+pub struct Overlapping<'a, K, V> where K: Ord + 'a, V: 'a {
+    root: Option<&'a Node<Span<K, V>>>,
+    current: Option<&'a Node<Span<K, V>>>,
+    end: K,
+}
+
+impl<'a, K, V> Iterator for Overlapping<'a, K, V> where K: Ord {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<(&'a Range<K>, &'a V)> {
+        let node = self.current?;
+        let span = node.value();
+        if span.range.start >= self.end {
+            self.current = None;
+            return None;
+        }
+
+        self.current = self.root.and_then(|root| node.successor(root));
+        Some((&span.range, &span.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use std::ops::Range;
+    use RangeMap;
+
+    pub fn ranges(map: &RangeMap<u32, char>) -> Vec<(Range<u32>, char)> {
+        map.iter().map(|(range, &value)| (range.clone(), value)).collect()
+    }
+
+    mod range_map {
+        use super::*;
+
+        #[test]
+        fn looks_up_the_range_a_key_falls_in() {
+            let map: RangeMap<u32, char> = vec![(0..10, 'a'), (20..30, 'b')].into_iter().collect();
+            expect!(map.get(&0)).to(be_some().value(&'a'));
+            expect!(map.get(&9)).to(be_some().value(&'a'));
+            expect!(map.get(&10)).to(be_none());
+            expect!(map.get(&25)).to(be_some().value(&'b'));
+            expect!(map.get(&30)).to(be_none());
+            expect!(map.get_key_value(&22)).to(be_some().value((&(20..30), &'b')));
+        }
+
+        #[test]
+        fn splits_and_cuts_back_what_an_insert_overlaps() {
+            let mut map = RangeMap::new();
+            map.insert(0..10, 'a');
+            map.insert(3..5, 'b');
+            expect!(ranges(&map)).to(be_equal_to(vec![(0..3, 'a'), (3..5, 'b'), (5..10, 'a')]));
+
+            map.insert(4..12, 'c');
+            expect!(ranges(&map)).to(be_equal_to(vec![(0..3, 'a'), (3..4, 'b'), (4..12, 'c')]));
+
+            // an empty range changes nothing
+            map.insert(6..6, 'd');
+            expect!(map.len()).to(be_equal_to(3));
+        }
+
+        #[test]
+        fn merges_touching_ranges_with_equal_values() {
+            let mut map = RangeMap::new();
+            map.insert(0..5, 'a');
+            map.insert(10..15, 'a');
+            map.insert(5..10, 'a');
+            expect!(ranges(&map)).to(be_equal_to(vec![(0..15, 'a')]));
+
+            // only with equal values
+            map.insert(15..20, 'b');
+            map.insert(2..4, 'a');
+            expect!(ranges(&map)).to(be_equal_to(vec![(0..15, 'a'), (15..20, 'b')]));
+        }
+
+        #[test]
+        fn removes_the_keys_in_a_range() {
+            let mut map: RangeMap<u32, char> = vec![(0..10, 'a'), (10..20, 'b')].into_iter().collect();
+            map.remove(5..12);
+            expect!(ranges(&map)).to(be_equal_to(vec![(0..5, 'a'), (12..20, 'b')]));
+            map.remove(14..16);
+            expect!(ranges(&map)).to(be_equal_to(vec![(0..5, 'a'), (12..14, 'b'), (16..20, 'b')]));
+            map.remove(0..100);
+            expect!(map.is_empty()).to(be_true());
+        }
+
+        #[test]
+        fn finds_the_ranges_overlapping_a_range() {
+            let map: RangeMap<u32, char> = vec![(0..10, 'a'), (10..20, 'b'), (25..30, 'c')].into_iter().collect();
+            let overlapping = |range| map.overlapping(range).map(|(range, &value)| (range.clone(), value)).collect::<Vec<_>>();
+            expect!(overlapping(5..12)).to(be_equal_to(vec![(0..10, 'a'), (10..20, 'b')]));
+            expect!(overlapping(20..25)).to(be_equal_to(vec![]));
+            expect!(overlapping(19..26)).to(be_equal_to(vec![(10..20, 'b'), (25..30, 'c')]));
+            expect!(overlapping(5..5)).to(be_equal_to(vec![]));
+        }
+
+        #[test]
+        fn agrees_with_a_value_per_key() {
+            let mut map = RangeMap::new();
+            let mut keys = [None; 70];
+            for step in 0..300u32 {
+                let start = step * 37 % 60;
+                let end = start + step * 11 % 9;
+                let value = ['a', 'b', 'c'][step as usize % 3];
+                if step % 4 == 3 {
+                    map.remove(start..end);
+                    keys[start as usize..end as usize].iter_mut().for_each(|key| *key = None);
+                } else {
+                    map.insert(start..end, value);
+                    keys[start as usize..end as usize].iter_mut().for_each(|key| *key = Some(value));
+                }
+
+                for (key, &value) in keys.iter().enumerate() {
+                    expect!(map.get(&(key as u32)).cloned()).to(be_equal_to(value));
+                }
+                // touching ranges never have equal values
+                let ranges = ranges(&map);
+                for pair in ranges.windows(2) {
+                    expect!(pair[0].0.end < pair[1].0.start || pair[0].1 != pair[1].1).to(be_true());
+                }
+            }
+        }
+    }
+}
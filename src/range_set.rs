@@ -0,0 +1,182 @@
+// A set of keys kept as disjoint half-open ranges, for tracking free space,
+// reserved ids and the like, where the keys come in long runs. It's a
+// `RangeMap` with nothing for values, and since all of those are equal,
+// ranges that overlap or touch are always merged into one. So the ranges
+// the set hands out are the maximal runs of keys in it.
+
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+use std::ops::Range;
+
+use range_map::{self, RangeMap};
+
+/// A set of keys stored as the disjoint ranges they make up.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RangeSet<K> where K: Ord {
+    map: RangeMap<K, ()>,
+}
+
+impl<K> RangeSet<K> where K: Ord {
+    pub const fn new() -> RangeSet<K> {
+        RangeSet { map: RangeMap::new() }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// The range of the set `key` falls in, if any.
+    pub fn get(&self, key: &K) -> Option<&Range<K>> {
+        self.map.get_key_value(key).map(|(range, _)| range)
+    }
+
+    /// The number of disjoint ranges the set is made of.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The ranges of the set, in order.
+    pub fn iter(&self) -> Iter<'_, K> {
+        Iter { ranges: self.map.iter() }
+    }
+
+    /// The ranges of the set that overlap `range`, in order, whole.
+    pub fn overlapping(&self, range: Range<K>) -> Overlapping<'_, K> {
+        Overlapping { ranges: self.map.overlapping(range) }
+    }
+}
+
+impl<K> RangeSet<K> where K: Ord + Clone {
+    /// Adds the keys in `range`, merging it with the ranges it overlaps or
+    /// touches.
+    pub fn insert(&mut self, range: Range<K>) {
+        self.map.insert(range, ());
+    }
+
+    /// Takes out the keys in `range`, cutting back or splitting the ranges
+    /// it overlaps.
+    pub fn remove(&mut self, range: Range<K>) {
+        self.map.remove(range);
+    }
+}
+
+impl<K> Default for RangeSet<K> where K: Ord {
+    fn default() -> RangeSet<K> {
+        RangeSet::new()
+    }
+}
+
+impl<K> Debug for RangeSet<K> where K: Ord + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<K> FromIterator<Range<K>> for RangeSet<K> where K: Ord + Clone {
+    fn from_iter<I>(iter: I) -> RangeSet<K> where I: IntoIterator<Item = Range<K>> {
+        let mut set = RangeSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<K> Extend<Range<K>> for RangeSet<K> where K: Ord + Clone {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item = Range<K>> {
+        for range in iter {
+            self.insert(range);
+        }
+    }
+}
+
+impl<'a, K> IntoIterator for &'a RangeSet<K> where K: Ord {
+    type Item = &'a Range<K>;
+    type IntoIter = Iter<'a, K>;
+
+    fn into_iter(self) -> Iter<'a, K> {
+        self.iter()
+    }
+}
+
+/// Iterates over the ranges of a `RangeSet` in order.
+pub struct Iter<'a, K> where K: Ord + 'a {
+    ranges: range_map::Iter<'a, K, ()>,
+}
+
+impl<'a, K> Iterator for Iter<'a, K> where K: Ord {
+    type Item = &'a Range<K>;
+
+    fn next(&mut self) -> Option<&'a Range<K>> {
+        self.ranges.next().map(|(range, _)| range)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ranges.size_hint()
+    }
+}
+
+impl<'a, K> ExactSizeIterator for Iter<'a, K> where K: Ord {}
+
+/// Iterates over the ranges of a `RangeSet` that overlap a range, made by
+/// `RangeSet::overlapping`.
+pub struct Overlapping<'a, K> where K: Ord + 'a {
+    ranges: range_map::Overlapping<'a, K, ()>,
+}
+
+impl<'a, K> Iterator for Overlapping<'a, K> where K: Ord {
+    type Item = &'a Range<K>;
+
+    fn next(&mut self) -> Option<&'a Range<K>> {
+        self.ranges.next().map(|(range, _)| range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use std::ops::Range;
+    use RangeSet;
+
+    pub fn ranges(set: &RangeSet<u32>) -> Vec<Range<u32>> {
+        set.iter().cloned().collect()
+    }
+
+    mod range_set {
+        use super::*;
+
+        #[test]
+        fn merges_ranges_that_overlap_or_touch() {
+            let mut set = RangeSet::new();
+            set.insert(10..20);
+            set.insert(30..40);
+            set.insert(0..5);
+            expect!(ranges(&set)).to(be_equal_to(vec![0..5, 10..20, 30..40]));
+
+            set.insert(15..30);
+            set.insert(5..6);
+            expect!(ranges(&set)).to(be_equal_to(vec![0..6, 10..40]));
+            expect!(set.contains(&39)).to(be_true());
+            expect!(set.contains(&40)).to(be_false());
+            expect!(set.contains(&7)).to(be_false());
+            expect!(set.get(&12)).to(be_some().value(&(10..40)));
+        }
+
+        #[test]
+        fn splits_ranges_a_removal_falls_inside() {
+            let mut set = RangeSet::new();
+            set.insert(0..100);
+            set.remove(10..20);
+            set.remove(90..200);
+            expect!(ranges(&set)).to(be_equal_to(vec![0..10, 20..90]));
+            expect!(set.overlapping(5..25).cloned().collect::<Vec<_>>()).to(be_equal_to(vec![0..10, 20..90]));
+            expect!(set.overlapping(10..20).next()).to(be_none());
+
+            set.remove(0..10);
+            set.remove(20..90);
+            expect!(set.is_empty()).to(be_true());
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// Renders the shape of a tree, one line per level, with arrows pointing
+// to the children of each node:
+//   <B.2>
+// B.1     <R.4>
+//       B.3   B.5>
+//                R.6
+// Every subtree gets its own range of columns, with the node's label
+// placed right after the columns taken up by its left subtree, so labels
+// never overlap and the in-order sequence reads left to right.
+
+use std::fmt::{self, Display};
+
+use allocator::Allocator;
+use node::{Node, Link};
+
+pub fn fmt_tree<T>(link: &Link<T>, f: &mut fmt::Formatter) -> fmt::Result where T: PartialOrd + Display {
+    let root = match link.as_ref() {
+        Some(root) => root,
+        None => return Ok(()),
+    };
+
+    // each entry is the column its subtree starts at, and the subtree
+    let mut level = vec![(0, root)];
+    while !level.is_empty() {
+        let mut next_level = vec![];
+        let mut column = 0;
+        for (offset, node) in level {
+            let label = node_str(node);
+            let label_column = offset + tree_width(node.left());
+
+            let mut text = String::new();
+            let mut start = label_column;
+            if let Some(left) = node.left().as_ref() {
+                // the arrow takes the last column of the left subtree,
+                // which is always drawn on a lower level
+                text.push('<');
+                start -= 1;
+                next_level.push((offset, left));
+            }
+            text.push_str(&label);
+            if let Some(right) = node.right().as_ref() {
+                text.push('>');
+                next_level.push((label_column + label.chars().count(), right));
+            }
+
+            write!(f, "{:1$}{2}", "", start - column, text)?;
+            column = start + text.chars().count();
+        }
+
+        level = next_level;
+        if !level.is_empty() {
+            writeln!(f)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Renders the tree top down, one node per line, with box drawing lines
+// connecting each node to its children, left child first:
+//   B.2
+//   ├── B.1
+//   └── R.4
+//       ├── B.3
+//       └── B.5
+//           ├── ·
+//           └── R.6
+// A node with only one child gets a `·` in place of the missing one, so
+// the side the child is on stays unambiguous.
+pub fn unicode_tree<T>(link: &Link<T>) -> String where T: PartialOrd + Display {
+    unicode_tree_with(link, &node_str)
+}
+
+// the same drawing, with each node labelled by `label`
+pub fn unicode_tree_with<T, A>(link: &Link<T, A>, label: &dyn Fn(&Node<T, A>) -> String) -> String
+where T: PartialOrd, A: Allocator + Clone {
+    let mut out = String::new();
+    if let Some(root) = link.as_ref() {
+        out.push_str(&label(root));
+        push_unicode_children(root, label, &mut String::new(), &mut out);
+    }
+
+    out
+}
+
+fn push_unicode_children<T, A>(node: &Node<T, A>, label: &dyn Fn(&Node<T, A>) -> String, prefix: &mut String, out: &mut String)
+where T: PartialOrd, A: Allocator + Clone {
+    if node.left().is_none() && node.right().is_none() {
+        return;
+    }
+
+    for &(child, last) in &[(node.left(), false), (node.right(), true)] {
+        out.push('\n');
+        out.push_str(prefix);
+        out.push_str(if last { "└── " } else { "├── " });
+        let child = match child.as_ref() {
+            Some(child) => child,
+            None => {
+                out.push('·');
+                continue;
+            },
+        };
+
+        out.push_str(&label(child));
+        let len = prefix.len();
+        prefix.push_str(if last { "    " } else { "│   " });
+        push_unicode_children(child, label, prefix, out);
+        prefix.truncate(len);
+    }
+}
+
+// Renders the tree as a Mermaid flowchart. Nodes are numbered in
+// pre-order and colored through the `red` and `black` classes, and each
+// edge is labelled with the side the child hangs off:
+//   graph TD
+//       n0["2"]:::black
+//       n0 -->|L| n1
+//       n1["1"]:::black
+//       n0 -->|R| n2
+//       n2["3"]:::black
+pub fn mermaid_tree<T>(link: &Link<T>) -> String where T: PartialOrd + Display {
+    let mut out = String::from("graph TD\n");
+    if let Some(root) = link.as_ref() {
+        push_mermaid_node(root, &mut 0, &mut out);
+    }
+    out.push_str("    classDef red fill:#d22,stroke:#800,color:#fff\n");
+    out.push_str("    classDef black fill:#222,stroke:#000,color:#fff");
+    out
+}
+
+fn push_mermaid_node<T>(node: &Node<T>, next_id: &mut usize, out: &mut String) where T: PartialOrd + Display {
+    let id = *next_id;
+    *next_id += 1;
+    let class = if node.is_red() { "red" } else { "black" };
+    // quotes would end the label early, mermaid spells them as an entity
+    let label = node.value().to_string().replace('"', "#quot;");
+    out.push_str(&format!("    n{}[\"{}\"]:::{}\n", id, label, class));
+
+    for &(child, side) in &[(node.left(), "L"), (node.right(), "R")] {
+        if let Some(child) = child.as_ref() {
+            out.push_str(&format!("    n{} -->|{}| n{}\n", id, side, *next_id));
+            push_mermaid_node(child, next_id, out);
+        }
+    }
+}
+
+// Dumps the shape of the tree as nested JSON objects, with missing
+// children as null. It doesn't go through serde, so values are written
+// as strings of their Display output whatever their type:
+//   {"value":"2","color":"black","left":{"value":"1",...},"right":null}
+pub fn json_tree<T>(link: &Link<T>) -> String where T: PartialOrd + Display {
+    let mut out = String::new();
+    push_json_node(link, &mut out);
+    out
+}
+
+fn push_json_node<T>(link: &Link<T>, out: &mut String) where T: PartialOrd + Display {
+    let node = match link.as_ref() {
+        Some(node) => node,
+        None => return out.push_str("null"),
+    };
+
+    out.push_str("{\"value\":");
+    push_json_string(&node.value().to_string(), out);
+    out.push_str(if node.is_red() { ",\"color\":\"red\",\"left\":" } else { ",\"color\":\"black\",\"left\":" });
+    push_json_node(node.left(), out);
+    out.push_str(",\"right\":");
+    push_json_node(node.right(), out);
+    out.push('}');
+}
+
+fn push_json_string(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn tree_width<T: PartialOrd + Display>(link: &Link<T>) -> usize {
+    match link.as_ref() {
+        Some(node) => tree_width(node.left()) + node_str(node).chars().count() + tree_width(node.right()),
+        None => 0,
+    }
+}
+
+fn node_str<T: PartialOrd + Display>(node: &Node<T>) -> String {
+    format!("{:?}.{}", node.color(), node.value())
+}
@@ -0,0 +1,184 @@
+// Trees serialize as a plain sequence of their elements in sorted order,
+// the same way the std sets do. A `ChangeSet` serializes as a sequence of
+// its changes, each one the way serde's derive would serialize the
+// `Change` enum.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, EnumAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use changeset::{Change, ChangeSet};
+use RedBlackTree;
+
+impl<T> Serialize for RedBlackTree<T> where T: PartialOrd + Serialize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+struct TreeVisitor<T> {
+    marker: PhantomData<T>,
+}
+
+impl<'de, T> Visitor<'de> for TreeVisitor<T> where T: PartialOrd + Deserialize<'de> {
+    type Value = RedBlackTree<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<RedBlackTree<T>, A::Error> {
+        // don't trust the size hint too much, it comes from the input
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+
+        // anything this crate serialized is already sorted, and can be
+        // built directly in O(n) without sorting or rebalancing
+        if values.windows(2).all(|pair| pair[0] <= pair[1]) {
+            Ok(RedBlackTree::from_sorted_vec(values))
+        } else {
+            Ok(values.into_iter().collect())
+        }
+    }
+}
+
+impl<'de, T> Deserialize<'de> for RedBlackTree<T> where T: PartialOrd + Deserialize<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RedBlackTree<T>, D::Error> {
+        deserializer.deserialize_seq(TreeVisitor { marker: PhantomData })
+    }
+}
+
+impl<T> Serialize for Change<T> where T: Serialize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Change::Insert(ref value) => serializer.serialize_newtype_variant("Change", 0, "Insert", value),
+            Change::Remove(ref value) => serializer.serialize_newtype_variant("Change", 1, "Remove", value),
+        }
+    }
+}
+
+const CHANGES: &[&str] = &["Insert", "Remove"];
+
+// which of the two changes it is, by name in self-describing formats and
+// by index in the others
+enum Kind {
+    Insert,
+    Remove,
+}
+
+struct KindVisitor;
+
+impl<'de> Visitor<'de> for KindVisitor {
+    type Value = Kind;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("`Insert` or `Remove`")
+    }
+
+    fn visit_u64<E: de::Error>(self, index: u64) -> Result<Kind, E> {
+        match index {
+            0 => Ok(Kind::Insert),
+            1 => Ok(Kind::Remove),
+            _ => Err(E::invalid_value(de::Unexpected::Unsigned(index), &"0 or 1")),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Kind, E> {
+        match name {
+            "Insert" => Ok(Kind::Insert),
+            "Remove" => Ok(Kind::Remove),
+            _ => Err(E::unknown_variant(name, CHANGES)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Kind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Kind, D::Error> {
+        deserializer.deserialize_identifier(KindVisitor)
+    }
+}
+
+struct ChangeVisitor<T> {
+    marker: PhantomData<T>,
+}
+
+impl<'de, T> Visitor<'de> for ChangeVisitor<T> where T: Deserialize<'de> {
+    type Value = Change<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a change")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Change<T>, A::Error> {
+        let (kind, value) = data.variant()?;
+        match kind {
+            Kind::Insert => value.newtype_variant().map(Change::Insert),
+            Kind::Remove => value.newtype_variant().map(Change::Remove),
+        }
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Change<T> where T: Deserialize<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Change<T>, D::Error> {
+        deserializer.deserialize_enum("Change", CHANGES, ChangeVisitor { marker: PhantomData })
+    }
+}
+
+impl<T> Serialize for ChangeSet<T> where T: Serialize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de, T> Deserialize<'de> for ChangeSet<T> where T: Deserialize<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ChangeSet<T>, D::Error> {
+        Vec::<Change<T>>::deserialize(deserializer).map(|changes| changes.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use expectest::prelude::*;
+    use changeset::{Change, ChangeSet};
+    use RedBlackTree;
+
+    mod serde {
+        use super::*;
+
+        #[test]
+        fn serializes_the_elements_as_a_sorted_sequence() {
+            let tree: RedBlackTree<_> = vec![3, 1, 2].into_iter().collect();
+            expect!(serde_json::to_string(&tree).unwrap()).to(be_equal_to("[1,2,3]".to_string()));
+        }
+
+        #[test]
+        fn deserializes_any_sequence_into_a_tree() {
+            let tree: RedBlackTree<u32> = serde_json::from_str("[5,1,4,1]").unwrap();
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 1, 4, 5]));
+
+            let tree: RedBlackTree<u32> = serde_json::from_str("[1,2,3,4,5,6,7]").unwrap();
+            expect!(tree.count).to(be_equal_to(7));
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((1..8).collect::<Vec<_>>()));
+
+            let err = serde_json::from_str::<RedBlackTree<u32>>("{}");
+            expect!(err.is_err()).to(be_true());
+        }
+
+        #[test]
+        fn round_trips_a_change_set() {
+            let changes: ChangeSet<u32> = vec![Change::Insert(3), Change::Remove(1)].into_iter().collect();
+            let json = serde_json::to_string(&changes).unwrap();
+            expect!(json.clone()).to(be_equal_to(r#"[{"Insert":3},{"Remove":1}]"#.to_string()));
+            expect!(serde_json::from_str::<ChangeSet<u32>>(&json).unwrap()).to(be_equal_to(changes));
+
+            let err = serde_json::from_str::<ChangeSet<u32>>(r#"[{"Replace":3}]"#);
+            expect!(err.is_err()).to(be_true());
+        }
+    }
+}
@@ -0,0 +1,325 @@
+// A sorted multiset of integer keys where every key from some point on can
+// be moved by the same amount in O(log n), for the markers of a text
+// editor, which all move along when text is typed in before them, and the
+// like. Each node holds a shift that's still to be added to every key in
+// its subtree, its own included. Shifting the keys from `x` on splits the
+// tree at `x`, adds to the shift at the root of the upper half, and joins
+// the halves back together.
+//
+// A node's shift has to be handed down to its children before the node is
+// moved around, or it would land on keys it was never meant for, which is
+// why `RedBlackTree`'s balancing won't do. This is the arena's, which
+// balances by splitting and joining and hands a node's `Augment` down
+// whenever it passes it, with the shift as the augmentation. Lookups don't
+// change anything, and add up the shifts on their way down instead.
+
+use std::error::Error;
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Add;
+
+use arena::{Arena, ArenaNode, Augment, Link};
+use get_dir;
+
+type Nodes<K> = Arena<K, Shift<K>, Vec<ArenaNode<K, Shift<K>>>>;
+
+// still to be added to every key in the subtree, the node's own included
+#[derive(Clone, Copy)]
+struct Shift<K>(K);
+
+/// A sorted multiset of keys that can move all the keys from a point on
+/// at once. The keys are integers, or anything else that can be added up
+/// with `Default` for zero.
+#[derive(Clone)]
+pub struct ShiftRedBlackTree<K> where K: Copy + Ord + Add<Output = K> + Default {
+    arena: Nodes<K>,
+}
+
+impl<K> Augment<K> for Shift<K> where K: Copy + Ord + Add<Output = K> + Default {
+    fn push_down(&mut self, key: &mut K, left: Option<&mut Shift<K>>, right: Option<&mut Shift<K>>) {
+        let shift = mem::take(&mut self.0);
+        if shift == K::default() {
+            return;
+        }
+
+        *key = *key + shift;
+        for child in left.into_iter().chain(right) {
+            child.0 = child.0 + shift;
+        }
+    }
+}
+
+impl<K> ShiftRedBlackTree<K> where K: Copy + Ord + Add<Output = K> + Default {
+    pub const fn new() -> ShiftRedBlackTree<K> {
+        ShiftRedBlackTree { arena: Arena::new(Vec::new()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.len() == 0
+    }
+
+    /// Adds `key`, after any keys equal to it.
+    pub fn insert(&mut self, key: K) {
+        let mut shift = K::default();
+        let (index, _) = self.arena.find(|other, augment| {
+            shift = shift + augment.0;
+            Some(get_dir(key < *other + shift))
+        });
+
+        self.arena.push_at(index, key, Shift(K::default()));
+    }
+
+    /// Takes out one key equal to `key`, and says whether there was one.
+    pub fn remove(&mut self, key: &K) -> bool {
+        match self.find(key) {
+            Some(index) => self.arena.remove_at(index).is_some(),
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.find(key).is_some()
+    }
+
+    /// Adds `delta` to every key that's at least `from`, in O(log n).
+    /// Fails, leaving the keys as they were, if that would move a key
+    /// below one that isn't being shifted. The keys mustn't overflow.
+    pub fn shift_keys_from(&mut self, from: K, delta: K) -> Result<(), ShiftError> {
+        let mut shift = K::default();
+        let (index, _) = self.arena.find(|other, augment| {
+            shift = shift + augment.0;
+            Some(get_dir(from <= *other + shift))
+        });
+
+        let before = index.checked_sub(1).and_then(|before| self.key_at(before));
+        if let (Some(last), Some(first)) = (before, self.key_at(index)) {
+            if last > first + delta {
+                return Err(ShiftError(()));
+            }
+        }
+
+        self.arena.update_from(index, |augment| augment.0 = augment.0 + delta);
+        Ok(())
+    }
+
+    pub fn first(&self) -> Option<K> {
+        self.key_at(0)
+    }
+
+    pub fn last(&self) -> Option<K> {
+        self.key_at(self.len().checked_sub(1)?)
+    }
+
+    /// The keys in ascending order.
+    pub fn iter(&self) -> Iter<'_, K> {
+        let mut iter = Iter { arena: &self.arena, stack: Vec::new(), remaining: self.len() };
+        iter.push_left_spine(self.arena.root(), K::default());
+        iter
+    }
+
+    // the position of a key equal to `key`
+    fn find(&self, key: &K) -> Option<usize> {
+        let mut shift = K::default();
+        let (index, found) = self.arena.find(|other, augment| {
+            shift = shift + augment.0;
+            let here = *other + shift;
+            if *key == here { None } else { Some(get_dir(*key < here)) }
+        });
+
+        found.map(|_| index)
+    }
+
+    // the key with `index` keys before it, with all the shifts on the way
+    // down added on
+    fn key_at(&self, index: usize) -> Option<K> {
+        let mut index = index;
+        let mut shift = K::default();
+        let mut link = self.arena.root();
+        while let Some(node) = self.arena.get(link) {
+            shift = shift + node.augment().0;
+            let left_size = self.arena.size(node.left());
+            if index < left_size {
+                link = node.left();
+            } else if index == left_size {
+                return Some(*node.value() + shift);
+            } else {
+                index -= left_size + 1;
+                link = node.right();
+            }
+        }
+
+        None
+    }
+}
+
+impl<K> Default for ShiftRedBlackTree<K> where K: Copy + Ord + Add<Output = K> + Default {
+    fn default() -> ShiftRedBlackTree<K> {
+        ShiftRedBlackTree::new()
+    }
+}
+
+impl<K> Debug for ShiftRedBlackTree<K> where K: Copy + Ord + Add<Output = K> + Default + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<K> PartialEq for ShiftRedBlackTree<K> where K: Copy + Ord + Add<Output = K> + Default {
+    fn eq(&self, other: &ShiftRedBlackTree<K>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K> Eq for ShiftRedBlackTree<K> where K: Copy + Ord + Add<Output = K> + Default {}
+
+impl<K> FromIterator<K> for ShiftRedBlackTree<K> where K: Copy + Ord + Add<Output = K> + Default {
+    fn from_iter<I>(iter: I) -> ShiftRedBlackTree<K> where I: IntoIterator<Item = K> {
+        let mut tree = ShiftRedBlackTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K> Extend<K> for ShiftRedBlackTree<K> where K: Copy + Ord + Add<Output = K> + Default {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item = K> {
+        for key in iter {
+            self.insert(key);
+        }
+    }
+}
+
+impl<'a, K> IntoIterator for &'a ShiftRedBlackTree<K> where K: Copy + Ord + Add<Output = K> + Default {
+    type Item = K;
+    type IntoIter = Iter<'a, K>;
+
+    fn into_iter(self) -> Iter<'a, K> {
+        self.iter()
+    }
+}
+
+/// Iterates over the keys of a `ShiftRedBlackTree` in ascending order.
+pub struct Iter<'a, K> where K: 'a {
+    arena: &'a Nodes<K>,
+    // the nodes still to come on the way back up, each with the shifts
+    // above it added up
+    stack: Vec<(&'a ArenaNode<K, Shift<K>>, K)>,
+    remaining: usize,
+}
+
+impl<'a, K> Iter<'a, K> where K: Copy + Ord + Add<Output = K> + Default {
+    fn push_left_spine(&mut self, link: Link, shift: K) {
+        let mut shift = shift;
+        let mut link = link;
+        while let Some(node) = self.arena.get(link) {
+            self.stack.push((node, shift));
+            shift = shift + node.augment().0;
+            link = node.left();
+        }
+    }
+}
+
+impl<'a, K> Iterator for Iter<'a, K> where K: Copy + Ord + Add<Output = K> + Default {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        let (node, shift) = self.stack.pop()?;
+        let shift = shift + node.augment().0;
+        self.push_left_spine(node.right(), shift);
+        self.remaining -= 1;
+        Some(*node.value() + shift)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K> ExactSizeIterator for Iter<'a, K> where K: Copy + Ord + Add<Output = K> + Default {}
+
+/// Returned by `ShiftRedBlackTree::shift_keys_from` when the shift would
+/// put the keys out of order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShiftError(());
+
+impl fmt::Display for ShiftError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the shift would move keys below ones that stay put")
+    }
+}
+
+impl Error for ShiftError {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use super::{ShiftError, ShiftRedBlackTree};
+
+    pub fn keys(tree: &ShiftRedBlackTree<i64>) -> Vec<i64> {
+        tree.iter().collect()
+    }
+
+    mod shift_red_black_tree {
+        use super::*;
+
+        #[test]
+        fn moves_the_keys_from_a_point_on() {
+            let mut tree: ShiftRedBlackTree<i64> = vec![10, 20, 20, 30, 40].into_iter().collect();
+            expect!(tree.shift_keys_from(20, 5)).to(be_ok());
+            expect!(keys(&tree)).to(be_equal_to(vec![10, 25, 25, 35, 45]));
+            expect!(tree.contains(&35)).to(be_true());
+            expect!(tree.contains(&30)).to(be_false());
+
+            // back down, as far as the key before
+            expect!(tree.shift_keys_from(35, -10)).to(be_ok());
+            expect!(keys(&tree)).to(be_equal_to(vec![10, 25, 25, 25, 35]));
+            expect!(tree.first()).to(be_some().value(10));
+            expect!(tree.last()).to(be_some().value(35));
+        }
+
+        #[test]
+        fn refuses_to_put_the_keys_out_of_order() {
+            let mut tree: ShiftRedBlackTree<i64> = vec![10, 20, 30].into_iter().collect();
+            expect!(tree.shift_keys_from(15, -11)).to(be_err().value(ShiftError(())));
+            expect!(keys(&tree)).to(be_equal_to(vec![10, 20, 30]));
+            // nothing below to run into
+            expect!(tree.shift_keys_from(0, -100)).to(be_ok());
+            expect!(keys(&tree)).to(be_equal_to(vec![-90, -80, -70]));
+        }
+
+        #[test]
+        fn inserts_and_removes_among_shifted_keys() {
+            let mut tree = ShiftRedBlackTree::new();
+            let mut model = Vec::new();
+            for step in 0..400i64 {
+                let key = step * 37 % 101;
+                match step % 5 {
+                    0..=2 => {
+                        tree.insert(key);
+                        model.push(key);
+                        model.sort();
+                    },
+                    3 => {
+                        let found = model.iter().position(|&other| other == key);
+                        expect!(tree.remove(&key)).to(be_equal_to(found.is_some()));
+                        if let Some(index) = found {
+                            model.remove(index);
+                        }
+                    },
+                    _ => {
+                        expect!(tree.shift_keys_from(key, 3)).to(be_ok());
+                        model.iter_mut().filter(|other| **other >= key).for_each(|other| *other += 3);
+                    },
+                }
+
+                tree.arena.assert_balanced();
+                expect!(tree.len()).to(be_equal_to(model.len()));
+                expect!(keys(&tree)).to(be_equal_to(model.clone()));
+            }
+        }
+    }
+}
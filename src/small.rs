@@ -0,0 +1,242 @@
+// A tree that keeps its first N elements in a sorted array inside the
+// struct itself, so a tree that never grows past N never allocates. The
+// element that would make it N + 1 moves everything into an ordinary
+// `RedBlackTree`, and from then on it's just that tree, even if it shrinks
+// back down, so that a tree hovering around N doesn't move its elements
+// back and forth.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+use std::mem;
+use std::slice;
+
+use inline::InlineVec;
+use {RedBlackIterator, RedBlackTree};
+
+/// A sorted multiset that stores up to `N` elements inline and only
+/// allocates nodes once it holds more than that.
+pub struct SmallRedBlackTree<T, const N: usize = 8> where T: PartialOrd {
+    storage: Storage<T, N>,
+}
+
+enum Storage<T, const N: usize> where T: PartialOrd {
+    Inline(InlineVec<T, N>),
+    Spilled(RedBlackTree<T>),
+}
+
+impl<T, const N: usize> SmallRedBlackTree<T, N> where T: PartialOrd {
+    pub const fn new() -> SmallRedBlackTree<T, N> {
+        SmallRedBlackTree { storage: Storage::Inline(InlineVec::new()) }
+    }
+
+    pub fn len(&self) -> usize {
+        match self.storage {
+            Storage::Inline(ref values) => values.len(),
+            Storage::Spilled(ref tree) => tree.count,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the elements have moved out into heap-allocated nodes.
+    pub fn spilled(&self) -> bool {
+        match self.storage {
+            Storage::Inline(_) => false,
+            Storage::Spilled(_) => true,
+        }
+    }
+
+    pub fn insert(&mut self, value: T) {
+        let values = match self.storage {
+            Storage::Inline(ref mut values) => values,
+            Storage::Spilled(ref mut tree) => return tree.insert(value),
+        };
+
+        if !values.is_full() {
+            // equal elements go after the ones already there, like in the tree
+            let position = values.as_slice().partition_point(|probe| value.partial_cmp(probe) != Some(Ordering::Less));
+            return values.insert(position, value);
+        }
+
+        // spilling first keeps the elements if the insert's comparisons panic
+        let values = mem::replace(values, InlineVec::new());
+        self.storage = Storage::Spilled(RedBlackTree::from_sorted_vec(values.into_vec()));
+        self.insert(value);
+    }
+
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        match self.storage {
+            Storage::Inline(ref mut values) => {
+                let position = values.as_slice().partition_point(|probe| *probe < *value);
+                if position < values.len() && values.as_slice()[position] == *value {
+                    Some(values.remove(position))
+                } else {
+                    None
+                }
+            },
+            Storage::Spilled(ref mut tree) => tree.remove(value),
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        match self.storage {
+            Storage::Inline(ref values) => values.as_slice().contains(value),
+            Storage::Spilled(ref tree) => tree.contains(value),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let inner = match self.storage {
+            Storage::Inline(ref values) => IterInner::Inline(values.as_slice().iter()),
+            Storage::Spilled(ref tree) => IterInner::Spilled(tree.iter()),
+        };
+
+        Iter { inner }
+    }
+
+    /// Removes every element. A spilled tree goes back to storing its
+    /// elements inline.
+    pub fn clear(&mut self) {
+        self.storage = Storage::Inline(InlineVec::new());
+    }
+
+    /// Turns this into an ordinary `RedBlackTree`, in O(n) if the elements
+    /// are still inline.
+    pub fn into_tree(self) -> RedBlackTree<T> {
+        match self.storage {
+            Storage::Inline(values) => RedBlackTree::from_sorted_vec(values.into_vec()),
+            Storage::Spilled(tree) => tree,
+        }
+    }
+}
+
+impl<T, const N: usize> Clone for SmallRedBlackTree<T, N> where T: PartialOrd + Clone {
+    fn clone(&self) -> SmallRedBlackTree<T, N> {
+        let storage = match self.storage {
+            Storage::Inline(ref values) => Storage::Inline(values.clone()),
+            Storage::Spilled(ref tree) => Storage::Spilled(tree.clone()),
+        };
+
+        SmallRedBlackTree { storage }
+    }
+}
+
+impl<T, const N: usize> Default for SmallRedBlackTree<T, N> where T: PartialOrd {
+    fn default() -> SmallRedBlackTree<T, N> {
+        SmallRedBlackTree::new()
+    }
+}
+
+impl<T, const N: usize> Debug for SmallRedBlackTree<T, N> where T: PartialOrd + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for SmallRedBlackTree<T, N> where T: PartialOrd {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> SmallRedBlackTree<T, N> {
+        let mut tree = SmallRedBlackTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T, const N: usize> Extend<T> for SmallRedBlackTree<T, N> where T: PartialOrd {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a SmallRedBlackTree<T, N> where T: PartialOrd {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T, const N: usize> From<SmallRedBlackTree<T, N>> for RedBlackTree<T> where T: PartialOrd {
+    fn from(tree: SmallRedBlackTree<T, N>) -> RedBlackTree<T> {
+        tree.into_tree()
+    }
+}
+
+/// Iterates over a `SmallRedBlackTree` in ascending order.
+pub struct Iter<'a, T> where T: PartialOrd + 'a {
+    inner: IterInner<'a, T>,
+}
+
+enum IterInner<'a, T> where T: PartialOrd + 'a {
+    Inline(slice::Iter<'a, T>),
+    Spilled(RedBlackIterator<'a, T>),
+}
+
+impl<'a, T> Iterator for Iter<'a, T> where T: PartialOrd {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        match self.inner {
+            IterInner::Inline(ref mut iter) => iter.next(),
+            IterInner::Spilled(ref mut iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.inner {
+            IterInner::Inline(ref iter) => iter.size_hint(),
+            IterInner::Spilled(ref iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> where T: PartialOrd {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use small::SmallRedBlackTree;
+    use RedBlackTree;
+
+    mod small_tree {
+        use super::*;
+
+        #[test]
+        fn keeps_up_to_n_elements_inline() {
+            let mut tree = SmallRedBlackTree::<_, 4>::new();
+            for value in [3, 1, 3, 2] {
+                tree.insert(value);
+            }
+
+            expect!(tree.spilled()).to(be_false());
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2, 3, 3]));
+            expect!(tree.contains(&2)).to(be_true());
+            expect!(tree.remove(&3)).to(be_some().value(3));
+            expect!(tree.remove(&5)).to(be_none());
+            expect!(tree.len()).to(be_equal_to(3));
+        }
+
+        #[test]
+        fn spills_into_nodes_past_n_elements() {
+            let mut tree: SmallRedBlackTree<_, 4> = (0..4).rev().collect();
+            tree.insert(10);
+            expect!(tree.spilled()).to(be_true());
+            expect!(tree.len()).to(be_equal_to(5));
+            expect!(tree.clone().into_tree() == (0..4).chain(Some(10)).collect::<RedBlackTree<_>>()).to(be_true());
+
+            for value in 0..4 {
+                tree.remove(&value);
+            }
+            expect!(tree.spilled()).to(be_true());
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![10]));
+
+            tree.clear();
+            expect!(tree.spilled()).to(be_false());
+        }
+    }
+}
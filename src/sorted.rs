@@ -0,0 +1,244 @@
+// The interface the trees in this crate share, as a trait, so that code can
+// be written once against any of them, or against `BTreeSet`, and the
+// backing collection swapped out by changing a type. It covers what they
+// all do the same way: inserting, removing, looking up and walking the
+// elements in order. Each tree's other methods stay inherent.
+//
+// The trees are multisets and `BTreeSet` isn't, so inserting an element
+// equal to one already in a `BTreeSet` leaves it as it was, where a tree
+// holds both. Code that needs one behavior or the other has to ask for a
+// particular type.
+
+use std::collections::btree_set::{self, BTreeSet};
+
+use allocator::Allocator;
+use {arena, chunked, cow, small};
+use {ArenaRedBlackTree, ChunkedRedBlackTree, CowRedBlackTree, RedBlackIterator, RedBlackTree, SmallRedBlackTree};
+
+/// A collection that keeps its elements in ascending order.
+pub trait SortedSet {
+    type Item;
+    type Iter<'a>: Iterator<Item = &'a Self::Item> where Self: 'a;
+
+    fn insert(&mut self, value: Self::Item);
+
+    /// Removes one element equal to `value`, if there is one, and hands
+    /// it back.
+    fn remove(&mut self, value: &Self::Item) -> Option<Self::Item>;
+
+    fn contains(&self, value: &Self::Item) -> bool;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The elements in ascending order.
+    fn iter(&self) -> Self::Iter<'_>;
+
+    fn first(&self) -> Option<&Self::Item> {
+        self.iter().next()
+    }
+
+    fn last(&self) -> Option<&Self::Item> {
+        self.iter().last()
+    }
+}
+
+impl<T, A> SortedSet for RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    type Item = T;
+    type Iter<'a> = RedBlackIterator<'a, T, A> where Self: 'a;
+
+    fn insert(&mut self, value: T) {
+        self.insert(value);
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        self.remove(value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self.contains(value)
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+    fn iter(&self) -> RedBlackIterator<'_, T, A> {
+        self.iter()
+    }
+}
+
+impl<T> SortedSet for ArenaRedBlackTree<T> where T: PartialOrd {
+    type Item = T;
+    type Iter<'a> = arena::Iter<'a, T> where Self: 'a;
+
+    fn insert(&mut self, value: T) {
+        self.insert(value);
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        self.remove(value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self.contains(value)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn iter(&self) -> arena::Iter<'_, T> {
+        self.iter()
+    }
+}
+
+impl<T, const N: usize> SortedSet for ChunkedRedBlackTree<T, N> where T: PartialOrd {
+    type Item = T;
+    type Iter<'a> = chunked::Iter<'a, T, N> where Self: 'a;
+
+    fn insert(&mut self, value: T) {
+        self.insert(value);
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        self.remove(value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self.contains(value)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn iter(&self) -> chunked::Iter<'_, T, N> {
+        self.iter()
+    }
+}
+
+impl<T> SortedSet for CowRedBlackTree<T> where T: PartialOrd + Clone {
+    type Item = T;
+    type Iter<'a> = cow::Iter<'a, T> where Self: 'a;
+
+    fn insert(&mut self, value: T) {
+        self.insert(value);
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        self.remove(value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self.contains(value)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn iter(&self) -> cow::Iter<'_, T> {
+        self.iter()
+    }
+}
+
+impl<T, const N: usize> SortedSet for SmallRedBlackTree<T, N> where T: PartialOrd {
+    type Item = T;
+    type Iter<'a> = small::Iter<'a, T> where Self: 'a;
+
+    fn insert(&mut self, value: T) {
+        self.insert(value);
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        self.remove(value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self.contains(value)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn iter(&self) -> small::Iter<'_, T> {
+        self.iter()
+    }
+}
+
+impl<T> SortedSet for BTreeSet<T> where T: Ord {
+    type Item = T;
+    type Iter<'a> = btree_set::Iter<'a, T> where Self: 'a;
+
+    /// Does nothing if the set already holds an equal element.
+    fn insert(&mut self, value: T) {
+        self.insert(value);
+    }
+
+    fn remove(&mut self, value: &T) -> Option<T> {
+        self.take(value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self.contains(value)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn iter(&self) -> btree_set::Iter<'_, T> {
+        self.iter()
+    }
+
+    fn first(&self) -> Option<&T> {
+        self.first()
+    }
+
+    fn last(&self) -> Option<&T> {
+        self.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use sorted::SortedSet;
+    use std::collections::BTreeSet;
+    use {ArenaRedBlackTree, ChunkedRedBlackTree, CowRedBlackTree, RedBlackTree, SmallRedBlackTree};
+
+    // the same work, written once against the trait
+    pub fn exercise<S>() -> (Vec<i32>, Option<i32>, Option<i32>) where S: SortedSet<Item = i32> + Default {
+        let mut set = S::default();
+        expect!(set.is_empty()).to(be_true());
+        for value in [5, 1, 9, 3, 7] {
+            set.insert(value);
+        }
+
+        expect!(set.remove(&3)).to(be_some().value(3));
+        expect!(set.remove(&4)).to(be_none());
+        expect!(set.contains(&9)).to(be_true());
+        expect!(set.len()).to(be_equal_to(4));
+        (set.iter().cloned().collect(), set.first().cloned(), set.last().cloned())
+    }
+
+    mod sorted_set {
+        use super::*;
+
+        #[test]
+        fn behaves_the_same_for_every_backend() {
+            let expected = (vec![1, 5, 7, 9], Some(1), Some(9));
+            expect!(exercise::<BTreeSet<i32>>()).to(be_equal_to(expected.clone()));
+            expect!(exercise::<RedBlackTree<i32>>()).to(be_equal_to(expected.clone()));
+            expect!(exercise::<ArenaRedBlackTree<i32>>()).to(be_equal_to(expected.clone()));
+            expect!(exercise::<ChunkedRedBlackTree<i32, 2>>()).to(be_equal_to(expected.clone()));
+            expect!(exercise::<CowRedBlackTree<i32>>()).to(be_equal_to(expected.clone()));
+            expect!(exercise::<SmallRedBlackTree<i32, 2>>()).to(be_equal_to(expected));
+        }
+    }
+}
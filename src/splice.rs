@@ -0,0 +1,199 @@
+// Replacing a run of neighboring elements all at once. The tree is split
+// where the range starts and ends, and the replacement is joined in where
+// the range was, so the whole edit walks a few paths down the tree instead
+// of taking elements out and putting others in one at a time.
+
+use std::ops::{Bound, RangeBounds};
+
+use allocator::Global;
+use node::{Node, NodeHelper, Pool};
+use stats::Recorder;
+use {invariants, RedBlackTree};
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// Takes out the elements in `range` and puts `replacement`'s elements
+    /// in their place, returning the ones taken out as a tree of their own.
+    /// If `replacement`'s elements all sort between the elements either
+    /// side of the range, which they do when they're all in the range, this
+    /// takes O(log n). Otherwise they're inserted one at a time.
+    pub fn splice<R>(&mut self, range: R, replacement: RedBlackTree<T>) -> RedBlackTree<T> where R: RangeBounds<T> {
+        let start = match range.start_bound() {
+            Bound::Included(start) => self.bound_rank(start, false),
+            Bound::Excluded(start) => self.bound_rank(start, true),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => self.bound_rank(end, true),
+            Bound::Excluded(end) => self.bound_rank(end, false),
+            Bound::Unbounded => self.count,
+        };
+        // a range that ends before it starts holds nothing
+        let end = end.max(start);
+
+        // every comparison is made before the tree is split, so one that
+        // panics leaves it as it was
+        let fits = match replacement.root.as_ref() {
+            Some(root) => {
+                start.checked_sub(1).and_then(|previous| self.select(previous)).is_none_or(|previous| previous <= root.leftmost().value())
+                    && self.select(end).is_none_or(|next| root.rightmost().value() <= next)
+            },
+            None => true,
+        };
+
+        let (left, rest) = Node::split_at_rank(self.root.take(), start);
+        let (mut removed, right) = Node::split_at_rank(rest, end - start);
+        Node::ensure_root_black(&mut removed);
+        let removed = RedBlackTree {
+            root: removed,
+            count: end - start,
+            pool: Pool::new(Global),
+            stats: Recorder::new(),
+            trace: self.trace,
+        };
+
+        let mut replacement = replacement;
+        self.count -= end - start;
+        if fits {
+            let left = Node::concat(left, replacement.root.take());
+            self.root = Node::concat(left, right);
+            self.count += replacement.count;
+        } else {
+            self.root = Node::concat(left, right);
+        }
+
+        Node::ensure_root_black(&mut self.root);
+        if !fits {
+            for value in replacement {
+                self.insert(value);
+            }
+        }
+
+        invariants::verify(self, "splice");
+        invariants::verify(&removed, "splice");
+        removed
+    }
+
+    // the number of elements less than `value`, or with `inclusive` the
+    // number less than or equal to it
+    fn bound_rank(&self, value: &T, inclusive: bool) -> usize {
+        let mut rank = 0;
+        let mut link = &self.root;
+        while let Some(node) = link.as_ref() {
+            let before = if inclusive { *node.value() <= *value } else { *node.value() < *value };
+            if before {
+                rank += node.left().size() + 1;
+                link = node.right();
+            } else {
+                link = node.left();
+            }
+        }
+
+        rank
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use std::cell::Cell;
+    use std::cmp::Ordering;
+    use std::panic;
+    use RedBlackTree;
+
+    pub fn values(tree: &RedBlackTree<u32>) -> Vec<u32> {
+        tree.iter().cloned().collect()
+    }
+
+    mod splice {
+        use super::*;
+
+        #[test]
+        fn replaces_the_elements_in_the_range() {
+            let mut tree: RedBlackTree<u32> = (0..100).map(|value| value * 2).collect();
+            let replacement: RedBlackTree<u32> = (41..60).filter(|value| value % 2 == 1).collect();
+            let removed = tree.splice(40..60, replacement);
+
+            let expected: Vec<u32> = (0..20).map(|value| value * 2).chain((20..30).map(|value| value * 2 + 1)).chain((30..100).map(|value| value * 2)).collect();
+            expect!(values(&tree)).to(be_equal_to(expected));
+            expect!(values(&removed)).to(be_equal_to((20..30).map(|value| value * 2).collect::<Vec<_>>()));
+            expect!(tree.iter().len()).to(be_equal_to(100));
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+            expect!(removed.check_invariants().is_ok()).to(be_true());
+        }
+
+        #[test]
+        fn handles_open_and_empty_ranges() {
+            let mut tree: RedBlackTree<u32> = (0..50).collect();
+            let removed = tree.splice(..10, RedBlackTree::new());
+            expect!(values(&removed)).to(be_equal_to((0..10).collect::<Vec<_>>()));
+
+            let removed = tree.splice(40.., vec![45, 40].into_iter().collect());
+            expect!(values(&removed)).to(be_equal_to((40..50).collect::<Vec<_>>()));
+
+            // nothing in the range, so the replacement just goes in
+            let removed = tree.splice(20..20, vec![20].into_iter().collect());
+            expect!(removed.iter().next()).to(be_none());
+            expect!(values(&tree)).to(be_equal_to((10..21).chain(20..40).chain(vec![40, 45]).collect::<Vec<_>>()));
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+
+            let removed = tree.splice(.., RedBlackTree::new());
+            expect!(removed.iter().len()).to(be_equal_to(33));
+            expect!(tree.iter().next()).to(be_none());
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+        }
+
+        #[test]
+        fn inserts_a_replacement_that_reaches_past_the_range() {
+            let mut tree: RedBlackTree<u32> = (0..30).collect();
+            let removed = tree.splice(10..=19, vec![5, 15, 25].into_iter().collect());
+            expect!(values(&removed)).to(be_equal_to((10..20).collect::<Vec<_>>()));
+            expect!(values(&tree)).to(be_equal_to((0..6).chain(5..10).chain(vec![15]).chain(20..26).chain(25..30).collect::<Vec<_>>()));
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+        }
+
+        #[test]
+        fn leaves_the_tree_as_it_was_when_a_comparison_panics() {
+            thread_local!(static FUSE: Cell<Option<usize>> = const { Cell::new(None) });
+
+            #[derive(Debug, PartialEq)]
+            struct Touchy(u32);
+            impl PartialOrd for Touchy {
+                fn partial_cmp(&self, other: &Touchy) -> Option<Ordering> {
+                    FUSE.with(|fuse| match fuse.get() {
+                        Some(0) => panic!("comparison failed"),
+                        Some(left) => fuse.set(Some(left - 1)),
+                        None => {},
+                    });
+                    self.0.partial_cmp(&other.0)
+                }
+            }
+
+            let mut spliced = false;
+            for fuse in 0.. {
+                let mut tree: RedBlackTree<_> = (0..32).map(|value| Touchy(value * 2)).collect();
+                let replacement: RedBlackTree<_> = (10..20).map(|value| Touchy(value * 2 + 1)).collect();
+                FUSE.with(|cell| cell.set(Some(fuse)));
+                let removed = panic::catch_unwind(panic::AssertUnwindSafe(|| tree.splice(Touchy(20)..Touchy(40), replacement)));
+                FUSE.with(|cell| cell.set(None));
+
+                let values: Vec<u32> = tree.iter().map(|value| value.0).collect();
+                let expected: Vec<u32> = (0..10).map(|value| value * 2).chain((10..20).map(|value| value * 2 + 1)).chain((20..32).map(|value| value * 2)).collect();
+                expect!(tree.count).to(be_equal_to(tree.iter().len()));
+                expect!(tree.check_invariants().is_ok()).to(be_true());
+                spliced = removed.is_ok();
+                if spliced {
+                    expect!(values).to(be_equal_to(expected));
+                    break;
+                }
+
+                // checking the tree afterwards compares elements too, and a
+                // panic there comes once the splice is done
+                if !(cfg!(feature = "debug-invariants") && values == expected) {
+                    expect!(values).to(be_equal_to((0..32).map(|value| value * 2).collect::<Vec<_>>()));
+                }
+            }
+
+            expect!(spliced).to(be_true());
+        }
+    }
+}
@@ -0,0 +1,211 @@
+// Counts of the rebalancing work a tree does, kept when the `stats` feature
+// is on. The node code reports every rotation, recoloring and comparison
+// to counters for the current thread. A tree operation zeroes those when
+// it starts and adds them to the tree's totals for that kind of operation
+// when it's done, so the numbers a tree reports only ever cover work done
+// on it. Without the feature all of this compiles down to nothing.
+
+#[cfg(feature = "stats")]
+use std::cell::Cell;
+#[cfg(feature = "stats")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The work done by one kind of operation on a tree.
+#[cfg(feature = "stats")]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct OpStats {
+    /// How many times the operation was called.
+    pub calls: u64,
+    pub rotations: u64,
+    /// Changes of any node's color from red to black or back.
+    pub recolorings: u64,
+    /// Comparisons between elements.
+    pub comparisons: u64,
+}
+
+/// The work a tree has done since it was made, or since `reset_stats`.
+#[cfg(feature = "stats")]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Stats {
+    pub insert: OpStats,
+    pub remove: OpStats,
+    pub contains: OpStats,
+}
+
+#[cfg(feature = "stats")]
+impl Stats {
+    /// The work of every kind of operation added together.
+    pub fn total(&self) -> OpStats {
+        let mut total = OpStats::default();
+        for op in &[self.insert, self.remove, self.contains] {
+            total.calls += op.calls;
+            total.rotations += op.rotations;
+            total.recolorings += op.recolorings;
+            total.comparisons += op.comparisons;
+        }
+
+        total
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum Op {
+    Insert,
+    Remove,
+    Contains,
+}
+
+#[cfg(feature = "stats")]
+#[derive(Copy, Clone, Default)]
+struct Counts {
+    rotations: u64,
+    recolorings: u64,
+    comparisons: u64,
+}
+
+#[cfg(feature = "stats")]
+thread_local! {
+    static COUNTS: Cell<Counts> = Cell::new(Counts::default());
+}
+
+#[cfg(feature = "stats")]
+fn count(add: fn(&mut Counts)) {
+    COUNTS.with(|counts| {
+        let mut current = counts.get();
+        add(&mut current);
+        counts.set(current);
+    });
+}
+
+#[cfg(feature = "stats")]
+pub fn rotation() {
+    count(|counts| counts.rotations += 1);
+}
+
+#[cfg(feature = "stats")]
+pub fn recoloring() {
+    count(|counts| counts.recolorings += 1);
+}
+
+#[cfg(feature = "stats")]
+pub fn comparison() {
+    count(|counts| counts.comparisons += 1);
+}
+
+// starts counting the work of an operation
+#[cfg(feature = "stats")]
+pub fn start() {
+    COUNTS.with(|counts| counts.set(Counts::default()));
+}
+
+#[cfg(not(feature = "stats"))]
+#[inline(always)]
+pub fn rotation() {}
+
+#[cfg(not(feature = "stats"))]
+#[inline(always)]
+pub fn recoloring() {}
+
+#[cfg(not(feature = "stats"))]
+#[inline(always)]
+pub fn comparison() {}
+
+#[cfg(not(feature = "stats"))]
+#[inline(always)]
+pub fn start() {}
+
+// A tree's running totals. They're atomics, rather than cells, so that
+// `contains` can count through a shared reference without the feature
+// making trees stop being `Sync`.
+#[cfg(feature = "stats")]
+#[derive(Default)]
+pub struct Recorder {
+    // calls, rotations, recolorings and comparisons for each `Op`
+    totals: [[AtomicU64; 4]; 3],
+}
+
+#[cfg(not(feature = "stats"))]
+#[derive(Default)]
+pub struct Recorder;
+
+#[cfg(feature = "stats")]
+impl Recorder {
+    pub const fn new() -> Recorder {
+        Recorder { totals: [const { [const { AtomicU64::new(0) }; 4] }; 3] }
+    }
+
+    // adds what's been counted since `start` to the totals for `op`
+    pub fn finish(&self, op: Op) {
+        let counts = COUNTS.with(|counts| counts.get());
+        let totals = &self.totals[op as usize];
+        for (total, n) in totals.iter().zip(&[1, counts.rotations, counts.recolorings, counts.comparisons]) {
+            total.fetch_add(*n, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        let op = |op: Op| {
+            let totals = &self.totals[op as usize];
+            OpStats {
+                calls: totals[0].load(Ordering::Relaxed),
+                rotations: totals[1].load(Ordering::Relaxed),
+                recolorings: totals[2].load(Ordering::Relaxed),
+                comparisons: totals[3].load(Ordering::Relaxed),
+            }
+        };
+
+        Stats {
+            insert: op(Op::Insert),
+            remove: op(Op::Remove),
+            contains: op(Op::Contains),
+        }
+    }
+
+    pub fn reset(&self) {
+        for total in self.totals.iter().flatten() {
+            total.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+impl Recorder {
+    pub const fn new() -> Recorder {
+        Recorder
+    }
+
+    #[inline(always)]
+    pub fn finish(&self, _op: Op) {}
+}
+
+#[cfg(all(test, feature = "stats"))]
+mod tests {
+    pub use expectest::prelude::*;
+    pub use stats::{OpStats, Stats};
+    pub use RedBlackTree;
+
+    mod stats {
+        use super::*;
+
+        #[test]
+        fn counts_the_work_of_each_kind_of_operation() {
+            let mut tree = RedBlackTree::new();
+            for value in 1..4 {
+                tree.insert(value);
+            }
+            // the third insert rotates 2 up to the root
+            expect!(tree.stats().insert).to(be_equal_to(OpStats { calls: 3, rotations: 1, recolorings: 3, comparisons: 3 }));
+
+            expect!(tree.contains(&3)).to(be_true());
+            // one comparison against each node on the way down
+            expect!(tree.stats().contains).to(be_equal_to(OpStats { calls: 1, rotations: 0, recolorings: 0, comparisons: 2 }));
+
+            expect!(tree.remove(&4)).to(be_none());
+            expect!(tree.stats().remove.calls).to(be_equal_to(1));
+            expect!(tree.stats().total().calls).to(be_equal_to(5));
+
+            tree.reset_stats();
+            expect!(tree.stats()).to(be_equal_to(Stats::default()));
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! proptest strategies for trees and for sequences of operations on them.
+
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+use proptest::collection::{vec, SizeRange};
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+use render;
+use RedBlackTree;
+
+/// Generates trees of `size` elements, counting duplicates, drawn from
+/// `element`.
+pub fn tree<S>(element: S, size: impl Into<SizeRange>) -> impl Strategy<Value = RedBlackTree<S::Value>>
+where S: Strategy, S::Value: PartialOrd + Debug {
+    vec(element, size).prop_map(|values| values.into_iter().collect())
+}
+
+/// A single change to a tree.
+#[derive(Clone, Debug)]
+pub enum Op<T> {
+    Insert(T),
+    Remove(T),
+}
+
+impl<T> Op<T> where T: PartialOrd {
+    pub fn apply(self, tree: &mut RedBlackTree<T>) {
+        match self {
+            Op::Insert(value) => tree.insert(value),
+            Op::Remove(value) => {
+                tree.remove(&value);
+            }
+        }
+    }
+}
+
+/// Generates `size` inserts and removes, evenly mixed, of values drawn
+/// from `element`. Keeping `element` to a small range makes the removes
+/// likely to hit something.
+pub fn ops<S>(element: S, size: impl Into<SizeRange>) -> impl Strategy<Value = Vec<Op<S::Value>>>
+where S: Strategy, S::Value: PartialOrd + Debug {
+    vec((any::<bool>(), element).prop_map(|(insert, value)| {
+        if insert { Op::Insert(value) } else { Op::Remove(value) }
+    }), size)
+}
+
+/// Applies `ops` to a tree and to a sorted `Vec` standing in for it, and
+/// fails at the first operation where the two disagree or the tree stops
+/// being a valid red-black tree, with a drawing of the tree at that point
+/// in the message. Used as the body of a proptest test over `ops`, a
+/// failing sequence gets shrunk down to the shortest one that still fails.
+pub fn check_model<T>(ops: Vec<Op<T>>) -> Result<(), TestCaseError> where T: PartialOrd + Clone + Debug {
+    let mut tree = RedBlackTree::new();
+    let mut model: Vec<T> = vec![];
+    for (step, op) in ops.into_iter().enumerate() {
+        let description = format!("{:?}", op);
+        let problem = match op {
+            Op::Insert(value) => {
+                let position = model.partition_point(|probe| value.partial_cmp(probe) != Some(Ordering::Less));
+                model.insert(position, value.clone());
+                tree.insert(value);
+                None
+            },
+            Op::Remove(value) => {
+                let expected = model.iter().position(|probe| *probe == value).map(|position| model.remove(position));
+                let removed = tree.remove(&value);
+                if removed == expected {
+                    None
+                } else {
+                    Some(format!("removing returned {:?} instead of {:?}", removed, expected))
+                }
+            },
+        };
+
+        let problem = problem
+            .or_else(|| tree.check_invariants().err().map(|error| error.to_string()))
+            .or_else(|| if tree.iter().eq(model.iter()) { None } else { Some(format!("the tree holds {:?} instead of {:?}", tree, model)) });
+        if let Some(problem) = problem {
+            let drawing = render::unicode_tree_with(&tree.root, &|node| format!("{:?}.{:?}", node.color(), node.value()));
+            return Err(TestCaseError::fail(format!("{} at step {}, {}:\n{}", problem, step, description, drawing)));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use proptest::test_runner::TestRunner;
+    use super::{check_model, ops, tree, Op};
+    use RedBlackTree;
+
+    mod strategies {
+        use super::*;
+
+        #[test]
+        fn generates_trees_within_the_given_bounds() {
+            let mut runner = TestRunner::default();
+            runner.run(&tree(10..20u32, 5..8), |tree| {
+                assert!(tree.count >= 5 && tree.count < 8);
+                assert!(tree.iter().all(|value| (10..20).contains(value)));
+                Ok(())
+            }).unwrap();
+        }
+
+        #[test]
+        fn generates_operation_sequences() {
+            let mut runner = TestRunner::default();
+            runner.run(&ops(0..4u8, 16), |ops| {
+                assert_eq!(ops.len(), 16);
+                assert!(ops.iter().all(|op| match *op { Op::Insert(value) | Op::Remove(value) => value < 4 }));
+                Ok(())
+            }).unwrap();
+        }
+
+        #[test]
+        fn applies_operations_to_a_tree() {
+            let mut tree = RedBlackTree::new();
+            for op in [Op::Insert(2), Op::Insert(1), Op::Remove(2), Op::Insert(3)] {
+                op.apply(&mut tree);
+            }
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![1, 3]));
+        }
+
+        #[test]
+        fn keeps_the_tree_in_line_with_the_model() {
+            let mut runner = TestRunner::default();
+            runner.run(&ops(0..16u8, 0..200), check_model).unwrap();
+            expect!(check_model(vec![Op::Insert(1.5), Op::Remove(2.0), Op::Remove(1.5)]).is_ok()).to(be_true());
+        }
+    }
+}
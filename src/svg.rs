@@ -0,0 +1,112 @@
+// Draws the tree as an SVG picture: every node is a circle filled with its
+// color, placed in the column of its in-order position and the row of its
+// depth, with a line to each of its children.
+
+use std::fmt::{Display, Write};
+
+use node::{Node, Link};
+
+// sizes in pixels
+const RADIUS: usize = 18;
+const SPACING: usize = 44;
+const LEVEL: usize = 60;
+const MARGIN: usize = RADIUS + 4;
+
+pub fn svg_tree<T>(link: &Link<T>) -> String where T: PartialOrd + Display {
+    let mut layout = Layout { nodes: vec![], edges: vec![] };
+    if let Some(root) = link.as_ref() {
+        layout.place(root, 0, &mut 0);
+    }
+
+    let columns = layout.nodes.len();
+    let rows = layout.nodes.iter().map(|node| node.1 + 1).max().unwrap_or(0);
+    let width = 2 * MARGIN + columns.saturating_sub(1) * SPACING;
+    let height = 2 * MARGIN + rows.saturating_sub(1) * LEVEL;
+
+    // writing to a String never fails
+    let mut out = String::new();
+    let _ = writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {0} {1}">"#, width, height);
+    // the lines go first so the circles get drawn over their ends
+    for &((x1, y1), (x2, y2)) in &layout.edges {
+        let _ = writeln!(out, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="gray"/>"#,
+            x(x1), y(y1), x(x2), y(y2));
+    }
+    for &(column, row, red, ref label) in &layout.nodes {
+        let fill = if red { "red" } else { "black" };
+        let _ = writeln!(out, r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#, x(column), y(row), RADIUS, fill);
+        let _ = writeln!(out, r#"<text x="{}" y="{}" fill="white" text-anchor="middle" dominant-baseline="central" font-family="monospace">{}</text>"#,
+            x(column), y(row), label);
+    }
+    out.push_str("</svg>");
+    out
+}
+
+fn x(column: usize) -> usize {
+    MARGIN + column * SPACING
+}
+
+fn y(row: usize) -> usize {
+    MARGIN + row * LEVEL
+}
+
+struct Layout {
+    // (column, row, is red, label) of each node
+    nodes: Vec<(usize, usize, bool, String)>,
+    // the (column, row) at each end of every parent to child line
+    edges: Vec<((usize, usize), (usize, usize))>,
+}
+
+impl Layout {
+    // places the subtree starting at `column`, returning the column its
+    // root ends up in
+    fn place<T: PartialOrd + Display>(&mut self, node: &Node<T>, row: usize, column: &mut usize) -> usize {
+        let left = node.left().as_ref().map(|left| self.place(left, row + 1, column));
+        let own = *column;
+        *column += 1;
+        let right = node.right().as_ref().map(|right| self.place(right, row + 1, column));
+
+        for child in left.into_iter().chain(right) {
+            self.edges.push(((own, row), (child, row + 1)));
+        }
+        self.nodes.push((own, row, node.is_red(), escape(&node.value().to_string())));
+        own
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod svg {
+        use super::*;
+
+        #[test]
+        fn draws_a_circle_for_every_node_and_a_line_for_every_edge() {
+            let tree: RedBlackTree<_> = (1..7).collect();
+            let svg = tree.to_svg();
+            expect!(svg.starts_with("<svg ")).to(be_true());
+            expect!(svg.ends_with("</svg>")).to(be_true());
+            expect!(svg.matches("<circle ").count()).to(be_equal_to(6));
+            expect!(svg.matches("<line ").count()).to(be_equal_to(5));
+            expect!(svg.matches(r#"fill="red""#).count() + svg.matches(r#"fill="black""#).count()).to(be_equal_to(6));
+        }
+
+        #[test]
+        fn escapes_the_labels() {
+            let mut tree = RedBlackTree::new();
+            tree.insert("<&>");
+            expect!(tree.to_svg().contains(">&lt;&amp;&gt;</text>")).to(be_true());
+        }
+
+        #[test]
+        fn draws_an_empty_picture_for_an_empty_tree() {
+            let svg = RedBlackTree::<u8>::new().to_svg();
+            expect!(svg.contains("<circle")).to(be_false());
+        }
+    }
+}
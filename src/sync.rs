@@ -0,0 +1,135 @@
+// A `RedBlackTree` that can be shared between threads, behind an
+// `Arc<RwLock<_>>`. Lookups take the read lock and changes the write lock,
+// one operation at a time, so several threads can look things up at once.
+//
+// A thread panicking while it holds the lock poisons it, but a panic in
+// the middle of a tree operation leaves the tree as it was (apart from an
+// element being inserted), so the lock's contents are taken as they are
+// rather than passing the poisoning on to every other thread.
+
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::vec;
+
+use RedBlackTree;
+
+/// A handle on a `RedBlackTree` shared between threads. Cloning the handle
+/// gives another handle on the same tree.
+pub struct SyncRedBlackTree<T> where T: PartialOrd {
+    tree: Arc<RwLock<RedBlackTree<T>>>,
+}
+
+impl<T> SyncRedBlackTree<T> where T: PartialOrd {
+    pub fn new() -> SyncRedBlackTree<T> {
+        SyncRedBlackTree::from(RedBlackTree::new())
+    }
+
+    pub fn insert(&self, value: T) {
+        self.write().insert(value);
+    }
+
+    pub fn remove(&self, value: &T) -> Option<T> {
+        self.write().remove(value)
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.read().contains(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The tree, locked for reading for as long as the guard is kept.
+    /// Iterating over it this way doesn't copy anything, but every change
+    /// waits until the guard is dropped.
+    pub fn read(&self) -> RwLockReadGuard<'_, RedBlackTree<T>> {
+        self.tree.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The tree, locked for writing for as long as the guard is kept, for
+    /// making several changes that no one sees halfway through.
+    pub fn write(&self) -> RwLockWriteGuard<'_, RedBlackTree<T>> {
+        self.tree.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Iterates over a copy of the elements as they were when it was
+    /// called. The lock is only held while they're copied, so changes
+    /// made meanwhile don't wait on the iteration, and it doesn't see them.
+    pub fn snapshot_iter(&self) -> vec::IntoIter<T> where T: Clone {
+        self.read().iter().cloned().collect::<Vec<_>>().into_iter()
+    }
+}
+
+// another handle on the same tree
+impl<T> Clone for SyncRedBlackTree<T> where T: PartialOrd {
+    fn clone(&self) -> SyncRedBlackTree<T> {
+        SyncRedBlackTree { tree: self.tree.clone() }
+    }
+}
+
+impl<T> Default for SyncRedBlackTree<T> where T: PartialOrd {
+    fn default() -> SyncRedBlackTree<T> {
+        SyncRedBlackTree::new()
+    }
+}
+
+impl<T> From<RedBlackTree<T>> for SyncRedBlackTree<T> where T: PartialOrd {
+    fn from(tree: RedBlackTree<T>) -> SyncRedBlackTree<T> {
+        SyncRedBlackTree { tree: Arc::new(RwLock::new(tree)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use sync::SyncRedBlackTree;
+    use std::thread;
+
+    mod sync_tree {
+        use super::*;
+
+        #[test]
+        fn takes_inserts_from_several_threads_at_once() {
+            let tree = SyncRedBlackTree::new();
+            thread::scope(|scope| {
+                for start in 0..4 {
+                    let tree = tree.clone();
+                    scope.spawn(move || {
+                        for value in 0..250 {
+                            tree.insert(value * 4 + start);
+                        }
+                    });
+                }
+                scope.spawn(|| {
+                    // whatever it sees is in order, however far the inserts have got
+                    let snapshot: Vec<_> = tree.snapshot_iter().collect();
+                    expect!(snapshot.windows(2).all(|pair| pair[0] < pair[1])).to(be_true());
+                });
+            });
+
+            expect!(tree.len()).to(be_equal_to(1000));
+            expect!(tree.read().iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..1000).collect::<Vec<_>>()));
+            expect!(tree.remove(&10)).to(be_some().value(10));
+            expect!(tree.contains(&10)).to(be_false());
+        }
+
+        #[test]
+        fn keeps_working_after_a_thread_panics_holding_the_lock() {
+            let tree = SyncRedBlackTree::new();
+            tree.insert(1);
+            let other = tree.clone();
+            let panicked = thread::spawn(move || {
+                let _guard = other.write();
+                panic!("gave up while writing");
+            }).join();
+
+            expect!(panicked.is_err()).to(be_true());
+            tree.insert(2);
+            expect!(tree.snapshot_iter().collect::<Vec<_>>()).to(be_equal_to(vec![1, 2]));
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// What the `verify!` macro expands to. It walks the tree through a
+// `Cursor`, since the nodes themselves aren't public, and panics at the
+// first node that doesn't match the diagram.
+
+use std::fmt::Debug;
+
+use allocator::Allocator;
+use node::Node;
+use {Color, RedBlackTree};
+
+/// Asserts the exact shape of a tree, node by node, from a diagram in the
+/// same layout `render_ascii` draws: each node is its color, `R` or `B`,
+/// a dot and its value, with `<` and `>` marking which children it has.
+/// The nodes are listed level by level, left to right.
+///
+/// ```ignore
+/// verify!{ tree =>
+///         < B.2 >
+///     B.1   < R.4 >
+///         B.3   < B.6 >
+///             R.5     R.7
+/// };
+/// ```
+///
+/// A tree with no nodes is `verify!{ tree => None }`.
+#[macro_export]
+macro_rules! verify {
+    // helper rule
+    (@expr $e:expr) => {$e};
+
+    (@color R) => { $crate::Color::Red };
+    (@color B) => { $crate::Color::Black };
+
+    (@expect $node:expr => $c:tt.$v:tt) => {{
+        $node.expect_node($crate::verify!(@color $c), &$crate::verify!(@expr $v));
+    }};
+    (@expect $node:expr => None) => {{
+        $node.expect_none();
+    }};
+
+    // edge case where root is None
+    (@match_node [$node:expr] => None) => {
+        $crate::verify!(@expect $node => None);
+    };
+    // two children
+    (@match_node [$node:expr, $($stack:expr),+] => <$c:tt.$v:tt> $($nodes:tt)+) => {{
+        $crate::verify!(@expect $node => $c.$v);
+        $crate::verify!(@match_node [$($stack),+, $node.left(), $node.right()] => $($nodes)+);
+    }};
+    // two children and one stack item
+    (@match_node [$node:expr] => <$c:tt.$v:tt> $($nodes:tt)+) => {{
+        $crate::verify!(@expect $node => $c.$v);
+        $crate::verify!(@match_node [$node.left(), $node.right()] => $($nodes)+);
+    }};
+    // only right child
+    (@match_node [$node:expr, $($stack:expr),+] => $c:tt.$v:tt> $($nodes:tt)+) => {{
+        $crate::verify!(@expect $node => $c.$v);
+        $crate::verify!(@expect $node.left() => None);
+        $crate::verify!(@match_node [$($stack),+, $node.right()] => $($nodes)+);
+    }};
+    // only right child and one stack item
+    (@match_node [$node:expr] => $c:tt.$v:tt> $($nodes:tt)+) => {{
+        $crate::verify!(@expect $node => $c.$v);
+        $crate::verify!(@expect $node.left() => None);
+        $crate::verify!(@match_node [$node.right()] => $($nodes)+);
+    }};
+    // only left child
+    (@match_node [$node:expr, $($stack:expr),+] => <$c:tt.$v:tt $($nodes:tt)+) => {{
+        $crate::verify!(@expect $node => $c.$v);
+        $crate::verify!(@expect $node.right() => None);
+        $crate::verify!(@match_node [$($stack),+, $node.left()] => $($nodes)+);
+    }};
+    // only left child and one stack item
+    (@match_node [$node:expr] => <$c:tt.$v:tt $($nodes:tt)+) => {{
+        $crate::verify!(@expect $node => $c.$v);
+        $crate::verify!(@expect $node.right() => None);
+        $crate::verify!(@match_node [$node.left()] => $($nodes)+);
+    }};
+    // no children
+    (@match_node [$node:expr, $($stack:expr),+] => $c:tt.$v:tt $($nodes:tt)+) => {{
+        $crate::verify!(@expect $node => $c.$v);
+        $crate::verify!(@expect $node.right() => None);
+        $crate::verify!(@expect $node.left() => None);
+        $crate::verify!(@match_node [$($stack),+] => $($nodes)+);
+    }};
+    // no children and one stack item
+    (@match_node [$node:expr] => $c:tt.$v:tt) => {{
+        $crate::verify!(@expect $node => $c.$v);
+        $crate::verify!(@expect $node.left() => None);
+        $crate::verify!(@expect $node.right() => None);
+    }};
+
+    // initial macro call
+    ($tree:expr => $($nodes:tt)+) => {
+        $crate::verify!(@match_node [$crate::test_macros::root(&$tree)] => $($nodes)+);
+    };
+}
+
+pub fn root<T, A>(tree: &RedBlackTree<T, A>) -> Cursor<'_, T, A> where T: PartialOrd, A: Allocator + Clone {
+    Cursor { node: tree.root.as_ref() }
+}
+
+// A place in the tree, which may or may not have a node in it.
+pub struct Cursor<'a, T: 'a, A: 'a> where T: PartialOrd, A: Allocator + Clone {
+    node: Option<&'a Node<T, A>>,
+}
+
+impl<'a, T, A> Clone for Cursor<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    fn clone(&self) -> Cursor<'a, T, A> {
+        *self
+    }
+}
+
+impl<'a, T, A> Copy for Cursor<'a, T, A> where T: PartialOrd, A: Allocator + Clone {}
+
+impl<'a, T, A> Cursor<'a, T, A> where T: PartialOrd + Debug, A: Allocator + Clone {
+    // going down from an empty place stays empty, but the macro always
+    // checks a node is there before looking at its children
+    pub fn left(self) -> Cursor<'a, T, A> {
+        Cursor { node: self.node.and_then(|node| node.left().as_ref()) }
+    }
+
+    pub fn right(self) -> Cursor<'a, T, A> {
+        Cursor { node: self.node.and_then(|node| node.right().as_ref()) }
+    }
+
+    pub fn expect_node(self, color: Color, value: &T) {
+        let matches = self.node.is_some_and(|node| node.color() == color && *node.value() == *value);
+        if !matches {
+            panic!("verify!: expected {}.{:?}, found {}", letter(color), value, self.describe());
+        }
+    }
+
+    pub fn expect_none(self) {
+        if self.node.is_some() {
+            panic!("verify!: expected None, found {}", self.describe());
+        }
+    }
+
+    fn describe(self) -> String {
+        match self.node {
+            Some(node) => format!("{}.{:?}", letter(node.color()), node.value()),
+            None => "None".to_string(),
+        }
+    }
+}
+
+fn letter(color: Color) -> &'static str {
+    match color {
+        Color::Red => "R",
+        Color::Black => "B",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use RedBlackTree;
+
+    mod verify {
+        use super::*;
+
+        #[test]
+        fn accepts_a_tree_drawn_exactly() {
+            let tree: RedBlackTree<_> = (1..4).collect();
+            verify!{ tree =>
+                < B.2 >
+                B.1   B.3
+            };
+            verify!{ RedBlackTree::<u32>::new() => None };
+        }
+
+        #[test]
+        #[should_panic]
+        fn rejects_a_node_of_the_wrong_color() {
+            let tree: RedBlackTree<_> = (1..4).collect();
+            verify!{ tree =>
+                < B.2 >
+                R.1   B.3
+            };
+        }
+
+        #[test]
+        #[should_panic]
+        fn rejects_a_missing_child() {
+            let tree: RedBlackTree<_> = (1..3).collect();
+            verify!{ tree =>
+                < B.1 >
+                R.0   R.2
+            };
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Differential testing of `RedBlackTree` against the standard library's
+//! `BTreeSet`: the same operations go to both, and every answer the tree
+//! gives has to match the set's. Code that extends or wraps the tree can
+//! drive a `Differential` by hand, or hand it to `run` for a random mix
+//! of operations.
+
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::ops::Bound::Included;
+
+use RedBlackTree;
+
+/// A `RedBlackTree` and a `BTreeSet` that get the same operations, with
+/// every result checked against each other.
+pub struct Differential<T> where T: Ord {
+    tree: RedBlackTree<T>,
+    // the tree keeps duplicates and a set doesn't, so each element goes in
+    // with the number of the insert that added it
+    model: BTreeSet<(T, u64)>,
+    inserts: u64,
+    operations: usize,
+}
+
+impl<T> Differential<T> where T: Ord + Clone + Debug {
+    pub fn new() -> Differential<T> {
+        Differential { tree: RedBlackTree::new(), model: BTreeSet::new(), inserts: 0, operations: 0 }
+    }
+
+    pub fn tree(&self) -> &RedBlackTree<T> {
+        &self.tree
+    }
+
+    pub fn insert(&mut self, value: T) {
+        self.operations += 1;
+        self.model.insert((value.clone(), self.inserts));
+        self.inserts += 1;
+        self.tree.insert(value);
+    }
+
+    /// Removes one copy of `value` from both, and checks that they agree
+    /// on whether there was one.
+    pub fn remove(&mut self, value: &T) -> Option<T> {
+        self.operations += 1;
+        let copy = self.model.range((Included((value.clone(), 0)), Included((value.clone(), u64::MAX)))).next().cloned();
+        if let Some(ref copy) = copy {
+            self.model.remove(copy);
+        }
+
+        let removed = self.tree.remove(value);
+        assert_eq!(removed, copy.map(|(value, _)| value), "remove({:?}) after {} operations", value, self.operations);
+        removed
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        let expected = self.model.range((Included((value.clone(), 0)), Included((value.clone(), u64::MAX)))).next().is_some();
+        let found = self.tree.contains(value);
+        assert_eq!(found, expected, "contains({:?}) after {} operations", value, self.operations);
+        found
+    }
+
+    /// Checks that the tree holds the same elements as the set, in the
+    /// same order, and that it's still a valid red-black tree.
+    pub fn check(&self) {
+        let expected: Vec<&T> = self.model.iter().map(|(value, _)| value).collect();
+        let elements: Vec<&T> = self.tree.iter().collect();
+        assert_eq!(elements, expected, "elements after {} operations", self.operations);
+        assert_eq!(self.tree.iter().len(), expected.len(), "length after {} operations", self.operations);
+        if let Err(error) = self.tree.check_invariants() {
+            panic!("{} after {} operations", error, self.operations);
+        }
+    }
+}
+
+impl<T> Default for Differential<T> where T: Ord + Clone + Debug {
+    fn default() -> Differential<T> {
+        Differential::new()
+    }
+}
+
+/// Runs `steps` random inserts, removes and lookups, checking the whole
+/// tree after each one. `value` turns a random number into an element;
+/// mapping into a small range, like `|n| n % 64`, gives plenty of
+/// duplicates and removes that hit. The same `seed` always runs the same
+/// operations, so a failure can be replayed.
+pub fn run<T, F>(seed: u64, steps: usize, mut value: F) -> Differential<T>
+where T: Ord + Clone + Debug, F: FnMut(u64) -> T {
+    let mut random = XorShift(seed ^ 0x9e37_79b9_7f4a_7c15 | 1);
+    let mut differential = Differential::new();
+    for _ in 0..steps {
+        let choice = random.next() % 8;
+        let element = value(random.next());
+        if choice < 4 {
+            differential.insert(element);
+        } else if choice < 7 {
+            differential.remove(&element);
+        } else {
+            differential.contains(&element);
+        }
+
+        differential.check();
+    }
+
+    differential
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use test_support::{run, Differential};
+
+    mod test_support {
+        use super::*;
+
+        #[test]
+        fn agrees_with_a_btreeset_over_random_operations() {
+            for seed in 0..8 {
+                let differential = run(seed, 500, |n| n % 64);
+                expect!(differential.tree().iter().count() > 0).to(be_true());
+            }
+
+            run(8, 300, |n| format!("{:03}", n % 100));
+        }
+
+        #[test]
+        fn checks_each_answer_as_its_given() {
+            let mut differential = Differential::new();
+            differential.insert(3);
+            differential.insert(3);
+            expect!(differential.remove(&3)).to(be_some().value(3));
+            expect!(differential.contains(&3)).to(be_true());
+            expect!(differential.remove(&4)).to(be_none());
+            differential.check();
+        }
+
+        #[test]
+        #[should_panic]
+        fn notices_when_the_tree_and_the_set_disagree() {
+            let mut differential = Differential::new();
+            differential.insert(1);
+            differential.tree.insert(2);
+            differential.check();
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// A queue of items waiting for their deadlines, for timeouts and the like
+// where a timer wheel's resolution isn't needed. The tree keeps the items
+// in deadline order, so the next deadline is at the leftmost node and the
+// items that are due come off the left end one after another.
+//
+// The deadlines can be anything ordered, an `Instant`, a tick count or a
+// `Duration` since some start, and the items don't need to be comparable
+// at all. Items with the same deadline come out in the order they went
+// in.
+
+use std::cmp::Ordering;
+
+use node::Dir;
+use RedBlackTree;
+
+// an item in the tree, which orders the items by their deadlines alone
+struct Timer<K, V> {
+    deadline: K,
+    item: V,
+}
+
+impl<K, V> PartialEq for Timer<K, V> where K: PartialEq {
+    fn eq(&self, other: &Timer<K, V>) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl<K, V> PartialOrd for Timer<K, V> where K: PartialOrd {
+    fn partial_cmp(&self, other: &Timer<K, V>) -> Option<Ordering> {
+        self.deadline.partial_cmp(&other.deadline)
+    }
+}
+
+/// Items with deadlines, handed out once their deadlines have passed.
+pub struct TimerQueue<K, V> where K: PartialOrd {
+    tree: RedBlackTree<Timer<K, V>>,
+}
+
+impl<K, V> TimerQueue<K, V> where K: PartialOrd {
+    pub const fn new() -> TimerQueue<K, V> {
+        TimerQueue { tree: RedBlackTree::new() }
+    }
+
+    pub fn insert_at(&mut self, deadline: K, item: V) {
+        self.tree.insert(Timer { deadline, item });
+    }
+
+    /// The earliest deadline of the items waiting, if there are any.
+    pub fn next_deadline(&self) -> Option<&K> {
+        self.tree.root.as_ref().map(|root| &root.leftmost().value().deadline)
+    }
+
+    /// Takes out the items whose deadlines are at or before `now`, earliest
+    /// first, as the iterator is advanced. Those it doesn't get to stay in
+    /// the queue.
+    pub fn pop_expired(&mut self, now: K) -> Expired<'_, K, V> {
+        Expired { queue: self, now }
+    }
+
+    // takes out the earliest item, if it's due by `now`
+    fn pop_due(&mut self, now: &K) -> Option<(K, V)> {
+        if self.next_deadline()? > now {
+            return None;
+        }
+
+        let timer = self.tree.remove_where(|node| if node.left().is_some() { Some(Dir::Left) } else { None })?;
+        Some((timer.deadline, timer.item))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.count == 0
+    }
+}
+
+impl<K, V> Default for TimerQueue<K, V> where K: PartialOrd {
+    fn default() -> TimerQueue<K, V> {
+        TimerQueue::new()
+    }
+}
+
+/// Takes the items that are due out of a `TimerQueue`, made by
+/// `TimerQueue::pop_expired`.
+pub struct Expired<'a, K, V> where K: PartialOrd + 'a, V: 'a {
+    queue: &'a mut TimerQueue<K, V>,
+    now: K,
+}
+
+impl<'a, K, V> Iterator for Expired<'a, K, V> where K: PartialOrd {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.queue.pop_due(&self.now)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.queue.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use std::time::{Duration, Instant};
+    use TimerQueue;
+
+    mod timer_queue {
+        use super::*;
+
+        #[test]
+        fn hands_out_the_items_that_are_due() {
+            let mut queue = TimerQueue::new();
+            queue.insert_at(30, "c");
+            queue.insert_at(10, "a");
+            queue.insert_at(20, "b");
+            queue.insert_at(10, "a2");
+            expect!(queue.next_deadline()).to(be_some().value(&10));
+
+            // equal deadlines come out in the order they went in
+            expect!(queue.pop_expired(20).collect::<Vec<_>>()).to(be_equal_to(vec![(10, "a"), (10, "a2"), (20, "b")]));
+            expect!(queue.pop_expired(20).next()).to(be_none());
+            expect!(queue.next_deadline()).to(be_some().value(&30));
+            expect!(queue.len()).to(be_equal_to(1));
+
+            expect!(queue.pop_expired(99).collect::<Vec<_>>()).to(be_equal_to(vec![(30, "c")]));
+            expect!(queue.next_deadline()).to(be_none());
+            expect!(queue.is_empty()).to(be_true());
+        }
+
+        #[test]
+        fn leaves_the_items_it_did_not_get_to() {
+            let start = Instant::now();
+            let mut queue = TimerQueue::new();
+            for seconds in 0..4 {
+                queue.insert_at(start + Duration::from_secs(seconds), seconds);
+            }
+
+            expect!(queue.pop_expired(start + Duration::from_secs(10)).next()).to(be_some().value((start, 0)));
+            expect!(queue.len()).to(be_equal_to(3));
+        }
+    }
+}
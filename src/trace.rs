@@ -0,0 +1,269 @@
+// Structured logging of the work a tree does, through `tracing` when the
+// `tracing` feature is on. Every insert, removal and lookup gets a span of
+// its own, and every rotation and recoloring is an event inside it, so a
+// subscriber can show what each operation did to the tree. Without the
+// feature all of this compiles down to nothing, like the counters in
+// `stats`.
+//
+// Trees don't need their elements to be `Debug`, so the events only show
+// the elements once the tree has been given a way to print them, by
+// `RedBlackTree::trace_values`. The node code doing the rotating and
+// recoloring doesn't know which tree it's working on, so while a tree
+// rebalances it lends its printer to a thread local, where the events find
+// it. Rebalancing runs none of the elements' code, so the only nodes that
+// can see the printer are that tree's. The printer is taken back out while
+// an event is being recorded, in case the elements' `Debug` works on trees
+// of its own.
+
+#[cfg(feature = "tracing")]
+use std::cell::Cell;
+#[cfg(feature = "tracing")]
+use std::fmt::{self, Debug};
+
+#[cfg(feature = "tracing")]
+use tracing::span::EnteredSpan;
+
+use node::{Color, Dir};
+use stats::Op;
+
+// prints the element behind the pointer, which has to be of the type the
+// printer was made for
+#[cfg(feature = "tracing")]
+type Printer = unsafe fn(*const (), &mut fmt::Formatter) -> fmt::Result;
+
+#[cfg(feature = "tracing")]
+thread_local! {
+    static LENT: Cell<Option<Printer>> = const { Cell::new(None) };
+}
+
+// A tree's printer for its elements, if it has one. It's kept as a plain
+// pointer to a function, rather than anything mentioning `T`, so that it
+// doesn't change how the tree's type parameters vary.
+#[cfg(feature = "tracing")]
+#[derive(Copy, Clone, Default)]
+pub struct Tracer {
+    printer: Option<Printer>,
+}
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Copy, Clone, Default)]
+pub struct Tracer;
+
+#[cfg(feature = "tracing")]
+impl Tracer {
+    pub const fn new() -> Tracer {
+        Tracer { printer: None }
+    }
+
+    pub fn print_values<T>(&mut self) where T: Debug {
+        self.printer = Some(print::<T>);
+    }
+
+    // lends the printer to the events until the guard is dropped. Only
+    // code working on this tree's nodes, and none of their elements' own
+    // code, can run until then.
+    pub fn lend(&self) -> Lent {
+        Lent { previous: LENT.with(|lent| lent.replace(self.printer)) }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl Tracer {
+    pub const fn new() -> Tracer {
+        Tracer
+    }
+
+    #[inline(always)]
+    pub fn lend(&self) -> Lent {
+        Lent
+    }
+}
+
+#[cfg(feature = "tracing")]
+unsafe fn print<T>(value: *const (), f: &mut fmt::Formatter) -> fmt::Result where T: Debug {
+    (*(value as *const T)).fmt(f)
+}
+
+#[cfg(feature = "tracing")]
+pub struct Lent {
+    previous: Option<Printer>,
+}
+
+#[cfg(not(feature = "tracing"))]
+pub struct Lent;
+
+#[cfg(feature = "tracing")]
+impl Drop for Lent {
+    fn drop(&mut self) {
+        LENT.with(|lent| lent.set(self.previous));
+    }
+}
+
+// an element in an event, shown as `_` when its tree has no printer
+#[cfg(feature = "tracing")]
+struct Value {
+    value: *const (),
+    printer: Option<Printer>,
+}
+
+#[cfg(feature = "tracing")]
+impl Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.printer {
+            // the printer was lent by the tree that owns the element
+            Some(printer) => unsafe { printer(self.value, f) },
+            None => f.write_str("_"),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn with_printer<F>(event: F) where F: FnOnce(Option<Printer>) {
+    let printer = LENT.with(Cell::take);
+    event(printer);
+    LENT.with(|lent| lent.set(printer));
+}
+
+// enters a span for an operation on a tree of `len` elements
+#[cfg(feature = "tracing")]
+pub fn span(op: Op, len: usize) -> EnteredSpan {
+    let span = match op {
+        Op::Insert => tracing::trace_span!("insert", len),
+        Op::Remove => tracing::trace_span!("remove", len),
+        Op::Contains => tracing::trace_span!("contains", len),
+    };
+
+    span.entered()
+}
+
+// `down` is moved down to its `dir` side and `up` takes its place
+#[cfg(feature = "tracing")]
+pub fn rotation<T>(dir: Dir, down: &T, up: &T) {
+    with_printer(|printer| {
+        let down = Value { value: down as *const T as *const (), printer };
+        let up = Value { value: up as *const T as *const (), printer };
+        tracing::trace!(?dir, ?down, ?up, "rotation");
+    });
+}
+
+#[cfg(feature = "tracing")]
+pub fn recoloring<T>(value: &T, color: Color) {
+    with_printer(|printer| {
+        let value = Value { value: value as *const T as *const (), printer };
+        tracing::trace!(?value, ?color, "recoloring");
+    });
+}
+
+#[cfg(not(feature = "tracing"))]
+pub struct EnteredSpan;
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub fn span(_op: Op, _len: usize) -> EnteredSpan {
+    EnteredSpan
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub fn rotation<T>(_dir: Dir, _down: &T, _up: &T) {}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub fn recoloring<T>(_value: &T, _color: Color) {}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use expectest::prelude::*;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+    use RedBlackTree;
+
+    // writes down every event as its message followed by its fields
+    pub struct Recording(Arc<Mutex<Vec<String>>>);
+
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}{}", value, self.0);
+            } else {
+                self.0 += &format!(" {}={:?}", field.name(), value);
+            }
+        }
+    }
+
+    impl Subscriber for Recording {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields(String::new());
+            span.record(&mut fields);
+            self.0.lock().unwrap().push(format!("{}{}", span.metadata().name(), fields.0));
+            Id::from_u64(1)
+        }
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields(String::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+        fn enter(&self, _span: &Id) {}
+        fn exit(&self, _span: &Id) {}
+    }
+
+    pub fn record<F>(f: F) -> Vec<String> where F: FnOnce() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Recording(lines.clone()), f);
+        let lines = lines.lock().unwrap().clone();
+        lines
+    }
+
+    mod tracing_feature {
+        use super::*;
+
+        #[test]
+        fn traces_rotations_and_recolorings_inside_each_operation() {
+            let mut tree = RedBlackTree::new();
+            tree.trace_values();
+            let lines = record(|| {
+                for value in 1..4 {
+                    tree.insert(value);
+                }
+                tree.contains(&3);
+            });
+
+            // the third insert rotates 2 up to the root
+            expect!(lines).to(be_equal_to(vec![
+                "insert len=0".to_string(),
+                "recoloring value=1 color=B".to_string(),
+                "insert len=1".to_string(),
+                "insert len=2".to_string(),
+                "recoloring value=1 color=R".to_string(),
+                "recoloring value=2 color=B".to_string(),
+                "rotation dir=Left down=1 up=2".to_string(),
+                "contains len=3".to_string(),
+            ]));
+        }
+
+        #[test]
+        fn leaves_out_elements_without_a_printer() {
+            let mut tree = RedBlackTree::new();
+            let lines = record(|| {
+                for value in 1..4 {
+                    tree.insert(value);
+                }
+            });
+
+            expect!(lines.contains(&"rotation dir=Left down=_ up=_".to_string())).to(be_true());
+        }
+    }
+}
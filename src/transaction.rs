@@ -0,0 +1,120 @@
+// Changes to a tree made all together or not at all. A transaction doesn't
+// touch the tree until it's committed; it only lists the inserts and
+// removes it's been given. Committing makes them in order, and rolling
+// back, or dropping the transaction, throws the list away, which leaves
+// the tree exactly as it was, down to its shape and colors. Undoing the
+// changes one by one couldn't promise that, since taking an element back
+// out of a tree doesn't put its nodes back the way they were.
+//
+// The transaction borrows the tree mutably, so nothing else can see or
+// change the tree while the changes are being made.
+
+use allocator::{Allocator, Global};
+use RedBlackTree;
+
+/// A list of changes to a `RedBlackTree`, made by `RedBlackTree::begin`,
+/// that are made to the tree by `commit` and thrown away by `rollback`.
+pub struct Transaction<'a, T, A = Global> where T: PartialOrd + 'a, A: Allocator + Clone + 'a {
+    tree: &'a mut RedBlackTree<T, A>,
+    changes: Vec<Change<T>>,
+}
+
+enum Change<T> {
+    Insert(T),
+    Remove(T),
+}
+
+impl<T, A> RedBlackTree<T, A> where T: PartialOrd, A: Allocator + Clone {
+    /// Starts a transaction on the tree, which has the tree to itself
+    /// until it's committed or rolled back.
+    pub fn begin(&mut self) -> Transaction<'_, T, A> {
+        Transaction { tree: self, changes: Vec::new() }
+    }
+}
+
+impl<'a, T, A> Transaction<'a, T, A> where T: PartialOrd, A: Allocator + Clone {
+    pub fn insert(&mut self, value: T) {
+        self.changes.push(Change::Insert(value));
+    }
+
+    /// Removes one element equal to `value` when the transaction is
+    /// committed, if there is one by then.
+    pub fn remove(&mut self, value: &T) where T: Clone {
+        self.changes.push(Change::Remove(value.clone()));
+    }
+
+    /// The number of changes waiting to be made.
+    pub fn pending(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// The tree as it was when the transaction began, without any of the
+    /// transaction's changes.
+    pub fn tree(&self) -> &RedBlackTree<T, A> {
+        self.tree
+    }
+
+    /// Makes the changes, in the order they were given, and returns the
+    /// elements the removes took out of the tree. The changes can only be
+    /// interrupted by a comparison panicking, which leaves the ones before
+    /// it made.
+    pub fn commit(self) -> Vec<T> {
+        let mut removed = Vec::new();
+        for change in self.changes {
+            match change {
+                Change::Insert(value) => self.tree.insert(value),
+                Change::Remove(value) => removed.extend(self.tree.remove(&value)),
+            }
+        }
+
+        removed
+    }
+
+    /// Throws the changes away, leaving the tree as it was. Dropping the
+    /// transaction does the same.
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod transaction {
+        use super::*;
+
+        #[test]
+        fn makes_all_of_its_changes_when_committed() {
+            let mut tree: RedBlackTree<_> = (0..8).collect();
+            let mut transaction = tree.begin();
+            transaction.insert(10);
+            transaction.remove(&3);
+            // the element inserted first is there to remove by the time it's committed
+            transaction.remove(&10);
+            transaction.remove(&20);
+            expect!(transaction.pending()).to(be_equal_to(4));
+            expect!(transaction.tree().contains(&3)).to(be_true());
+
+            expect!(transaction.commit()).to(be_equal_to(vec![3, 10]));
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec![0, 1, 2, 4, 5, 6, 7]));
+            expect!(tree.check_invariants().is_ok()).to(be_true());
+        }
+
+        #[test]
+        fn leaves_the_tree_exactly_as_it_was_when_rolled_back() {
+            let mut tree: RedBlackTree<_> = (0..8).collect();
+            let before = tree.clone();
+            let mut transaction = tree.begin();
+            transaction.remove(&5);
+            transaction.insert(5);
+            transaction.rollback();
+            expect!(tree.structural_eq(&before)).to(be_true());
+
+            {
+                let mut transaction = tree.begin();
+                transaction.insert(9);
+            }
+            expect!(tree.structural_eq(&before)).to(be_true());
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// Making a new tree out of an old one's elements without sorting or
+// rebalancing anything. `map` keeps the old tree's shape and colors, which
+// still make a valid red-black tree as long as the elements keep their
+// order, and only puts the new elements in new nodes. `filter` builds its
+// tree in one go from the elements it keeps, which come out of the old
+// tree already sorted.
+
+use allocator::Global;
+use node::{Link, Node, OwnedNode, Pool};
+use stats::Recorder;
+use trace::Tracer;
+use {invariants, RedBlackTree};
+
+impl<T> RedBlackTree<T> where T: PartialOrd {
+    /// A tree of `f` applied to every element, in O(n), reusing this
+    /// tree's shape. `f` is called on the elements in ascending order, and
+    /// has to keep them in that order, as with `from_sorted_iter`. If it
+    /// doesn't, the new tree keeps the elements where they were, and
+    /// lookups can miss elements that are there. The `debug-invariants`
+    /// feature checks the order, and panics if it's wrong.
+    pub fn map<U, F>(mut self, f: F) -> RedBlackTree<U> where U: PartialOrd, F: FnMut(T) -> U {
+        let mut f = f;
+        let tree = RedBlackTree {
+            root: Node::map_link(self.root.take(), &mut f),
+            count: self.count,
+            pool: Pool::new(Global),
+            stats: Recorder::new(),
+            trace: Tracer::new(),
+        };
+
+        invariants::verify(&tree, "map");
+        tree
+    }
+
+    /// A tree of the elements `keep` returns true for, in O(n), called on
+    /// the elements in ascending order. The others are dropped.
+    pub fn filter<F>(self, keep: F) -> RedBlackTree<T> where F: FnMut(&T) -> bool {
+        RedBlackTree::from_sorted_vec(self.into_iter().filter(keep).collect())
+    }
+}
+
+impl<T> Node<T> where T: PartialOrd {
+    // the subtree with `f` applied to its elements in order, in the same
+    // shape and colors, freeing the old nodes as it goes
+    fn map_link<U, F>(link: Link<T>, f: &mut F) -> Link<U> where U: PartialOrd, F: FnMut(T) -> U {
+        let mut old = match link.into_owned() {
+            Some(old) => old,
+            None => return Link::none(),
+        };
+
+        let color = old.color();
+        let left = Self::map_link(old.left_mut().take(), f);
+        let right = old.right_mut().take();
+        let value = f(old.into_node().into_value());
+        let right = Self::map_link(right, f);
+
+        let mut node = OwnedNode::new(Node::new(value));
+        node.set_left(left);
+        node.set_right(right);
+        node.set_color(color);
+        node.update();
+        node.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use RedBlackTree;
+
+    mod map {
+        use super::*;
+
+        #[test]
+        fn keeps_the_shape_and_colors() {
+            let tree: RedBlackTree<u32> = (0..100).collect();
+            let doubled = tree.clone().map(|value| value as u64 * 2);
+            expect!(doubled.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..100).map(|value| value * 2).collect::<Vec<_>>()));
+            expect!(doubled.to_level_order().into_iter().map(|node| node.map(|(color, _)| color)).collect::<Vec<_>>())
+                .to(be_equal_to(tree.to_level_order().into_iter().map(|node| node.map(|(color, _)| color)).collect::<Vec<_>>()));
+            expect!(doubled.check_invariants().is_ok()).to(be_true());
+            expect!(doubled.contains(&64)).to(be_true());
+        }
+
+        #[test]
+        fn calls_the_function_in_order() {
+            let tree: RedBlackTree<_> = vec![3, 1, 2].into_iter().collect();
+            let mut seen = vec![];
+            let tree = tree.map(|value| {
+                seen.push(value);
+                value.to_string()
+            });
+
+            expect!(seen).to(be_equal_to(vec![1, 2, 3]));
+            expect!(tree.iter().cloned().collect::<Vec<_>>()).to(be_equal_to(vec!["1".to_string(), "2".to_string(), "3".to_string()]));
+            expect!(RedBlackTree::<u32>::new().map(|value| value + 1).iter().next()).to(be_none());
+        }
+
+        #[test]
+        #[cfg(feature = "debug-invariants")]
+        #[should_panic]
+        fn catches_a_function_that_reorders() {
+            let tree: RedBlackTree<i32> = (0..10).collect();
+            tree.map(|value| -value);
+        }
+    }
+
+    mod filter {
+        use super::*;
+
+        #[test]
+        fn keeps_the_elements_the_predicate_picks() {
+            let tree: RedBlackTree<u32> = (0..100).rev().collect();
+            let evens = tree.filter(|value| value % 2 == 0);
+            expect!(evens.iter().cloned().collect::<Vec<_>>()).to(be_equal_to((0..50).map(|value| value * 2).collect::<Vec<_>>()));
+            expect!(evens.check_invariants().is_ok()).to(be_true());
+
+            let none = evens.filter(|_| false);
+            expect!(none.iter().next()).to(be_none());
+            expect!(none.check_invariants().is_ok()).to(be_true());
+        }
+    }
+}
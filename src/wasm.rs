@@ -0,0 +1,123 @@
+// JavaScript bindings. JS only has one number type, so the exported class
+// is a tree of f64s, and NaN is kept out of it since it has no place in an
+// ordering.
+
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use RedBlackTree;
+
+#[wasm_bindgen]
+pub struct JsRedBlackTree {
+    tree: RedBlackTree<f64>,
+}
+
+#[wasm_bindgen]
+impl JsRedBlackTree {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsRedBlackTree {
+        JsRedBlackTree { tree: RedBlackTree::new() }
+    }
+
+    /// Adds `value` to the tree. Returns false, leaving the tree alone,
+    /// if `value` is NaN.
+    pub fn insert(&mut self, value: f64) -> bool {
+        if value.is_nan() {
+            return false;
+        }
+
+        self.tree.insert(value);
+        true
+    }
+
+    /// Removes one copy of `value`, returning whether there was one.
+    pub fn remove(&mut self, value: f64) -> bool {
+        self.tree.remove(&value).is_some()
+    }
+
+    pub fn has(&self, value: f64) -> bool {
+        self.tree.contains(&value)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.tree.count
+    }
+
+    /// The values in `[start, end)`, in ascending order, as a
+    /// `Float64Array`. Takes O(log n) to find the first one, and then
+    /// O(1) amortized for each one after it.
+    pub fn range(&self, start: f64, end: f64) -> Vec<f64> {
+        let root = match self.tree.root.as_ref() {
+            Some(root) => root,
+            None => return Vec::new(),
+        };
+
+        // the first value >= start, found on the way down, in O(log n)
+        let mut next = None;
+        let mut link = &self.tree.root;
+        while let Some(node) = link.as_ref() {
+            if *node.value() >= start {
+                next = Some(node);
+                link = node.left();
+            } else {
+                link = node.right();
+            }
+        }
+
+        let mut values = Vec::new();
+        while let Some(node) = next.filter(|node| *node.value() < end) {
+            values.push(*node.value());
+            next = node.successor(root);
+        }
+
+        values
+    }
+
+    /// Calls `callback(value)` for every value in ascending order. Stops
+    /// at, and rethrows, the first exception the callback throws.
+    #[wasm_bindgen(js_name = forEach)]
+    pub fn for_each(&self, callback: &Function) -> Result<(), JsValue> {
+        for &value in self.tree.iter() {
+            callback.call1(&JsValue::NULL, &JsValue::from_f64(value))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for JsRedBlackTree {
+    fn default() -> JsRedBlackTree {
+        JsRedBlackTree::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use super::JsRedBlackTree;
+
+    mod js_red_black_tree {
+        use super::*;
+
+        #[test]
+        fn keeps_an_ordered_set_of_numbers() {
+            let mut tree = JsRedBlackTree::new();
+            for &value in &[3.5, -1.0, 2.0, 8.0] {
+                expect!(tree.insert(value)).to(be_true());
+            }
+            expect!(tree.insert(f64::NAN)).to(be_false());
+
+            expect!(tree.size()).to(be_equal_to(4));
+            expect!(tree.has(2.0)).to(be_true());
+            expect!(tree.has(2.5)).to(be_false());
+            expect!(tree.range(0.0, 8.0)).to(be_equal_to(vec![2.0, 3.5]));
+            expect!(tree.range(9.0, 1.0).len()).to(be_equal_to(0));
+
+            expect!(tree.insert(2.0)).to(be_true());
+            expect!(tree.range(2.0, 3.5)).to(be_equal_to(vec![2.0, 2.0]));
+            expect!(tree.range(-5.0, 100.0)).to(be_equal_to(vec![-1.0, 2.0, 2.0, 3.5, 8.0]));
+            expect!(JsRedBlackTree::new().range(0.0, 1.0).len()).to(be_equal_to(0));
+        }
+    }
+}
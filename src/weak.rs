@@ -0,0 +1,214 @@
+// An ordered map that holds its values weakly, for registries of live
+// objects kept in key order, where being in the registry shouldn't keep an
+// object alive. An entry whose value has been dropped stays in the tree
+// until it's cleaned up, but the lookups and the iterator skip over it as
+// if it were gone.
+//
+// Dead entries are cleaned up by `prune`, and also every so often by
+// `insert`: once the map has doubled in size since it was last pruned, it
+// prunes itself. A prune is O(n) and comes at most once every n / 2
+// inserts, so a map that only ever has entries added and their values
+// dropped doesn't grow without bound, at O(1) amortized per insert.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::mem;
+use std::rc::{Rc, Weak};
+
+use node::{Dir, Follow, Node};
+use {RedBlackIterator, RedBlackTree};
+
+// an entry in the tree, which orders the entries by their keys alone
+struct Entry<K, V> {
+    key: K,
+    value: Weak<V>,
+}
+
+impl<K, V> PartialEq for Entry<K, V> where K: PartialEq {
+    fn eq(&self, other: &Entry<K, V>) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K, V> PartialOrd for Entry<K, V> where K: PartialOrd {
+    fn partial_cmp(&self, other: &Entry<K, V>) -> Option<Ordering> {
+        self.key.partial_cmp(&other.key)
+    }
+}
+
+/// An ordered map from keys to `Weak` references, whose entries go away
+/// once their values have been dropped.
+pub struct WeakValueMap<K, V> where K: Ord {
+    tree: RedBlackTree<Entry<K, V>>,
+    // the number of entries just after the last prune
+    pruned: usize,
+}
+
+// which way to go from `node` to find `key`, or None if it's a match
+fn search<K, V, Q>(key: &Q, node: &Node<Entry<K, V>>) -> Option<Dir> where K: Borrow<Q> + Ord, Q: Ord + ?Sized {
+    match key.cmp(node.value().key.borrow()) {
+        Ordering::Less => Some(Dir::Left),
+        Ordering::Equal => None,
+        Ordering::Greater => Some(Dir::Right),
+    }
+}
+
+impl<K, V> WeakValueMap<K, V> where K: Ord {
+    pub const fn new() -> WeakValueMap<K, V> {
+        WeakValueMap { tree: RedBlackTree::new(), pruned: 0 }
+    }
+
+    /// Maps `key` to a weak reference to `value`, and hands back the value
+    /// it was mapped to before, if that's still alive.
+    pub fn insert(&mut self, key: K, value: &Rc<V>) -> Option<Rc<V>> {
+        let replaced = self.tree.remove_where(|node| search(&key, node));
+        self.tree.insert(Entry { key, value: Rc::downgrade(value) });
+        if self.tree.count >= 2 * self.pruned.max(8) {
+            self.prune();
+        }
+
+        replaced.and_then(|entry| entry.value.upgrade())
+    }
+
+    /// The value mapped to `key`, unless there isn't one or it's been
+    /// dropped.
+    pub fn get<Q>(&self, key: &Q) -> Option<Rc<V>> where K: Borrow<Q>, Q: Ord + ?Sized {
+        let mut link = &self.tree.root;
+        while let Some(node) = link.as_ref() {
+            match search(key, node) {
+                Some(dir) => link = node.follow(dir),
+                None => return node.value().value.upgrade(),
+            }
+        }
+
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.get(key).is_some()
+    }
+
+    /// Takes out the entry for `key`, and hands back its value if it was
+    /// still alive.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<Rc<V>> where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.tree.remove_where(|node| search(key, node)).and_then(|entry| entry.value.upgrade())
+    }
+
+    /// Takes out every entry whose value has been dropped, in O(n), and
+    /// says how many there were.
+    pub fn prune(&mut self) -> usize {
+        let before = self.tree.count;
+        let tree = mem::take(&mut self.tree);
+        self.tree = RedBlackTree::from_sorted_iter(tree.into_iter().filter(|entry| entry.value.strong_count() > 0));
+        self.pruned = self.tree.count;
+        before - self.pruned
+    }
+
+    /// The number of entries, counting those whose values have been
+    /// dropped but that haven't been pruned yet.
+    pub fn len(&self) -> usize {
+        self.tree.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.count == 0
+    }
+
+    /// The entries whose values are alive, in key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { entries: self.tree.iter() }
+    }
+}
+
+impl<K, V> Default for WeakValueMap<K, V> where K: Ord {
+    fn default() -> WeakValueMap<K, V> {
+        WeakValueMap::new()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a WeakValueMap<K, V> where K: Ord {
+    type Item = (&'a K, Rc<V>);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+/// Iterates over the live entries of a `WeakValueMap` in key order,
+/// passing over the ones whose values have been dropped.
+pub struct Iter<'a, K, V> where K: Ord + 'a, V: 'a {
+    entries: RedBlackIterator<'a, Entry<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> where K: Ord {
+    type Item = (&'a K, Rc<V>);
+
+    fn next(&mut self) -> Option<(&'a K, Rc<V>)> {
+        self.entries.by_ref().find_map(|entry| entry.value.upgrade().map(|value| (&entry.key, value)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.entries.size_hint().1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use std::rc::Rc;
+    use WeakValueMap;
+
+    pub fn live(map: &WeakValueMap<u32, &'static str>) -> Vec<(u32, &'static str)> {
+        map.iter().map(|(&key, value)| (key, *value)).collect()
+    }
+
+    mod weak_value_map {
+        use super::*;
+
+        #[test]
+        fn forgets_values_once_they_are_dropped() {
+            let (a, b, c) = (Rc::new("a"), Rc::new("b"), Rc::new("c"));
+            let mut map = WeakValueMap::new();
+            map.insert(3, &c);
+            map.insert(1, &a);
+            map.insert(2, &b);
+            expect!(live(&map)).to(be_equal_to(vec![(1, "a"), (2, "b"), (3, "c")]));
+
+            drop(b);
+            expect!(map.get(&2)).to(be_none());
+            expect!(map.contains_key(&1)).to(be_true());
+            expect!(live(&map)).to(be_equal_to(vec![(1, "a"), (3, "c")]));
+            expect!(map.len()).to(be_equal_to(3));
+
+            expect!(map.prune()).to(be_equal_to(1));
+            expect!(map.len()).to(be_equal_to(2));
+            expect!(map.remove(&3).map(|value| *value)).to(be_some().value("c"));
+            expect!(live(&map)).to(be_equal_to(vec![(1, "a")]));
+        }
+
+        #[test]
+        fn replaces_the_value_for_a_key() {
+            let (a, b) = (Rc::new("a"), Rc::new("b"));
+            let mut map = WeakValueMap::new();
+            expect!(map.insert(1, &a)).to(be_none());
+            expect!(map.insert(1, &b).map(|value| *value)).to(be_some().value("a"));
+            expect!(live(&map)).to(be_equal_to(vec![(1, "b")]));
+
+            // a dead value doesn't come back out
+            drop(b);
+            expect!(map.insert(1, &a)).to(be_none());
+        }
+
+        #[test]
+        fn prunes_itself_as_it_grows() {
+            let mut map = WeakValueMap::new();
+            for key in 0..1000 {
+                map.insert(key, &Rc::new("gone"));
+            }
+
+            expect!(map.len() < 16).to(be_true());
+            expect!(live(&map)).to(be_equal_to(vec![]));
+        }
+    }
+}
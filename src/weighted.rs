@@ -0,0 +1,342 @@
+// A sorted multiset where every element carries a weight, for picking
+// elements at random in proportion to their weights, as a load balancer
+// picks servers. Each node keeps the sum of the weights in its subtree, so
+// a pick walks down from the root, going left or right by how the random
+// point falls against the left subtree's sum, in O(log n). The sums change
+// whenever nodes move, and `RedBlackTree`'s balancing knows nothing of
+// them, so this is the arena's tree with the sum as its augmentation,
+// which it works out again for every node it moves. All the comparing is
+// done on a walk down beforehand, so a comparison that panics leaves the
+// tree as it was.
+
+use std::fmt::{self, Debug};
+use std::iter::FromIterator;
+
+#[cfg(feature = "rand")]
+use rand::Rng;
+
+use arena::{Arena, ArenaNode, Augment, Nodes};
+use search_dir;
+
+#[derive(Clone, Copy)]
+struct Weight {
+    weight: f64,
+    // of the whole subtree, this node included
+    sum: f64,
+}
+
+/// A sorted multiset of elements with weights, that can pick one at random
+/// with a chance proportional to its weight in O(log n).
+#[derive(Clone)]
+pub struct WeightedRedBlackTree<T> where T: PartialOrd {
+    arena: Arena<T, Weight, Vec<ArenaNode<T, Weight>>>,
+}
+
+impl<T> Augment<T> for Weight {
+    fn pull_up(&mut self, _value: &T, left: Option<&Weight>, right: Option<&Weight>) {
+        self.sum = left.map_or(0.0, |left| left.sum) + self.weight + right.map_or(0.0, |right| right.sum);
+    }
+}
+
+impl<T> WeightedRedBlackTree<T> where T: PartialOrd {
+    pub const fn new() -> WeightedRedBlackTree<T> {
+        WeightedRedBlackTree { arena: Arena::new(Vec::new()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.len() == 0
+    }
+
+    /// The sum of all the weights.
+    pub fn total_weight(&self) -> f64 {
+        self.arena.get(self.arena.root()).map_or(0.0, |root| root.augment().sum)
+    }
+
+    /// Adds `value` with `weight`, after any elements equal to it.
+    /// Negative and NaN weights count as 0.
+    pub fn insert(&mut self, value: T, weight: f64) {
+        let weight = if weight > 0.0 { weight } else { 0.0 };
+        let index = self.arena.insert_position(&value);
+        self.arena.push_at(index, value, Weight { weight, sum: weight });
+    }
+
+    /// Takes out one element equal to `value`, if there is one, and hands
+    /// it back with its weight.
+    pub fn remove(&mut self, value: &T) -> Option<(T, f64)> {
+        let index = self.arena.position(value)?;
+        self.arena.remove_at(index).map(|(value, weight)| (value, weight.weight))
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.arena.position(value).is_some()
+    }
+
+    /// The weight of an element equal to `value`, if there is one.
+    pub fn weight(&self, value: &T) -> Option<f64> {
+        let (_, found) = self.arena.find(|other, _| search_dir(value, other));
+        self.arena.get(found).map(|node| node.augment().weight)
+    }
+
+    /// The elements in ascending order, with their weights.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { nodes: self.arena.nodes() }
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<T> WeightedRedBlackTree<T> where T: PartialOrd {
+    /// Picks an element uniformly at random, whatever the weights, in
+    /// O(log n).
+    pub fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        let len = self.len();
+        if len == 0 {
+            return None;
+        }
+
+        let found = self.arena.select(rng.gen_range(0..len));
+        self.arena.get(found).map(ArenaNode::value)
+    }
+
+    /// Picks an element at random with a chance proportional to its
+    /// weight, in O(log n). Returns `None` if no element has a positive
+    /// weight, or the weights add up to infinity.
+    pub fn random_weighted<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        let total = self.total_weight();
+        if total <= 0.0 || !total.is_finite() {
+            return None;
+        }
+
+        let mut target = rng.gen::<f64>() * total;
+        // the last element with a positive weight that the walk has gone
+        // right of, for when rounding leaves the target past the end of
+        // the sums
+        let mut last = None;
+        let mut link = self.arena.root();
+        while let Some(node) = self.arena.get(link) {
+            let left_sum = self.arena.get(node.left()).map_or(0.0, |left| left.augment().sum);
+            if target < left_sum {
+                link = node.left();
+                continue;
+            }
+
+            let weight = node.augment().weight;
+            target -= left_sum;
+            if target < weight {
+                return Some(node.value());
+            }
+
+            target -= weight;
+            if weight > 0.0 {
+                last = Some(node.value());
+            }
+            link = node.right();
+        }
+
+        last
+    }
+}
+
+impl<T> Default for WeightedRedBlackTree<T> where T: PartialOrd {
+    fn default() -> WeightedRedBlackTree<T> {
+        WeightedRedBlackTree::new()
+    }
+}
+
+impl<T> Debug for WeightedRedBlackTree<T> where T: PartialOrd + Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T> FromIterator<(T, f64)> for WeightedRedBlackTree<T> where T: PartialOrd {
+    fn from_iter<I>(iter: I) -> WeightedRedBlackTree<T> where I: IntoIterator<Item = (T, f64)> {
+        let mut tree = WeightedRedBlackTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T> Extend<(T, f64)> for WeightedRedBlackTree<T> where T: PartialOrd {
+    fn extend<I>(&mut self, iter: I) where I: IntoIterator<Item = (T, f64)> {
+        for (value, weight) in iter {
+            self.insert(value, weight);
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a WeightedRedBlackTree<T> where T: PartialOrd {
+    type Item = (&'a T, f64);
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Iterates over the elements of a `WeightedRedBlackTree` in ascending
+/// order, with their weights.
+pub struct Iter<'a, T> where T: 'a {
+    nodes: Nodes<'a, T, Weight>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (&'a T, f64);
+
+    fn next(&mut self) -> Option<(&'a T, f64)> {
+        self.nodes.next().map(|node| (node.value(), node.augment().weight))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use expectest::prelude::*;
+    use super::WeightedRedBlackTree;
+
+    // checks the balance, and that every node's sum adds up
+    pub fn check(tree: &WeightedRedBlackTree<u32>) {
+        tree.arena.assert_balanced();
+        let sum = |link| tree.arena.get(link).map_or(0.0, |node| node.augment().sum);
+        for node in tree.arena.nodes() {
+            expect!(node.augment().sum).to(be_equal_to(sum(node.left()) + node.augment().weight + sum(node.right())));
+        }
+    }
+
+    pub fn values(tree: &WeightedRedBlackTree<u32>) -> Vec<u32> {
+        tree.iter().map(|(value, _)| *value).collect()
+    }
+
+    mod weighted_red_black_tree {
+        use super::*;
+
+        #[test]
+        fn keeps_the_weights_with_the_elements() {
+            let mut tree: WeightedRedBlackTree<u32> = vec![(3, 1.5), (1, 2.0), (2, -1.0), (1, 0.5)].into_iter().collect();
+            expect!(tree.iter().map(|(value, weight)| (*value, weight)).collect::<Vec<_>>())
+                .to(be_equal_to(vec![(1, 2.0), (1, 0.5), (2, 0.0), (3, 1.5)]));
+            expect!(tree.total_weight()).to(be_equal_to(4.0));
+            expect!(tree.weight(&3)).to(be_some().value(1.5));
+            expect!(tree.weight(&4)).to(be_none());
+
+            expect!(tree.remove(&3)).to(be_some().value((3, 1.5)));
+            expect!(tree.remove(&3)).to(be_none());
+            expect!(tree.contains(&2)).to(be_true());
+            expect!(tree.total_weight()).to(be_equal_to(2.5));
+            expect!(tree.len()).to(be_equal_to(3));
+        }
+
+        #[test]
+        fn keeps_the_sums_right_through_inserts_and_removes() {
+            let mut tree = WeightedRedBlackTree::new();
+            let mut model = Vec::new();
+            for step in 0..400u32 {
+                let value = step * 37 % 101;
+                if step % 3 == 2 {
+                    let found = model.iter().position(|&(other, _)| other == value);
+                    let removed = tree.remove(&value);
+                    expect!(removed.is_some()).to(be_equal_to(found.is_some()));
+                    if let Some(index) = found {
+                        model.remove(index);
+                    }
+                } else {
+                    tree.insert(value, (value % 4) as f64);
+                    let index = model.iter().position(|&(other, _)| other > value).unwrap_or(model.len());
+                    model.insert(index, (value, (value % 4) as f64));
+                }
+
+                check(&tree);
+                expect!(values(&tree)).to(be_equal_to(model.iter().map(|&(value, _)| value).collect::<Vec<_>>()));
+                expect!(tree.total_weight()).to(be_equal_to(model.iter().map(|&(_, weight)| weight).sum::<f64>()));
+            }
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    mod random {
+        use super::*;
+        use rand::rngs::StdRng;
+        use rand::{RngCore, SeedableRng};
+
+        // always hands out the largest number it can, which puts the
+        // random point as close to the top of the weights as it gets
+        pub struct Highest;
+
+        impl RngCore for Highest {
+            fn next_u32(&mut self) -> u32 {
+                u32::MAX
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                u64::MAX
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                dest.iter_mut().for_each(|byte| *byte = u8::MAX);
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+                self.fill_bytes(dest);
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn picks_every_element_uniformly() {
+            let tree: WeightedRedBlackTree<u32> = (0..10).map(|value| (value, value as f64)).collect();
+            let mut rng = StdRng::seed_from_u64(7);
+            let mut counts = [0; 10];
+            for _ in 0..10_000 {
+                if let Some(value) = tree.random(&mut rng) {
+                    counts[*value as usize] += 1;
+                }
+            }
+
+            expect!(counts.iter().all(|&count| (800..1200).contains(&count))).to(be_true());
+            expect!(WeightedRedBlackTree::<u32>::new().random(&mut rng)).to(be_none());
+        }
+
+        #[test]
+        fn picks_in_proportion_to_the_weights() {
+            let tree: WeightedRedBlackTree<u32> = vec![(0, 1.0), (1, 0.0), (2, 3.0), (3, f64::NAN), (4, -2.0)].into_iter().collect();
+            let mut rng = StdRng::seed_from_u64(7);
+            let mut counts = [0; 5];
+            for _ in 0..10_000 {
+                if let Some(value) = tree.random_weighted(&mut rng) {
+                    counts[*value as usize] += 1;
+                }
+            }
+
+            expect!(counts[1] + counts[3] + counts[4]).to(be_equal_to(0));
+            expect!((2200..2800).contains(&counts[0])).to(be_true());
+            expect!(counts[0] + counts[2]).to(be_equal_to(10_000));
+        }
+
+        #[test]
+        fn picks_nothing_without_a_positive_weight() {
+            let mut rng = StdRng::seed_from_u64(7);
+            let tree: WeightedRedBlackTree<u32> = vec![(0, 0.0), (1, -1.0), (2, f64::NAN)].into_iter().collect();
+            expect!(tree.random_weighted(&mut rng)).to(be_none());
+            expect!(WeightedRedBlackTree::<u32>::new().random_weighted(&mut rng)).to(be_none());
+
+            let tree: WeightedRedBlackTree<u32> = vec![(0, f64::INFINITY)].into_iter().collect();
+            expect!(tree.random_weighted(&mut rng)).to(be_none());
+        }
+
+        #[test]
+        fn picks_the_last_weighted_element_at_the_top() {
+            // with the point as near the total as it gets, rounding mustn't
+            // let it land on the zero weights after the last positive one
+            let tree: WeightedRedBlackTree<u32> = (0..100).map(|value| (value, if value < 60 { 0.1 } else { 0.0 })).collect();
+            expect!(tree.random_weighted(&mut Highest)).to(be_some().value(&59));
+        }
+    }
+}